@@ -5,6 +5,15 @@ use std::path::PathBuf;
 /// Options for the `check` subcommand
 #[derive(Debug, StructOpt)]
 pub struct Opts {
+    /// Ignore universe levels, allowing `Type : Type` (this is unsound!)
+    #[structopt(long = "type-in-type")]
+    pub type_in_type: bool,
+
+    /// Directories to search for imported modules, in addition to the
+    /// directories of the files being checked
+    #[structopt(long = "import-path", parse(from_os_str))]
+    pub import_paths: Vec<PathBuf>,
+
     /// Files to check
     #[structopt(name = "FILE", parse(from_os_str))]
     pub files: Vec<PathBuf>,
@@ -15,36 +24,33 @@ pub fn run(color: ColorChoice, opts: Opts) -> Result<(), Error> {
     use codespan::CodeMap;
     use codespan_reporting;
 
-    use semantics::{self, TcEnv};
-    use syntax::parse;
-    use syntax::translation::{Desugar, DesugarEnv};
+    use loader::Loader;
+    use semantics::{TcEnv, WarningEnv};
 
     let mut codemap = CodeMap::new();
-    let tc_env = TcEnv::default();
-    let desugar_env = DesugarEnv::new(tc_env.mappings());
+    let mut tc_env = TcEnv::default();
     let writer = StandardStream::stderr(color);
 
+    if opts.type_in_type {
+        tc_env.set_type_in_type(true);
+        codespan_reporting::emit(&mut writer.lock(), &codemap, &super::type_in_type_warning())?;
+    }
+
+    let search_path = super::search_path(&opts.import_paths, &opts.files);
+    let mut loader = Loader::new(tc_env, search_path);
+
     let mut is_error = false;
     for path in opts.files {
         let file = codemap.add_filemap_from_disk(path)?;
-        let (module, parse_errors) = parse::module(&file);
 
-        let mut is_parse_error = false;
-        for error in parse_errors {
-            codespan_reporting::emit(&mut writer.lock(), &codemap, &error.to_diagnostic())?;
+        if let Err(diagnostics) = loader.load_file(&mut codemap, &file) {
+            for diagnostic in diagnostics {
+                codespan_reporting::emit(&mut writer.lock(), &codemap, &diagnostic)?;
+            }
             is_error = true;
-            is_parse_error = true;
-        }
-        if is_parse_error {
-            continue;
         }
-
-        match semantics::check_module(&tc_env, &module.desugar(&desugar_env)) {
-            Ok(_) => {},
-            Err(err) => {
-                codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic())?;
-                is_error = true;
-            },
+        for warning in loader.tc_env().take_warnings() {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &warning.to_diagnostic())?;
         }
     }
     if is_error {
@@ -0,0 +1,72 @@
+use codespan_reporting::termcolor::{ColorChoice, StandardStream};
+use failure::Error;
+use std::path::PathBuf;
+
+/// Options for the `doc` subcommand
+#[derive(Debug, StructOpt)]
+pub struct Opts {
+    /// The directory to write the documentation to
+    #[structopt(long = "output-dir", default_value = "doc", parse(from_os_str))]
+    pub output_dir: PathBuf,
+
+    /// Directories to search for imported modules, in addition to the
+    /// directories of the files being documented
+    #[structopt(long = "import-path", parse(from_os_str))]
+    pub import_paths: Vec<PathBuf>,
+
+    /// Files to document
+    #[structopt(name = "FILE", parse(from_os_str))]
+    pub files: Vec<PathBuf>,
+}
+
+/// Run the `doc` subcommand with the given options
+///
+/// A Markdown file is written for each of the given files, named after the
+/// module that the file contains.
+pub fn run(color: ColorChoice, opts: Opts) -> Result<(), Error> {
+    use codespan::CodeMap;
+    use codespan_reporting;
+    use std::fs;
+
+    use doc;
+    use loader::Loader;
+    use semantics::{TcEnv, WarningEnv};
+
+    let mut codemap = CodeMap::new();
+    let writer = StandardStream::stderr(color);
+
+    let search_path = super::search_path(&opts.import_paths, &opts.files);
+    let mut loader = Loader::new(TcEnv::default(), search_path);
+
+    fs::create_dir_all(&opts.output_dir)?;
+
+    let mut is_error = false;
+    for path in opts.files {
+        let name = match path.file_stem() {
+            Some(stem) => stem.to_string_lossy().into_owned(),
+            None => return Err(format_err!("`{}` is not a file", path.display())),
+        };
+        let file = codemap.add_filemap_from_disk(path)?;
+
+        match loader.load_module(&mut codemap, &file, &name) {
+            Ok(module) => {
+                let markdown = doc::module_to_markdown(loader.tc_env(), &name, &module);
+                fs::write(opts.output_dir.join(name).with_extension("md"), markdown)?;
+            },
+            Err(diagnostics) => {
+                for diagnostic in diagnostics {
+                    codespan_reporting::emit(&mut writer.lock(), &codemap, &diagnostic)?;
+                }
+                is_error = true;
+            },
+        }
+        for warning in loader.tc_env().take_warnings() {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &warning.to_diagnostic())?;
+        }
+    }
+    if is_error {
+        Err(format_err!("encountered an error!"))
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+use codespan_reporting::termcolor::{ColorChoice, StandardStream};
+use failure::Error;
+use std::path::PathBuf;
+
+/// Options for the `fmt` subcommand
+#[derive(Debug, StructOpt)]
+pub struct Opts {
+    /// Check that the files are formatted, without modifying them
+    #[structopt(long = "check")]
+    pub check: bool,
+
+    /// The width that lines should be wrapped to
+    #[structopt(long = "width", default_value = "80")]
+    pub width: usize,
+
+    /// Files to format
+    #[structopt(name = "FILE", parse(from_os_str))]
+    pub files: Vec<PathBuf>,
+}
+
+/// Run the `fmt` subcommand with the given options
+///
+/// Files are formatted in place, unless `--check` is given, in which case the
+/// names of the files that are not formatted are reported instead.
+pub fn run(color: ColorChoice, opts: Opts) -> Result<(), Error> {
+    use codespan::CodeMap;
+    use codespan_reporting;
+    use std::fs;
+
+    use format;
+
+    let mut codemap = CodeMap::new();
+    let writer = StandardStream::stderr(color);
+
+    let mut is_error = false;
+    let mut unformatted = Vec::new();
+    for path in opts.files {
+        let file = codemap.add_filemap_from_disk(&path)?;
+
+        match format::format_module(&file, opts.width) {
+            Ok(ref src) if src == file.src() => {},
+            Ok(src) => {
+                if opts.check {
+                    unformatted.push(path);
+                } else {
+                    fs::write(&path, src)?;
+                }
+            },
+            Err(errors) => {
+                for error in errors {
+                    codespan_reporting::emit(&mut writer.lock(), &codemap, &error.to_diagnostic())?;
+                }
+                is_error = true;
+            },
+        }
+    }
+
+    if is_error {
+        Err(format_err!("encountered an error!"))
+    } else if !unformatted.is_empty() {
+        for path in &unformatted {
+            eprintln!("`{}` is not formatted", path.display());
+        }
+        Err(format_err!("{} file(s) are not formatted", unformatted.len()))
+    } else {
+        Ok(())
+    }
+}
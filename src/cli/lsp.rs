@@ -0,0 +1,19 @@
+use failure::Error;
+
+/// Options for the `lsp` subcommand
+#[derive(Debug, StructOpt)]
+pub struct Opts {}
+
+/// Run the `lsp` subcommand with the given options
+///
+/// The language server reads messages from the standard input, and writes its
+/// responses to the standard output.
+pub fn run(_opts: Opts) -> Result<(), Error> {
+    use std::io;
+
+    use lsp;
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    lsp::run(stdin.lock(), stdout.lock())
+}
@@ -1,9 +1,13 @@
 //! The command line interface for Pikelet
 
-use codespan_reporting::ColorArg;
+use codespan_reporting::{ColorArg, Diagnostic};
 use failure::Error;
+use std::path::PathBuf;
 
 pub mod check;
+pub mod doc;
+pub mod fmt;
+pub mod lsp;
 pub mod repl;
 
 // TODO: test using https://github.com/killercup/assert_cli
@@ -31,15 +35,57 @@ pub enum Command {
     #[structopt(name = "check")]
     Check(check::Opts),
 
+    /// Generate documentation for the given files
+    #[structopt(name = "doc")]
+    Doc(doc::Opts),
+
+    /// Format the given files
+    #[structopt(name = "fmt")]
+    Fmt(fmt::Opts),
+
+    /// Start a language server, communicating over stdio
+    #[structopt(name = "lsp")]
+    Lsp(lsp::Opts),
+
     /// A REPL for running expressions
     #[structopt(name = "repl")]
     Repl(repl::Opts),
 }
 
+/// The directories to search for imported modules
+///
+/// Imports are resolved relative to the given import paths first, and then
+/// relative to the directories of the given files.
+fn search_path(import_paths: &[PathBuf], files: &[PathBuf]) -> Vec<PathBuf> {
+    let mut search_path = import_paths.to_vec();
+    for path in files {
+        let dir = match path.parent() {
+            Some(dir) if dir.as_os_str().is_empty() => PathBuf::from("."),
+            Some(dir) => dir.to_path_buf(),
+            None => PathBuf::from("."),
+        };
+        if !search_path.contains(&dir) {
+            search_path.push(dir);
+        }
+    }
+    search_path
+}
+
+/// The warning to report when universe levels are being ignored
+fn type_in_type_warning() -> Diagnostic {
+    Diagnostic::new_warning(
+        "`--type-in-type` is enabled: universe levels will be ignored, making the type \
+         system unsound",
+    )
+}
+
 pub fn run(opts: Opts) -> Result<(), Error> {
     let color_choice = opts.color.into();
     match opts.command {
         Command::Check(check_opts) => check::run(color_choice, check_opts),
+        Command::Doc(doc_opts) => doc::run(color_choice, doc_opts),
+        Command::Fmt(fmt_opts) => fmt::run(color_choice, fmt_opts),
+        Command::Lsp(lsp_opts) => lsp::run(lsp_opts),
         Command::Repl(repl_opts) => repl::run(color_choice, &repl_opts),
     }
 }
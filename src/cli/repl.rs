@@ -8,8 +8,8 @@ use linefeed::{Interface, ReadResult, Signal};
 use std::path::PathBuf;
 use term_size;
 
-use semantics::{self, DeclarationEnv, DefinitionEnv, GlobalEnv, TcEnv};
-use syntax::parse;
+use semantics::{self, DeclarationEnv, DefinitionEnv, GlobalEnv, MetaEnv, TcEnv, WarningEnv};
+use syntax::{core, parse, raw};
 use syntax::translation::DesugarEnv;
 
 /// Options for the `repl` subcommand
@@ -27,6 +27,10 @@ pub struct Opts {
     #[structopt(long = "no-history")]
     pub no_history: bool,
 
+    /// Ignore universe levels, allowing `Type : Type` (this is unsound!)
+    #[structopt(long = "type-in-type")]
+    pub type_in_type: bool,
+
     /// The file to save the command history to
     #[structopt(
         long = "history-file",
@@ -102,6 +106,11 @@ pub fn run(color: ColorChoice, opts: &Opts) -> Result<(), Error> {
         print_welcome_banner();
     }
 
+    if opts.type_in_type {
+        tc_env.set_type_in_type(true);
+        codespan_reporting::emit(&mut writer.lock(), &codemap, &super::type_in_type_warning())?;
+    }
+
     // TODO: Load files
 
     loop {
@@ -130,6 +139,11 @@ pub fn run(color: ColorChoice, opts: &Opts) -> Result<(), Error> {
                         codespan_reporting::emit(&mut writer.lock(), &codemap, &diagnostic)?;
                     },
                 }
+
+                for warning in tc_env.take_warnings() {
+                    let diagnostic = warning.to_diagnostic();
+                    codespan_reporting::emit(&mut writer.lock(), &codemap, &diagnostic)?;
+                }
             },
             ReadResult::Signal(Signal::Quit) | ReadResult::Eof => break,
             ReadResult::Signal(Signal::Interrupt) => println!("Interrupt"),
@@ -173,7 +187,7 @@ fn eval_print(
 
         ReplCommand::Eval(parse_term) => {
             let raw_term = parse_term.desugar(desugar_env);
-            let (term, inferred) = semantics::infer_term(tc_env, &raw_term)?;
+            let (term, inferred) = elaborate(tc_env, &raw_term)?;
             let evaluated = semantics::nf_term(tc_env, &term)?;
 
             let ann_term = Term::Ann(
@@ -187,7 +201,7 @@ fn eval_print(
             use syntax::core::{RcTerm, Term};
 
             let raw_term = parse_term.desugar(desugar_env);
-            let (term, inferred) = semantics::infer_term(tc_env, &raw_term)?;
+            let (term, inferred) = elaborate(tc_env, &raw_term)?;
 
             let ann_term = Term::Ann(term, RcTerm::from(Term::from(&*inferred)));
 
@@ -199,8 +213,10 @@ fn eval_print(
             println!("{}", raw_term.to_doc().group().pretty(term_width()));
         },
         ReplCommand::Let(name, parse_term) => {
+            use syntax::core::RcTerm;
+
             let raw_term = parse_term.desugar(desugar_env);
-            let (term, inferred) = semantics::infer_term(tc_env, &raw_term)?;
+            let (term, inferred) = elaborate(tc_env, &raw_term)?;
 
             let ann_term = Term::Ann(
                 Box::new(Term::Name(ByteSpan::default(), name.clone(), None)),
@@ -210,6 +226,10 @@ fn eval_print(
             println!("{}", ann_term.to_doc().group().pretty(term_width()));
 
             let free_var = desugar_env.on_binding(&name);
+            let level_params = RcTerm::from(&*inferred).level_params();
+            if !level_params.is_empty() {
+                tc_env.insert_level_params(free_var.clone(), level_params);
+            }
             tc_env.insert_declaration(free_var.clone(), inferred);
             tc_env.insert_definition(free_var.clone(), term);
 
@@ -217,7 +237,7 @@ fn eval_print(
         },
         ReplCommand::TypeOf(parse_term) => {
             let raw_term = parse_term.desugar(desugar_env);
-            let (_, inferred) = semantics::infer_term(tc_env, &raw_term)?;
+            let (_, inferred) = elaborate(tc_env, &raw_term)?;
 
             let inferred = inferred.resugar(tc_env.resugar_env());
 
@@ -231,6 +251,23 @@ fn eval_print(
     Ok(ControlFlow::Continue)
 }
 
+/// Infer the type of a term, making sure that all of its metavariables were
+/// solved, and filling in their solutions
+fn elaborate(
+    tc_env: &TcEnv,
+    raw_term: &raw::RcTerm,
+) -> Result<(core::RcTerm, core::RcValue), EvalPrintError> {
+    let prior_unsolved = tc_env.unsolved_metas();
+    let (term, inferred) = semantics::infer_term(tc_env, raw_term)?;
+    semantics::check_metas_solved(tc_env, &prior_unsolved)?;
+    semantics::solve_level_constraints(tc_env, raw_term.span())?;
+    tc_env.default_level_metas();
+    let term = semantics::zonk_term(tc_env, &term);
+    let inferred = semantics::zonk_value(tc_env, &inferred)?;
+
+    Ok((term, inferred))
+}
+
 #[derive(Clone)]
 enum ControlFlow {
     Break,
@@ -0,0 +1,297 @@
+//! Generation of documentation from the doc comments of modules
+//!
+//! Documentation is written as Markdown, so that it can be included in the
+//! book. The signatures of items are rendered as HTML, allowing the names that
+//! they mention to link to the items that define them.
+
+use codespan::ByteIndex;
+use moniker::Binder;
+use std::collections::{HashMap, HashSet};
+
+use semantics::{DeclarationEnv, GlobalEnv};
+use syntax::concrete;
+use syntax::core::{self, Item};
+use syntax::pretty::ToDoc;
+use syntax::translation::{resugar_ann, Resugar, ResugarEnv};
+use syntax::Label;
+
+#[cfg(test)]
+mod tests;
+
+/// The width that signatures are wrapped to
+const SIGNATURE_WIDTH: usize = 80;
+/// Marks the start of a name that should be linked to the item it refers to
+const LINK_START: char = '\u{1}';
+/// Marks the end of a name that should be linked to the item it refers to
+const LINK_END: char = '\u{2}';
+
+/// The documentation for a single item
+struct Entry {
+    /// The name of the item, as used in other modules
+    label: String,
+    /// The rendered signature of the item
+    signature: String,
+    /// The doc comments of the item
+    doc: String,
+    /// The names and doc comments of the constructors of a data type
+    constructors: Vec<(String, String)>,
+}
+
+/// Generate Markdown documentation for the public items of a module
+///
+/// The module must have been checked in the given environment, so that the
+/// types of definitions without declarations can be looked up.
+pub fn module_to_markdown<Env>(env: &Env, name: &str, module: &core::Module) -> String
+where
+    Env: DeclarationEnv + GlobalEnv,
+{
+    let resugar_env = env.resugar_env();
+
+    // Items are hidden if either their declaration or their definition was
+    // marked as private
+    let mut hidden = HashSet::new();
+    let mut definition_docs = HashMap::new();
+    for item in &module.items {
+        match *item {
+            Item::Declaration {
+                ref binder,
+                private: true,
+                ..
+            }
+            | Item::Definition {
+                ref binder,
+                private: true,
+                ..
+            } => {
+                hidden.insert(binder);
+            },
+            Item::Definition {
+                ref binder,
+                ref doc,
+                ..
+            } => {
+                definition_docs.insert(binder, doc);
+            },
+            Item::Declaration { .. } | Item::Data { .. } | Item::Error { .. } => {},
+        }
+    }
+
+    // Names that refer to documented items are marked when resugaring, so
+    // that only those names are linked, and not record labels that happen to
+    // share the same name
+    let mut anchors = HashMap::new();
+    let mut link_env = resugar_env.clone();
+    let mut add_link = |binder: &Binder<String>, label: &Label| {
+        let name = resugar_env.on_free_var(&binder.0);
+        let marked_name = format!("{}{}{}", LINK_START, name, LINK_END);
+        link_env.rename(&binder.0, marked_name);
+        anchors.insert(name, label.0.clone());
+    };
+    for item in &module.items {
+        match *item {
+            Item::Declaration {
+                ref label,
+                ref binder,
+                ..
+            }
+            | Item::Definition {
+                ref label,
+                ref binder,
+                private: false,
+                ..
+            } => {
+                if !hidden.contains(binder) {
+                    add_link(binder, label);
+                }
+            },
+            Item::Data {
+                ref label,
+                ref binder,
+                ref constructors,
+                private: false,
+                ..
+            } => {
+                add_link(binder, label);
+                for constructor in constructors {
+                    add_link(&constructor.binder, &constructor.label);
+                }
+            },
+            Item::Definition { .. } | Item::Data { .. } | Item::Error { .. } => {},
+        }
+    }
+
+    let mut entries = Vec::new();
+    let mut declared = HashSet::new();
+    for item in &module.items {
+        match *item {
+            Item::Declaration {
+                ref doc,
+                ref label,
+                ref binder,
+                ref term,
+                ..
+            } => {
+                if hidden.contains(binder) {
+                    continue;
+                }
+                declared.insert(binder);
+
+                let doc = match definition_docs.get(binder) {
+                    Some(def_doc) if doc.is_empty() => def_doc.to_string(),
+                    Some(def_doc) if !def_doc.is_empty() => format!("{}\n\n{}", doc, def_doc),
+                    Some(_) | None => doc.clone(),
+                };
+                let name = resugar_env.on_free_var(&binder.0);
+                let ann = resugar_ann(&link_env, term);
+
+                entries.push(Entry {
+                    label: label.0.clone(),
+                    signature: declaration_signature(name, ann),
+                    doc,
+                    constructors: Vec::new(),
+                });
+            },
+            Item::Definition {
+                ref doc,
+                ref label,
+                ref binder,
+                private,
+                ..
+            } => {
+                if private || hidden.contains(binder) || declared.contains(binder) {
+                    continue;
+                }
+
+                let name = resugar_env.on_free_var(&binder.0);
+                let ty = env
+                    .get_declaration(&binder.0)
+                    .expect("undeclared module item");
+                let ann = resugar_ann(&link_env, &ty.to_folded_term());
+
+                entries.push(Entry {
+                    label: label.0.clone(),
+                    signature: declaration_signature(name, ann),
+                    doc: doc.clone(),
+                    constructors: Vec::new(),
+                });
+            },
+            Item::Data {
+                ref doc,
+                ref label,
+                ref constructors,
+                private,
+                ..
+            } => {
+                if private {
+                    continue;
+                }
+
+                entries.push(Entry {
+                    label: label.0.clone(),
+                    signature: data_signature(&link_env, item),
+                    doc: doc.clone(),
+                    constructors: constructors
+                        .iter()
+                        .map(|constructor| (constructor.label.0.clone(), constructor.doc.clone()))
+                        .collect(),
+                });
+            },
+            // Items that failed to type check have nothing to document
+            Item::Error { .. } => {},
+        }
+    }
+
+    let mut markdown = format!("# {}\n", name);
+    if !module.doc.is_empty() {
+        markdown.push_str(&format!("\n{}\n", module.doc));
+    }
+    for entry in entries {
+        markdown.push_str(&format!(
+            "\n<a id=\"{0}\"></a>\n## `{0}`\n\n<pre><code>{1}</code></pre>\n",
+            entry.label,
+            link_names(&entry.signature, &anchors),
+        ));
+        if !entry.doc.is_empty() {
+            markdown.push_str(&format!("\n{}\n", entry.doc));
+        }
+        if !entry.constructors.is_empty() {
+            markdown.push_str("\nConstructors:\n\n");
+            for (label, doc) in entry.constructors {
+                markdown.push_str(&format!("- <a id=\"{0}\"></a>`{0}`", label));
+                if !doc.is_empty() {
+                    // Indent the following lines so that they remain part of
+                    // the list item
+                    markdown.push_str(&format!(": {}", doc.replace("\n", "\n  ")));
+                }
+                markdown.push('\n');
+            }
+        }
+    }
+
+    markdown
+}
+
+/// Render the signature of a declaration
+fn declaration_signature(name: String, ann: concrete::Term) -> String {
+    let item = concrete::Item::Declaration {
+        doc: Vec::new(),
+        private: None,
+        partial: None,
+        name: (ByteIndex::default(), name),
+        ann,
+    };
+
+    item.to_doc().group().pretty(SIGNATURE_WIDTH).to_string()
+}
+
+/// Render the signature of a data type, including its constructors
+fn data_signature(resugar_env: &ResugarEnv, item: &Item) -> String {
+    let module = core::Module {
+        doc: String::new(),
+        items: vec![item.clone()],
+    };
+    let mut item = match module.resugar(resugar_env) {
+        concrete::Module::Valid { mut items, .. } => items.remove(0),
+        concrete::Module::Error(_) => unreachable!("resugared an invalid module"),
+    };
+
+    // The doc comments are shown separately from the signature
+    if let concrete::Item::Data {
+        ref mut doc,
+        ref mut constructors,
+        ..
+    } = item
+    {
+        doc.clear();
+        for constructor in constructors {
+            constructor.doc.clear();
+        }
+    }
+
+    item.to_doc().group().pretty(SIGNATURE_WIDTH).to_string()
+}
+
+/// Escape a rendered signature for inclusion in HTML, linking the names that
+/// were marked when it was resugared
+fn link_names(src: &str, anchors: &HashMap<String, String>) -> String {
+    let mut html = String::with_capacity(src.len());
+    let mut chars = src.chars();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            LINK_START => {
+                let name = chars
+                    .by_ref()
+                    .take_while(|&ch| ch != LINK_END)
+                    .collect::<String>();
+                html.push_str(&format!("<a href=\"#{}\">{}</a>", anchors[&name], name));
+            },
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '&' => html.push_str("&amp;"),
+            ch => html.push(ch),
+        }
+    }
+
+    html
+}
@@ -0,0 +1,220 @@
+use codespan::{CodeMap, FileName};
+use codespan_reporting;
+use codespan_reporting::termcolor::{ColorChoice, StandardStream};
+
+use semantics::{self, TcEnv};
+use syntax::parse;
+use syntax::translation::{Desugar, DesugarEnv};
+
+use super::*;
+
+fn module_markdown(name: &str, src: &str) -> String {
+    let mut codemap = CodeMap::new();
+    let mut tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
+    let (concrete_module, errors) = parse::module(&filemap);
+
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for error in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &error.to_diagnostic()).unwrap();
+        }
+        panic!("parse error!")
+    }
+
+    let raw_module = concrete_module.desugar(&desugar_env);
+    let (module, errors) = semantics::check_imported_module(&mut tc_env, name, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+
+    module_to_markdown(&tc_env, name, &module)
+}
+
+#[test]
+fn module() {
+    let src = r#"
+        ||! Natural numbers
+
+        ||| The natural numbers
+        data Nat : Type where {
+            ||| Zero
+            zero : Nat;
+            succ : Nat -> Nat;
+        };
+
+        ||| Add two natural numbers
+        add : Nat -> Nat -> Nat;
+        ||| Defined by recursion on the first argument
+        add m n = case m of {
+            zero => n;
+            succ k => succ (add k n);
+        };
+
+        one = succ zero;
+    "#;
+
+    assert_eq!(
+        module_markdown("nat", src),
+        r##"# nat
+
+Natural numbers
+
+<a id="Nat"></a>
+## `Nat`
+
+<pre><code>data <a href="#Nat">Nat</a> : Type where {
+    <a href="#zero">zero</a> : <a href="#Nat">Nat</a>;
+    <a href="#succ">succ</a> : <a href="#Nat">Nat</a> -&gt; <a href="#Nat">Nat</a>;
+};</code></pre>
+
+The natural numbers
+
+Constructors:
+
+- <a id="zero"></a>`zero`: Zero
+- <a id="succ"></a>`succ`
+
+<a id="add"></a>
+## `add`
+
+<pre><code>add : <a href="#Nat">Nat</a> -&gt; <a href="#Nat">Nat</a> -&gt; <a href="#Nat">Nat</a>;</code></pre>
+
+Add two natural numbers
+
+Defined by recursion on the first argument
+
+<a id="one"></a>
+## `one`
+
+<pre><code>one : <a href="#Nat">Nat</a>;</code></pre>
+"##,
+    );
+}
+
+#[test]
+fn private_items() {
+    let src = r#"
+        private helper : Type;
+        helper = String;
+
+        name : Type;
+        private name = String;
+
+        greeting : helper;
+        greeting = "hello";
+    "#;
+
+    assert_eq!(
+        module_markdown("greeting", src),
+        r##"# greeting
+
+<a id="greeting"></a>
+## `greeting`
+
+<pre><code>greeting : helper;</code></pre>
+"##,
+    );
+}
+
+#[test]
+fn record_labels_are_not_linked() {
+    let src = r#"
+        id : {a : Type} -> a -> a;
+        id x = x;
+
+        ops : Record { id : String -> String };
+        ops = record { id = id };
+    "#;
+
+    assert_eq!(
+        module_markdown("ops", src),
+        r##"# ops
+
+<a id="id"></a>
+## `id`
+
+<pre><code>id : {a : Type} -&gt; a -&gt; a;</code></pre>
+
+<a id="ops"></a>
+## `ops`
+
+<pre><code>ops : Record { id : String -&gt; String };</code></pre>
+"##,
+    );
+}
+
+#[test]
+fn nested_applications_and_equality_types() {
+    let src = r#"
+        data Nat : Type where {
+            zero : Nat;
+            succ : Nat -> Nat;
+        };
+
+        two : Nat;
+        two = succ (succ zero);
+
+        two-is-two : (two = succ (succ zero));
+        two-is-two = refl;
+
+        proofs : Record { two-is-two : (two = two) };
+        proofs = record { two-is-two = refl };
+
+        Pair : Type -> Type -> Type;
+        Pair a b = Record { fst : a; snd : b };
+
+        nested : Pair (Pair String String) String;
+        nested = record { fst = record { fst = "a"; snd = "b" }; snd = "c" };
+    "#;
+
+    assert_eq!(
+        module_markdown("two", src),
+        r##"# two
+
+<a id="Nat"></a>
+## `Nat`
+
+<pre><code>data <a href="#Nat">Nat</a> : Type where {
+    <a href="#zero">zero</a> : <a href="#Nat">Nat</a>;
+    <a href="#succ">succ</a> : <a href="#Nat">Nat</a> -&gt; <a href="#Nat">Nat</a>;
+};</code></pre>
+
+Constructors:
+
+- <a id="zero"></a>`zero`
+- <a id="succ"></a>`succ`
+
+<a id="two"></a>
+## `two`
+
+<pre><code>two : <a href="#Nat">Nat</a>;</code></pre>
+
+<a id="two-is-two"></a>
+## `two-is-two`
+
+<pre><code>two-is-two : (<a href="#two">two</a> = <a href="#succ">succ</a> (<a href="#succ">succ</a> <a href="#zero">zero</a>));</code></pre>
+
+<a id="proofs"></a>
+## `proofs`
+
+<pre><code>proofs : Record { two-is-two : (<a href="#two">two</a> = <a href="#two">two</a>) };</code></pre>
+
+<a id="Pair"></a>
+## `Pair`
+
+<pre><code>Pair : Type -&gt; Type -&gt; Type;</code></pre>
+
+<a id="nested"></a>
+## `nested`
+
+<pre><code>nested : <a href="#Pair">Pair</a> (<a href="#Pair">Pair</a> String String) String;</code></pre>
+"##,
+    );
+}
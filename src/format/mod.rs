@@ -0,0 +1,367 @@
+//! Formatting of source files
+//!
+//! Modules are parsed into the concrete syntax, and then laid out again using
+//! its pretty printer. The concrete syntax does not record ordinary comments,
+//! so these are recovered from the tokens of the file and placed back between
+//! the items that they were originally found between. Comments inside of an
+//! item are kept as trivia attached to the token that they follow, and are
+//! laid out next to that token once the item has been formatted.
+//!
+//! Blank lines between items are preserved, but runs of blank lines are
+//! collapsed into one.
+
+use codespan::{ByteIndex, ByteSpan, FileMap, FileName};
+use std::mem;
+
+use syntax::concrete::{Item, Module};
+use syntax::parse::{self, Lexer, ParseError, Token};
+use syntax::pretty::ToDoc;
+
+#[cfg(test)]
+mod tests;
+
+/// The default width that source files are formatted to
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// The indentation added to lines that had to be split by a comment
+const CONTINUATION_INDENT: &str = "    ";
+
+/// A token along with its position in the source
+type SpannedToken<'input> = (ByteIndex, Token<&'input str>, ByteIndex);
+
+/// A formatted piece of the source file
+struct Chunk {
+    /// The span of source code that the chunk was formatted from
+    span: ByteSpan,
+    /// Whether the chunk is a comment, allowing it to trail after the end of
+    /// the previous chunk
+    is_comment: bool,
+    /// The formatted source code
+    src: String,
+}
+
+/// A comment found inside of a chunk
+struct Comment {
+    /// The number of tokens that came before the comment in the chunk, not
+    /// counting other comments
+    index: usize,
+    /// Whether the comment was on the same line as the token before it
+    is_trailing: bool,
+    /// Whether the comment was separated from the token before it by a blank
+    /// line
+    is_spaced: bool,
+    /// The source code of the comment
+    src: String,
+}
+
+/// Format the module contained in a file, wrapping lines to the given width
+pub fn format_module(filemap: &FileMap, width: usize) -> Result<String, Vec<ParseError>> {
+    let (module, errors) = parse::module(filemap);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let (module_doc, items) = match module {
+        Module::Valid { doc, items } => (doc, items),
+        Module::Error(_) => unreachable!("parse errors were already reported"),
+    };
+
+    // Any lexer errors were reported when parsing
+    let tokens = Lexer::new(filemap)
+        .filter_map(Result::ok)
+        .collect::<Vec<_>>();
+    let comments = tokens
+        .iter()
+        .filter_map(|&(start, ref token, end)| match *token {
+            Token::LineComment(comment) => Some((ByteSpan::new(start, end), comment)),
+            _ => None,
+        }).collect::<Vec<_>>();
+    let slice = |span: ByteSpan| filemap.src_slice(span).unwrap().to_owned();
+
+    let mut chunks = Vec::new();
+
+    let module_doc_spans = tokens
+        .iter()
+        .filter_map(|&(start, ref token, end)| match *token {
+            Token::ModuleDocComment(_) => Some(ByteSpan::new(start, end)),
+            _ => None,
+        }).collect::<Vec<_>>();
+    if let (Some(first), Some(last)) = (module_doc_spans.first(), module_doc_spans.last()) {
+        let span = first.to(*last);
+        let src = layout_with_comments(filemap, &tokens, span, width, |_| {
+            module_doc
+                .iter()
+                .map(|line| match line.as_str() {
+                    "" => "||!".to_owned(),
+                    line => format!("||! {}", line),
+                }).collect::<Vec<_>>()
+                .join("\n")
+        });
+
+        chunks.push(Chunk {
+            span,
+            is_comment: false,
+            src,
+        });
+    }
+
+    for item in &items {
+        let span = item_span(&tokens, item);
+        let src = layout_with_comments(filemap, &tokens, span, width, |width| {
+            let src = item.to_doc().group().pretty(width).to_string();
+            src.lines()
+                .map(str::trim_end)
+                .collect::<Vec<_>>()
+                .join("\n")
+        });
+
+        chunks.push(Chunk {
+            span,
+            is_comment: false,
+            src,
+        });
+    }
+
+    for &(span, comment) in &comments {
+        if chunks.iter().all(|chunk| !chunk.span.contains(span)) {
+            chunks.push(Chunk {
+                span,
+                is_comment: true,
+                src: format!("--{}", comment.trim_end()),
+            });
+        }
+    }
+
+    chunks.sort_by_key(|chunk| chunk.span.start());
+
+    let mut src = String::new();
+    let mut prev_end = None;
+    for chunk in chunks {
+        if let Some(prev_end) = prev_end {
+            let gap = slice(ByteSpan::new(prev_end, chunk.span.start()));
+            match gap.matches('\n').count() {
+                0 if chunk.is_comment => src.push(' '),
+                0 | 1 => src.push('\n'),
+                _ => src.push_str("\n\n"),
+            }
+        }
+        src.push_str(&chunk.src);
+        prev_end = Some(chunk.span.end());
+    }
+    if !src.is_empty() {
+        src.push('\n');
+    }
+
+    Ok(src)
+}
+
+/// Find the span of an item, including its doc comments and the trailing
+/// semicolon, along with any comments in between
+fn item_span(tokens: &[SpannedToken], item: &Item) -> ByteSpan {
+    let span = item.span();
+
+    let first = tokens
+        .iter()
+        .position(|&(start, _, _)| start >= span.start())
+        .expect("item without tokens");
+    let mut start = span.start();
+    for &(doc_start, ref token, _) in tokens[..first].iter().rev() {
+        match *token {
+            Token::DocComment(_) => start = doc_start,
+            Token::LineComment(_) => {},
+            _ => break,
+        }
+    }
+
+    let end = tokens[first..]
+        .iter()
+        .find(|&&(start, ref token, _)| start >= span.end() && *token == Token::Semi)
+        .map_or(span.end(), |&(_, _, end)| end);
+
+    ByteSpan::new(start, end)
+}
+
+/// Lay out a chunk of source code, keeping the comments that were found in its
+/// span next to the tokens that they followed
+///
+/// Line comments have to end the line that they are on, so the chunk is laid
+/// out at the widest width, up to `width`, where every comment falls at the
+/// start or the end of a line. If there is no such width, the lines are split
+/// wherever a comment needs to go.
+fn layout_with_comments<F>(
+    filemap: &FileMap,
+    tokens: &[SpannedToken],
+    span: ByteSpan,
+    width: usize,
+    layout: F,
+) -> String
+where
+    F: Fn(usize) -> String,
+{
+    let mut code_tokens = Vec::new();
+    let mut comments = Vec::new();
+    let mut prev_line = None;
+    let mut prev_end = None;
+    for &(start, ref token, end) in tokens {
+        if !span.contains(ByteSpan::new(start, end)) {
+            continue;
+        }
+
+        let line = filemap.find_line(start).unwrap();
+        let gap = prev_end.map_or("", |prev_end| {
+            filemap.src_slice(ByteSpan::new(prev_end, start)).unwrap()
+        });
+        prev_end = Some(end);
+        match *token {
+            // Separators are not always printed in the same places
+            Token::Semi => {},
+            Token::LineComment(comment) => comments.push(Comment {
+                index: code_tokens.len(),
+                is_trailing: prev_line == Some(line),
+                is_spaced: gap.matches('\n').count() > 1,
+                src: format!("--{}", comment.trim_end()),
+            }),
+            ref token => {
+                code_tokens.push(token);
+                prev_line = Some(line);
+            },
+        }
+    }
+
+    if comments.is_empty() {
+        return layout(width);
+    }
+
+    let mut split_src = None;
+    for width in (0..=width).rev() {
+        match place_comments(&layout(width), &code_tokens, &comments) {
+            Some((src, false)) => return src,
+            Some((src, true)) => split_src = split_src.or(Some(src)),
+            None => {},
+        }
+    }
+
+    split_src.unwrap_or_else(|| {
+        // The tokens were never laid out as they were written, so we can't
+        // tell where the comments belong. Keep them together before the
+        // chunk instead.
+        let comment_lines = comments.iter().map(|comment| comment.src.clone());
+        comment_lines
+            .chain(Some(layout(width)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    })
+}
+
+/// Insert comments into a chunk of formatted source code, returning `None` if
+/// the tokens of the formatted code do not match the original ones
+///
+/// Also returns whether any lines had to be split to make room for the
+/// comments.
+fn place_comments(
+    src: &str,
+    code_tokens: &[&Token<&str>],
+    comments: &[Comment],
+) -> Option<(String, bool)> {
+    let filemap = FileMap::new(FileName::virtual_("formatted"), src.to_owned());
+    let all_tokens = Lexer::new(&filemap)
+        .filter_map(Result::ok)
+        .collect::<Vec<_>>();
+    let tokens = all_tokens
+        .iter()
+        .enumerate()
+        .filter(|&(_, &(_, ref token, _))| *token != Token::Semi)
+        .collect::<Vec<_>>();
+    let tokens_match = tokens.len() == code_tokens.len()
+        && tokens
+            .iter()
+            .zip(code_tokens)
+            .all(|(&(_, &(_, ref token, _)), code_token)| {
+                mem::discriminant(token) == mem::discriminant(*code_token)
+            });
+    if !tokens_match {
+        return None;
+    }
+
+    let offset = |index| {
+        let span = ByteSpan::new(filemap.span().start(), index);
+        filemap.src_slice(span).unwrap().len()
+    };
+
+    let mut placed_src = String::new();
+    let mut is_split = false;
+    let mut pos = 0;
+    for comment in comments {
+        let comment_pos = if comment.is_trailing {
+            // Keep any separator that follows the token before the comment
+            let (i, &(_, _, end)) = tokens[comment.index - 1];
+            match all_tokens.get(i + 1) {
+                Some(&(_, Token::Semi, semi_end)) => offset(semi_end),
+                _ => offset(end),
+            }
+        } else {
+            tokens
+                .get(comment.index)
+                .map_or(src.len(), |&(_, &(start, _, _))| offset(start))
+        };
+        placed_src.push_str(&src[pos..comment_pos]);
+        pos = comment_pos;
+
+        let rest_of_line = src[pos..].split('\n').next().unwrap();
+        let line = &placed_src[placed_src.rfind('\n').map_or(0, |i| i + 1)..];
+        let indent = line[..line.len() - line.trim_start().len()].to_owned();
+
+        if comment.is_trailing {
+            placed_src.push(' ');
+            placed_src.push_str(&comment.src);
+            if !rest_of_line.trim().is_empty() {
+                is_split = true;
+                placed_src.push('\n');
+                placed_src.push_str(&indent);
+                placed_src.push_str(CONTINUATION_INDENT);
+                pos += rest_of_line.len() - rest_of_line.trim_start().len();
+            }
+        } else if line.trim().is_empty() {
+            // Comments before closing delimiters belong to the lines above
+            let next_token = tokens
+                .get(comment.index)
+                .map(|&(_, &(_, ref token, _))| token);
+            let is_closing =
+                next_token == Some(&Token::RBrace) || next_token == Some(&Token::RParen);
+            let prev_line = placed_src[..placed_src.len() - line.len()]
+                .trim_end_matches('\n')
+                .rsplit('\n')
+                .next()
+                .unwrap();
+            let comment_indent = if is_closing {
+                prev_line[..prev_line.len() - prev_line.trim_start().len()].to_owned()
+            } else {
+                indent.clone()
+            };
+
+            let len = placed_src.len() - line.len();
+            placed_src.truncate(len);
+            if comment.is_spaced && !placed_src.ends_with("\n\n") {
+                placed_src.push('\n');
+            }
+            placed_src.push_str(&comment_indent);
+            placed_src.push_str(&comment.src);
+            placed_src.push('\n');
+            placed_src.push_str(&indent);
+        } else {
+            is_split = true;
+            let indent = indent + CONTINUATION_INDENT;
+            let len = placed_src.trim_end().len();
+            placed_src.truncate(len);
+            placed_src.push('\n');
+            placed_src.push_str(&indent);
+            placed_src.push_str(&comment.src);
+            placed_src.push('\n');
+            placed_src.push_str(&indent);
+            pos += rest_of_line.len() - rest_of_line.trim_start().len();
+        }
+    }
+    placed_src.push_str(&src[pos..]);
+
+    Some((placed_src, is_split))
+}
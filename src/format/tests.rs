@@ -0,0 +1,183 @@
+use codespan::{CodeMap, FileName};
+use codespan_reporting;
+use codespan_reporting::termcolor::{ColorChoice, StandardStream};
+
+use library;
+
+use super::*;
+
+fn format(src: &str, width: usize) -> String {
+    let mut codemap = CodeMap::new();
+    let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
+
+    match format_module(&filemap, width) {
+        Ok(src) => src,
+        Err(errors) => {
+            let writer = StandardStream::stdout(ColorChoice::Always);
+            for error in errors {
+                codespan_reporting::emit(&mut writer.lock(), &codemap, &error.to_diagnostic())
+                    .unwrap();
+            }
+            panic!("parse error!")
+        },
+    }
+}
+
+#[test]
+fn items() {
+    let src = "id  :{ a : Type }->a -> a;id x=x;\n\n\n\nconst : {a b : Type} -> a -> b -> a;\nconst x y = x;";
+
+    assert_eq!(
+        format(src, DEFAULT_WIDTH),
+        "id : {a : Type} -> a -> a;\nid x = x;\n\nconst : {a b : Type} -> a -> b -> a;\nconst x y = x;\n",
+    );
+}
+
+#[test]
+fn width() {
+    let src = r#"
+        compose : {a b c : Type} -> (b -> c) -> (a -> b) -> (a -> c);
+        compose f g x = f (g x);
+
+        pair : Record { fst : String; snd : String };
+        pair = record { fst = "hello"; snd = "world" };
+
+        choose : Bool -> String;
+        choose b = case b of { true => "yes"; false => "no"; };
+    "#;
+
+    assert_eq!(
+        format(src, 40),
+        r#"compose : {a b c : Type} ->
+    (b -> c) ->
+    (a -> b) ->
+    (a -> c);
+compose f g x = f (g x);
+
+pair : Record {
+    fst : String;
+    snd : String
+};
+pair = record {
+    fst = "hello";
+    snd = "world"
+};
+
+choose : Bool -> String;
+choose b = case b of {
+    true => "yes";
+    false => "no";
+};
+"#,
+    );
+}
+
+#[test]
+fn comments() {
+    let src = r#"
+-- The identity function
+id : {a : Type} -> a -> a; -- its type
+id x = x;
+
+-- A record
+pair = record {
+    fst = "hello"; -- the first field
+    snd = "world";
+};
+
+-- The end
+"#;
+
+    assert_eq!(
+        format(src, DEFAULT_WIDTH),
+        r#"-- The identity function
+id : {a : Type} -> a -> a; -- its type
+id x = x;
+
+-- A record
+pair = record {
+    fst = "hello"; -- the first field
+    snd = "world"
+};
+
+-- The end
+"#,
+    );
+}
+
+#[test]
+fn comments_in_items() {
+    let src = r#"
+id  :{ a : Type }->a -> a;
+id x =  -- the argument
+    x;
+
+pair = record { fst = "hello"; -- the first field
+snd = "world";
+
+    -- no more fields
+};
+
+choose : Bool -> String;
+choose b = case b of { true => "yes"; -- yes
+false => "no"; };
+"#;
+
+    let formatted = format(src, DEFAULT_WIDTH);
+
+    assert_eq!(
+        formatted,
+        r#"id : {a : Type} -> a -> a;
+id x = -- the argument
+    x;
+
+pair = record {
+    fst = "hello"; -- the first field
+    snd = "world"
+
+    -- no more fields
+};
+
+choose : Bool -> String;
+choose b = case b of {
+    true => "yes"; -- yes
+    false => "no";
+};
+"#,
+    );
+    assert_eq!(format(&formatted, DEFAULT_WIDTH), formatted);
+}
+
+#[test]
+fn doc_comments() {
+    let src = r#"
+||! A module
+
+||| The identity function
+id : {a : Type} -> a -> a;
+id x = x;
+
+||| Optional values
+data Option (a : Type) : Type where {
+    ||| No value
+    none : Option a;
+    some : a -> Option a;
+};
+
+||| A pair of strings
+Pair = Record {
+    ||| The first string
+    fst : String;
+    snd : String;
+};
+"#;
+
+    assert_eq!(format(src, DEFAULT_WIDTH), &src[1..]);
+}
+
+#[test]
+fn idempotent_prelude() {
+    let formatted = format(library::PRELUDE, DEFAULT_WIDTH);
+
+    assert_eq!(format(&formatted, DEFAULT_WIDTH), formatted);
+}
@@ -27,8 +27,15 @@
 //!      syntax::parse::grammar
 //!                 |
 //!                 v
+//!    .-------------------------.
+//!    | syntax::cst::SyntaxNode |
+//!    '-------------------------'
+//!                 |
+//!       syntax::parse::lower
+//!                 |
+//!                 v
 //!     .------------------------.
-//!     | syntax::concrete::Term |---------> Code formatter (TODO)
+//!     | syntax::concrete::Term |---------> format
 //!     '------------------------'
 //!                 |
 //!    syntax::translation::desugar
@@ -85,6 +92,11 @@
 //! for this. Unfortunately this uses a quite slow method of name binding, and could
 //! result in performance blowouts in the future. This is something to keep an eye on!
 //!
+//! Evaluation avoids most of this cost: the values of bound variables are looked
+//! up from an environment by their de Bruijn index, rather than being substituted
+//! into the bodies of scopes. The core terms themselves are still bound using
+//! `moniker`, however, so scopes are still unbound when they are checked.
+//!
 //! ## Performance considerations
 //!
 //! As you can see from the diagram above, this architecture leads to an
@@ -143,6 +155,9 @@ extern crate pretty_assertions;
 extern crate unicode_xid;
 
 mod library;
+pub mod doc;
+pub mod format;
+pub mod loader;
 pub mod semantics;
 pub mod syntax;
 
@@ -157,6 +172,15 @@ extern crate term_size;
 #[cfg(feature = "cli")]
 pub mod cli;
 
+#[cfg(feature = "lsp")]
+extern crate lsp_types;
+#[cfg(feature = "lsp")]
+#[macro_use]
+extern crate serde_json;
+
+#[cfg(feature = "lsp")]
+pub mod lsp;
+
 use codespan::{CodeMap, FileMap, FileName};
 use codespan_reporting::Diagnostic;
 
@@ -175,10 +199,14 @@ pub fn load_file(file: &FileMap) -> Result<core::Module, Vec<Diagnostic>> {
     let tc_env = TcEnv::default();
     let desugar_env = DesugarEnv::new(tc_env.mappings());
     let raw_module = concrete_module.desugar(&desugar_env);
-    semantics::check_module(&tc_env, &raw_module).map_err(|err| {
-        diagnostics.push(err.to_diagnostic());
-        diagnostics
-    })
+    let (module, errors) = semantics::check_module(&tc_env, &raw_module);
+    diagnostics.extend(errors.iter().map(|err| err.to_diagnostic()));
+
+    if diagnostics.is_empty() {
+        Ok(module)
+    } else {
+        Err(diagnostics)
+    }
 }
 
 pub fn load_prelude(codemap: &mut CodeMap) -> core::Module {
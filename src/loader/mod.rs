@@ -0,0 +1,357 @@
+//! Loading of modules from the file system
+//!
+//! Modules are imported by their path, for example `import data.nat;`, which
+//! is resolved to a file named `data/nat.pi` in one of the directories of the
+//! search path. Imported modules are checked before the modules that import
+//! them, and are then bound to records containing their items.
+
+use codespan::{ByteIndex, ByteSpan, CodeMap, FileMap, FileName};
+use codespan_reporting::{Diagnostic, Label};
+use im::{HashMap, HashSet};
+use moniker::FreeVar;
+use std::path::PathBuf;
+
+use semantics::{self, ModuleEnv, TcEnv, TypeError};
+use syntax::concrete;
+use syntax::core;
+use syntax::parse;
+use syntax::raw;
+use syntax::translation::{Desugar, DesugarEnv};
+
+#[cfg(test)]
+mod tests;
+
+/// An error encountered while loading the modules imported by another module
+#[derive(Fail, Debug, Clone, PartialEq)]
+pub enum LoadError {
+    #[fail(display = "Unable to find the module `{}`", module)]
+    ModuleNotFound {
+        span: ByteSpan,
+        module: String,
+        search_path: Vec<PathBuf>,
+    },
+    #[fail(display = "Unable to read `{}`: {}", path, message)]
+    ReadFailed {
+        span: ByteSpan,
+        path: String,
+        message: String,
+    },
+    #[fail(display = "The module `{}` imports itself", module)]
+    ImportCycle {
+        span: ByteSpan,
+        module: String,
+        cycle: Vec<String>,
+    },
+    #[fail(display = "The module `{}` failed to load", module)]
+    ImportFailed { span: ByteSpan, module: String },
+}
+
+impl LoadError {
+    /// Convert the error into a diagnostic message
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match *self {
+            LoadError::ModuleNotFound {
+                span,
+                ref module,
+                ref search_path,
+            } => {
+                let search_path = search_path
+                    .iter()
+                    .map(|dir| format!("`{}`", dir.display()))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                Diagnostic::new_error(format!(
+                    "unable to find the module `{}` in the search path: {}",
+                    module, search_path,
+                )).with_label(Label::new_primary(span).with_message("imported here"))
+            },
+            LoadError::ReadFailed {
+                span,
+                ref path,
+                ref message,
+            } => Diagnostic::new_error(format!("unable to read `{}`: {}", path, message))
+                .with_label(Label::new_primary(span).with_message("imported here")),
+            LoadError::ImportCycle {
+                span,
+                ref module,
+                ref cycle,
+            } => Diagnostic::new_error(format!(
+                "the module `{}` imports itself by way of {}",
+                module,
+                cycle.join(" -> "),
+            )).with_label(Label::new_primary(span).with_message("the cyclic import")),
+            LoadError::ImportFailed { span, ref module } => {
+                Diagnostic::new_error(format!("the module `{}` failed to load", module))
+                    .with_label(Label::new_primary(span).with_message("imported here"))
+            },
+        }
+    }
+}
+
+/// Loads modules from the file system, along with the modules that they
+/// import
+pub struct Loader {
+    /// The directories to look for imported modules in, in order of priority
+    search_path: Vec<PathBuf>,
+    /// The type checking environment, containing the modules that have been
+    /// imported so far
+    tc_env: TcEnv,
+    /// The names that were in scope before any modules were imported
+    mappings: HashMap<String, FreeVar<String>>,
+    /// The modules that are in the process of being loaded, used to detect
+    /// cyclic imports
+    loading: Vec<String>,
+    /// The modules that could not be loaded, so that their errors are only
+    /// reported the first time they are imported
+    failed: HashSet<String>,
+}
+
+impl Loader {
+    pub fn new(tc_env: TcEnv, search_path: Vec<PathBuf>) -> Loader {
+        Loader {
+            search_path,
+            mappings: tc_env.mappings(),
+            tc_env,
+            loading: Vec::new(),
+            failed: HashSet::new(),
+        }
+    }
+
+    /// The type checking environment, containing the modules that have been
+    /// imported so far
+    pub fn tc_env(&self) -> &TcEnv {
+        &self.tc_env
+    }
+
+    /// Load a module from a file that has been added to the codemap, loading
+    /// any modules that it imports beforehand
+    pub fn load_file(
+        &mut self,
+        codemap: &mut CodeMap,
+        file: &FileMap,
+    ) -> Result<core::Module, Vec<Diagnostic>> {
+        // The file might be imported by one of the modules that it imports,
+        // but only if it can be found in the search path
+        let name = self.module_name(file);
+        if let Some(ref name) = name {
+            self.loading.push(name.clone());
+        }
+        let result = self.desugar_file(codemap, file);
+        if name.is_some() {
+            self.loading.pop();
+        }
+
+        let (raw_module, diagnostics) = result?;
+        let (module, errors) = semantics::check_module(&self.tc_env, &raw_module);
+        type_check_result(module, diagnostics, errors)
+    }
+
+    /// Load a module from a file that has been added to the codemap, adding
+    /// it to the type checking environment under the given name
+    ///
+    /// Unlike `load_file`, the items of the module remain in the environment
+    /// afterwards, allowing their types to be looked up.
+    pub fn load_module(
+        &mut self,
+        codemap: &mut CodeMap,
+        file: &FileMap,
+        name: &str,
+    ) -> Result<core::Module, Vec<Diagnostic>> {
+        let (_, module, diagnostics) = self.analyse_module(codemap, file, name)?;
+        if diagnostics.is_empty() {
+            Ok(module)
+        } else {
+            Err(diagnostics)
+        }
+    }
+
+    /// Load a module in the same way as `load_module`, returning both the
+    /// desugared and the elaborated module, along with the diagnostics that
+    /// were found
+    ///
+    /// The elaborated module is returned even if it contains errors, so long as
+    /// the modules that it imports could be loaded. The desugared module
+    /// shares its binders with the elaborated module, allowing the names in
+    /// the source code to be resolved to the items that they refer to.
+    pub fn analyse_module(
+        &mut self,
+        codemap: &mut CodeMap,
+        file: &FileMap,
+        name: &str,
+    ) -> Result<(raw::Module, core::Module, Vec<Diagnostic>), Vec<Diagnostic>> {
+        self.loading.push(name.to_owned());
+        let result = self.desugar_file(codemap, file);
+        self.loading.pop();
+
+        let (raw_module, mut diagnostics) = result?;
+        let (module, errors) = semantics::check_imported_module(&mut self.tc_env, name, &raw_module);
+        diagnostics.extend(errors.iter().map(TypeError::to_diagnostic));
+        Ok((raw_module, module, diagnostics))
+    }
+
+    /// Parse and desugar a file, loading the modules that it imports
+    ///
+    /// The parts of the file that could not be parsed are left as errors in
+    /// the desugared module, and are returned alongside it as diagnostics, so
+    /// that the rest of the module can still be checked.
+    fn desugar_file(
+        &mut self,
+        codemap: &mut CodeMap,
+        file: &FileMap,
+    ) -> Result<(raw::Module, Vec<Diagnostic>), Vec<Diagnostic>> {
+        let (concrete_module, parse_errors) = parse::module(file);
+        let mut diagnostics = parse_errors
+            .iter()
+            .map(|err| err.to_diagnostic())
+            .collect::<Vec<_>>();
+
+        // Names from previously imported modules must not leak into scope, so
+        // we always start from the names that were there originally
+        let mut desugar_env = DesugarEnv::new(self.mappings.clone());
+
+        if let concrete::Module::Valid { ref items, .. } = concrete_module {
+            for item in items {
+                if let concrete::Item::Import { span, ref path, .. } = *item {
+                    match self.load_import(codemap, span, path) {
+                        Ok(module) => {
+                            let entry = self.tc_env.get_module(&module).expect("module not loaded");
+                            for &(ref label, ref free_var) in &entry.constructors {
+                                desugar_env.import_constructor(&label.0, free_var.clone());
+                            }
+                        },
+                        Err(import_diagnostics) => {
+                            diagnostics.extend(import_diagnostics);
+                            return Err(diagnostics);
+                        },
+                    }
+                }
+            }
+        }
+
+        Ok((concrete_module.desugar(&desugar_env), diagnostics))
+    }
+
+    /// Load an imported module, unless it has been loaded already, returning
+    /// the name that it was loaded under
+    fn load_import(
+        &mut self,
+        codemap: &mut CodeMap,
+        span: ByteSpan,
+        path: &[(ByteIndex, String)],
+    ) -> Result<String, Vec<Diagnostic>> {
+        let module = path
+            .iter()
+            .map(|&(_, ref name)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(".");
+
+        if self.failed.contains(&module) {
+            return Err(vec![LoadError::ImportFailed { span, module }.to_diagnostic()]);
+        }
+        if self.tc_env.get_module(&module).is_some() {
+            return Ok(module);
+        }
+        if let Some(index) = self.loading.iter().position(|other| *other == module) {
+            let mut cycle = self.loading[index..].to_vec();
+            cycle.push(module.clone());
+            return Err(vec![
+                LoadError::ImportCycle {
+                    span,
+                    module,
+                    cycle,
+                }.to_diagnostic(),
+            ]);
+        }
+
+        let file_path = match self.resolve(path) {
+            Some(file_path) => file_path,
+            None => {
+                return Err(vec![
+                    LoadError::ModuleNotFound {
+                        span,
+                        module,
+                        search_path: self.search_path.clone(),
+                    }.to_diagnostic(),
+                ]);
+            },
+        };
+        let file = match codemap.add_filemap_from_disk(&file_path) {
+            Ok(file) => file,
+            Err(err) => {
+                return Err(vec![
+                    LoadError::ReadFailed {
+                        span,
+                        path: file_path.display().to_string(),
+                        message: err.to_string(),
+                    }.to_diagnostic(),
+                ]);
+            },
+        };
+
+        self.loading.push(module.clone());
+        let result = self.desugar_file(codemap, &file).and_then(|(raw_module, diagnostics)| {
+            let (module, errors) =
+                semantics::check_imported_module(&mut self.tc_env, &module, &raw_module);
+            type_check_result(module, diagnostics, errors)
+        });
+        self.loading.pop();
+
+        match result {
+            Ok(_) => Ok(module),
+            Err(diagnostics) => {
+                self.failed.insert(module);
+                Err(diagnostics)
+            },
+        }
+    }
+
+    /// The name that a file would be imported by, if it can be found in one
+    /// of the directories of the search path
+    fn module_name(&self, file: &FileMap) -> Option<String> {
+        let file_path = match *file.name() {
+            FileName::Real(ref file_path) => file_path.canonicalize().ok()?,
+            FileName::Virtual(_) => return None,
+        };
+
+        self.search_path
+            .iter()
+            .filter_map(|dir| {
+                let dir = dir.canonicalize().ok()?;
+                let module_path = file_path.strip_prefix(dir).ok()?.with_extension("");
+                let names = module_path
+                    .iter()
+                    .map(|name| name.to_str())
+                    .collect::<Option<Vec<_>>>()?;
+                Some(names.join("."))
+            }).next()
+    }
+
+    /// Find the file that a module path refers to, looking through each
+    /// directory in the search path in turn
+    fn resolve(&self, path: &[(ByteIndex, String)]) -> Option<PathBuf> {
+        self.search_path
+            .iter()
+            .map(|dir| {
+                let mut file_path = dir.clone();
+                file_path.extend(path.iter().map(|&(_, ref name)| name));
+                file_path.set_extension("pi");
+                file_path
+            }).find(|file_path| file_path.is_file())
+    }
+}
+
+/// Combine the diagnostics from parsing a module with the errors found while
+/// type checking it, returning the module only if there were none
+fn type_check_result(
+    module: core::Module,
+    mut diagnostics: Vec<Diagnostic>,
+    errors: Vec<TypeError>,
+) -> Result<core::Module, Vec<Diagnostic>> {
+    diagnostics.extend(errors.iter().map(TypeError::to_diagnostic));
+    if diagnostics.is_empty() {
+        Ok(module)
+    } else {
+        Err(diagnostics)
+    }
+}
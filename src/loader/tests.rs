@@ -0,0 +1,147 @@
+use codespan::CodeMap;
+use codespan_reporting;
+use codespan_reporting::termcolor::{ColorChoice, StandardStream};
+use std::path::PathBuf;
+
+use super::*;
+
+fn fixtures_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/loader/fixtures")
+}
+
+fn load_fixture(codemap: &mut CodeMap, name: &str) -> Result<core::Module, Vec<Diagnostic>> {
+    let mut loader = Loader::new(TcEnv::default(), vec![fixtures_dir()]);
+    let file = codemap
+        .add_filemap_from_disk(fixtures_dir().join(name))
+        .unwrap();
+
+    loader.load_file(codemap, &file)
+}
+
+#[test]
+fn load_file() {
+    let mut codemap = CodeMap::new();
+
+    if let Err(diagnostics) = load_fixture(&mut codemap, "main.pi") {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for diagnostic in diagnostics {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &diagnostic).unwrap();
+        }
+        panic!("load error!")
+    }
+}
+
+#[test]
+fn load_file_module_not_found() {
+    let mut codemap = CodeMap::new();
+
+    let diagnostics = match load_fixture(&mut codemap, "missing.pi") {
+        Ok(_) => panic!("expected error"),
+        Err(diagnostics) => diagnostics,
+    };
+
+    assert_eq!(diagnostics.len(), 1);
+    assert!(
+        diagnostics[0]
+            .message
+            .starts_with("unable to find the module `does.not.exist`")
+    );
+}
+
+#[test]
+fn load_file_import_cycle() {
+    let mut codemap = CodeMap::new();
+
+    let diagnostics = match load_fixture(&mut codemap, "cyc-a.pi") {
+        Ok(_) => panic!("expected error"),
+        Err(diagnostics) => diagnostics,
+    };
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "the module `cyc-a` imports itself by way of cyc-a -> cyc-b -> cyc-a",
+    );
+}
+
+#[test]
+fn load_file_import_level_metas() {
+    let mut codemap = CodeMap::new();
+    let mut loader = Loader::new(TcEnv::default(), vec![fixtures_dir()]);
+    let file = codemap
+        .add_filemap_from_disk(fixtures_dir().join("main.pi"))
+        .unwrap();
+
+    if let Err(diagnostics) = loader.load_file(&mut codemap, &file) {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for diagnostic in diagnostics {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &diagnostic).unwrap();
+        }
+        panic!("load error!")
+    }
+
+    // `data Nat : Type` is elaborated with a level metavariable, which must
+    // be solved in the type of the record that the module is bound to
+    let entry = loader.tc_env().get_module("lib.nat").unwrap();
+    assert_eq!(core::RcTerm::from(&*entry.ty).level_metas(), vec![]);
+}
+
+#[test]
+fn load_file_match_imported_constructors() {
+    let mut codemap = CodeMap::new();
+
+    if let Err(diagnostics) = load_fixture(&mut codemap, "match-import.pi") {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for diagnostic in diagnostics {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &diagnostic).unwrap();
+        }
+        panic!("load error!")
+    }
+}
+
+#[test]
+fn load_file_failed_import_cached() {
+    let mut codemap = CodeMap::new();
+    let mut loader = Loader::new(TcEnv::default(), vec![fixtures_dir()]);
+    let file = codemap
+        .add_filemap_from_disk(fixtures_dir().join("import-broken.pi"))
+        .unwrap();
+
+    let diagnostics = match loader.load_file(&mut codemap, &file) {
+        Ok(_) => panic!("expected error"),
+        Err(diagnostics) => diagnostics,
+    };
+
+    assert_eq!(diagnostics.len(), 1);
+    assert!(
+        diagnostics[0]
+            .message
+            .starts_with("unable to find the module `does.not.exist`")
+    );
+
+    // The module that failed to load is not loaded again
+    let diagnostics = match loader.load_file(&mut codemap, &file) {
+        Ok(_) => panic!("expected error"),
+        Err(diagnostics) => diagnostics,
+    };
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].message, "the module `broken` failed to load");
+}
+
+#[test]
+fn load_file_parse_error() {
+    let mut codemap = CodeMap::new();
+
+    let diagnostics = match load_fixture(&mut codemap, "parse-error.pi") {
+        Ok(_) => panic!("expected error"),
+        Err(diagnostics) => diagnostics,
+    };
+
+    // The type error after the parse error is reported as well
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(
+        diagnostics[1].message,
+        "found a numeric literal, but expected a type `String`",
+    );
+}
@@ -0,0 +1,501 @@
+use codespan::{ByteIndex, ByteOffset, ByteSpan, CodeMap, FileMap, FileName, LineIndex};
+use codespan_reporting::{self, LabelStyle, Severity};
+use lsp_types::{
+    CompletionItem, Diagnostic, DiagnosticSeverity, Hover, HoverContents, Location, MarkupContent,
+    MarkupKind, Position, Range, Url,
+};
+use im::HashMap;
+use moniker::{Embed, FreeVar, Var};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use loader::Loader;
+use semantics::{DeclarationEnv, GlobalEnv, TcEnv, WarningEnv};
+use syntax::core;
+use syntax::cst::{SyntaxNode, SyntaxToken, TokenKind};
+use syntax::parse::{self, Token};
+use syntax::raw;
+use syntax::translation::Resugar;
+
+/// The results of analysing a document
+pub struct Analysis {
+    file: Arc<FileMap>,
+    /// The lossless syntax tree of the document, used to find what is under
+    /// the cursor
+    tree: SyntaxNode,
+    /// The desugared module, used to find the variables that the names in
+    /// the document refer to, if the modules that it imports could be loaded
+    raw_module: Option<raw::Module>,
+    /// The elaborated module, including any items that failed to type check
+    core_module: Option<core::Module>,
+    /// The type checking environment, containing the items of the module
+    tc_env: TcEnv,
+    /// The names that are in scope before any items are checked
+    mappings: HashMap<String, FreeVar<String>>,
+    diagnostics: Vec<codespan_reporting::Diagnostic>,
+}
+
+impl Analysis {
+    /// Parse and type check a document, loading any modules that it imports
+    /// from the directory that it is in
+    pub fn new(uri: &Url, src: String) -> Analysis {
+        let mut codemap = CodeMap::new();
+        let path = uri.to_file_path().ok();
+        let name = match path {
+            Some(ref path) => FileName::real(path.clone()),
+            None => FileName::virtual_(uri.to_string()),
+        };
+        let file = codemap.add_filemap(name, src);
+
+        let (tree, _) = parse::module_tree(&file);
+
+        let search_path = path
+            .as_ref()
+            .and_then(|path| path.parent())
+            .map_or_else(Vec::new, |dir| vec![PathBuf::from(dir)]);
+        let tc_env = TcEnv::default();
+        let mappings = tc_env.mappings();
+        let mut loader = Loader::new(tc_env, search_path);
+        let module_name = path
+            .as_ref()
+            .and_then(|path| path.file_stem())
+            .map_or("main".to_owned(), |stem| stem.to_string_lossy().into_owned());
+
+        let (raw_module, core_module, mut diagnostics) =
+            match loader.analyse_module(&mut codemap, &file, &module_name) {
+                Ok((raw_module, core_module, diagnostics)) => {
+                    (Some(raw_module), Some(core_module), diagnostics)
+                },
+                Err(diagnostics) => (None, None, diagnostics),
+            };
+        let tc_env = loader.tc_env().clone();
+        diagnostics.extend(
+            tc_env
+                .take_warnings()
+                .iter()
+                .map(|warning| warning.to_diagnostic()),
+        );
+
+        Analysis {
+            file,
+            tree,
+            raw_module,
+            core_module,
+            tc_env,
+            mappings,
+            diagnostics,
+        }
+    }
+
+    /// The errors and warnings found in the document
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let span = self.file.span();
+
+        self.diagnostics
+            .iter()
+            .map(|diagnostic| {
+                // Labels in other files, like the modules that this one
+                // imports, can't be shown in this document
+                let labels = diagnostic
+                    .labels
+                    .iter()
+                    .filter(|label| span.contains(label.span))
+                    .collect::<Vec<_>>();
+                let primary = labels
+                    .iter()
+                    .find(|label| label.style == LabelStyle::Primary)
+                    .or_else(|| labels.first());
+
+                let range = primary.map_or(Range::default(), |label| self.range(label.span));
+                let mut message = diagnostic.message.clone();
+                if let Some(label_message) = primary.and_then(|label| label.message.as_ref()) {
+                    message.push('\n');
+                    message.push_str(label_message);
+                }
+                let severity = match diagnostic.severity {
+                    Severity::Bug | Severity::Error => DiagnosticSeverity::ERROR,
+                    Severity::Warning => DiagnosticSeverity::WARNING,
+                    Severity::Note => DiagnosticSeverity::INFORMATION,
+                    Severity::Help => DiagnosticSeverity::HINT,
+                };
+
+                Diagnostic {
+                    range,
+                    severity: Some(severity),
+                    source: Some("pikelet".to_owned()),
+                    message,
+                    ..Diagnostic::default()
+                }
+            }).collect()
+    }
+
+    /// The type of the name under the cursor, along with its documentation
+    pub fn hover(&self, position: Position) -> Option<Hover> {
+        let (token, free_var) = self.name_at(position)?;
+        let doc = self.lookup(&free_var)?;
+        let ty = self.tc_env.get_declaration(&free_var)?;
+        let ty = ty.resugar(self.tc_env.resugar_env());
+
+        let mut value = format!("```pikelet\n{} : {}\n```", token.text(), ty);
+        if !doc.is_empty() {
+            value.push_str("\n\n");
+            value.push_str(&doc);
+        }
+
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(self.range(token.span())),
+        })
+    }
+
+    /// The location of the item that the name under the cursor refers to
+    pub fn definition(&self, uri: &Url, position: Position) -> Option<Location> {
+        let (_, free_var) = self.name_at(position)?;
+        let raw_module = self.raw_module.as_ref()?;
+
+        // Definitions are preferred over the declarations that precede them
+        let mut span = None;
+        for item in &raw_module.items {
+            match *item {
+                raw::Item::Definition {
+                    label_span,
+                    ref binder,
+                    ..
+                } if binder.0 == free_var => {
+                    span = Some(label_span);
+                    break;
+                },
+                raw::Item::Declaration {
+                    label_span,
+                    ref binder,
+                    ..
+                }
+                | raw::Item::Import {
+                    label_span,
+                    ref binder,
+                    ..
+                } if binder.0 == free_var => span = Some(label_span),
+                raw::Item::Data {
+                    label_span,
+                    ref binder,
+                    ref constructors,
+                    ..
+                } => {
+                    let found = constructors
+                        .iter()
+                        .map(|constructor| (constructor.label_span, &constructor.binder))
+                        .chain(Some((label_span, binder)))
+                        .find(|&(_, binder)| binder.0 == free_var);
+                    if let Some((label_span, _)) = found {
+                        span = Some(label_span);
+                        break;
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        Some(Location::new(uri.clone(), self.range(span?)))
+    }
+
+    /// The names that are in scope at the top level of the document
+    pub fn completions(&self) -> Vec<CompletionItem> {
+        let mut names = Vec::new();
+        if let Some(ref core_module) = self.core_module {
+            for item in &core_module.items {
+                let binders = match *item {
+                    core::Item::Declaration { ref binder, .. }
+                    | core::Item::Definition { ref binder, .. }
+                    | core::Item::Error { ref binder, .. } => vec![binder],
+                    core::Item::Data {
+                        ref binder,
+                        ref constructors,
+                        ..
+                    } => Some(binder)
+                        .into_iter()
+                        .chain(constructors.iter().map(|constructor| &constructor.binder))
+                        .collect(),
+                };
+                names.extend(binders.into_iter().filter_map(|binder| {
+                    let pretty_name = binder.0.pretty_name.as_ref()?;
+                    Some((pretty_name.clone(), binder.0.clone()))
+                }));
+            }
+        }
+        // The items of the document shadow the names that were already in
+        // scope, and the sort keeps them first
+        names.extend(
+            self.mappings
+                .iter()
+                .map(|(name, free_var)| (name.clone(), free_var.clone())),
+        );
+        names.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
+        names.dedup_by(|lhs, rhs| lhs.0 == rhs.0);
+
+        names
+            .into_iter()
+            .map(|(name, free_var)| {
+                let detail = self
+                    .tc_env
+                    .get_declaration(&free_var)
+                    .map(|ty| ty.resugar(self.tc_env.resugar_env()).to_string());
+
+                CompletionItem {
+                    label: name,
+                    detail,
+                    ..CompletionItem::default()
+                }
+            }).collect()
+    }
+
+    /// Find the documentation of a variable that was bound at the top level of
+    /// the document, or that was in scope beforehand
+    fn lookup(&self, free_var: &FreeVar<String>) -> Option<String> {
+        if let Some(ref core_module) = self.core_module {
+            for item in &core_module.items {
+                match *item {
+                    core::Item::Declaration {
+                        ref binder,
+                        ref doc,
+                        ..
+                    }
+                    | core::Item::Definition {
+                        ref binder,
+                        ref doc,
+                        ..
+                    }
+                    | core::Item::Error {
+                        ref binder,
+                        ref doc,
+                        ..
+                    } if binder.0 == *free_var => return Some(doc.clone()),
+                    core::Item::Data {
+                        ref binder,
+                        ref doc,
+                        ref constructors,
+                        ..
+                    } => {
+                        if binder.0 == *free_var {
+                            return Some(doc.clone());
+                        }
+                        for constructor in constructors {
+                            if constructor.binder.0 == *free_var {
+                                return Some(constructor.doc.clone());
+                            }
+                        }
+                    },
+                    core::Item::Declaration { .. }
+                    | core::Item::Definition { .. }
+                    | core::Item::Error { .. } => {},
+                }
+            }
+        }
+
+        if self.mappings.values().any(|global| global == free_var) {
+            Some(String::new())
+        } else {
+            None
+        }
+    }
+
+    /// Find the name under the cursor, along with the variable that it refers
+    /// to in the desugared module
+    ///
+    /// Names that are bound locally, for example by a lambda or a case
+    /// pattern, are not free in the desugared module, so they are skipped.
+    fn name_at(&self, position: Position) -> Option<(SyntaxToken, FreeVar<String>)> {
+        let index = self.byte_index(position)?;
+        let node = self.tree.covering_node(index);
+        let token = node.tokens().into_iter().find(|token| {
+            let span = token.span();
+            span.start() <= index && index < span.end()
+        })?;
+
+        match *token.kind() {
+            TokenKind::Token(Token::Ident(_)) => {},
+            _ => return None,
+        }
+
+        match var_at_item(self.raw_module.as_ref()?, index)? {
+            Var::Free(free_var) => Some((token, free_var)),
+            Var::Bound(_) => None,
+        }
+    }
+
+    fn byte_index(&self, position: Position) -> Option<ByteIndex> {
+        let line_span = self.file.line_span(LineIndex(position.line)).ok()?;
+        let line = self.file.src_slice(line_span).ok()?;
+
+        // Positions are measured in UTF-16 code units
+        let mut character = 0;
+        for (offset, ch) in line.char_indices() {
+            if character >= position.character {
+                return Some(line_span.start() + ByteOffset::from_str(&line[..offset]));
+            }
+            character += ch.len_utf16() as u32;
+        }
+        Some(line_span.end())
+    }
+
+    fn position(&self, index: ByteIndex) -> Position {
+        let line = self.file.find_line(index).unwrap();
+        let line_start = self.file.line_byte_index(line).unwrap();
+        let prefix = self.file.src_slice(ByteSpan::new(line_start, index)).unwrap();
+
+        Position::new(line.0, prefix.encode_utf16().count() as u32)
+    }
+
+    fn range(&self, span: ByteSpan) -> Range {
+        Range::new(self.position(span.start()), self.position(span.end()))
+    }
+}
+
+/// Returns `true` if the span contains the byte index
+fn contains(span: ByteSpan, index: ByteIndex) -> bool {
+    span.start() <= index && index < span.end()
+}
+
+/// Find the variable at a byte index in the items of a desugared module,
+/// including the names of the items themselves
+fn var_at_item(module: &raw::Module, index: ByteIndex) -> Option<Var<String>> {
+    for item in &module.items {
+        let (binders, terms) = match *item {
+            raw::Item::Declaration {
+                label_span,
+                ref binder,
+                ref term,
+                ..
+            }
+            | raw::Item::Definition {
+                label_span,
+                ref binder,
+                ref term,
+                ..
+            } => (vec![(label_span, binder)], vec![term]),
+            raw::Item::Data {
+                label_span,
+                ref binder,
+                ref params,
+                ref term,
+                ref constructors,
+                ..
+            } => (
+                Some((label_span, binder))
+                    .into_iter()
+                    .chain(
+                        constructors
+                            .iter()
+                            .map(|constructor| (constructor.label_span, &constructor.binder)),
+                    ).collect(),
+                params
+                    .iter()
+                    .map(|&(_, ref ann)| ann)
+                    .chain(Some(term))
+                    .chain(constructors.iter().map(|constructor| &constructor.term))
+                    .collect(),
+            ),
+            raw::Item::Import {
+                label_span,
+                ref binder,
+                ..
+            } => (vec![(label_span, binder)], vec![]),
+            raw::Item::Error(_) => continue,
+        };
+
+        for (label_span, binder) in binders {
+            if contains(label_span, index) {
+                return Some(Var::Free(binder.0.clone()));
+            }
+        }
+        for term in terms {
+            if let Some(var) = var_at(term, index) {
+                return Some(var);
+            }
+        }
+    }
+
+    None
+}
+
+/// Find the variable at a byte index in a desugared term
+///
+/// The scopes in the term are not unbound, so variables that were bound
+/// locally are returned as `Var::Bound`.
+fn var_at(term: &raw::RcTerm, index: ByteIndex) -> Option<Var<String>> {
+    if !contains(term.span(), index) {
+        return None;
+    }
+
+    match *term.inner {
+        raw::Term::Var(_, ref var, _) => Some(var.clone()),
+        raw::Term::Universe(_, _)
+        | raw::Term::Literal(_)
+        | raw::Term::Hole(_)
+        | raw::Term::Refl(_)
+        | raw::Term::Error(_) => None,
+        raw::Term::Extern(_, _, _, ref ty) => var_at(ty, index),
+        raw::Term::Ann(ref expr, ref ty) => var_at(expr, index).or_else(|| var_at(ty, index)),
+        raw::Term::Pi(_, _, _, ref scope) | raw::Term::Lam(_, _, _, ref scope) => {
+            let (_, Embed(ref ann)) = scope.unsafe_pattern;
+            var_at(ann, index).or_else(|| var_at(&scope.unsafe_body, index))
+        },
+        raw::Term::App(ref head, _, ref arg) => var_at(head, index).or_else(|| var_at(arg, index)),
+        raw::Term::Eq(ref lhs, ref rhs) => var_at(lhs, index).or_else(|| var_at(rhs, index)),
+        raw::Term::If(_, ref cond, ref if_true, ref if_false) => var_at(cond, index)
+            .or_else(|| var_at(if_true, index))
+            .or_else(|| var_at(if_false, index)),
+        raw::Term::J(_, ref motive, ref base, ref proof) => var_at(motive, index)
+            .or_else(|| var_at(base, index))
+            .or_else(|| var_at(proof, index)),
+        raw::Term::RecordType(_, ref scope) | raw::Term::Record(_, ref scope) => scope
+            .unsafe_pattern
+            .unsafe_patterns
+            .iter()
+            .filter_map(|&(_, _, Embed(ref term))| var_at(term, index))
+            .next(),
+        raw::Term::Proj(_, ref expr, _, _) => var_at(expr, index),
+        raw::Term::Case(_, ref head, ref clauses) => var_at(head, index).or_else(|| {
+            clauses
+                .iter()
+                .filter_map(|clause| {
+                    var_at_pattern(&clause.unsafe_pattern, index)
+                        .or_else(|| var_at(&clause.unsafe_body, index))
+                }).next()
+        }),
+        raw::Term::Array(_, ref elems) => {
+            elems.iter().filter_map(|elem| var_at(elem, index)).next()
+        },
+        raw::Term::Let(_, ref scope) => {
+            let (_, Embed((ref ann, ref bind))) = scope.unsafe_pattern;
+            var_at(ann, index)
+                .or_else(|| var_at(bind, index))
+                .or_else(|| var_at(&scope.unsafe_body, index))
+        },
+    }
+}
+
+/// Find the variable at a byte index in a desugared pattern
+fn var_at_pattern(pattern: &raw::RcPattern, index: ByteIndex) -> Option<Var<String>> {
+    if !contains(pattern.span(), index) {
+        return None;
+    }
+
+    match *pattern.inner {
+        raw::Pattern::Ann(ref pattern, Embed(ref ty)) => {
+            var_at_pattern(pattern, index).or_else(|| var_at(ty, index))
+        },
+        raw::Pattern::Var(_, Embed(ref var), _) => Some(var.clone()),
+        // The name of the constructor is the only part of the pattern that
+        // isn't covered by its arguments
+        raw::Pattern::Constr(_, Embed(ref var), ref args) => Some(
+            args.iter()
+                .filter_map(|arg| var_at_pattern(arg, index))
+                .next()
+                .unwrap_or_else(|| var.clone()),
+        ),
+        raw::Pattern::Binder(_, _) | raw::Pattern::Literal(_) | raw::Pattern::Error(_) => None,
+    }
+}
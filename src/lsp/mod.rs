@@ -0,0 +1,322 @@
+//! A language server for Pikelet
+//!
+//! The server speaks the [Language Server Protocol] over a pair of streams,
+//! usually the standard input and output of the process. Documents are
+//! analysed in full whenever they are opened or changed, publishing any
+//! diagnostics that were found, and the results of the analysis are then used
+//! to answer requests for hover information, definitions and completions.
+//!
+//! [Language Server Protocol]: https://microsoft.github.io/language-server-protocol/
+
+use failure::Error;
+use lsp_types::notification::{self, Notification};
+use lsp_types::request::{self, Request};
+use lsp_types::{
+    CompletionOptions, CompletionResponse, GotoDefinitionResponse, HoverProviderCapability,
+    InitializeResult, OneOf, PublishDiagnosticsParams, ServerCapabilities, ServerInfo,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use serde_json::{self, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+mod analysis;
+#[cfg(test)]
+mod tests;
+
+use self::analysis::Analysis;
+
+/// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Run the language server, reading messages from the client until it asks
+/// the server to exit, or until the input is closed
+pub fn run<R: BufRead, W: Write>(mut input: R, output: W) -> Result<(), Error> {
+    let mut server = Server::new(output);
+
+    while let Some(message) = read_message(&mut input)? {
+        let is_running = match message {
+            Ok(message) => server.handle(message)?,
+            // We don't know which request the message was, so the error is
+            // reported without an id
+            Err(error) => {
+                server.respond(&Value::Null, Err(error))?;
+                true
+            },
+        };
+        if !is_running {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Read a message from the client, returning `None` if the input was closed
+///
+/// Messages that are malformed are returned as errors to respond with, so
+/// that the server can carry on with the messages that follow them.
+fn read_message<R: BufRead>(input: &mut R) -> Result<Option<Result<Value, ResponseError>>, Error> {
+    let mut content_length = None;
+    let mut header_error = None;
+
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        let mut parts = header.splitn(2, ": ");
+        if let (Some("Content-Length"), Some(value)) = (parts.next(), parts.next()) {
+            match value.parse::<usize>() {
+                Ok(value) => content_length = Some(value),
+                Err(error) => header_error = Some(format!("invalid `Content-Length`: {}", error)),
+            }
+        }
+    }
+
+    let content_length = match (header_error, content_length) {
+        (Some(message), _) => return Ok(Some(Err(ResponseError::new(INVALID_REQUEST, message)))),
+        (None, None) => {
+            let message = "message without a `Content-Length` header";
+            return Ok(Some(Err(ResponseError::new(INVALID_REQUEST, message))));
+        },
+        (None, Some(content_length)) => content_length,
+    };
+    let mut content = vec![0; content_length];
+    input.read_exact(&mut content)?;
+
+    Ok(Some(
+        serde_json::from_slice(&content)
+            .map_err(|error| ResponseError::new(PARSE_ERROR, error.to_string())),
+    ))
+}
+
+/// Write a message to the client
+fn write_message<W: Write>(output: &mut W, message: &Value) -> Result<(), Error> {
+    let content = serde_json::to_string(message)?;
+    write!(output, "Content-Length: {}\r\n\r\n{}", content.len(), content)?;
+    output.flush()?;
+
+    Ok(())
+}
+
+/// An error to respond to a request with
+#[derive(Debug)]
+struct ResponseError {
+    code: i64,
+    message: String,
+}
+
+impl ResponseError {
+    fn new<S: Into<String>>(code: i64, message: S) -> ResponseError {
+        ResponseError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+struct Server<W> {
+    output: W,
+    /// The analyses of the documents that are open in the editor
+    documents: HashMap<Url, Analysis>,
+    /// Whether the client has asked the server to shut down
+    is_shutdown: bool,
+}
+
+impl<W: Write> Server<W> {
+    fn new(output: W) -> Server<W> {
+        Server {
+            output,
+            documents: HashMap::new(),
+            is_shutdown: false,
+        }
+    }
+
+    /// Handle a message from the client, returning `false` once the client
+    /// has asked the server to exit
+    fn handle(&mut self, message: Value) -> Result<bool, Error> {
+        if !message.is_object() {
+            let error = ResponseError::new(INVALID_REQUEST, "message is not an object");
+            self.respond(&Value::Null, Err(error))?;
+            return Ok(true);
+        }
+
+        let method = message
+            .get("method")
+            .and_then(Value::as_str)
+            .map(str::to_owned);
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        match (message.get("id"), method) {
+            (Some(id), Some(method)) => {
+                let result = self.handle_request(&method, params);
+                self.respond(id, result)?;
+            },
+            (None, Some(ref method)) if method == notification::Exit::METHOD => return Ok(false),
+            (None, Some(method)) => self.handle_notification(&method, params)?,
+            // The server never sends requests, so there are no responses to
+            // handle
+            (_, None) => {},
+        }
+
+        Ok(true)
+    }
+
+    /// Send the response to a request
+    fn respond(&mut self, id: &Value, result: Result<Value, ResponseError>) -> Result<(), Error> {
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": error.code, "message": error.message },
+            }),
+        };
+
+        write_message(&mut self.output, &response)
+    }
+
+    fn handle_request(&mut self, method: &str, params: Value) -> Result<Value, ResponseError> {
+        if self.is_shutdown {
+            return Err(ResponseError::new(
+                INVALID_REQUEST,
+                "the server has been shut down",
+            ));
+        }
+
+        match method {
+            request::Initialize::METHOD => {
+                self.request::<request::Initialize, _>(params, |_, _| InitializeResult {
+                    capabilities: ServerCapabilities {
+                        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+                            TextDocumentSyncKind::FULL,
+                        )),
+                        hover_provider: Some(HoverProviderCapability::Simple(true)),
+                        definition_provider: Some(OneOf::Left(true)),
+                        completion_provider: Some(CompletionOptions::default()),
+                        ..ServerCapabilities::default()
+                    },
+                    server_info: Some(ServerInfo {
+                        name: "pikelet".to_owned(),
+                        version: Some(env!("CARGO_PKG_VERSION").to_owned()),
+                    }),
+                })
+            },
+            request::Shutdown::METHOD => self.request::<request::Shutdown, _>(params, |server, ()| {
+                server.is_shutdown = true;
+            }),
+            request::HoverRequest::METHOD => {
+                self.request::<request::HoverRequest, _>(params, |server, params| {
+                    let params = params.text_document_position_params;
+                    let analysis = server.documents.get(&params.text_document.uri)?;
+                    analysis.hover(params.position)
+                })
+            },
+            request::GotoDefinition::METHOD => {
+                self.request::<request::GotoDefinition, _>(params, |server, params| {
+                    let params = params.text_document_position_params;
+                    let uri = params.text_document.uri;
+                    let analysis = server.documents.get(&uri)?;
+                    let location = analysis.definition(&uri, params.position)?;
+                    Some(GotoDefinitionResponse::Scalar(location))
+                })
+            },
+            request::Completion::METHOD => {
+                self.request::<request::Completion, _>(params, |server, params| {
+                    let uri = params.text_document_position.text_document.uri;
+                    let analysis = server.documents.get(&uri)?;
+                    Some(CompletionResponse::Array(analysis.completions()))
+                })
+            },
+            _ => Err(ResponseError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown request `{}`", method),
+            )),
+        }
+    }
+
+    /// Decode the parameters of a request, and encode the result of handling
+    /// it
+    fn request<R, F>(&mut self, params: Value, handle: F) -> Result<Value, ResponseError>
+    where
+        R: Request,
+        F: FnOnce(&mut Self, R::Params) -> R::Result,
+    {
+        let params = serde_json::from_value(params)
+            .map_err(|error| ResponseError::new(INVALID_PARAMS, error.to_string()))?;
+
+        Ok(serde_json::to_value(handle(self, params)).unwrap())
+    }
+
+    /// Handle a notification from the client. Notifications that can't be
+    /// decoded are ignored, because there is no way to respond to them.
+    fn handle_notification(&mut self, method: &str, params: Value) -> Result<(), Error> {
+        match method {
+            notification::DidOpenTextDocument::METHOD => {
+                if let Some(params) = decode::<notification::DidOpenTextDocument>(params) {
+                    let document = params.text_document;
+                    self.update(document.uri, document.version, document.text)?;
+                }
+            },
+            notification::DidChangeTextDocument::METHOD => {
+                if let Some(mut params) = decode::<notification::DidChangeTextDocument>(params) {
+                    // We only ask for the full text of documents, so the last
+                    // change contains all of it
+                    if let Some(change) = params.content_changes.pop() {
+                        let document = params.text_document;
+                        self.update(document.uri, document.version, change.text)?;
+                    }
+                }
+            },
+            notification::DidCloseTextDocument::METHOD => {
+                if let Some(params) = decode::<notification::DidCloseTextDocument>(params) {
+                    let uri = params.text_document.uri;
+                    self.documents.remove(&uri);
+                    self.notify::<notification::PublishDiagnostics>(PublishDiagnosticsParams::new(
+                        uri,
+                        Vec::new(),
+                        None,
+                    ))?;
+                }
+            },
+            // Other notifications, like `initialized`, don't need handling
+            _ => {},
+        }
+
+        Ok(())
+    }
+
+    /// Analyse the new contents of a document, publishing its diagnostics
+    fn update(&mut self, uri: Url, version: i32, text: String) -> Result<(), Error> {
+        let analysis = Analysis::new(&uri, text);
+        let diagnostics = analysis.diagnostics();
+        self.documents.insert(uri.clone(), analysis);
+
+        self.notify::<notification::PublishDiagnostics>(PublishDiagnosticsParams::new(
+            uri,
+            diagnostics,
+            Some(version),
+        ))
+    }
+
+    /// Send a notification to the client
+    fn notify<N: Notification>(&mut self, params: N::Params) -> Result<(), Error> {
+        write_message(
+            &mut self.output,
+            &json!({ "jsonrpc": "2.0", "method": N::METHOD, "params": params }),
+        )
+    }
+}
+
+fn decode<N: Notification>(params: Value) -> Option<N::Params> {
+    serde_json::from_value(params).ok()
+}
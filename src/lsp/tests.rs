@@ -0,0 +1,378 @@
+use serde_json::Value;
+use std::io::Cursor;
+
+use super::*;
+
+const URI: &str = "file:///pikelet/test.pi";
+
+/// A client that sends a scripted sequence of messages to the server
+struct Client {
+    input: Vec<u8>,
+    next_id: u64,
+}
+
+impl Client {
+    /// Create a client that has already initialized the server
+    fn new() -> Client {
+        let mut client = Client {
+            input: Vec::new(),
+            next_id: 0,
+        };
+        client.request("initialize", json!({ "capabilities": {} }));
+        client.notify("initialized", json!({}));
+        client
+    }
+
+    fn send(&mut self, message: &Value) {
+        write_message(&mut self.input, message).unwrap();
+    }
+
+    fn send_raw(&mut self, message: &str) {
+        self.input.extend_from_slice(message.as_bytes());
+    }
+
+    /// Send a request, returning its id
+    fn request(&mut self, method: &str, params: Value) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        id
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn open(&mut self, src: &str) {
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": { "uri": URI, "languageId": "pikelet", "version": 1, "text": src },
+            }),
+        );
+    }
+
+    fn request_at(&mut self, method: &str, line: u32, character: u32) -> u64 {
+        self.request(
+            method,
+            json!({
+                "textDocument": { "uri": URI },
+                "position": { "line": line, "character": character },
+            }),
+        )
+    }
+
+    /// Run the server over the messages that were sent, returning the
+    /// messages that it sent back
+    fn finish(mut self) -> Messages {
+        let id = self.request("shutdown", Value::Null);
+        self.notify("exit", Value::Null);
+
+        let mut output = Vec::new();
+        run(Cursor::new(self.input), &mut output).unwrap();
+
+        let mut output = Cursor::new(output);
+        let mut messages = Vec::new();
+        while let Some(message) = read_message(&mut output).unwrap() {
+            messages.push(message.unwrap());
+        }
+
+        let messages = Messages(messages);
+        assert_eq!(messages.response(id)["result"], Value::Null);
+        messages
+    }
+}
+
+struct Messages(Vec<Value>);
+
+impl Messages {
+    fn response(&self, id: u64) -> &Value {
+        self.0
+            .iter()
+            .find(|message| message["id"] == id)
+            .unwrap_or_else(|| panic!("no response to request {}", id))
+    }
+
+    fn result(&self, id: u64) -> &Value {
+        let response = self.response(id);
+        assert_eq!(response["error"], Value::Null, "{}", response);
+        &response["result"]
+    }
+
+    /// The errors that were sent in response to malformed messages
+    fn malformed_errors(&self) -> Vec<&Value> {
+        self.0
+            .iter()
+            .filter(|message| message.get("id") == Some(&Value::Null))
+            .map(|message| &message["error"])
+            .collect()
+    }
+
+    fn notifications(&self, method: &str) -> Vec<&Value> {
+        self.0
+            .iter()
+            .filter(|message| message["method"] == method)
+            .map(|message| &message["params"])
+            .collect()
+    }
+}
+
+fn range(start: (u32, u32), end: (u32, u32)) -> Value {
+    json!({
+        "start": { "line": start.0, "character": start.1 },
+        "end": { "line": end.0, "character": end.1 },
+    })
+}
+
+#[test]
+fn initialize() {
+    let client = Client::new();
+    let messages = client.finish();
+
+    let result = messages.result(0);
+    assert_eq!(result["serverInfo"]["name"], "pikelet");
+    assert_eq!(result["capabilities"]["textDocumentSync"], 1);
+    assert_eq!(result["capabilities"]["hoverProvider"], true);
+    assert_eq!(result["capabilities"]["definitionProvider"], true);
+    assert!(result["capabilities"]["completionProvider"].is_object());
+}
+
+#[test]
+fn unknown_request() {
+    let mut client = Client::new();
+    let id = client.request("pikelet/unknown", Value::Null);
+    let messages = client.finish();
+
+    assert_eq!(messages.response(id)["error"]["code"], METHOD_NOT_FOUND);
+}
+
+#[test]
+fn malformed_messages() {
+    let mut client = Client::new();
+    client.send_raw("Content-Length: 6\r\n\r\n{oops}");
+    client.send_raw("Content-Length: six\r\n\r\n");
+    client.send_raw("Content-Type: application/vscode-jsonrpc\r\n\r\n");
+    client.send(&json!([1, 2, 3]));
+    client.open(SRC);
+    let id = client.request_at("textDocument/hover", 5, 8);
+    let messages = client.finish();
+
+    let codes = messages
+        .malformed_errors()
+        .into_iter()
+        .map(|error| error["code"].clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        codes,
+        vec![
+            json!(PARSE_ERROR),
+            json!(INVALID_REQUEST),
+            json!(INVALID_REQUEST),
+            json!(INVALID_REQUEST),
+        ],
+    );
+    assert_eq!(messages.result(id)["range"], range((5, 7), (5, 9)));
+}
+
+#[test]
+fn diagnostics_ok() {
+    let mut client = Client::new();
+    client.open("id : {a : Type} -> a -> a;\nid x = x;\n");
+    let messages = client.finish();
+
+    let published = messages.notifications("textDocument/publishDiagnostics");
+    assert_eq!(published.len(), 1);
+    assert_eq!(published[0]["uri"], URI);
+    assert_eq!(published[0]["version"], 1);
+    assert_eq!(published[0]["diagnostics"], json!([]));
+}
+
+#[test]
+fn diagnostics_type_error() {
+    let mut client = Client::new();
+    client.open("x : String;\nx = 1;\n");
+    let messages = client.finish();
+
+    let published = messages.notifications("textDocument/publishDiagnostics");
+    assert_eq!(published.len(), 1);
+    let diagnostics = published[0]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(diagnostics[0]["source"], "pikelet");
+    assert_eq!(diagnostics[0]["range"], range((1, 4), (1, 5)));
+}
+
+#[test]
+fn diagnostics_parse_error() {
+    let mut client = Client::new();
+    client.open("x : String;\nx = ;\n");
+    let messages = client.finish();
+
+    let published = messages.notifications("textDocument/publishDiagnostics");
+    let diagnostics = published[0]["diagnostics"].as_array().unwrap();
+    assert!(!diagnostics.is_empty());
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(diagnostics[0]["range"]["start"]["line"], 1);
+}
+
+#[test]
+fn diagnostics_change_and_close() {
+    let mut client = Client::new();
+    client.open("x : String;\nx = 1;\n");
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": [{ "text": "x : String;\nx = \"hello\";\n" }],
+        }),
+    );
+    client.notify("textDocument/didClose", json!({ "textDocument": { "uri": URI } }));
+    let messages = client.finish();
+
+    let published = messages.notifications("textDocument/publishDiagnostics");
+    assert_eq!(published.len(), 3);
+    assert_eq!(published[0]["diagnostics"].as_array().unwrap().len(), 1);
+    assert_eq!(published[1]["version"], 2);
+    assert_eq!(published[1]["diagnostics"], json!([]));
+    assert_eq!(published[2]["diagnostics"], json!([]));
+}
+
+const SRC: &str = "||| The identity function
+id : {a : Type} -> a -> a;
+id x = x;
+
+test : String;
+test = id \"hello\";
+";
+
+#[test]
+fn hover_item() {
+    let mut client = Client::new();
+    client.open(SRC);
+    let id = client.request_at("textDocument/hover", 5, 8);
+    let messages = client.finish();
+
+    let result = messages.result(id);
+    assert_eq!(result["range"], range((5, 7), (5, 9)));
+    let contents = result["contents"]["value"].as_str().unwrap();
+    assert!(contents.starts_with("```pikelet\nid : "), "{}", contents);
+    assert!(contents.ends_with("The identity function"), "{}", contents);
+}
+
+#[test]
+fn hover_global() {
+    let mut client = Client::new();
+    client.open(SRC);
+    let id = client.request_at("textDocument/hover", 4, 8);
+    let messages = client.finish();
+
+    let contents = messages.result(id)["contents"]["value"].as_str().unwrap();
+    assert_eq!(contents, "```pikelet\nString : Type\n```");
+}
+
+#[test]
+fn hover_local() {
+    let mut client = Client::new();
+    client.open(SRC);
+    let id = client.request_at("textDocument/hover", 2, 7);
+    let messages = client.finish();
+
+    assert_eq!(messages.result(id), &Value::Null);
+}
+
+const SHADOWED_SRC: &str = "x : String;
+x = \"hello\";
+
+f : String -> String;
+f x = x;
+
+y : String;
+y = f x;
+";
+
+#[test]
+fn hover_local_shadowing_item() {
+    let mut client = Client::new();
+    client.open(SHADOWED_SRC);
+    // The parameter of `f`, followed by its use in the body
+    let ids = [(4, 2), (4, 6)]
+        .iter()
+        .map(|&(line, character)| {
+            let hover_id = client.request_at("textDocument/hover", line, character);
+            let definition_id = client.request_at("textDocument/definition", line, character);
+            (hover_id, definition_id)
+        }).collect::<Vec<_>>();
+    let item_id = client.request_at("textDocument/hover", 7, 6);
+    let messages = client.finish();
+
+    let contents = messages.result(item_id)["contents"]["value"].as_str().unwrap();
+    assert_eq!(contents, "```pikelet\nx : String\n```");
+    for &(hover_id, definition_id) in &ids {
+        assert_eq!(messages.result(hover_id), &Value::Null);
+        assert_eq!(messages.result(definition_id), &Value::Null);
+    }
+}
+
+#[test]
+fn hover_with_errors() {
+    let mut client = Client::new();
+    client.open("x : String;\nx = 1;\n\ny : String;\ny = x;\n");
+    let id = client.request_at("textDocument/hover", 4, 4);
+    let messages = client.finish();
+
+    let contents = messages.result(id)["contents"]["value"].as_str().unwrap();
+    assert_eq!(contents, "```pikelet\nx : String\n```");
+}
+
+#[test]
+fn definition() {
+    let mut client = Client::new();
+    client.open(SRC);
+    let id = client.request_at("textDocument/definition", 5, 7);
+    let messages = client.finish();
+
+    let result = messages.result(id);
+    assert_eq!(result["uri"], URI);
+    assert_eq!(result["range"]["start"], json!({ "line": 2, "character": 0 }));
+}
+
+#[test]
+fn definition_item_shadowed_by_local() {
+    let mut client = Client::new();
+    client.open(SHADOWED_SRC);
+    let id = client.request_at("textDocument/definition", 7, 6);
+    let messages = client.finish();
+
+    let result = messages.result(id);
+    assert_eq!(result["range"], range((1, 0), (1, 1)));
+}
+
+#[test]
+fn completion() {
+    let mut client = Client::new();
+    client.open(SRC);
+    let id = client.request_at("textDocument/completion", 5, 7);
+    let messages = client.finish();
+
+    let items = messages.result(id).as_array().unwrap();
+    let labels = items
+        .iter()
+        .map(|item| item["label"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert!(labels.contains(&"id"));
+    assert!(labels.contains(&"test"));
+    assert!(labels.contains(&"String"));
+
+    let string = items.iter().find(|item| item["label"] == "String").unwrap();
+    assert_eq!(string["detail"], "Type");
+}
+
+#[test]
+fn shutdown() {
+    let mut client = Client::new();
+    client.request("shutdown", Value::Null);
+    let id = client.request_at("textDocument/hover", 0, 0);
+    let messages = client.finish();
+
+    assert_eq!(messages.response(id)["error"]["code"], INVALID_REQUEST);
+}
@@ -0,0 +1,607 @@
+//! Coverage checking for case expressions
+//!
+//! We check that the patterns of a case expression are exhaustive, and that
+//! each clause could match some value that the previous clauses did not. This
+//! is based on the usefulness algorithm described in [Warnings for pattern
+//! matching](http://moscova.inria.fr/~maranget/papers/warn/index.html).
+//!
+//! The types of the columns are tracked where they are known, so that the
+//! constructors of indexed data types that could never produce a value of the
+//! scrutinee's type are not required to be covered.
+
+use codespan::ByteSpan;
+use im::HashMap;
+use moniker::{Embed, FreeVar, Var};
+
+use syntax::concrete;
+use syntax::core::{self, Literal, RcPattern, RcType, RcValue, Term, Value};
+use syntax::translation::Resugar;
+use syntax::{LevelShift, Plicity};
+
+use semantics::unify::force;
+use semantics::{
+    apply_closure, eval_term, DataEnv, DefinitionEnv, InternalError, MetaEnv, TypeError,
+    WarningEnv,
+};
+
+/// A simplified pattern, used during coverage checking
+#[derive(Debug, Clone, PartialEq)]
+enum Pat {
+    /// Matches any value
+    Wild,
+    /// Matches a constant by structural equality
+    Const(Const),
+    /// Matches a data constructor, along with its arguments
+    Constr(FreeVar<String>, Vec<Pat>),
+}
+
+/// Constants that can appear in patterns
+#[derive(Debug, Clone, PartialEq)]
+enum Const {
+    Literal(Literal),
+    /// A variable whose value could not be determined
+    Var(Var<String>),
+}
+
+/// The head of a pattern that is not a wildcard
+#[derive(Debug, Clone, PartialEq)]
+enum Ctor {
+    Const(Const),
+    Constr(FreeVar<String>),
+}
+
+/// Check the patterns of a case expression on a value of type `ty`, returning
+/// an error if some values are not covered by any of them. Patterns that are
+/// covered by the clauses that came before them are reported as warnings.
+pub fn check_coverage<Env>(
+    env: &Env,
+    span: ByteSpan,
+    ty: &RcType,
+    patterns: &[(ByteSpan, RcPattern)],
+) -> Result<(), TypeError>
+where
+    Env: MetaEnv + DefinitionEnv + DataEnv + WarningEnv,
+{
+    // Patterns that could not be parsed have already been reported, and we
+    // can't know what they would have matched
+    if patterns.iter().any(|&(_, ref pattern)| has_errors(pattern)) {
+        return Ok(());
+    }
+
+    let tys = [Some(ty.clone())];
+    let mut rows = Vec::with_capacity(patterns.len());
+
+    for &(pattern_span, ref pattern) in patterns {
+        let row = vec![lower_pattern(env, pattern)?];
+        if !is_useful(env, &rows, &row, &tys) {
+            env.report_warning(TypeError::UnreachablePattern { span: pattern_span });
+        }
+        rows.push(row);
+    }
+
+    let missing = missing_patterns(env, &rows, &tys);
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(TypeError::NonExhaustivePatterns {
+            span,
+            missing: missing
+                .iter()
+                .map(|row| resugar_pat(env, &row[0], false))
+                .collect(),
+        })
+    }
+}
+
+/// Returns `true` if the pattern contains any poisoned patterns
+fn has_errors(pattern: &RcPattern) -> bool {
+    match *pattern.inner {
+        core::Pattern::Ann(ref pattern, _) => has_errors(pattern),
+        core::Pattern::Binder(_) | core::Pattern::Var(_, _) | core::Pattern::Literal(_) => false,
+        core::Pattern::Constr(_, ref args) => args.iter().any(|&(_, ref arg)| has_errors(arg)),
+        core::Pattern::Error => true,
+    }
+}
+
+/// Simplify an elaborated pattern, looking up the values of any variables
+fn lower_pattern<Env>(env: &Env, pattern: &RcPattern) -> Result<Pat, TypeError>
+where
+    Env: DefinitionEnv + DataEnv,
+{
+    match *pattern.inner {
+        core::Pattern::Ann(ref pattern, _) => lower_pattern(env, pattern),
+        core::Pattern::Binder(_) | core::Pattern::Error => Ok(Pat::Wild),
+        core::Pattern::Var(Embed(ref var), _) => {
+            if let Var::Free(ref free_var) = *var {
+                if let Some(term) = env.get_definition(free_var) {
+                    if let Value::Literal(ref literal) = **eval_term(env, term)?.unfold() {
+                        return Ok(Pat::Const(Const::Literal(literal.clone())));
+                    }
+                }
+            }
+            Ok(Pat::Const(Const::Var(var.clone())))
+        },
+        core::Pattern::Literal(ref literal) => Ok(Pat::Const(Const::Literal(literal.clone()))),
+        core::Pattern::Constr(Embed(ref var), ref args) => match *var {
+            // Constructors of data types that failed to type check have
+            // already been reported, so we assume that they match anything
+            Var::Free(ref free_var) if env.get_constructor_data(free_var).is_none() => {
+                Ok(Pat::Wild)
+            },
+            Var::Free(ref free_var) => Ok(Pat::Constr(
+                free_var.clone(),
+                args.iter()
+                    .map(|&(_, ref arg)| lower_pattern(env, arg))
+                    .collect::<Result<_, _>>()?,
+            )),
+            Var::Bound(_) => Ok(Pat::Const(Const::Var(var.clone()))),
+        },
+    }
+}
+
+/// The plicities of the arguments that a constructor takes, not including
+/// the parameters of its data type
+fn constructor_plicities<Env>(env: &Env, free_var: &FreeVar<String>) -> Vec<Plicity>
+where
+    Env: DataEnv,
+{
+    let params = env
+        .get_constructor_data(free_var)
+        .and_then(|data_var| env.get_data(data_var))
+        .map_or(0, |data| data.params);
+
+    let mut plicities = Vec::new();
+    let ty = env.get_declaration(free_var).map(|ty| &*ty.unfold().inner);
+    if let Some(&Value::Pi(plicity, _, _, ref closure)) = ty {
+        // The remaining parameters are still in the body of the closure
+        plicities.push(plicity);
+        let mut ty = closure.term.unsafe_body.clone();
+        loop {
+            ty = match *ty.inner {
+                Term::Pi(plicity, _, ref scope) => {
+                    plicities.push(plicity);
+                    scope.unsafe_body.clone()
+                },
+                _ => break,
+            };
+        }
+    }
+
+    plicities.split_off(params.min(plicities.len()))
+}
+
+fn ctor_arity<Env>(env: &Env, ctor: &Ctor) -> usize
+where
+    Env: DataEnv,
+{
+    match *ctor {
+        Ctor::Const(_) => 0,
+        Ctor::Constr(ref free_var) => constructor_plicities(env, free_var).len(),
+    }
+}
+
+/// The types of the arguments of a constructor, when it is used to match on
+/// a value of type `ty`, or `None` if the constructor can never produce a
+/// value of that type
+///
+/// The types are left unknown if they can't be worked out, in which case the
+/// arguments are checked without them.
+fn ctor_arg_tys<Env>(env: &Env, ctor: &Ctor, ty: &Option<RcType>) -> Option<Vec<Option<RcType>>>
+where
+    Env: MetaEnv + DefinitionEnv + DataEnv,
+{
+    let arity = ctor_arity(env, ctor);
+    match (ctor, ty) {
+        (&Ctor::Constr(ref free_var), &Some(ref ty)) => {
+            match instantiate_constructor(env, free_var, ty, arity) {
+                Ok(arg_tys) => arg_tys,
+                Err(_) => Some(vec![None; arity]),
+            }
+        },
+        (_, _) => Some(vec![None; arity]),
+    }
+}
+
+/// Instantiate the type of a constructor with the parameters of `ty`,
+/// returning the types of its arguments if the indices of the type it
+/// returns could be the same as those of `ty`
+fn instantiate_constructor<Env>(
+    env: &Env,
+    free_var: &FreeVar<String>,
+    ty: &RcType,
+    arity: usize,
+) -> Result<Option<Vec<Option<RcType>>>, InternalError>
+where
+    Env: MetaEnv + DefinitionEnv + DataEnv,
+{
+    let unknown = Ok(Some(vec![None; arity]));
+    let data_var = env.get_constructor_data(free_var);
+    let (data_var, constr_ty) = match (data_var, env.get_declaration(free_var)) {
+        (Some(data_var), Some(constr_ty)) => (data_var, constr_ty),
+        (_, _) => return unknown,
+    };
+    let params = env.get_data(data_var).map_or(0, |data| data.params);
+    let ty = force(env, ty)?;
+    let spine = match ty.free_var_app() {
+        Some((ty_var, LevelShift(0), spine)) if ty_var == data_var && spine.len() >= params => {
+            spine
+        },
+        _ => return unknown,
+    };
+
+    // Start with the arguments as unknown variables, finding the values of
+    // those that are determined by the indices of the scrutinee's type
+    let arg_vars = (0..arity).map(|_| FreeVar::fresh(None)).collect::<Vec<_>>();
+    let args = arg_vars
+        .iter()
+        .map(|arg_var| RcValue::from(Value::var(Var::Free(arg_var.clone()), 0)))
+        .collect::<Vec<_>>();
+    let return_ty = match apply_constructor_ty(env, constr_ty, &spine[..params], &args)? {
+        Some((_, return_ty)) => force(env, &return_ty)?,
+        None => return unknown,
+    };
+
+    let mut solutions = HashMap::new();
+    if let Some((_, _, return_spine)) = return_ty.free_var_app() {
+        let indices = <_>::zip(return_spine.iter(), spine).skip(params);
+        for (&(_, ref index1), &(_, ref index2)) in indices {
+            if match_index(env, index1, index2, &arg_vars, &mut solutions)? {
+                return Ok(None);
+            }
+        }
+    }
+
+    let args = <_>::zip(arg_vars.iter(), args)
+        .map(|(arg_var, arg)| solutions.get(arg_var).cloned().unwrap_or(arg))
+        .collect::<Vec<_>>();
+    match apply_constructor_ty(env, constr_ty, &spine[..params], &args)? {
+        Some((arg_tys, _)) => Ok(Some(arg_tys.into_iter().map(Some).collect())),
+        None => unknown,
+    }
+}
+
+/// Apply the type of a constructor to the parameters of its data type and to
+/// the given arguments, returning the types of the arguments along with the
+/// type that the constructor returns
+fn apply_constructor_ty<Env>(
+    env: &Env,
+    constr_ty: &RcType,
+    params: &[(Plicity, RcValue)],
+    args: &[RcValue],
+) -> Result<Option<(Vec<RcType>, RcType)>, InternalError>
+where
+    Env: MetaEnv + DefinitionEnv,
+{
+    let mut ty = constr_ty.clone();
+    for &(_, ref param) in params {
+        ty = match *force(env, &ty)? {
+            Value::Pi(_, _, _, ref closure) => apply_closure(env, closure, param.clone())?,
+            _ => return Ok(None),
+        };
+    }
+
+    let mut arg_tys = Vec::with_capacity(args.len());
+    for arg in args {
+        ty = match *force(env, &ty)? {
+            Value::Pi(_, _, ref ann, ref closure) => {
+                arg_tys.push(ann.clone());
+                apply_closure(env, closure, arg.clone())?
+            },
+            _ => return Ok(None),
+        };
+    }
+
+    Ok(Some((arg_tys, ty)))
+}
+
+/// Match an index of the type returned by a constructor against the
+/// corresponding index of the scrutinee's type, recording the values of the
+/// constructor's arguments that it determines
+///
+/// Returns `true` if the indices can never be equal, because they are built
+/// from different constructors or literals.
+fn match_index<Env>(
+    env: &Env,
+    index1: &RcValue,
+    index2: &RcValue,
+    arg_vars: &[FreeVar<String>],
+    solutions: &mut HashMap<FreeVar<String>, RcValue>,
+) -> Result<bool, InternalError>
+where
+    Env: MetaEnv + DefinitionEnv + DataEnv,
+{
+    let index1 = force(env, index1)?;
+    let index2 = force(env, index2)?;
+
+    if let Some((free_var, _, spine)) = index1.free_var_app() {
+        if spine.is_empty() && arg_vars.contains(free_var) && !solutions.contains_key(free_var) {
+            solutions.insert(free_var.clone(), index2.clone());
+            return Ok(false);
+        }
+    }
+
+    match (&*index1, &*index2) {
+        (&Value::Literal(ref literal1), &Value::Literal(ref literal2)) => Ok(literal1 != literal2),
+        (&Value::Constr(ref var1, ref spine1), &Value::Constr(ref var2, ref spine2)) => {
+            if var1 != var2 {
+                return Ok(true);
+            }
+            for (&(_, ref arg1), &(_, ref arg2)) in <_>::zip(spine1.iter(), spine2) {
+                if match_index(env, arg1, arg2, arg_vars, solutions)? {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        },
+        (_, _) => Ok(false),
+    }
+}
+
+fn ctor_to_pat(ctor: &Ctor, args: Vec<Pat>) -> Pat {
+    match *ctor {
+        Ctor::Const(ref value) => Pat::Const(value.clone()),
+        Ctor::Constr(ref free_var) => Pat::Constr(free_var.clone(), args),
+    }
+}
+
+/// The distinct heads that appear in the first column of the rows
+fn head_ctors(rows: &[Vec<Pat>]) -> Vec<Ctor> {
+    let mut ctors = Vec::new();
+    for row in rows {
+        let ctor = match row[0] {
+            Pat::Wild => continue,
+            Pat::Const(ref value) => Ctor::Const(value.clone()),
+            Pat::Constr(ref free_var, _) => Ctor::Constr(free_var.clone()),
+        };
+        if !ctors.contains(&ctor) {
+            ctors.push(ctor);
+        }
+    }
+    ctors
+}
+
+/// Every head that could appear in a column of type `ty`, if there are
+/// finitely many
+fn signature<Env>(env: &Env, heads: &[Ctor], ty: &Option<RcType>) -> Option<Vec<Ctor>>
+where
+    Env: MetaEnv + DefinitionEnv + DataEnv,
+{
+    for head in heads {
+        match *head {
+            Ctor::Constr(ref free_var) => {
+                let data_var = env.get_constructor_data(free_var)?;
+                let data = env.get_data(data_var)?;
+
+                return Some(
+                    data.constructors
+                        .iter()
+                        .cloned()
+                        .map(Ctor::Constr)
+                        .filter(|ctor| ctor_arg_tys(env, ctor, ty).is_some())
+                        .collect(),
+                );
+            },
+            Ctor::Const(Const::Literal(Literal::Bool(_))) => {
+                return Some(vec![
+                    Ctor::Const(Const::Literal(Literal::Bool(true))),
+                    Ctor::Const(Const::Literal(Literal::Bool(false))),
+                ]);
+            },
+            Ctor::Const(_) => {},
+        }
+    }
+
+    None
+}
+
+/// Keep the rows that could match the given head, replacing their first
+/// column with the arguments of the head
+fn specialize(rows: &[Vec<Pat>], ctor: &Ctor, arity: usize) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter_map(|row| {
+            let mut new_row = match (&row[0], ctor) {
+                (&Pat::Wild, _) => vec![Pat::Wild; arity],
+                (&Pat::Const(ref value1), &Ctor::Const(ref value2)) if value1 == value2 => vec![],
+                (&Pat::Constr(ref free_var1, ref args), &Ctor::Constr(ref free_var2))
+                    if free_var1 == free_var2 =>
+                {
+                    args.clone()
+                },
+                (_, _) => return None,
+            };
+            new_row.extend(row[1..].iter().cloned());
+            Some(new_row)
+        }).collect()
+}
+
+/// Keep the rows that start with a wildcard, removing their first column
+fn default_rows(rows: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter(|row| row[0] == Pat::Wild)
+        .map(|row| row[1..].to_vec())
+        .collect()
+}
+
+/// Returns true if `row` matches some values that are not matched by `rows`,
+/// where `tys` are the types of the columns
+fn is_useful<Env>(env: &Env, rows: &[Vec<Pat>], row: &[Pat], tys: &[Option<RcType>]) -> bool
+where
+    Env: MetaEnv + DefinitionEnv + DataEnv,
+{
+    let (head, rest) = match row.split_first() {
+        Some(split) => split,
+        None => return rows.is_empty(),
+    };
+
+    let specialize_row = |ctor: &Ctor, args: Vec<Pat>| {
+        // Constructors that can't produce a value of the column's type never
+        // match anything
+        let mut new_tys = match ctor_arg_tys(env, ctor, &tys[0]) {
+            Some(arg_tys) => arg_tys,
+            None => return false,
+        };
+        new_tys.extend(tys[1..].iter().cloned());
+
+        let arity = args.len();
+        let mut new_row = args;
+        new_row.extend(rest.iter().cloned());
+        is_useful(env, &specialize(rows, ctor, arity), &new_row, &new_tys)
+    };
+
+    match *head {
+        Pat::Wild => {
+            let heads = head_ctors(rows);
+            match signature(env, &heads, &tys[0]) {
+                Some(ref ctors) if ctors.iter().all(|ctor| heads.contains(ctor)) => {
+                    ctors.iter().any(|ctor| {
+                        let arity = ctor_arity(env, ctor);
+                        specialize_row(ctor, vec![Pat::Wild; arity])
+                    })
+                },
+                _ => is_useful(env, &default_rows(rows), rest, &tys[1..]),
+            }
+        },
+        Pat::Const(ref value) => specialize_row(&Ctor::Const(value.clone()), vec![]),
+        Pat::Constr(ref free_var, ref args) => {
+            specialize_row(&Ctor::Constr(free_var.clone()), args.clone())
+        },
+    }
+}
+
+/// Returns example rows that are not matched by `rows`, where `tys` are the
+/// types of the columns
+fn missing_patterns<Env>(env: &Env, rows: &[Vec<Pat>], tys: &[Option<RcType>]) -> Vec<Vec<Pat>>
+where
+    Env: MetaEnv + DefinitionEnv + DataEnv,
+{
+    if tys.is_empty() {
+        return if rows.is_empty() { vec![vec![]] } else { vec![] };
+    }
+
+    let heads = head_ctors(rows);
+    let ctors = signature(env, &heads, &tys[0]);
+
+    match ctors {
+        Some(ref ctors) if ctors.iter().all(|ctor| heads.contains(ctor)) => ctors
+            .iter()
+            .flat_map(|ctor| {
+                let arity = ctor_arity(env, ctor);
+                let mut new_tys =
+                    ctor_arg_tys(env, ctor, &tys[0]).unwrap_or_else(|| vec![None; arity]);
+                new_tys.extend(tys[1..].iter().cloned());
+                let missing = missing_patterns(env, &specialize(rows, ctor, arity), &new_tys);
+
+                missing.into_iter().map(move |mut row| {
+                    let rest = row.split_off(arity);
+                    let mut new_row = vec![ctor_to_pat(ctor, row)];
+                    new_row.extend(rest);
+                    new_row
+                })
+            }).collect(),
+        _ => {
+            let missing = missing_patterns(env, &default_rows(rows), &tys[1..]);
+            if missing.is_empty() {
+                return vec![];
+            }
+
+            let missing_heads = match ctors {
+                _ if heads.is_empty() => vec![Pat::Wild],
+                Some(ctors) => ctors
+                    .iter()
+                    .filter(|ctor| !heads.contains(ctor))
+                    .map(|ctor| ctor_to_pat(ctor, vec![Pat::Wild; ctor_arity(env, ctor)]))
+                    .collect(),
+                None => vec![example_const(&heads)],
+            };
+
+            missing_heads
+                .iter()
+                .flat_map(|head| {
+                    missing.iter().map(move |row| {
+                        let mut new_row = vec![head.clone()];
+                        new_row.extend(row.iter().cloned());
+                        new_row
+                    })
+                }).collect()
+        },
+    }
+}
+
+/// Find a literal that is not matched by any of the given heads, falling back
+/// to a wildcard if one could not be found
+fn example_const(heads: &[Ctor]) -> Pat {
+    let literals = heads
+        .iter()
+        .filter_map(|head| match *head {
+            Ctor::Const(Const::Literal(ref literal)) => Some(literal),
+            Ctor::Const(Const::Var(_)) | Ctor::Constr(_) => None,
+        }).collect::<Vec<_>>();
+
+    let example = literals.first().and_then(|&literal| {
+        (0..=literals.len() as u64)
+            .filter_map(|n| nth_literal(literal, n))
+            .find(|candidate| !literals.contains(&candidate))
+    });
+
+    match example {
+        Some(literal) => Pat::Const(Const::Literal(literal)),
+        None => Pat::Wild,
+    }
+}
+
+/// The `n`th example literal of the same type as `literal`
+fn nth_literal(literal: &Literal, n: u64) -> Option<Literal> {
+    use std::char;
+
+    match *literal {
+        Literal::Bool(_) => None,
+        Literal::String(_) => Some(Literal::String("a".repeat(n as usize))),
+        Literal::Char(_) => char::from_u32('a' as u32 + n as u32).map(Literal::Char),
+        Literal::U8(_) => Some(Literal::U8(n as u8)),
+        Literal::U16(_) => Some(Literal::U16(n as u16)),
+        Literal::U32(_) => Some(Literal::U32(n as u32)),
+        Literal::U64(_) => Some(Literal::U64(n)),
+        Literal::I8(_) => Some(Literal::I8(n as i8)),
+        Literal::I16(_) => Some(Literal::I16(n as i16)),
+        Literal::I32(_) => Some(Literal::I32(n as i32)),
+        Literal::I64(_) => Some(Literal::I64(n as i64)),
+        Literal::F32(_) => Some(Literal::F32(n as f32)),
+        Literal::F64(_) => Some(Literal::F64(n as f64)),
+    }
+}
+
+/// Convert a simplified pattern back into a concrete pattern, for reporting
+/// in errors
+fn resugar_pat<Env>(env: &Env, pat: &Pat, wrap: bool) -> concrete::Pattern
+where
+    Env: DataEnv,
+{
+    match *pat {
+        Pat::Wild => concrete::Pattern::Name(ByteSpan::default(), "_".to_owned(), None),
+        Pat::Const(Const::Literal(ref literal)) => {
+            core::Pattern::Literal(literal.clone()).resugar(env.resugar_env())
+        },
+        Pat::Const(Const::Var(ref var)) => {
+            core::Pattern::Var(Embed(var.clone()), LevelShift(0)).resugar(env.resugar_env())
+        },
+        Pat::Constr(ref free_var, ref args) => {
+            let var = Embed(Var::Free(free_var.clone()));
+            let head = core::Pattern::Var(var, LevelShift(0)).resugar(env.resugar_env());
+            let args = <_>::zip(constructor_plicities(env, free_var).into_iter(), args)
+                .filter(|&(plicity, _)| plicity == Plicity::Explicit)
+                .map(|(_, arg)| resugar_pat(env, arg, true))
+                .collect::<Vec<_>>();
+
+            if args.is_empty() {
+                head
+            } else if wrap {
+                concrete::Pattern::Parens(
+                    ByteSpan::default(),
+                    Box::new(concrete::Pattern::App(Box::new(head), args)),
+                )
+            } else {
+                concrete::Pattern::App(Box::new(head), args)
+            }
+        },
+    }
+}
@@ -1,10 +1,18 @@
-use im::HashMap;
-use moniker::{Binder, FreeVar};
+use codespan::ByteSpan;
+use im::{HashMap, HashSet};
+use moniker::{Binder, BoundTerm, FreeVar};
+use std::cell::RefCell;
 use std::fmt;
 use std::rc::Rc;
 
-use syntax::core::{Literal, RcTerm, RcType, RcValue, Spine, Value};
+use syntax::core::{
+    Closure, Literal, Locals, MetaVar, RcTerm, RcType, RcValue, Spine, Value,
+};
 use syntax::translation::ResugarEnv;
+use syntax::{Label, Level, LevelMeta, Multiplicity, Plicity};
+
+use semantics::errors::TypeError;
+use semantics::eval_term;
 
 // Some helper traits for marshalling between Rust and Pikelet values
 //
@@ -103,7 +111,7 @@ fn default_extern_definitions() -> HashMap<&'static str, Extern> {
                     let mut arg_index = 0;
                     $(
                         arg_index += 1;
-                        let $param_name = <$PType>::try_from_value_ref(&params[arg_index - 1])?;
+                        let $param_name = <$PType>::try_from_value_ref(&params[arg_index - 1].1)?;
                     )*
                     Ok(<$RType>::into_value($body))
                 } else {
@@ -306,9 +314,16 @@ impl Default for Globals {
     }
 }
 
-pub trait GlobalEnv: Clone {
+/// The environments are captured by the thunks of glued values, so they must
+/// own their contents
+pub trait GlobalEnv: Clone + 'static {
     fn resugar_env(&self) -> &ResugarEnv;
     fn globals(&self) -> &Globals;
+    /// Whether universe levels should be ignored, giving us `Type : Type`
+    ///
+    /// This makes the type system inconsistent, but can be handy when
+    /// prototyping code that would otherwise run into universe errors.
+    fn type_in_type(&self) -> bool;
 }
 
 /// An environment that contains declarations
@@ -318,16 +333,174 @@ pub trait DeclarationEnv: GlobalEnv {
     fn extend_declarations<T>(&mut self, iter: T)
     where
         T: IntoIterator<Item = (FreeVar<String>, RcType)>;
+    /// The universe level parameters that a declaration is polymorphic over
+    fn get_level_params(&self, free_var: &FreeVar<String>) -> Option<&[String]>;
+    fn insert_level_params(&mut self, free_var: FreeVar<String>, params: Vec<String>);
 }
 
 /// An environment that contains definitions
 pub trait DefinitionEnv: GlobalEnv {
     fn get_extern_definition(&self, name: &str) -> Option<&Extern>;
     fn get_definition(&self, free_var: &FreeVar<String>) -> Option<&RcTerm>;
+    /// The value of the definition, if it has already been evaluated
+    fn get_definition_value(&self, free_var: &FreeVar<String>) -> Option<&RcValue>;
     fn insert_definition(&mut self, free_var: FreeVar<String>, RcTerm);
     fn extend_definitions<T>(&mut self, iter: T)
     where
         T: IntoIterator<Item = (FreeVar<String>, RcTerm)>;
+    /// Returns `true` if the definition refers back to itself, either directly
+    /// or by way of other definitions
+    fn is_recursive(&self, free_var: &FreeVar<String>) -> bool;
+    fn insert_recursive_definition(&mut self, free_var: FreeVar<String>, term: RcTerm);
+    /// Returns `true` if the definition was declared as `partial`, in which
+    /// case it might not terminate
+    fn is_partial(&self, free_var: &FreeVar<String>) -> bool;
+    fn insert_partial(&mut self, free_var: FreeVar<String>);
+}
+
+/// A data type that has been declared
+#[derive(Clone, Debug)]
+pub struct DataEntry {
+    /// The number of parameters that the data type takes
+    pub params: usize,
+    /// The constructors of the data type, in the order they were declared
+    pub constructors: Vec<FreeVar<String>>,
+}
+
+/// An environment that contains data types
+pub trait DataEnv: DeclarationEnv {
+    fn get_data(&self, free_var: &FreeVar<String>) -> Option<&DataEntry>;
+    /// Look up the data type that the given constructor belongs to
+    fn get_constructor_data(&self, free_var: &FreeVar<String>) -> Option<&FreeVar<String>>;
+    fn insert_data(&mut self, free_var: FreeVar<String>, data: DataEntry);
+}
+
+/// A module that has been checked, ready to be imported by other modules
+#[derive(Clone, Debug)]
+pub struct ModuleEntry {
+    /// The record containing the items of the module
+    pub term: RcTerm,
+    /// The type of the record
+    pub ty: RcType,
+    /// The constructors of the data types exported by the module, which can
+    /// be matched on without qualifying them with the name of the module
+    pub constructors: Vec<(Label, FreeVar<String>)>,
+}
+
+/// An environment that contains the modules that can be imported
+pub trait ModuleEnv: GlobalEnv {
+    fn get_module(&self, name: &str) -> Option<&ModuleEntry>;
+    fn insert_module(&mut self, name: String, module: ModuleEntry);
+}
+
+/// The reason that a metavariable was inserted during elaboration
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MetaSource {
+    /// A hole, written explicitly as `?` or left behind by an omitted
+    /// type annotation
+    Hole,
+    /// An implicit argument that was not supplied
+    ImplicitArg,
+}
+
+/// A metavariable that was inserted during elaboration
+#[derive(Clone, Debug)]
+pub struct MetaEntry {
+    /// The span of the term that caused the metavariable to be inserted
+    pub span: ByteSpan,
+    /// The reason the metavariable was inserted
+    pub source: MetaSource,
+    /// The type that the solution must have
+    pub ty: RcType,
+    /// The declarations that were in scope when the metavariable was inserted.
+    /// Solutions may only refer to these.
+    pub scope: HashMap<FreeVar<String>, RcType>,
+    /// The solution, if one has been found
+    pub solution: Option<RcValue>,
+}
+
+/// The state of the metavariables and level constraints at some point during
+/// elaboration, so that we can go back to it if an item fails to type check
+#[derive(Clone, Debug)]
+pub struct MetaSnapshot {
+    /// The number of metavariables that had been inserted
+    metas: usize,
+    /// The metavariables that were yet to be solved
+    unsolved_metas: Vec<MetaVar>,
+    /// The number of level metavariables that had been inserted
+    level_metas: usize,
+    /// The level metavariables that were yet to be solved
+    unsolved_level_metas: Vec<LevelMeta>,
+    /// The level constraints that were waiting to be solved. These are kept
+    /// in full, because solving the constraints removes them.
+    level_constraints: Vec<(Level, Level)>,
+}
+
+/// An environment that contains metavariables
+///
+/// Unlike declarations and definitions, metavariables are shared between all
+/// of the copies of an environment that we make when entering into scopes, so
+/// that solutions found while checking a subterm are visible once we return
+/// from it. For this reason these methods only take `&self`.
+pub trait MetaEnv: DeclarationEnv {
+    fn fresh_meta(&self, span: ByteSpan, source: MetaSource, ty: RcType) -> MetaVar;
+    fn get_meta(&self, meta: MetaVar) -> MetaEntry;
+    fn solve_meta(&self, meta: MetaVar, solution: RcValue);
+    fn unsolved_metas(&self) -> Vec<MetaVar>;
+    fn fresh_level_meta(&self) -> LevelMeta;
+    fn get_level_meta(&self, meta: LevelMeta) -> Option<Level>;
+    fn solve_level_meta(&self, meta: LevelMeta, solution: Level);
+    /// Record that the first level must be less than or equal to the second,
+    /// to be solved later on once more is known about the levels involved
+    fn add_level_constraint(&self, lower: Level, upper: Level);
+    /// Remove all of the level constraints that have been recorded so far
+    fn take_level_constraints(&self) -> Vec<(Level, Level)>;
+    /// Solve any level metavariables that remain unsolved with the lowest
+    /// universe level
+    fn default_level_metas(&self);
+    /// Record the current state of the metavariables and level constraints
+    fn snapshot_metas(&self) -> MetaSnapshot;
+    /// Forget the metavariables, solutions and level constraints that were
+    /// added since the snapshot was taken
+    fn restore_metas(&self, snapshot: MetaSnapshot);
+}
+
+/// An environment that collects warnings
+///
+/// Like metavariables, warnings are shared between all of the copies of an
+/// environment, so that they can be collected once checking has finished.
+pub trait WarningEnv: GlobalEnv {
+    fn report_warning(&self, warning: TypeError);
+    /// Remove all of the warnings that have been reported so far
+    fn take_warnings(&self) -> Vec<TypeError>;
+    /// The number of warnings that have been reported so far
+    fn warning_count(&self) -> usize;
+    /// Forget the warnings that were reported after the first `count`
+    fn truncate_warnings(&self, count: usize);
+}
+
+/// An environment that counts the uses of variables that were bound with a
+/// multiplicity
+///
+/// Like metavariables, the counts are shared between all of the copies of an
+/// environment, so that the uses found in the body of a binder can be seen
+/// once it has been checked.
+pub trait UsageEnv: GlobalEnv {
+    /// Start counting the uses of a variable
+    fn track_usage(&self, free_var: FreeVar<String>);
+    /// Record a use of a variable, if its uses are being counted
+    fn record_usage(&self, free_var: &FreeVar<String>);
+    /// Stop counting the uses of a variable, returning how many there were
+    fn untrack_usage(&self, free_var: &FreeVar<String>) -> Multiplicity;
+    /// Reset the counts of all of the tracked variables to zero, returning the
+    /// uses that were recorded since the last reset
+    fn take_usages(&self) -> HashMap<FreeVar<String>, Multiplicity>;
+    /// Add some previously taken uses back on to the current counts
+    fn add_usages(&self, usages: HashMap<FreeVar<String>, Multiplicity>);
+    /// The current counts of all of the tracked variables
+    fn usages(&self) -> HashMap<FreeVar<String>, Multiplicity>;
+    /// Replace the tracked variables and their counts
+    fn set_usages(&self, usages: HashMap<FreeVar<String>, Multiplicity>);
 }
 
 /// The type checking environment
@@ -351,8 +524,40 @@ pub struct TcEnv {
     extern_definitions: HashMap<&'static str, Extern>,
     /// The type annotations of the binders we have passed over
     declarations: HashMap<FreeVar<String>, RcType>,
+    /// The universe level parameters of the level-polymorphic declarations
+    level_params: HashMap<FreeVar<String>, Vec<String>>,
     /// Any definitions we have passed over
     definitions: HashMap<FreeVar<String>, RcTerm>,
+    /// The values of the definitions, evaluated ahead of time so that we
+    /// don't have to evaluate them again each time they are used
+    definition_values: HashMap<FreeVar<String>, RcValue>,
+    /// The names without definitions that the evaluated definitions refer
+    /// to. Defining one of these means that the values could be unfolded
+    /// further, so they must be evaluated again.
+    undefined_references: HashSet<FreeVar<String>>,
+    /// The definitions that refer back to themselves
+    recursive_definitions: HashSet<FreeVar<String>>,
+    /// The declarations that were marked as `partial`
+    partial_declarations: HashSet<FreeVar<String>>,
+    /// The data types we have passed over
+    data: HashMap<FreeVar<String>, DataEntry>,
+    /// The data types that each constructor belongs to
+    constructors: HashMap<FreeVar<String>, FreeVar<String>>,
+    /// The modules that have been checked, keyed by their names
+    modules: HashMap<String, ModuleEntry>,
+    /// The metavariables that have been inserted during elaboration
+    metas: Rc<RefCell<Vec<MetaEntry>>>,
+    /// The solutions to the level metavariables that have been inserted
+    /// during elaboration
+    level_metas: Rc<RefCell<Vec<Option<Level>>>>,
+    /// The universe level constraints that are waiting to be solved
+    level_constraints: Rc<RefCell<Vec<(Level, Level)>>>,
+    /// The warnings that have been reported during elaboration
+    warnings: Rc<RefCell<Vec<TypeError>>>,
+    /// The uses of the variables whose multiplicities are being checked
+    usages: Rc<RefCell<HashMap<FreeVar<String>, Multiplicity>>>,
+    /// Whether universe levels are being ignored
+    type_in_type: bool,
 }
 
 impl TcEnv {
@@ -364,6 +569,29 @@ impl TcEnv {
                 Some((pretty_name.clone(), free_var.clone()))
             }).collect()
     }
+
+    /// Evaluate a definition ahead of time, provided that its value won't
+    /// change as we learn more about the environment
+    fn evaluate_definition(&mut self, free_var: FreeVar<String>, term: &RcTerm) {
+        if self.is_recursive(&free_var) || !self.unsolved_metas().is_empty() {
+            return;
+        }
+
+        if let Ok(value) = eval_term(self, term) {
+            for referenced in term.free_vars() {
+                if !self.definitions.contains_key(&referenced) {
+                    self.undefined_references.insert(referenced);
+                }
+            }
+            self.definition_values.insert(free_var, value);
+        }
+    }
+
+    /// Ignore universe levels when type checking, making the type system
+    /// unsound
+    pub fn set_type_in_type(&mut self, type_in_type: bool) {
+        self.type_in_type = type_in_type;
+    }
 }
 
 impl Default for TcEnv {
@@ -377,7 +605,21 @@ impl Default for TcEnv {
             globals: Rc::new(Globals::default()),
             extern_definitions: default_extern_definitions(),
             declarations: HashMap::new(),
+            level_params: HashMap::new(),
             definitions: HashMap::new(),
+            definition_values: HashMap::new(),
+            undefined_references: HashSet::new(),
+            recursive_definitions: HashSet::new(),
+            partial_declarations: HashSet::new(),
+            data: HashMap::new(),
+            constructors: HashMap::new(),
+            modules: HashMap::new(),
+            metas: Rc::new(RefCell::new(Vec::new())),
+            level_metas: Rc::new(RefCell::new(Vec::new())),
+            level_constraints: Rc::new(RefCell::new(Vec::new())),
+            warnings: Rc::new(RefCell::new(Vec::new())),
+            usages: Rc::new(RefCell::new(HashMap::new())),
+            type_in_type: false,
         };
 
         let var_bool = tc_env.globals.bool.clone();
@@ -400,16 +642,28 @@ impl Default for TcEnv {
         let universe0 = RcValue::from(Value::universe(0));
         let bool_ty = RcValue::from(Value::var(Var::Free(var_bool.clone()), 0));
         let bool_lit = |value| RcTerm::from(Term::Literal(Literal::Bool(value)));
-        let array_ty = RcValue::from(Value::Pi(Scope::new(
-            (
-                Binder(FreeVar::fresh_unnamed()),
-                Embed(RcValue::from(Value::var(Var::Free(var_u64.clone()), 0))),
+        let array_ty = RcValue::from(Value::Pi(
+            Plicity::Explicit,
+            Multiplicity::Many,
+            RcValue::from(Value::var(Var::Free(var_u64.clone()), 0)),
+            Closure::new(
+                Locals::new(),
+                Scope::new(
+                    Binder(FreeVar::fresh_unnamed()),
+                    RcTerm::from(Term::Pi(
+                        Plicity::Explicit,
+                        Multiplicity::Many,
+                        Scope::new(
+                            (
+                                Binder(FreeVar::fresh_unnamed()),
+                                Embed(RcTerm::from(Term::universe(0))),
+                            ),
+                            RcTerm::from(Term::universe(0)),
+                        ),
+                    )),
+                ),
             ),
-            RcValue::from(Value::Pi(Scope::new(
-                (Binder(FreeVar::fresh_unnamed()), Embed(universe0.clone())),
-                universe0.clone(),
-            ))),
-        )));
+        ));
 
         tc_env.insert_declaration(var_bool, universe0.clone());
         tc_env.insert_declaration(var_true_.clone(), bool_ty.clone());
@@ -443,6 +697,10 @@ impl GlobalEnv for TcEnv {
     fn globals(&self) -> &Globals {
         &self.globals
     }
+
+    fn type_in_type(&self) -> bool {
+        self.type_in_type
+    }
 }
 
 impl DeclarationEnv for TcEnv {
@@ -459,7 +717,17 @@ impl DeclarationEnv for TcEnv {
     where
         T: IntoIterator<Item = (FreeVar<String>, RcType)>,
     {
-        self.declarations.extend(iter)
+        for (free_var, ty) in iter {
+            self.insert_declaration(free_var, ty);
+        }
+    }
+
+    fn get_level_params(&self, free_var: &FreeVar<String>) -> Option<&[String]> {
+        self.level_params.get(free_var).map(Vec::as_slice)
+    }
+
+    fn insert_level_params(&mut self, free_var: FreeVar<String>, params: Vec<String>) {
+        self.level_params.insert(free_var, params);
     }
 }
 
@@ -472,9 +740,18 @@ impl DefinitionEnv for TcEnv {
         self.definitions.get(free_var)
     }
 
+    fn get_definition_value(&self, free_var: &FreeVar<String>) -> Option<&RcValue> {
+        self.definition_values.get(free_var)
+    }
+
     fn insert_definition(&mut self, free_var: FreeVar<String>, term: RcTerm) {
         self.resugar_env.on_binder(&Binder(free_var.clone()));
-        self.definitions.insert(free_var, term);
+        if self.undefined_references.contains(&free_var) {
+            self.definition_values = HashMap::new();
+            self.undefined_references = HashSet::new();
+        }
+        self.definitions.insert(free_var.clone(), term.clone());
+        self.evaluate_definition(free_var, &term);
     }
 
     fn extend_definitions<T>(&mut self, iter: T)
@@ -483,4 +760,207 @@ impl DefinitionEnv for TcEnv {
     {
         self.definitions.extend(iter)
     }
+
+    fn is_recursive(&self, free_var: &FreeVar<String>) -> bool {
+        self.recursive_definitions.contains(free_var)
+    }
+
+    fn insert_recursive_definition(&mut self, free_var: FreeVar<String>, term: RcTerm) {
+        self.recursive_definitions.insert(free_var.clone());
+        self.insert_definition(free_var, term);
+    }
+
+    fn is_partial(&self, free_var: &FreeVar<String>) -> bool {
+        self.partial_declarations.contains(free_var)
+    }
+
+    fn insert_partial(&mut self, free_var: FreeVar<String>) {
+        self.partial_declarations.insert(free_var);
+    }
+}
+
+impl DataEnv for TcEnv {
+    fn get_data(&self, free_var: &FreeVar<String>) -> Option<&DataEntry> {
+        self.data.get(free_var)
+    }
+
+    fn get_constructor_data(&self, free_var: &FreeVar<String>) -> Option<&FreeVar<String>> {
+        self.constructors.get(free_var)
+    }
+
+    fn insert_data(&mut self, free_var: FreeVar<String>, data: DataEntry) {
+        for constructor in &data.constructors {
+            self.constructors
+                .insert(constructor.clone(), free_var.clone());
+        }
+        self.data.insert(free_var, data);
+    }
+}
+
+impl ModuleEnv for TcEnv {
+    fn get_module(&self, name: &str) -> Option<&ModuleEntry> {
+        self.modules.get(name)
+    }
+
+    fn insert_module(&mut self, name: String, module: ModuleEntry) {
+        self.modules.insert(name, module);
+    }
+}
+
+impl MetaEnv for TcEnv {
+    fn fresh_meta(&self, span: ByteSpan, source: MetaSource, ty: RcType) -> MetaVar {
+        let mut metas = self.metas.borrow_mut();
+        let meta = MetaVar(metas.len() as u32);
+        metas.push(MetaEntry {
+            span,
+            source,
+            ty,
+            scope: self.declarations.clone(),
+            solution: None,
+        });
+        meta
+    }
+
+    fn get_meta(&self, meta: MetaVar) -> MetaEntry {
+        self.metas.borrow()[meta.0 as usize].clone()
+    }
+
+    fn solve_meta(&self, meta: MetaVar, solution: RcValue) {
+        self.metas.borrow_mut()[meta.0 as usize].solution = Some(solution);
+    }
+
+    fn unsolved_metas(&self) -> Vec<MetaVar> {
+        self.metas
+            .borrow()
+            .iter()
+            .enumerate()
+            .filter(|&(_, entry)| entry.solution.is_none())
+            .map(|(index, _)| MetaVar(index as u32))
+            .collect()
+    }
+
+    fn fresh_level_meta(&self) -> LevelMeta {
+        let mut level_metas = self.level_metas.borrow_mut();
+        let meta = LevelMeta(level_metas.len() as u32);
+        level_metas.push(None);
+        meta
+    }
+
+    fn get_level_meta(&self, meta: LevelMeta) -> Option<Level> {
+        self.level_metas.borrow()[meta.0 as usize].clone()
+    }
+
+    fn solve_level_meta(&self, meta: LevelMeta, solution: Level) {
+        self.level_metas.borrow_mut()[meta.0 as usize] = Some(solution);
+    }
+
+    fn add_level_constraint(&self, lower: Level, upper: Level) {
+        self.level_constraints.borrow_mut().push((lower, upper));
+    }
+
+    fn take_level_constraints(&self) -> Vec<(Level, Level)> {
+        self.level_constraints.borrow_mut().drain(..).collect()
+    }
+
+    fn default_level_metas(&self) {
+        for solution in self.level_metas.borrow_mut().iter_mut() {
+            if solution.is_none() {
+                *solution = Some(Level::from(0));
+            }
+        }
+    }
+
+    fn snapshot_metas(&self) -> MetaSnapshot {
+        let level_metas = self.level_metas.borrow();
+
+        MetaSnapshot {
+            metas: self.metas.borrow().len(),
+            unsolved_metas: self.unsolved_metas(),
+            level_metas: level_metas.len(),
+            unsolved_level_metas: (0..level_metas.len())
+                .filter(|&index| level_metas[index].is_none())
+                .map(|index| LevelMeta(index as u32))
+                .collect(),
+            level_constraints: self.level_constraints.borrow().clone(),
+        }
+    }
+
+    fn restore_metas(&self, snapshot: MetaSnapshot) {
+        let mut metas = self.metas.borrow_mut();
+        metas.truncate(snapshot.metas);
+        for meta in snapshot.unsolved_metas {
+            metas[meta.0 as usize].solution = None;
+        }
+
+        let mut level_metas = self.level_metas.borrow_mut();
+        level_metas.truncate(snapshot.level_metas);
+        for meta in snapshot.unsolved_level_metas {
+            level_metas[meta.0 as usize] = None;
+        }
+
+        *self.level_constraints.borrow_mut() = snapshot.level_constraints;
+    }
+}
+
+impl WarningEnv for TcEnv {
+    fn report_warning(&self, warning: TypeError) {
+        self.warnings.borrow_mut().push(warning);
+    }
+
+    fn take_warnings(&self) -> Vec<TypeError> {
+        self.warnings.borrow_mut().drain(..).collect()
+    }
+
+    fn warning_count(&self) -> usize {
+        self.warnings.borrow().len()
+    }
+
+    fn truncate_warnings(&self, count: usize) {
+        self.warnings.borrow_mut().truncate(count);
+    }
+}
+
+impl UsageEnv for TcEnv {
+    fn track_usage(&self, free_var: FreeVar<String>) {
+        self.usages.borrow_mut().insert(free_var, Multiplicity::Zero);
+    }
+
+    fn record_usage(&self, free_var: &FreeVar<String>) {
+        if let Some(usage) = self.usages.borrow_mut().get_mut(free_var) {
+            *usage += Multiplicity::One;
+        }
+    }
+
+    fn untrack_usage(&self, free_var: &FreeVar<String>) -> Multiplicity {
+        self.usages
+            .borrow_mut()
+            .remove(free_var)
+            .unwrap_or(Multiplicity::Zero)
+    }
+
+    fn take_usages(&self) -> HashMap<FreeVar<String>, Multiplicity> {
+        let mut usages = self.usages.borrow_mut();
+        let taken = usages.clone();
+        for usage in usages.iter_mut() {
+            *usage = Multiplicity::Zero;
+        }
+        taken
+    }
+
+    fn add_usages(&self, usages: HashMap<FreeVar<String>, Multiplicity>) {
+        let mut current_usages = self.usages.borrow_mut();
+        for (free_var, usage) in usages {
+            if let Some(current_usage) = current_usages.get_mut(&free_var) {
+                *current_usage += usage;
+            }
+        }
+    }
+
+    fn usages(&self) -> HashMap<FreeVar<String>, Multiplicity> {
+        self.usages.borrow().clone()
+    }
+
+    fn set_usages(&self, usages: HashMap<FreeVar<String>, Multiplicity>) {
+        *self.usages.borrow_mut() = usages;
+    }
 }
@@ -7,6 +7,8 @@ use moniker::{Binder, FreeVar, Var};
 use syntax;
 use syntax::concrete;
 use syntax::raw;
+use semantics::MetaSource;
+use syntax::{Level, Multiplicity, Plicity};
 
 /// An internal error. These are bugs!
 #[derive(Debug, Fail, Clone, PartialEq)]
@@ -20,6 +22,8 @@ pub enum InternalError {
     ArgumentAppliedToNonFunction,
     #[fail(display = "Expected a boolean expression.")]
     ExpectedBoolExpr,
+    #[fail(display = "Expected a proof of equality.")]
+    ExpectedEqualityProof,
     #[fail(display = "Projected on non-existent field `{}`.", label)]
     ProjectedOnNonExistentField { label: syntax::Label },
     #[fail(display = "No patterns matched the given expression.")]
@@ -47,6 +51,9 @@ impl InternalError {
                 Diagnostic::new_bug("argument applied to non-function")
             },
             InternalError::ExpectedBoolExpr => Diagnostic::new_bug("expected a boolean expression"),
+            InternalError::ExpectedEqualityProof => {
+                Diagnostic::new_bug("expected a proof of equality")
+            },
             InternalError::ProjectedOnNonExistentField { ref label } => {
                 Diagnostic::new_bug(format!("projected on non-existent field `{}`.", label))
             },
@@ -67,6 +74,15 @@ impl InternalError {
     }
 }
 
+/// A metavariable that was left unsolved at the end of elaboration
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsolvedMeta {
+    pub span: ByteSpan,
+    pub source: MetaSource,
+    /// The type of the metavariable, if it is known
+    pub expected: Option<Box<concrete::Term>>,
+}
+
 /// An error produced during type checking
 #[derive(Debug, Fail, Clone, PartialEq)]
 pub enum TypeError {
@@ -104,13 +120,15 @@ pub enum TypeError {
         found: Box<concrete::Term>,
     },
     #[fail(
-        display = "Type annotation needed for the function parameter `{}`",
-        name,
+        display = "Applied an {} argument to a function that expected an {} argument",
+        found,
+        expected,
     )]
-    FunctionParamNeedsAnnotation {
-        param_span: ByteSpan,
-        var_span: Option<ByteSpan>,
-        name: FreeVar<String>,
+    PlicityMismatch {
+        fn_span: ByteSpan,
+        arg_span: ByteSpan,
+        found: Plicity,
+        expected: Plicity,
     },
     #[fail(
         display = "Type annotation needed for the binder `{}`",
@@ -120,6 +138,18 @@ pub enum TypeError {
         span: ByteSpan,
         binder: Binder<String>,
     },
+    #[fail(
+        display = "The binder `{}` has multiplicity {}, but was used with multiplicity {}",
+        name,
+        expected,
+        found,
+    )]
+    UsageMismatch {
+        span: ByteSpan,
+        name: String,
+        found: Multiplicity,
+        expected: Multiplicity,
+    },
     #[fail(
         display = "found a `{}`, but expected a type `{}`",
         found,
@@ -136,14 +166,8 @@ pub enum TypeError {
     AmbiguousFloatLiteral { span: ByteSpan },
     #[fail(display = "Empty case expressions need type annotations.")]
     AmbiguousEmptyCase { span: ByteSpan },
-    #[fail(
-        display = "Unable to elaborate hole, expected: `{:?}`",
-        expected,
-    )]
-    UnableToElaborateHole {
-        span: ByteSpan,
-        expected: Option<Box<concrete::Term>>,
-    },
+    #[fail(display = "Unable to fill in some holes or implicit arguments")]
+    UnsolvedMetas { metas: Vec<UnsolvedMeta> },
     #[fail(
         display = "Type mismatch: found `{}` but `{}` was expected",
         found,
@@ -164,11 +188,18 @@ pub enum TypeError {
         span: ByteSpan,
         found: Box<concrete::Term>,
     },
+    #[fail(display = "Found `{}` but an equality was expected", found)]
+    ExpectedEquality {
+        span: ByteSpan,
+        found: Box<concrete::Term>,
+    },
     #[fail(display = "Not yet defined: `{}`", free_var)]
     UndefinedName {
         span: ByteSpan,
         free_var: FreeVar<String>,
     },
+    #[fail(display = "The module `{}` has not been loaded", module)]
+    UnresolvedImport { span: ByteSpan, module: String },
     #[fail(display = "Undefined extern name `{:?}`", name)]
     UndefinedExternName { span: ByteSpan, name: String },
     #[fail(
@@ -213,6 +244,89 @@ pub enum TypeError {
         found_size: u64,
         expected_size: u64,
     },
+    #[fail(
+        display = "Expected the type of a data type to end in a universe, but found `{}`",
+        found,
+    )]
+    InvalidDataSignature {
+        span: ByteSpan,
+        found: Box<concrete::Term>,
+    },
+    #[fail(
+        display = "The constructor `{}` is too large to fit in the universe of its data type",
+        name,
+    )]
+    ConstructorTooLarge {
+        span: ByteSpan,
+        name: String,
+        found: Level,
+        expected: Level,
+    },
+    #[fail(
+        display = "Universe inconsistency: `Type^{}` is not contained in `Type^{}`",
+        found,
+        expected,
+    )]
+    UniverseInconsistency {
+        span: ByteSpan,
+        found: Level,
+        expected: Level,
+    },
+    #[fail(
+        display = "Constructor returned a term of type `{}`, but `{}` was expected",
+        found,
+        expected,
+    )]
+    InvalidConstructorReturnType {
+        span: ByteSpan,
+        found: Box<concrete::Term>,
+        expected: Box<concrete::Term>,
+    },
+    #[fail(
+        display = "The data type `{}` does not occur strictly positively in the type `{}`",
+        name,
+        found,
+    )]
+    NonStrictlyPositive {
+        span: ByteSpan,
+        name: String,
+        found: Box<concrete::Term>,
+    },
+    #[fail(display = "Unable to show that `{}` terminates", name)]
+    NonTerminating {
+        name: String,
+        call_spans: Vec<ByteSpan>,
+    },
+    #[fail(display = "Not a constructor: `{}`", name)]
+    NotAConstructor { span: ByteSpan, name: String },
+    #[fail(
+        display = "The constructor `{}` does not construct a term of type `{}`",
+        name,
+        expected,
+    )]
+    ConstructorMismatch {
+        span: ByteSpan,
+        name: String,
+        expected: Box<concrete::Term>,
+    },
+    #[fail(
+        display = "Mismatched constructor arity: expected {} arguments but found {}",
+        expected_arity,
+        found_arity,
+    )]
+    ConstructorArityMismatch {
+        span: ByteSpan,
+        name: String,
+        found_arity: u64,
+        expected_arity: u64,
+    },
+    #[fail(display = "Non-exhaustive patterns")]
+    NonExhaustivePatterns {
+        span: ByteSpan,
+        missing: Vec<concrete::Pattern>,
+    },
+    #[fail(display = "Unreachable pattern")]
+    UnreachablePattern { span: ByteSpan },
     #[fail(display = "Internal error - this is a bug! {}", _0)]
     Internal(#[cause] InternalError),
 }
@@ -264,22 +378,34 @@ impl TypeError {
                 found,
             )).with_label(Label::new_primary(fn_span).with_message("the term"))
             .with_label(Label::new_secondary(arg_span).with_message("the applied argument")),
-            TypeError::FunctionParamNeedsAnnotation {
-                param_span,
-                var_span: _, // TODO
-                ref name,
+            TypeError::PlicityMismatch {
+                fn_span,
+                arg_span,
+                found,
+                expected,
             } => Diagnostic::new_error(format!(
-                "type annotation needed for the function parameter `{}`",
-                name
-            )).with_label(
-                Label::new_primary(param_span)
-                    .with_message("the parameter that requires an annotation"),
-            ),
+                "applied an {} argument to a function that expected an {} argument",
+                found, expected,
+            )).with_label(Label::new_primary(arg_span).with_message("the applied argument"))
+            .with_label(Label::new_secondary(fn_span).with_message("the function")),
             TypeError::BinderNeedsAnnotation { span, ref binder } => Diagnostic::new_error(
                 format!("type annotation needed for the binder `{}`", binder),
             ).with_label(
                 Label::new_primary(span).with_message("the binder that requires an annotation"),
             ),
+            TypeError::UsageMismatch {
+                span,
+                ref name,
+                found,
+                expected,
+            } => Diagnostic::new_error(format!(
+                "the binder `{}` has multiplicity {}, but was used with multiplicity {}",
+                name, expected, found,
+            )).with_label(Label::new_primary(span).with_message(match found {
+                Multiplicity::Zero => "never used at runtime in here",
+                Multiplicity::One => "used exactly once in here",
+                Multiplicity::Many => "used an unrestricted number of times in here",
+            })),
             TypeError::LiteralMismatch {
                 literal_span,
                 ref found,
@@ -306,20 +432,22 @@ impl TypeError {
             TypeError::AmbiguousEmptyCase { span } => Diagnostic::new_error(
                 "empty case expressions need type annotations",
             ).with_label(Label::new_primary(span).with_message("type annotation needed here")),
-            TypeError::UnableToElaborateHole {
-                span,
-                expected: None,
-                ..
-            } => Diagnostic::new_error("unable to elaborate hole")
-                .with_label(Label::new_primary(span).with_message("the hole")),
-            TypeError::UnableToElaborateHole {
-                span,
-                expected: Some(ref expected),
-                ..
-            } => Diagnostic::new_error(format!(
-                "unable to elaborate hole - expected: `{}`",
-                expected,
-            )).with_label(Label::new_primary(span).with_message("the hole")),
+            TypeError::UnsolvedMetas { ref metas } => metas.iter().fold(
+                Diagnostic::new_error("unable to fill in some holes or implicit arguments"),
+                |diagnostic, meta| {
+                    let kind = match meta.source {
+                        MetaSource::Hole => "a term",
+                        MetaSource::ImplicitArg => "an implicit argument",
+                    };
+                    let message = match meta.expected {
+                        Some(ref expected) => {
+                            format!("unable to infer {} of type `{}` here", kind, expected)
+                        },
+                        None => format!("unable to infer {} here", kind),
+                    };
+                    diagnostic.with_label(Label::new_primary(meta.span).with_message(message))
+                },
+            ),
             TypeError::UnexpectedFunction {
                 span, ref expected, ..
             } => Diagnostic::new_error(format!(
@@ -338,6 +466,14 @@ impl TypeError {
                 Diagnostic::new_error(format!("expected type, found a value of type `{}`", found))
                     .with_label(Label::new_primary(span).with_message("the value"))
             },
+            TypeError::ExpectedEquality { ref found, span } => Diagnostic::new_error(format!(
+                "expected a proof of equality, found a value of type `{}`",
+                found,
+            )).with_label(Label::new_primary(span).with_message("the proof")),
+            TypeError::UnresolvedImport { span, ref module } => {
+                Diagnostic::new_error(format!("the module `{}` has not been loaded", module))
+                    .with_label(Label::new_primary(span).with_message("imported here"))
+            },
             TypeError::UndefinedName { ref free_var, span } => {
                 Diagnostic::new_bug(format!("cannot find `{}` in scope", free_var))
                     .with_label(Label::new_primary(span).with_message("not found in this scope"))
@@ -387,6 +523,103 @@ impl TypeError {
             )).with_label(
                 Label::new_primary(span).with_message(format!("record with {} fields", found_size)),
             ),
+            TypeError::InvalidDataSignature { span, ref found } => Diagnostic::new_error(format!(
+                "expected the type of a data type to end in a universe, but found `{}`",
+                found,
+            )).with_label(Label::new_primary(span).with_message("the type of the data type")),
+            TypeError::ConstructorTooLarge {
+                span,
+                ref name,
+                ref found,
+                ref expected,
+            } => Diagnostic::new_error(format!(
+                "the constructor `{}` is too large to fit in the universe of its data type",
+                name,
+            )).with_label(Label::new_primary(span).with_message(format!(
+                "this type is in `Type^{}`, but the data type is in `Type^{}`",
+                found, expected,
+            ))),
+            TypeError::UniverseInconsistency {
+                span,
+                ref found,
+                ref expected,
+            } => Diagnostic::new_error("universe inconsistency").with_label(
+                Label::new_primary(span).with_message(format!(
+                    "the universe levels here require `Type^{}` to be contained in `Type^{}`",
+                    found, expected,
+                )),
+            ),
+            TypeError::InvalidConstructorReturnType {
+                span,
+                ref found,
+                ref expected,
+            } => Diagnostic::new_error(format!(
+                "constructor returned a term of type `{}`, but `{}` was expected",
+                found, expected,
+            )).with_label(Label::new_primary(span).with_message("the type of the constructor")),
+            TypeError::NonStrictlyPositive {
+                span,
+                ref name,
+                ref found,
+            } => Diagnostic::new_error(format!(
+                "the data type `{}` does not occur strictly positively in the type `{}`",
+                name, found,
+            )).with_label(Label::new_primary(span).with_message("the type of the constructor")),
+            TypeError::NonTerminating {
+                ref name,
+                ref call_spans,
+            } => {
+                let mut diagnostic =
+                    Diagnostic::new_error(format!("unable to show that `{}` terminates", name));
+                for (index, &span) in call_spans.iter().enumerate() {
+                    diagnostic = diagnostic.with_label(if index == 0 {
+                        Label::new_primary(span)
+                            .with_message("this call might not make its arguments smaller")
+                    } else {
+                        Label::new_secondary(span).with_message("which leads to this call")
+                    });
+                }
+                diagnostic
+            },
+            TypeError::NotAConstructor { span, ref name } => {
+                Diagnostic::new_error(format!("`{}` is not a constructor", name))
+                    .with_label(Label::new_primary(span).with_message("the pattern"))
+            },
+            TypeError::ConstructorMismatch {
+                span,
+                ref name,
+                ref expected,
+            } => Diagnostic::new_error(format!(
+                "the constructor `{}` does not construct a term of type `{}`",
+                name, expected,
+            )).with_label(Label::new_primary(span).with_message("the pattern")),
+            TypeError::NonExhaustivePatterns { span, ref missing } => {
+                let missing = missing
+                    .iter()
+                    .map(|pattern| format!("`{}`", pattern))
+                    .collect::<Vec<_>>();
+
+                Diagnostic::new_error("non-exhaustive patterns in case expression").with_label(
+                    Label::new_primary(span)
+                        .with_message(format!("patterns not covered: {}", missing.join(", "))),
+                )
+            },
+            TypeError::UnreachablePattern { span } => Diagnostic::new_warning("unreachable pattern")
+                .with_label(
+                    Label::new_primary(span)
+                        .with_message("this pattern is covered by the previous patterns"),
+                ),
+            TypeError::ConstructorArityMismatch {
+                span,
+                ref name,
+                found_arity,
+                expected_arity,
+            } => Diagnostic::new_error(format!(
+                "the constructor `{}` expected {} arguments but found {}",
+                name, expected_arity, found_arity,
+            )).with_label(
+                Label::new_primary(span).with_message(format!("{} arguments", found_arity)),
+            ),
         }
     }
 }
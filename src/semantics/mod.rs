@@ -5,66 +5,441 @@
 //! For more information, check out the theory appendix of the Pikelet book.
 
 use codespan::ByteSpan;
+use im::HashMap;
 use moniker::{Binder, BoundPattern, BoundTerm, Embed, FreeVar, Nest, Scope, Var};
 
 use syntax::core::{
-    Item, Literal, Module, Pattern, RcPattern, RcTerm, RcType, RcValue, Term, Value,
+    Closure, Constructor, Head, Item, Literal, Locals, MetaVar, Module, Neutral, Pattern,
+    RcNeutral, RcPattern, RcTerm, RcType, RcValue, Term, Value,
 };
+use syntax::concrete;
 use syntax::raw;
 use syntax::translation::Resugar;
-use syntax::{Level, LevelShift};
+use syntax::{Level, LevelMeta, LevelShift, LevelVar, Multiplicity, Plicity};
 
+mod coverage;
 mod env;
 mod errors;
 mod normalize;
+mod termination;
 #[cfg(test)]
 mod tests;
+mod unify;
 
-pub use self::env::{DeclarationEnv, DefinitionEnv, Extern, GlobalEnv, Globals, TcEnv};
-pub use self::errors::{InternalError, TypeError};
-pub use self::normalize::{match_value, nf_term};
+pub use self::env::{
+    DataEntry, DataEnv, DeclarationEnv, DefinitionEnv, Extern, GlobalEnv, Globals, MetaEntry,
+    MetaEnv, MetaSnapshot, MetaSource, ModuleEntry, ModuleEnv, TcEnv, UsageEnv, WarningEnv,
+};
+pub use self::coverage::check_coverage;
+pub use self::errors::{InternalError, TypeError, UnsolvedMeta};
+pub use self::normalize::{
+    apply, apply_closure, eval_nest_term, eval_term, eval_term_in, match_value, nf_term, Match,
+    open_closure, project, quote_value,
+};
+pub use self::termination::{check_definition_termination, check_termination};
+pub use self::unify::{zonk_term, zonk_value};
+
+use self::unify::{force, unify, unify_at};
 
 /// Type check and elaborate a module
-pub fn check_module<Env>(env: &Env, raw_module: &raw::Module) -> Result<Module, TypeError>
+///
+/// Checking continues past any items that fail to type check, so that all of
+/// the errors in the module can be reported at once. The failed items are
+/// replaced with `Item::Error` in the elaborated module.
+pub fn check_module<Env>(env: &Env, raw_module: &raw::Module) -> (Module, Vec<TypeError>)
 where
-    Env: DeclarationEnv + DefinitionEnv,
+    Env: MetaEnv + DefinitionEnv + DataEnv + ModuleEnv + UsageEnv + WarningEnv,
 {
-    use im::HashMap;
+    let (module, _, errors) = check_module_items(env.clone(), raw_module);
+
+    (module, errors)
+}
 
-    #[derive(Clone)]
-    pub enum ForwardDecl {
-        Pending(ByteSpan, RcTerm),
-        Defined(ByteSpan),
+/// Type check and elaborate a module, allowing it to be imported by later
+/// modules under the given name
+///
+/// The items of the module are added to the environment, and importing the
+/// module binds a record that refers to them.
+pub fn check_imported_module<Env>(
+    env: &mut Env,
+    name: &str,
+    raw_module: &raw::Module,
+) -> (Module, Vec<TypeError>)
+where
+    Env: MetaEnv + DefinitionEnv + DataEnv + ModuleEnv + UsageEnv + WarningEnv,
+{
+    let (module, mut module_env, mut errors) = check_module_items(env.clone(), raw_module);
+    match module_record(&module_env, &module) {
+        Ok(entry) => module_env.insert_module(name.to_owned(), entry),
+        Err(error) => errors.push(error),
     }
+    *env = module_env;
 
+    (module, errors)
+}
+
+/// The state of a name that has been bound by an item in a module
+#[derive(Clone)]
+enum ForwardDecl {
+    /// The name has been declared with the given type, but not yet defined
+    Pending(ByteSpan, RcTerm),
+    /// The name has been defined
+    Defined(ByteSpan),
+}
+
+/// Type check and elaborate the items of a module, returning the environment
+/// that they were added to, along with the errors that were found
+fn check_module_items<Env>(
+    mut env: Env,
+    raw_module: &raw::Module,
+) -> (Module, Env, Vec<TypeError>)
+where
+    Env: MetaEnv + DefinitionEnv + DataEnv + ModuleEnv + UsageEnv + WarningEnv,
+{
     // Declarations that may be waiting to be defined
     let mut forward_declarations = HashMap::new();
-    let mut env = env.clone();
+    // Metavariables that were left unsolved before we started on this module
+    // should not be reported as unsolved
+    let ignored_metas = env.unsolved_metas();
     // The elaborated items, pre-allocated to improve performance
     let mut items = Vec::with_capacity(raw_module.items.len());
+    let mut errors = Vec::new();
 
     // Iterate through the items in the module, checking each in turn
     for raw_item in &raw_module.items {
-        match *raw_item {
-            raw::Item::Declaration {
-                label_span,
-                ref label,
-                ref binder,
-                term: ref raw_term,
-            } => {
-                // Ensure that this declaration has not already been seen
+        // Items that could not be parsed have already been reported, and we
+        // don't know which names they were meant to bind
+        if let raw::Item::Error(_) = *raw_item {
+            continue;
+        }
+
+        // Check the item in copies of the environment, so that an item that
+        // fails part way through doesn't leave anything behind. The
+        // metavariables, warnings and usages are shared between the copies,
+        // so we record their state beforehand to restore them as well.
+        let metas = env.snapshot_metas();
+        let warning_count = env.warning_count();
+        let usages = env.usages();
+        let mut item_env = env.clone();
+        let mut item_forward_declarations = forward_declarations.clone();
+
+        // Definitions that might not terminate must be rejected before they
+        // can be unfolded by later items
+        let item = check_item(&mut item_env, &mut item_forward_declarations, raw_item)
+            .and_then(|item| {
+                check_definition_termination(raw_module, &items, &item)?;
+                Ok(item)
+            });
+
+        match item {
+            Ok(item) => {
+                env = item_env;
+                forward_declarations = item_forward_declarations;
+                items.push(item);
+            },
+            Err(error) => {
+                errors.push(error);
+                env.restore_metas(metas);
+                env.truncate_warnings(warning_count);
+                env.set_usages(usages);
+                items.push(poison_item(&mut env, &mut forward_declarations, raw_item));
+            },
+        }
+    }
+
+    // Report all of the metavariables that we were unable to solve at once
+    if let Err(error) = check_metas_solved(&env, &ignored_metas) {
+        errors.push(error);
+    }
+    // Declarations that were never defined may have left some level
+    // constraints behind
+    let pending_span = forward_declarations
+        .values()
+        .filter_map(|declaration| match *declaration {
+            ForwardDecl::Pending(span, _) => Some(span),
+            ForwardDecl::Defined(_) => None,
+        }).next();
+    if let Err(error) = solve_level_constraints(&env, pending_span.unwrap_or_default()) {
+        errors.push(error);
+    }
+    // Any levels that were left unconstrained can safely live in the lowest
+    // universe
+    env.default_level_metas();
+
+    // Metavariables may have been solved by items that came later in the
+    // module, so we zonk everything again now that we know all the solutions
+    let items = items
+        .into_iter()
+        .map(|item| match item {
+            Item::Declaration {
+                doc,
+                label,
+                binder,
+                term,
+                partial,
+                private,
+            } => Item::Declaration {
+                doc,
+                label,
+                binder,
+                term: zonk_term(&env, &term),
+                partial,
+                private,
+            },
+            Item::Definition {
+                doc,
+                label,
+                binder,
+                term,
+                private,
+            } => Item::Definition {
+                doc,
+                label,
+                binder,
+                term: zonk_term(&env, &term),
+                private,
+            },
+            Item::Data {
+                doc,
+                label,
+                binder,
+                params,
+                term,
+                constructors,
+                private,
+            } => Item::Data {
+                doc,
+                label,
+                binder,
+                params: params
+                    .into_iter()
+                    .map(|(binder, ann)| (binder, zonk_term(&env, &ann)))
+                    .collect(),
+                term: zonk_term(&env, &term),
+                constructors: constructors
+                    .into_iter()
+                    .map(|constructor| Constructor {
+                        term: zonk_term(&env, &constructor.term),
+                        ..constructor
+                    })
+                    .collect(),
+                private,
+            },
+            Item::Error { .. } => item,
+        })
+        .collect();
+
+    let module = Module {
+        doc: raw_module.doc.clone(),
+        items,
+    };
+
+    (module, env, errors)
+}
+
+/// Type check and elaborate an item, adding the names that it binds to the
+/// environment
+fn check_item<Env>(
+    env: &mut Env,
+    forward_declarations: &mut HashMap<Binder<String>, ForwardDecl>,
+    raw_item: &raw::Item,
+) -> Result<Item, TypeError>
+where
+    Env: MetaEnv + DefinitionEnv + DataEnv + ModuleEnv + UsageEnv + WarningEnv,
+{
+    match *raw_item {
+        raw::Item::Declaration {
+            ref doc,
+            label_span,
+            ref label,
+            ref binder,
+            term: ref raw_term,
+            partial,
+            private,
+        } => {
+            // Ensure that this declaration has not already been seen
+            match forward_declarations.get(binder) {
+                // There's already a definition associated with this name -
+                // we can't add a new declaration for it!
+                Some(&ForwardDecl::Defined(definition_span)) => {
+                    return Err(TypeError::DeclarationFollowedDefinition {
+                        definition_span,
+                        declaration_span: label_span,
+                        binder: binder.clone(),
+                    });
+                },
+                // There's a declaration  for this name already pending - we
+                // can't add a new one!
+                Some(&ForwardDecl::Pending(original_span, _)) => {
+                    return Err(TypeError::DuplicateDeclarations {
+                        original_span,
+                        duplicate_span: label_span,
+                        binder: binder.clone(),
+                    });
+                },
+                // No previous declaration for this name was seen, so we can
+                // go-ahead and type check, elaborate, and then add it to
+                // the context
+                None => {},
+            }
+
+            // Ensure that the declaration's type annotation is actually a type
+            let (term, _) = infer_universe(env, raw_term)?;
+            let term = zonk_term(env, &term);
+            // Remember the declaration for when we get to a subsequent definition
+            let declaration = ForwardDecl::Pending(label_span, term.clone());
+            forward_declarations.insert(binder.clone(), declaration);
+            // Add the declaration to the environment, allowing it to be
+            // referred to by later definitions before it has been defined.
+            // This is what allows for recursion and mutual recursion.
+            env.insert_declaration(binder.0.clone(), eval_term(env, &term)?);
+            if partial {
+                env.insert_partial(binder.0.clone());
+            }
+            // Declarations that mention level parameters are polymorphic
+            // over them, being instantiated afresh at each use site
+            let level_params = term.level_params();
+            if !level_params.is_empty() {
+                env.insert_level_params(binder.0.clone(), level_params);
+            }
+            // Add the declaration to the elaborated items
+            Ok(Item::Declaration {
+                doc: doc.clone(),
+                label: label.clone(),
+                binder: binder.clone(),
+                term,
+                partial,
+                private,
+            })
+        },
+
+        raw::Item::Definition {
+            ref doc,
+            label_span,
+            ref label,
+            ref binder,
+            term: ref raw_term,
+            private,
+        } => {
+            let is_declared = match forward_declarations.get(binder) {
+                Some(&ForwardDecl::Pending(_, _)) => true,
+                Some(&ForwardDecl::Defined(_)) | None => false,
+            };
+            let (term, ty) = match forward_declarations.get(binder).cloned() {
+                // This declaration was already given a definition, so this
+                // is an error!
+                //
+                // NOTE: Some languages (eg. Haskell, Agda, Idris, and
+                // Erlang) turn duplicate definitions into case matches.
+                // Languages like Elm don't. What should we do here?
+                Some(ForwardDecl::Defined(original_span)) => {
+                    return Err(TypeError::DuplicateDefinitions {
+                        original_span,
+                        duplicate_span: label_span,
+                        binder: binder.clone(),
+                    });
+                },
+                // We found a prior declaration, so we'll use it as a basis
+                // for checking the definition
+                Some(ForwardDecl::Pending(_, ty)) => {
+                    let ty = eval_term(env, &ty)?;
+                    (check_term(env, raw_term, &ty)?, ty)
+                },
+                // No prior declaration was found, so try to infer the type
+                // from the given definition alone
+                None => infer_term(env, raw_term)?,
+            };
+
+            // Now that the definition has been checked we can settle on
+            // the smallest universe levels that it is consistent with
+            solve_level_constraints(env, label_span)?;
+
+            // Definitions are polymorphic over the level parameters that
+            // they mention, along with any levels in their inferred type
+            // that were left unconstrained by the definition
+            let ty_term = zonk_term(env, &RcTerm::from(&*ty));
+            let mut level_params = match env.get_level_params(&binder.0) {
+                Some(level_params) => level_params.to_vec(),
+                None => ty_term.level_params(),
+            };
+            for param in term.level_params() {
+                if !level_params.contains(&param) {
+                    level_params.push(param);
+                }
+            }
+            if !is_declared {
+                for meta in ty_term.level_metas() {
+                    let param = (0..)
+                        .map(|index| format!("u{}", index))
+                        .find(|param| !level_params.contains(param))
+                        .unwrap();
+                    env.solve_level_meta(meta, Level::param(param.clone()));
+                    level_params.push(param);
+                }
+            }
+            // Everything else can live in the lowest universe
+            let term = zonk_term(env, &term);
+            for meta in ty_term.level_metas().into_iter().chain(term.level_metas()) {
+                if env.get_level_meta(meta).is_none() {
+                    env.solve_level_meta(meta, Level::from(0));
+                }
+            }
+            if !level_params.is_empty() {
+                env.insert_level_params(binder.0.clone(), level_params);
+            }
+
+            let term = zonk_term(env, &term);
+            let ty = zonk_value(env, &ty)?;
+
+            // We must not remove this from the list of pending
+            // declarations, lest we encounter another declaration or
+            // definition of the same name later on!
+            forward_declarations.insert(binder.clone(), ForwardDecl::Defined(label_span));
+            // Add the declaration and definition to the environment,
+            // allowing them to be used in later type checking
+            env.insert_declaration(binder.0.clone(), ty);
+            if is_recursive_definition(env, &binder.0, &term) {
+                env.insert_recursive_definition(binder.0.clone(), term.clone());
+            } else {
+                env.insert_definition(binder.0.clone(), term.clone());
+            }
+            // Add the definition to the elaborated items
+            Ok(Item::Definition {
+                doc: doc.clone(),
+                label: label.clone(),
+                binder: binder.clone(),
+                term,
+                private,
+            })
+        },
+
+        raw::Item::Data {
+            ref doc,
+            label_span,
+            ref label,
+            ref binder,
+            params: ref raw_params,
+            term: ref raw_term,
+            constructors: ref raw_constructors,
+            private,
+            ..
+        } => {
+            // Ensure that the data type and its constructors have not
+            // already been seen
+            let binders = Some((label_span, binder)).into_iter().chain(
+                raw_constructors
+                    .iter()
+                    .map(|raw_constructor| (raw_constructor.label_span, &raw_constructor.binder)),
+            );
+            for (label_span, binder) in binders {
                 match forward_declarations.get(binder) {
-                    // There's already a definition associated with this name -
-                    // we can't add a new declaration for it!
-                    Some(&ForwardDecl::Defined(definition_span)) => {
-                        return Err(TypeError::DeclarationFollowedDefinition {
-                            definition_span,
-                            declaration_span: label_span,
+                    Some(&ForwardDecl::Defined(original_span)) => {
+                        return Err(TypeError::DuplicateDefinitions {
+                            original_span,
+                            duplicate_span: label_span,
                             binder: binder.clone(),
                         });
                     },
-                    // There's a declaration  for this name already pending - we
-                    // can't add a new one!
                     Some(&ForwardDecl::Pending(original_span, _)) => {
                         return Err(TypeError::DuplicateDeclarations {
                             original_span,
@@ -72,143 +447,1009 @@ where
                             binder: binder.clone(),
                         });
                     },
-                    // No previous declaration for this name was seen, so we can
-                    // go-ahead and type check, elaborate, and then add it to
-                    // the context
                     None => {},
                 }
+                forward_declarations.insert(binder.clone(), ForwardDecl::Defined(label_span));
+            }
 
-                // Ensure that the declaration's type annotation is actually a type
-                let (term, _) = infer_universe(&env, raw_term)?;
-                // Remember the declaration for when we get to a subsequent definition
-                let declaration = ForwardDecl::Pending(label_span, term.clone());
-                forward_declarations.insert(binder.clone(), declaration);
-                // Add the declaration to the elaborated items
-                items.push(Item::Declaration {
-                    label: label.clone(),
-                    binder: binder.clone(),
-                    term,
-                });
+            // Check the parameters, bringing them into scope for the rest
+            // of the data type
+            let mut param_env = env.clone();
+            let mut params = Vec::with_capacity(raw_params.len());
+            for &(ref param_binder, ref raw_ann) in raw_params {
+                let (ann, _) = infer_universe(&param_env, raw_ann)?;
+                let ann_value = eval_term(&param_env, &ann)?;
+                param_env.insert_declaration(param_binder.0.clone(), ann_value);
+                params.push((param_binder.clone(), zonk_term(&param_env, &ann)));
+            }
+
+            // Ensure that the type of the data type ends in a universe
+            let (term, _) = infer_universe(&param_env, raw_term)?;
+            let term = zonk_term(&param_env, &term);
+            let term_value = eval_term(&param_env, &term)?;
+            let data_level = match data_level(&param_env, &term_value)? {
+                Some(level) => level,
+                None => {
+                    return Err(TypeError::InvalidDataSignature {
+                        span: raw_term.span(),
+                        found: Box::new(term_value.resugar(param_env.resugar_env())),
+                    });
+                },
+            };
+
+            // The data type is declared, but never defined, leaving it
+            // stuck as a neutral variable during normalization
+            let data_ty = params.iter().rev().fold(term.clone(), |acc, param| {
+                let (ref param_binder, ref param_ann) = *param;
+                RcTerm::from(Term::Pi(
+                    Plicity::Explicit,
+                    Multiplicity::Many,
+                    Scope::new((param_binder.clone(), Embed(param_ann.clone())), acc),
+                ))
+            });
+            let data_ty = eval_term(env, &data_ty)?;
+            env.insert_declaration(binder.0.clone(), data_ty.clone());
+            param_env.insert_declaration(binder.0.clone(), data_ty);
+
+            let mut constructors = Vec::with_capacity(raw_constructors.len());
+            for raw_constructor in raw_constructors {
+                let constructor = check_constructor(
+                    &param_env,
+                    binder,
+                    &params,
+                    &data_level,
+                    raw_constructor,
+                )?;
+
+                // Parameters are implicit arguments to the constructors
+                let constructor_ty =
+                    params
+                        .iter()
+                        .rev()
+                        .fold(constructor.term.clone(), |acc, param| {
+                            let (ref param_binder, ref param_ann) = *param;
+                            RcTerm::from(Term::Pi(
+                                Plicity::Implicit,
+                                Multiplicity::Many,
+                                Scope::new((param_binder.clone(), Embed(param_ann.clone())), acc),
+                            ))
+                        });
+                let constructor_ty = eval_term(env, &constructor_ty)?;
+                let definition = constructor_definition(&param_env, &params, &constructor)?;
+
+                env.insert_declaration(constructor.binder.0.clone(), constructor_ty);
+                env.insert_definition(constructor.binder.0.clone(), definition);
+                constructors.push(constructor);
+            }
+
+            // Settle on the smallest universe that the data type fits in
+            solve_level_constraints(env, label_span)?;
+            for meta in zonk_term(env, &term).level_metas() {
+                env.solve_level_meta(meta, Level::from(0));
+            }
+
+            env.insert_data(
+                binder.0.clone(),
+                DataEntry {
+                    params: params.len(),
+                    constructors: constructors
+                        .iter()
+                        .map(|constructor| constructor.binder.0.clone())
+                        .collect(),
+                },
+            );
+            Ok(Item::Data {
+                doc: doc.clone(),
+                label: label.clone(),
+                binder: binder.clone(),
+                params,
+                term,
+                constructors,
+                private,
+            })
+        },
+
+        raw::Item::Import {
+            ref doc,
+            span,
+            label_span,
+            ref label,
+            ref binder,
+            ref module,
+            private,
+        } => {
+            match forward_declarations.get(binder) {
+                Some(&ForwardDecl::Defined(original_span)) => {
+                    return Err(TypeError::DuplicateDefinitions {
+                        original_span,
+                        duplicate_span: label_span,
+                        binder: binder.clone(),
+                    });
+                },
+                Some(&ForwardDecl::Pending(original_span, _)) => {
+                    return Err(TypeError::DuplicateDeclarations {
+                        original_span,
+                        duplicate_span: label_span,
+                        binder: binder.clone(),
+                    });
+                },
+                None => {},
+            }
+
+            let entry = match env.get_module(module) {
+                Some(entry) => entry.clone(),
+                None => {
+                    return Err(TypeError::UnresolvedImport {
+                        span,
+                        module: module.clone(),
+                    });
+                },
+            };
+
+            // Imports are bound like any other definition, allowing the
+            // items of the module to be projected from the record
+            forward_declarations.insert(binder.clone(), ForwardDecl::Defined(label_span));
+            env.insert_declaration(binder.0.clone(), entry.ty);
+            env.insert_definition(binder.0.clone(), entry.term.clone());
+            Ok(Item::Definition {
+                doc: doc.clone(),
+                label: label.clone(),
+                binder: binder.clone(),
+                term: entry.term,
+                private,
+            })
+        },
+
+        raw::Item::Error(_) => unreachable!("unparsed items are skipped"),
+    }
+}
+
+/// Bind the names of an item that failed to type check to poisoned types,
+/// returning the error item that stands in for it
+///
+/// Names that were already bound by earlier items are left alone, because
+/// the item may have failed on account of them being bound again.
+fn poison_item<Env>(
+    env: &mut Env,
+    forward_declarations: &mut HashMap<Binder<String>, ForwardDecl>,
+    raw_item: &raw::Item,
+) -> Item
+where
+    Env: DeclarationEnv,
+{
+    let (doc, label_span, label, binder) = match *raw_item {
+        raw::Item::Declaration {
+            ref doc,
+            label_span,
+            ref label,
+            ref binder,
+            ..
+        }
+        | raw::Item::Definition {
+            ref doc,
+            label_span,
+            ref label,
+            ref binder,
+            ..
+        }
+        | raw::Item::Data {
+            ref doc,
+            label_span,
+            ref label,
+            ref binder,
+            ..
+        }
+        | raw::Item::Import {
+            ref doc,
+            label_span,
+            ref label,
+            ref binder,
+            ..
+        } => (doc, label_span, label, binder),
+        raw::Item::Error(_) => unreachable!("unparsed items are skipped"),
+    };
+    let is_declaration = match *raw_item {
+        raw::Item::Declaration { .. } => true,
+        raw::Item::Definition { .. }
+        | raw::Item::Data { .. }
+        | raw::Item::Import { .. }
+        | raw::Item::Error(_) => false,
+    };
+
+    let mut binders = vec![(label_span, binder)];
+    if let raw::Item::Data {
+        constructors: ref raw_constructors,
+        ..
+    } = *raw_item
+    {
+        binders.extend(
+            raw_constructors
+                .iter()
+                .map(|raw_constructor| (raw_constructor.label_span, &raw_constructor.binder)),
+        );
+    }
+
+    for (label_span, binder) in binders {
+        let declaration = match forward_declarations.get(binder).cloned() {
+            Some(ForwardDecl::Defined(_)) => continue,
+            Some(ForwardDecl::Pending(_, _)) if is_declaration => continue,
+            // The name keeps the type that it was declared with
+            Some(ForwardDecl::Pending(_, _)) => ForwardDecl::Defined(label_span),
+            None => {
+                env.insert_declaration(binder.0.clone(), RcValue::from(Value::Error));
+                if is_declaration {
+                    ForwardDecl::Pending(label_span, RcTerm::from(Term::Error))
+                } else {
+                    ForwardDecl::Defined(label_span)
+                }
             },
+        };
+        forward_declarations.insert(binder.clone(), declaration);
+    }
 
-            raw::Item::Definition {
-                label_span,
+    Item::Error {
+        doc: doc.clone(),
+        label: label.clone(),
+        binder: binder.clone(),
+    }
+}
+
+/// Collect the public definitions, data types, and constructors of a module
+/// into a record, along with the type of that record
+///
+/// Items are left out of the record if either their declaration or their
+/// definition was marked as private.
+///
+/// The fields refer back to the items of the module, so the environment that
+/// the module was checked in is needed to make use of the record.
+fn module_record<Env>(env: &Env, module: &Module) -> Result<ModuleEntry, TypeError>
+where
+    Env: DeclarationEnv + DefinitionEnv + MetaEnv,
+{
+    use std::collections::HashSet;
+
+    let mut private_binders = HashSet::new();
+    let mut binders = Vec::new();
+    let mut constructors = Vec::new();
+    for item in &module.items {
+        match *item {
+            Item::Declaration {
+                ref binder,
+                private: true,
+                ..
+            } => {
+                private_binders.insert(binder);
+            },
+            Item::Declaration { .. } | Item::Data { private: true, .. } => {},
+            Item::Definition {
                 ref label,
                 ref binder,
-                term: ref raw_term,
+                private,
+                ..
             } => {
-                let (term, ty) = match forward_declarations.get(binder).cloned() {
-                    // This declaration was already given a definition, so this
-                    // is an error!
-                    //
-                    // NOTE: Some languages (eg. Haskell, Agda, Idris, and
-                    // Erlang) turn duplicate definitions into case matches.
-                    // Languages like Elm don't. What should we do here?
-                    Some(ForwardDecl::Defined(original_span)) => {
-                        return Err(TypeError::DuplicateDefinitions {
-                            original_span,
-                            duplicate_span: label_span,
-                            binder: binder.clone(),
-                        });
-                    },
-                    // We found a prior declaration, so we'll use it as a basis
-                    // for checking the definition
-                    Some(ForwardDecl::Pending(_, ty)) => {
-                        let ty = nf_term(&env, &ty)?;
-                        (check_term(&env, &raw_term, &ty)?, ty)
+                if !private && !private_binders.contains(binder) {
+                    binders.push((label, binder));
+                }
+            },
+            Item::Data {
+                ref label,
+                ref binder,
+                constructors: ref data_constructors,
+                ..
+            } => {
+                binders.push((label, binder));
+                binders.extend(
+                    data_constructors
+                        .iter()
+                        .map(|constructor| (&constructor.label, &constructor.binder)),
+                );
+                constructors.extend(data_constructors.iter().map(|constructor| {
+                    (constructor.label.clone(), constructor.binder.0.clone())
+                }));
+            },
+            // Items that failed to type check are still exported, with
+            // poisoned types, so that they can be used without causing
+            // further errors
+            Item::Error {
+                ref label,
+                ref binder,
+                ..
+            } => {
+                if !private_binders.contains(binder) {
+                    binders.push((label, binder));
+                }
+            },
+        }
+    }
+
+    let mut fields = Vec::with_capacity(binders.len());
+    let mut field_tys = Vec::with_capacity(binders.len());
+    for (label, binder) in binders {
+        let ty = env.get_declaration(&binder.0).expect("undeclared module item");
+        let ty = zonk_term(env, &RcTerm::from(&**ty));
+        let term = RcTerm::from(Term::var(Var::Free(binder.0.clone()), 0));
+
+        fields.push((label.clone(), binder.clone(), Embed(term)));
+        field_tys.push((label.clone(), binder.clone(), Embed(ty)));
+    }
+
+    let term = RcTerm::from(Term::Record(Scope::new(Nest::new(fields), ())));
+    let ty = RcTerm::from(Term::RecordType(Scope::new(Nest::new(field_tys), ())));
+    let ty = eval_term(env, &ty)?;
+
+    Ok(ModuleEntry {
+        term,
+        ty,
+        constructors,
+    })
+}
+
+/// Returns the universe that a data type with the given type lives in, if
+/// the type ends in a universe
+fn data_level<Env>(env: &Env, ty: &RcType) -> Result<Option<Level>, InternalError>
+where
+    Env: DefinitionEnv,
+{
+    match **ty.unfold() {
+        Value::Universe(ref level) => Ok(Some(level.clone())),
+        Value::Pi(_, _, _, ref closure) => data_level(env, &open_closure(env, closure)?.1),
+        _ => Ok(None),
+    }
+}
+
+/// The name of a variable as it was written in the source, for use in error
+/// messages
+fn pretty_name(free_var: &FreeVar<String>) -> String {
+    match free_var.pretty_name {
+        Some(ref name) => name.clone(),
+        None => free_var.to_string(),
+    }
+}
+
+/// Resugar types to be shown together in an error message
+///
+/// The solutions of any metavariables are filled in first. Universe levels
+/// that have yet to be inferred are then named `?l`, `?l1`, and so on, in
+/// the order that they appear, rather than by the internal numbering of
+/// their metavariables.
+fn resugar_tys<Env>(env: &Env, tys: &[&RcType]) -> Vec<concrete::Term>
+where
+    Env: MetaEnv,
+{
+    let terms = tys
+        .iter()
+        .map(|ty| zonk_term(env, &RcTerm::from(ty.to_folded_term())))
+        .collect::<Vec<_>>();
+    let mut metas = Vec::new();
+    for term in &terms {
+        for meta in term.level_metas() {
+            if !metas.contains(&meta) {
+                metas.push(meta);
+            }
+        }
+    }
+
+    terms
+        .iter()
+        .map(|term| {
+            let term = term.substs_levels(&|var: &LevelVar| name_level_meta(&metas, var));
+            term.resugar(env.resugar_env())
+        }).collect()
+}
+
+/// Resugar the found and expected types of a mismatch, in the same way as
+/// `resugar_tys`
+fn resugar_mismatch<Env>(
+    env: &Env,
+    found: &RcType,
+    expected: &RcType,
+) -> (Box<concrete::Term>, Box<concrete::Term>)
+where
+    Env: MetaEnv,
+{
+    let mut tys = resugar_tys(env, &[found, expected]);
+    let expected = tys.pop().unwrap();
+    let found = tys.pop().unwrap();
+    (Box::new(found), Box::new(expected))
+}
+
+/// Resugar a type to be shown in an error message, in the same way as
+/// `resugar_tys`
+fn resugar_ty<Env>(env: &Env, ty: &RcType) -> Box<concrete::Term>
+where
+    Env: MetaEnv,
+{
+    Box::new(resugar_tys(env, &[ty]).pop().unwrap())
+}
+
+/// The name of an unsolved level metavariable in an error message, given the
+/// metavariables that appear in the message
+fn name_level_meta(metas: &[LevelMeta], var: &LevelVar) -> Option<Level> {
+    match *var {
+        LevelVar::Meta(meta) => metas.iter().position(|&other| other == meta).map(|index| {
+            match index {
+                0 => Level::param("?l"),
+                index => Level::param(format!("?l{}", index)),
+            }
+        }),
+        LevelVar::Param(_) => None,
+    }
+}
+
+/// Check a constructor of a data type, returning the elaborated constructor
+///
+/// We ensure that:
+///
+/// - the type of the constructor fits in the universe of the data type
+/// - the data type only occurs strictly positively in the arguments
+/// - the constructor returns the data type, applied to its parameters
+fn check_constructor<Env>(
+    env: &Env,
+    data_binder: &Binder<String>,
+    params: &[(Binder<String>, RcTerm)],
+    data_level: &Level,
+    raw_constructor: &raw::Constructor,
+) -> Result<Constructor, TypeError>
+where
+    Env: MetaEnv + DefinitionEnv + DataEnv + UsageEnv + WarningEnv,
+{
+    let data_var = &data_binder.0;
+    let span = raw_constructor.term.span();
+
+    let (term, level) = infer_universe(env, &raw_constructor.term)?;
+    let term = zonk_term(env, &term);
+    if !unify::level_le(env, &level, data_level) {
+        let found = unify::zonk_level(env, &level);
+        let expected = unify::zonk_level(env, data_level);
+        let mut metas = Vec::new();
+        for &(ref var, _) in found.vars().iter().chain(expected.vars()) {
+            if let LevelVar::Meta(meta) = *var {
+                if !metas.contains(&meta) {
+                    metas.push(meta);
+                }
+            }
+        }
+
+        return Err(TypeError::ConstructorTooLarge {
+            span,
+            name: pretty_name(&raw_constructor.binder.0),
+            found: found.substs(|var| name_level_meta(&metas, var)),
+            expected: expected.substs(|var| name_level_meta(&metas, var)),
+        });
+    }
+
+    // Walk the arguments of the constructor, declaring them as we go so that
+    // we can resugar any types that refer to them
+    let mut arg_env = env.clone();
+    let mut ty = eval_term(env, &term)?;
+    while let Value::Pi(_, _, ref ann, ref closure) = *ty.clone() {
+        let (Binder(free_var), body) = open_closure(env, closure)?;
+        if !is_strictly_positive(env, data_var, ann)? {
+            return Err(TypeError::NonStrictlyPositive {
+                span,
+                name: pretty_name(data_var),
+                found: Box::new(ann.resugar(arg_env.resugar_env())),
+            });
+        }
+        arg_env.insert_declaration(free_var, ann.clone());
+        ty = body;
+    }
+
+    // The constructor must return the data type, applied to the parameters
+    // followed by any indices
+    let is_valid_return_ty = match ty.free_var_app() {
+        Some((free_var, LevelShift(0), spine))
+            if free_var == data_var && spine.len() >= params.len() =>
+        {
+            let (param_args, index_args) = spine.split_at(params.len());
+
+            <_>::zip(params.iter(), param_args).all(|(param, arg)| {
+                let (Binder(ref param_var), _) = *param;
+                match arg.1.free_var_app() {
+                    Some((free_var, LevelShift(0), spine)) => {
+                        arg.0 == Plicity::Explicit && free_var == param_var && spine.is_empty()
                     },
-                    // No prior declaration was found, so try to infer the type
-                    // from the given definition alone
-                    None => infer_term(&env, &raw_term)?,
-                };
+                    _ => false,
+                }
+            }) && index_args.iter().all(|arg| !occurs_in(data_var, &arg.1))
+        },
+        _ => false,
+    };
 
-                // We must not remove this from the list of pending
-                // declarations, lest we encounter another declaration or
-                // definition of the same name later on!
-                forward_declarations.insert(binder.clone(), ForwardDecl::Defined(label_span));
-                // Add the declaration and definition to the environment,
-                // allowing them to be used in later type checking
-                env.insert_declaration(binder.0.clone(), ty);
-                env.insert_definition(binder.0.clone(), term.clone());
-                // Add the definition to the elaborated items
-                items.push(Item::Definition {
-                    label: label.clone(),
-                    binder: binder.clone(),
-                    term,
-                });
+    if !is_valid_return_ty {
+        let expected = Value::Neutral(
+            RcNeutral::from(Neutral::var(Var::Free(data_var.clone()), 0)),
+            params
+                .iter()
+                .map(|&(Binder(ref param_var), _)| {
+                    let param = RcValue::from(Value::var(Var::Free(param_var.clone()), 0));
+                    (Plicity::Explicit, param)
+                }).collect(),
+        );
+
+        return Err(TypeError::InvalidConstructorReturnType {
+            span,
+            found: Box::new(ty.resugar(arg_env.resugar_env())),
+            expected: Box::new(expected.resugar(env.resugar_env())),
+        });
+    }
+
+    Ok(Constructor {
+        doc: raw_constructor.doc.clone(),
+        label: raw_constructor.label.clone(),
+        binder: raw_constructor.binder.clone(),
+        term,
+    })
+}
+
+/// Returns true if the definition refers back to itself, either directly or by
+/// way of the definitions that it mentions
+fn is_recursive_definition<Env>(env: &Env, free_var: &FreeVar<String>, term: &RcTerm) -> bool
+where
+    Env: DefinitionEnv,
+{
+    use std::collections::HashSet;
+
+    let mut seen = HashSet::new();
+    let mut pending = vec![term.clone()];
+
+    while let Some(term) = pending.pop() {
+        for current in term.free_vars() {
+            if current == *free_var {
+                return true;
+            }
+            if let Some(term) = env.get_definition(&current) {
+                if seen.insert(current.clone()) {
+                    pending.push(term.clone());
+                }
+            }
+        }
+    }
+
+    false
+}
+
+/// Returns true if the free variable is mentioned in the given value
+fn occurs_in(free_var: &FreeVar<String>, value: &RcValue) -> bool {
+    RcTerm::from(&**value).free_vars().contains(free_var)
+}
+
+/// Returns true if the data type only occurs strictly positively in the type
+/// of a constructor argument. That is, it must either not occur at all, or
+/// only as the result of a function type:
+///
+/// ```text
+/// (x : A) -> (y : B) -> Data A
+/// ```
+fn is_strictly_positive<Env>(
+    env: &Env,
+    data_var: &FreeVar<String>,
+    ty: &RcValue,
+) -> Result<bool, InternalError>
+where
+    Env: DefinitionEnv,
+{
+    match **ty.unfold() {
+        Value::Pi(_, _, ref ann, ref closure) => Ok(!occurs_in(data_var, ann)
+            && is_strictly_positive(env, data_var, &open_closure(env, closure)?.1)?),
+        _ => match ty.free_var_app() {
+            Some((free_var, _, spine)) if free_var == data_var => {
+                Ok(spine.iter().all(|arg| !occurs_in(data_var, &arg.1)))
             },
+            _ => Ok(!occurs_in(data_var, ty)),
+        },
+    }
+}
+
+/// Construct the definition of a constructor, taking the parameters of the
+/// data type as implicit arguments
+fn constructor_definition<Env>(
+    env: &Env,
+    params: &[(Binder<String>, RcTerm)],
+    constructor: &Constructor,
+) -> Result<RcTerm, TypeError>
+where
+    Env: DefinitionEnv,
+{
+    let mut args = Vec::new();
+    let mut ty = eval_term(env, &constructor.term)?;
+    while let Value::Pi(plicity, multiplicity, ref ann, ref closure) = *ty.clone() {
+        let (binder, body) = open_closure(env, closure)?;
+        args.push((plicity, multiplicity, binder, RcTerm::from(&**ann)));
+        ty = body;
+    }
+
+    let term = RcTerm::from(Term::Constr(
+        Var::Free(constructor.binder.0.clone()),
+        args.iter()
+            .map(|&(plicity, _, Binder(ref free_var), _)| {
+                (plicity, RcTerm::from(Term::var(Var::Free(free_var.clone()), 0)))
+            }).collect(),
+    ));
+    let term = args
+        .into_iter()
+        .rev()
+        .fold(term, |acc, (plicity, multiplicity, binder, ann)| {
+            RcTerm::from(Term::Lam(plicity, multiplicity, Scope::new((binder, Embed(ann)), acc)))
+        });
+
+    Ok(params.iter().rev().fold(term, |acc, param| {
+        let (ref param_binder, ref param_ann) = *param;
+        RcTerm::from(Term::Lam(
+            Plicity::Implicit,
+            Multiplicity::Many,
+            Scope::new((param_binder.clone(), Embed(param_ann.clone())), acc),
+        ))
+    }))
+}
+
+/// Ensure that all of the metavariables inserted since `prior_unsolved` was
+/// collected have been solved, reporting the ones that remain if not
+pub fn check_metas_solved<Env>(env: &Env, prior_unsolved: &[MetaVar]) -> Result<(), TypeError>
+where
+    Env: MetaEnv,
+{
+    let unsolved = env
+        .unsolved_metas()
+        .into_iter()
+        .filter(|meta| !prior_unsolved.contains(meta))
+        .collect::<Vec<_>>();
+
+    // Holes whose types could not be inferred are given metavariables for
+    // their types as well, which we leave out so that they are only reported
+    // once, without a type
+    let unknown_ty = |meta| {
+        let entry = env.get_meta(meta);
+        match *zonk_term(env, &RcTerm::from(entry.ty.to_folded_term())).inner {
+            Term::Meta(ty_meta)
+                if unsolved.contains(&ty_meta) && env.get_meta(ty_meta).span == entry.span =>
+            {
+                Some(ty_meta)
+            },
+            _ => None,
         }
+    };
+    let ty_metas = unsolved
+        .iter()
+        .filter_map(|&meta| unknown_ty(meta))
+        .collect::<Vec<_>>();
+    let metas = unsolved
+        .iter()
+        .filter(|meta| !ty_metas.contains(meta))
+        .map(|&meta| {
+            let entry = env.get_meta(meta);
+            let expected = match unknown_ty(meta) {
+                Some(_) => None,
+                None => Some(resugar_ty(env, &entry.ty)),
+            };
+
+            UnsolvedMeta {
+                span: entry.span,
+                source: entry.source,
+                expected,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if metas.is_empty() {
+        Ok(())
+    } else {
+        Err(TypeError::UnsolvedMetas { metas })
     }
+}
 
-    Ok(Module { items })
+/// Solve the universe level constraints that have been collected so far,
+/// giving the level metavariables involved the smallest consistent levels
+pub fn solve_level_constraints<Env>(env: &Env, span: ByteSpan) -> Result<(), TypeError>
+where
+    Env: MetaEnv,
+{
+    unify::solve_level_constraints(env)
+        .map_err(|(found, expected)| TypeError::UniverseInconsistency {
+            span,
+            found,
+            expected,
+        })
 }
 
-/// Returns true if `ty1` is a subtype of `ty2`
-fn is_subtype<Env>(env: &Env, ty1: &RcType, ty2: &RcType) -> bool
+/// Returns true if `ty1` is a subtype of `ty2`, solving any metavariables
+/// that are needed to make this so
+fn is_subtype<Env>(env: &Env, ty1: &RcType, ty2: &RcType) -> Result<bool, TypeError>
 where
-    Env: DeclarationEnv,
+    Env: MetaEnv + DefinitionEnv,
 {
+    let ty1 = force(env, ty1)?;
+    let ty2 = force(env, ty2)?;
+
     match (&*ty1.inner, &*ty2.inner) {
         // ST-TYPE
-        (&Value::Universe(level1), &Value::Universe(level2)) => level1 <= level2,
+        (&Value::Universe(ref level1), &Value::Universe(ref level2)) => {
+            Ok(unify::level_le(env, level1, level2))
+        },
 
         // ST-PI
-        (&Value::Pi(ref scope1), &Value::Pi(ref scope2)) => {
-            let ((_, Embed(ann1)), body1, (Binder(free_var2), Embed(ann2)), body2) =
-                Scope::unbind2(scope1.clone(), scope2.clone());
+        (
+            &Value::Pi(plicity1, multiplicity1, ref ann1, ref closure1),
+            &Value::Pi(plicity2, multiplicity2, ref ann2, ref closure2),
+        ) if plicity1 == plicity2 && multiplicity1 == multiplicity2 =>
+        {
+            Ok(is_subtype(env, ann2, ann1)? && {
+                let (Binder(free_var2), body2) = open_closure(env, closure2)?;
+                let var = RcValue::from(Value::var(Var::Free(free_var2.clone()), 0));
+                let body1 = apply_closure(env, closure1, var)?;
 
-            is_subtype(env, &ann2, &ann1) && {
                 let mut env = env.clone();
-                env.insert_declaration(free_var2, ann2);
-                is_subtype(&env, &body1, &body2)
-            }
+                env.insert_declaration(free_var2, ann2.clone());
+                is_subtype(&env, &body1, &body2)?
+            })
         },
 
         // ST-RECORD-TYPE, ST-EMPTY-RECORD-TYPE
-        (&Value::RecordType(ref scope1), &Value::RecordType(ref scope2)) => {
-            if scope1.unsafe_pattern.unsafe_patterns.len()
-                != scope2.unsafe_pattern.unsafe_patterns.len()
+        (&Value::RecordType(ref closure1), &Value::RecordType(ref closure2)) => {
+            if closure1.term.unsafe_pattern.unsafe_patterns.len()
+                != closure2.term.unsafe_pattern.unsafe_patterns.len()
             {
-                return false;
+                return Ok(false);
             }
 
-            let (fields1, (), fields2, ()) = Scope::unbind2(scope1.clone(), scope2.clone());
+            let fields1 = &closure1.term.unsafe_pattern.unsafe_patterns;
+            let fields2 = &closure2.term.unsafe_pattern.unsafe_patterns;
 
             let mut env = env.clone();
-            for (field1, field2) in
-                Iterator::zip(fields1.unnest().into_iter(), fields2.unnest().into_iter())
-            {
-                let (label1, Binder(free_var1), Embed(ty1)) = field1;
-                let (label2, _, Embed(ty2)) = field2;
-
-                if label1 == label2 && is_subtype(&env, &ty1, &ty2) {
-                    env.insert_declaration(free_var1, ty1);
+            let mut values = Vec::with_capacity(fields1.len());
+            for (field1, field2) in Iterator::zip(fields1.iter(), fields2.iter()) {
+                let (ref label1, Binder(ref free_var1), Embed(ref ty1)) = *field1;
+                let (ref label2, _, Embed(ref ty2)) = *field2;
+
+                let ty1 = eval_nest_term(&env, &closure1.locals, &values, ty1)?;
+                let ty2 = eval_nest_term(&env, &closure2.locals, &values, ty2)?;
+                if label1 == label2 && is_subtype(&env, &ty1, &ty2)? {
+                    let free_var = FreeVar::fresh(free_var1.pretty_name.clone());
+                    values.push(RcValue::from(Value::var(Var::Free(free_var.clone()), 0)));
+                    env.insert_declaration(free_var, ty1);
                 } else {
-                    return false;
+                    return Ok(false);
                 }
             }
 
-            true
+            Ok(true)
+        },
+
+        // ST-ALPHA-EQ
+        (_, _) => Ok(unify(env, &ty1, &ty2)?),
+    }
+}
+
+/// Apply fresh metavariables to any leading implicit parameters in the type
+/// of a term, returning the elaborated application and its type
+fn insert_implicit_args<Env>(
+    env: &Env,
+    span: ByteSpan,
+    term: RcTerm,
+    ty: RcType,
+) -> Result<(RcTerm, RcType), TypeError>
+where
+    Env: MetaEnv + DefinitionEnv,
+{
+    let mut term = term;
+    let mut ty = ty;
+
+    loop {
+        let next_ty = match *force(env, &ty)? {
+            Value::Pi(Plicity::Implicit, _, ref ann, ref closure) => {
+                let meta = env.fresh_meta(span, MetaSource::ImplicitArg, ann.clone());
+
+                term = RcTerm::from(Term::App(term, Plicity::Implicit, RcTerm::from(Term::Meta(meta))));
+                let meta = RcValue::from(Value::from(Neutral::Head(Head::Meta(meta))));
+                apply_closure(env, closure, meta)?
+            },
+            _ => return Ok((term, ty)),
+        };
+
+        ty = next_ty;
+    }
+}
+
+/// Ensures that the given term is a universe, returning the level of that
+/// universe and its elaborated form.
+///
+/// Types are erased at runtime, so any uses of variables in them are ignored.
+fn infer_universe<Env>(env: &Env, raw_term: &raw::RcTerm) -> Result<(RcTerm, Level), TypeError>
+where
+    Env: MetaEnv + DefinitionEnv + DataEnv + UsageEnv + WarningEnv,
+{
+    let (term, ty) = scale_usages(env, Multiplicity::Zero, || infer_term(env, raw_term))?;
+    let ty = force(env, &ty)?;
+
+    // If the type is not yet known, default it to the lowest universe
+    if let Some(meta) = unify::unsolved_meta(env, &ty) {
+        env.solve_meta(meta, RcValue::from(Value::universe(0)));
+        return Ok((term, Level::from(0)));
+    }
+
+    match *ty {
+        Value::Universe(ref level) => Ok((term, level.clone())),
+        Value::Error => Ok((term, Level::from(0))),
+        _ => Err(TypeError::ExpectedUniverse {
+            span: raw_term.span(),
+            found: resugar_ty(env, &ty),
+        }),
+    }
+}
+
+/// Returns the level of the universe that the given type lives in, if it can
+/// be found
+///
+/// This is used to find the universe of an equality type from the type of the
+/// terms being compared, where we no longer have the raw syntax of that type.
+fn type_level<Env>(env: &Env, ty: &RcType) -> Result<Option<Level>, InternalError>
+where
+    Env: MetaEnv + DefinitionEnv,
+{
+    let ty = force(env, ty)?;
+
+    match *ty {
+        Value::Universe(ref level) => Ok(Some(level.succ())),
+        Value::Pi(_, _, ref ann, ref closure) => {
+            let ann_level = match type_level(env, ann)? {
+                Some(level) => level,
+                None => return Ok(None),
+            };
+            let (Binder(free_var), body) = open_closure(env, closure)?;
+            let mut body_env = env.clone();
+            body_env.insert_declaration(free_var, ann.clone());
+            Ok(type_level(&body_env, &body)?.map(|body_level| ann_level.max(&body_level)))
+        },
+        Value::RecordType(ref closure) => {
+            let mut env = env.clone();
+            let mut values = Vec::new();
+            let mut max_level = Level::from(0);
+            for &(_, Binder(ref free_var), Embed(ref ann)) in &closure.term.unsafe_pattern.unsafe_patterns {
+                let ann = eval_nest_term(&env, &closure.locals, &values, ann)?;
+                match type_level(&env, &ann)? {
+                    Some(level) => max_level = max_level.max(&level),
+                    None => return Ok(None),
+                }
+                let free_var = FreeVar::fresh(free_var.pretty_name.clone());
+                values.push(RcValue::from(Value::var(Var::Free(free_var.clone()), 0)));
+                env.insert_declaration(free_var, ann);
+            }
+            Ok(Some(max_level))
+        },
+        Value::Eq(ref ty, _, _) => type_level(env, ty),
+        Value::Neutral(ref neutral, ref spine) => {
+            let mut ty = match *neutral.inner {
+                Neutral::Head(Head::Var(Var::Free(ref free_var), shift)) => {
+                    match env.get_declaration(free_var) {
+                        Some(ty) => {
+                            let mut ty = ty.clone();
+                            ty.shift_universes(shift);
+                            // We don't know the levels that level-polymorphic
+                            // definitions were used at, so we leave them to be
+                            // solved later on
+                            if let Some(params) = env.get_level_params(free_var) {
+                                let levels = params
+                                    .iter()
+                                    .map(|param| {
+                                        (param.clone(), Level::meta(env.fresh_level_meta()))
+                                    }).collect::<Vec<_>>();
+                                ty.instantiate_universes(&levels);
+                            }
+                            ty
+                        },
+                        None => return Ok(None),
+                    }
+                },
+                Neutral::Head(Head::Meta(meta)) => env.get_meta(meta).ty,
+                Neutral::Head(Head::Extern(_, ref ty)) => ty.clone(),
+                Neutral::Head(Head::Var(Var::Bound(_), _))
+                | Neutral::If(_, _, _)
+                | Neutral::J(_, _, _)
+                | Neutral::Proj(_, _)
+                | Neutral::Case(_, _) => return Ok(None),
+            };
+
+            // Apply the arguments in the spine to the type of the head
+            for &(_, ref arg) in spine {
+                ty = match *force(env, &ty)? {
+                    Value::Pi(_, _, _, ref closure) => apply_closure(env, closure, arg.clone())?,
+                    _ => return Err(InternalError::ArgumentAppliedToNonFunction),
+                };
+            }
+
+            match *force(env, &ty)? {
+                Value::Universe(ref level) => Ok(Some(level.clone())),
+                _ => Ok(None),
+            }
         },
-
-        // ST-ALPHA-EQ
-        (_, _) => RcType::term_eq(ty1, ty2),
+        Value::Literal(_)
+        | Value::Lam(_, _, _, _)
+        | Value::Record(_)
+        | Value::Array(_)
+        | Value::Refl
+        | Value::Constr(_, _) => Ok(None),
+        Value::Glued(_, _, ref thunk) => type_level(env, thunk.force()?),
+        // Poisoned types could live in any universe, so we settle for the
+        // lowest one
+        Value::Error => Ok(Some(Level::from(0))),
     }
 }
 
-/// Ensures that the given term is a universe, returning the level of that
-/// universe and its elaborated form.
-fn infer_universe<Env>(env: &Env, raw_term: &raw::RcTerm) -> Result<(RcTerm, Level), TypeError>
+/// Elaborate part of a term whose uses of variables are scaled by the given
+/// multiplicity, for example an argument to a function, which is used as many
+/// times as the parameter it is supplied for
+fn scale_usages<Env, T, F>(env: &Env, multiplicity: Multiplicity, f: F) -> Result<T, TypeError>
 where
-    Env: DeclarationEnv + DefinitionEnv,
+    Env: UsageEnv,
+    F: FnOnce() -> Result<T, TypeError>,
 {
-    let (term, ty) = infer_term(env, raw_term)?;
-    match *ty {
-        Value::Universe(level) => Ok((term, level)),
-        _ => Err(TypeError::ExpectedUniverse {
-            span: raw_term.span(),
-            found: Box::new(ty.resugar(env.resugar_env())),
-        }),
+    let outer_usages = env.take_usages();
+    let result = f();
+    let inner_usages = env.take_usages();
+
+    env.add_usages(outer_usages);
+    env.add_usages(
+        inner_usages
+            .into_iter()
+            .map(|(free_var, usage)| (free_var, multiplicity * usage))
+            .collect(),
+    );
+
+    result
+}
+
+/// Elaborate the alternative branches of an `if` or `case` expression
+///
+/// Only one of the branches will be evaluated, so rather than adding up the
+/// uses of variables in each branch, we require them to agree.
+fn join_usages<Env, Bs, T, F>(env: &Env, branches: Bs, mut f: F) -> Result<Vec<T>, TypeError>
+where
+    Env: UsageEnv,
+    Bs: IntoIterator,
+    F: FnMut(Bs::Item) -> Result<T, TypeError>,
+{
+    let outer_usages = env.take_usages();
+    let mut branch_usages = None;
+    let results = branches
+        .into_iter()
+        .map(|branch| {
+            let result = f(branch)?;
+            let usages = env.take_usages();
+            branch_usages = Some(match branch_usages.take() {
+                None => usages,
+                Some(prev_usages) => HashMap::union_with(prev_usages, usages, Multiplicity::join),
+            });
+            Ok(result)
+        }).collect();
+
+    env.add_usages(outer_usages);
+    env.add_usages(branch_usages.unwrap_or_default());
+
+    results
+}
+
+/// Ensure that the variable bound by a parameter was used in a way that is
+/// allowed by its multiplicity, once the body of the binder has been checked
+fn check_usage<Env>(
+    env: &Env,
+    span: ByteSpan,
+    binder: Binder<String>,
+    multiplicity: Multiplicity,
+) -> Result<(), TypeError>
+where
+    Env: UsageEnv,
+{
+    let usage = env.untrack_usage(&binder.0);
+
+    if multiplicity == Multiplicity::Many || multiplicity == usage {
+        Ok(())
+    } else {
+        Err(TypeError::UsageMismatch {
+            span,
+            name: pretty_name(&binder.0),
+            found: usage,
+            expected: multiplicity,
+        })
     }
 }
 
@@ -220,7 +1461,7 @@ fn check_literal<Env>(
     expected_ty: &RcType,
 ) -> Result<Literal, TypeError>
 where
-    Env: GlobalEnv,
+    Env: GlobalEnv + MetaEnv,
 {
     match expected_ty.free_var_app() {
         // Conservatively forcing the shift to be zero for now. Perhaps this
@@ -278,10 +1519,20 @@ where
         Some(_) | None => {},
     }
 
+    // Any literal is compatible with a poisoned type
+    if let Value::Error = **expected_ty.unfold() {
+        return Ok(match *raw_literal {
+            raw::Literal::String(_, ref val) => Literal::String(val.clone()),
+            raw::Literal::Char(_, val) => Literal::Char(val),
+            raw::Literal::Int(_, val) => Literal::U64(val),
+            raw::Literal::Float(_, val) => Literal::F64(val),
+        });
+    }
+
     Err(TypeError::LiteralMismatch {
         literal_span: raw_literal.span(),
         found: raw_literal.clone(),
-        expected: Box::new(expected_ty.resugar(env.resugar_env())),
+        expected: resugar_ty(env, expected_ty),
     })
 }
 
@@ -313,7 +1564,7 @@ pub fn check_pattern<Env>(
     expected_ty: &RcType,
 ) -> Result<(RcPattern, Vec<(FreeVar<String>, RcType)>), TypeError>
 where
-    Env: DeclarationEnv + DefinitionEnv,
+    Env: MetaEnv + DefinitionEnv + DataEnv + UsageEnv + WarningEnv,
 {
     match (&*raw_pattern.inner, &*expected_ty.inner) {
         (&raw::Pattern::Binder(_, Binder(ref free_var)), _) => {
@@ -326,17 +1577,26 @@ where
             let literal = check_literal(env, raw_literal, expected_ty)?;
             return Ok((RcPattern::from(Pattern::Literal(literal)), vec![]));
         },
+        (&raw::Pattern::Var(span, Embed(Var::Free(ref free_var)), _), _)
+            if env.get_constructor_data(free_var).is_some() =>
+        {
+            return check_constr_pattern(env, span, free_var, &[], expected_ty);
+        },
+        (&raw::Pattern::Constr(span, Embed(Var::Free(ref free_var)), ref raw_args), _) => {
+            return check_constr_pattern(env, span, free_var, raw_args, expected_ty);
+        },
         _ => {},
     }
 
     let (pattern, inferred_ty, declarations) = infer_pattern(env, raw_pattern)?;
-    if is_subtype(env, &inferred_ty, expected_ty) {
+    if is_subtype(env, &inferred_ty, expected_ty)? {
         Ok((pattern, declarations))
     } else {
+        let (found, expected) = resugar_mismatch(env, &inferred_ty, expected_ty);
         Err(TypeError::Mismatch {
             span: raw_pattern.span(),
-            found: Box::new(inferred_ty.resugar(env.resugar_env())),
-            expected: Box::new(expected_ty.resugar(env.resugar_env())),
+            found,
+            expected,
         })
     }
 }
@@ -348,12 +1608,12 @@ pub fn infer_pattern<Env>(
     raw_pattern: &raw::RcPattern,
 ) -> Result<(RcPattern, RcType, Vec<(FreeVar<String>, RcType)>), TypeError>
 where
-    Env: DeclarationEnv + DefinitionEnv,
+    Env: MetaEnv + DefinitionEnv + DataEnv + UsageEnv + WarningEnv,
 {
     match *raw_pattern.inner {
         raw::Pattern::Ann(ref raw_pattern, Embed(ref raw_ty)) => {
             let (ty, _) = infer_universe(env, raw_ty)?;
-            let value_ty = nf_term(env, &ty)?;
+            let value_ty = eval_term(env, &ty)?;
             let (pattern, declarations) = check_pattern(env, raw_pattern, &value_ty)?;
 
             Ok((
@@ -366,6 +1626,11 @@ where
             span,
             binder: binder.clone(),
         }),
+        raw::Pattern::Var(span, Embed(Var::Free(ref free_var)), _)
+            if env.get_constructor_data(free_var).is_some() =>
+        {
+            infer_constr_pattern(env, span, free_var, &[], None)
+        },
         raw::Pattern::Var(span, Embed(ref var), shift) => match *var {
             Var::Free(ref free_var) => match env.get_declaration(free_var) {
                 Some(ty) => {
@@ -393,7 +1658,329 @@ where
             let (literal, ty) = infer_literal(env, literal)?;
             Ok((RcPattern::from(Pattern::Literal(literal)), ty, vec![]))
         },
+        raw::Pattern::Constr(span, Embed(ref var), ref raw_args) => match *var {
+            Var::Free(ref free_var) => infer_constr_pattern(env, span, free_var, raw_args, None),
+
+            // We should always be substituting bound variables with fresh
+            // variables when entering scopes using `unbind`, so if we've
+            // encountered one here this is definitely a bug!
+            Var::Bound(_) => Err(InternalError::UnexpectedBoundVar {
+                span: Some(span),
+                var: var.clone(),
+            }.into()),
+        },
+        raw::Pattern::Error(_) => Ok((
+            RcPattern::from(Pattern::Error),
+            RcValue::from(Value::Error),
+            vec![],
+        )),
+    }
+}
+
+/// Checks that a constructor pattern is compatible with the given type,
+/// returning the elaborated pattern and a vector of the declarations it
+/// introduced if successful
+fn check_constr_pattern<Env>(
+    env: &Env,
+    span: ByteSpan,
+    free_var: &FreeVar<String>,
+    raw_args: &[raw::RcPattern],
+    expected_ty: &RcType,
+) -> Result<(RcPattern, Vec<(FreeVar<String>, RcType)>), TypeError>
+where
+    Env: MetaEnv + DefinitionEnv + DataEnv + UsageEnv + WarningEnv,
+{
+    let data_var = match env.get_constructor_data(free_var) {
+        Some(data_var) => data_var.clone(),
+        None if is_poisoned(env, free_var) => {
+            return poisoned_constr_pattern(env, free_var, raw_args);
+        },
+        None => {
+            return Err(TypeError::NotAConstructor {
+                span,
+                name: pretty_name(free_var),
+            });
+        },
+    };
+    let params = env.get_data(&data_var).map_or(0, |data| data.params);
+    let expected_ty = force(env, expected_ty)?;
+
+    match expected_ty.free_var_app() {
+        // Take the parameters of the constructor from the expected type.
+        // Indices are not yet unified with the expected type.
+        Some((ty_var, LevelShift(0), spine)) if *ty_var == data_var && spine.len() >= params => {
+            let param_args = spine[..params].iter().map(|arg| arg.1.clone()).collect();
+            let (pattern, _, declarations) =
+                infer_constr_pattern(env, span, free_var, raw_args, Some(param_args))?;
+
+            Ok((pattern, declarations))
+        },
+        // The expected type might still be unknown, so we infer the type of
+        // the pattern and try to unify it with the expected type
+        _ => {
+            let (pattern, inferred_ty, declarations) =
+                infer_constr_pattern(env, span, free_var, raw_args, None)?;
+
+            if is_subtype(env, &inferred_ty, &expected_ty)? {
+                Ok((pattern, declarations))
+            } else {
+                Err(TypeError::ConstructorMismatch {
+                    span,
+                    name: pretty_name(free_var),
+                    expected: resugar_ty(env, &expected_ty),
+                })
+            }
+        },
+    }
+}
+
+/// Synthesize the type of a constructor pattern, returning the elaborated
+/// pattern, the inferred type, and a vector of the declarations it introduced
+/// if successful
+///
+/// The parameters of the data type are taken from `param_args` if they are
+/// known, otherwise fresh metavariables are inserted in their place.
+fn infer_constr_pattern<Env>(
+    env: &Env,
+    span: ByteSpan,
+    free_var: &FreeVar<String>,
+    raw_args: &[raw::RcPattern],
+    param_args: Option<Vec<RcValue>>,
+) -> Result<(RcPattern, RcType, Vec<(FreeVar<String>, RcType)>), TypeError>
+where
+    Env: MetaEnv + DefinitionEnv + DataEnv + UsageEnv + WarningEnv,
+{
+    let (data_var, ty) = match (env.get_constructor_data(free_var), env.get_declaration(free_var)) {
+        (Some(data_var), Some(ty)) => (data_var.clone(), ty.clone()),
+        (None, Some(_)) if is_poisoned(env, free_var) => {
+            let (pattern, declarations) = poisoned_constr_pattern(env, free_var, raw_args)?;
+            return Ok((pattern, RcValue::from(Value::Error), declarations));
+        },
+        (_, _) => {
+            return Err(TypeError::NotAConstructor {
+                span,
+                name: pretty_name(free_var),
+            });
+        },
+    };
+    let params = env.get_data(&data_var).map_or(0, |data| data.params);
+    let mut param_args = param_args.map(Vec::into_iter);
+    let mut ty = ty;
+
+    // Instantiate the parameters of the data type
+    for _ in 0..params {
+        let body = match **ty.unfold() {
+            Value::Pi(Plicity::Implicit, _, ref ann, ref closure) => {
+                let param_arg = match param_args.as_mut().and_then(Iterator::next) {
+                    Some(param_arg) => param_arg,
+                    None => {
+                        let meta = env.fresh_meta(span, MetaSource::ImplicitArg, ann.clone());
+                        RcValue::from(Value::from(Neutral::Head(Head::Meta(meta))))
+                    },
+                };
+                apply_closure(env, closure, param_arg)?
+            },
+            _ => {
+                return Err(TypeError::Internal(InternalError::Unimplemented {
+                    span: Some(span),
+                    message: "constructor with missing parameters".to_owned(),
+                }));
+            },
+        };
+        ty = body;
+    }
+
+    let expected_arity = constructor_arity(env, &ty)?;
+    if raw_args.len() as u64 != expected_arity {
+        return Err(TypeError::ConstructorArityMismatch {
+            span,
+            name: pretty_name(free_var),
+            found_arity: raw_args.len() as u64,
+            expected_arity,
+        });
+    }
+
+    // Check the arguments, threading the declarations they introduce through
+    // to subsequent arguments
+    let mut arg_env = env.clone();
+    let mut raw_args = raw_args.iter();
+    let mut args = Vec::new();
+    let mut declarations = Vec::new();
+    while let Value::Pi(plicity, _, ref ann, ref closure) = *ty.clone() {
+        // Implicit arguments can't be written in patterns, so they are always
+        // bound to fresh variables
+        let raw_arg = match plicity {
+            Plicity::Explicit => raw_args.next(),
+            Plicity::Implicit => None,
+        };
+        let (arg, arg_declarations) = match raw_arg {
+            Some(raw_arg) => check_pattern(&arg_env, raw_arg, ann)?,
+            None => {
+                let arg_var = FreeVar::fresh(closure.term.unsafe_pattern.0.pretty_name.clone());
+                let arg = RcPattern::from(Pattern::Binder(Binder(arg_var.clone())));
+                (arg, vec![(arg_var, ann.clone())])
+            },
+        };
+
+        let arg_value = eval_term(env, &pattern_to_term(&arg))?;
+        arg_env.extend_declarations(arg_declarations.iter().cloned());
+        declarations.extend(arg_declarations);
+        args.push((plicity, arg));
+
+        ty = apply_closure(env, closure, arg_value)?;
+    }
+
+    let pattern = RcPattern::from(Pattern::Constr(Embed(Var::Free(free_var.clone())), args));
+
+    Ok((pattern, ty, declarations))
+}
+
+/// Whether a variable was declared by an item that failed to type check
+fn is_poisoned<Env>(env: &Env, free_var: &FreeVar<String>) -> bool
+where
+    Env: DeclarationEnv,
+{
+    matches!(env.get_declaration(free_var).map(|ty| &*ty.inner), Some(&Value::Error))
+}
+
+/// Elaborate a pattern that uses a constructor of a data type that failed to
+/// type check. We don't know what the constructor expects, so the arguments
+/// are checked against poisoned types, and no further errors are reported.
+fn poisoned_constr_pattern<Env>(
+    env: &Env,
+    free_var: &FreeVar<String>,
+    raw_args: &[raw::RcPattern],
+) -> Result<(RcPattern, Vec<(FreeVar<String>, RcType)>), TypeError>
+where
+    Env: MetaEnv + DefinitionEnv + DataEnv + UsageEnv + WarningEnv,
+{
+    let error = RcValue::from(Value::Error);
+    let mut arg_env = env.clone();
+    let mut args = Vec::new();
+    let mut declarations = Vec::new();
+    for raw_arg in raw_args {
+        let (arg, arg_declarations) = check_pattern(&arg_env, raw_arg, &error)?;
+        arg_env.extend_declarations(arg_declarations.iter().cloned());
+        declarations.extend(arg_declarations);
+        args.push((Plicity::Explicit, arg));
+    }
+
+    let pattern = RcPattern::from(Pattern::Constr(Embed(Var::Free(free_var.clone())), args));
+
+    Ok((pattern, declarations))
+}
+
+/// The number of explicit arguments that a constructor expects
+fn constructor_arity<Env>(env: &Env, ty: &RcType) -> Result<u64, InternalError>
+where
+    Env: DefinitionEnv,
+{
+    match **ty.unfold() {
+        Value::Pi(plicity, _, _, ref closure) => {
+            let arity = constructor_arity(env, &open_closure(env, closure)?.1)?;
+            match plicity {
+                Plicity::Explicit => Ok(1 + arity),
+                Plicity::Implicit => Ok(arity),
+            }
+        },
+        _ => Ok(0),
+    }
+}
+
+/// Convert a pattern into the term that it matches against
+fn pattern_to_term(pattern: &RcPattern) -> RcTerm {
+    match *pattern.inner {
+        Pattern::Ann(ref pattern, _) => pattern_to_term(pattern),
+        Pattern::Binder(Binder(ref free_var)) => {
+            RcTerm::from(Term::var(Var::Free(free_var.clone()), 0))
+        },
+        Pattern::Var(Embed(ref var), shift) => RcTerm::from(Term::Var(var.clone(), shift)),
+        Pattern::Literal(ref literal) => RcTerm::from(Term::Literal(literal.clone())),
+        Pattern::Constr(Embed(ref var), ref args) => RcTerm::from(Term::Constr(
+            var.clone(),
+            args.iter()
+                .map(|&(plicity, ref arg)| (plicity, pattern_to_term(arg)))
+                .collect(),
+        )),
+        Pattern::Error => RcTerm::from(Term::Error),
+    }
+}
+
+/// Returns `true` if the pattern matches any value by binding it to a variable
+fn is_binder_pattern(pattern: &RcPattern) -> bool {
+    match *pattern.inner {
+        Pattern::Ann(ref pattern, _) => is_binder_pattern(pattern),
+        Pattern::Binder(_) => true,
+        Pattern::Var(_, _) | Pattern::Literal(_) | Pattern::Constr(_, _) | Pattern::Error => false,
+    }
+}
+
+/// Specialize the expected type of a branch of an `if` or `case` expression
+/// to the value that the scrutinee takes in that branch
+///
+/// The motive of the elimination is inferred by abstracting the expected type
+/// over the scrutinee. We can only do this when the scrutinee is a variable,
+/// otherwise the expected type is returned unchanged.
+///
+/// The types of the variables that are used in the branch are specialized in
+/// the same way, so that terms whose types mention the scrutinee still have
+/// the types that the branch expects.
+fn specialize_motive<Env>(
+    env: &Env,
+    head: &RcValue,
+    expected_ty: &RcType,
+    branch_term: &RcTerm,
+    raw_branch: &raw::RcTerm,
+) -> Result<(Env, RcType), TypeError>
+where
+    Env: DeclarationEnv + DefinitionEnv,
+{
+    let free_var = match head.free_var_app() {
+        Some((free_var, LevelShift(0), spine)) if spine.is_empty() => free_var,
+        Some(_) | None => return Ok((env.clone(), expected_ty.clone())),
+    };
+    let mappings = [(free_var.clone(), branch_term.clone())];
+
+    let mut branch_env = env.clone();
+    for var in raw_branch.free_vars() {
+        if let Some(ty) = env.get_declaration(&var) {
+            let ty = RcTerm::from(&**ty);
+            if ty.free_vars().contains(free_var) {
+                let ty = eval_term(env, &ty.substs(&mappings))?;
+                branch_env.insert_declaration(var, ty);
+            }
+        }
+    }
+
+    let expected_ty = RcTerm::from(&**expected_ty);
+    let expected_ty = eval_term(env, &expected_ty.substs(&mappings))?;
+
+    Ok((branch_env, expected_ty))
+}
+
+/// Check the coverage of the elaborated clauses of a case expression
+fn check_clauses_coverage<Env>(
+    env: &Env,
+    span: ByteSpan,
+    head_ty: &RcType,
+    raw_clauses: &[Scope<raw::RcPattern, raw::RcTerm>],
+    clauses: &[Scope<RcPattern, RcTerm>],
+) -> Result<(), TypeError>
+where
+    Env: MetaEnv + DefinitionEnv + DataEnv + WarningEnv,
+{
+    // The error in a poisoned scrutinee has already been reported, and we
+    // don't know which patterns it should be matched against
+    if let Value::Error = *force(env, head_ty)?.inner {
+        return Ok(());
     }
+
+    let patterns = <_>::zip(raw_clauses.iter(), clauses)
+        .map(|(raw_clause, clause)| {
+            (raw_clause.unsafe_pattern.span(), clause.unsafe_pattern.clone())
+        }).collect::<Vec<_>>();
+
+    check_coverage(env, span, head_ty, &patterns)
 }
 
 /// Checks that a term is compatible with the given type, returning the
@@ -404,39 +1991,126 @@ pub fn check_term<Env>(
     expected_ty: &RcType,
 ) -> Result<RcTerm, TypeError>
 where
-    Env: DeclarationEnv + DefinitionEnv,
+    Env: MetaEnv + DefinitionEnv + DataEnv + UsageEnv + WarningEnv,
 {
+    // Errors are reported using the type that we were given, which may still
+    // refer to global definitions by name
+    let given_ty = &match **expected_ty {
+        Value::Glued(_, _, _) => expected_ty.clone(),
+        _ => force(env, expected_ty)?,
+    };
+    let expected_ty = &force(env, given_ty)?;
+    // If the expected type is still unknown we'll need to fall through to
+    // C-CONV, so that it can be solved from the type we infer
+    let is_unsolved = unify::unsolved_meta(env, expected_ty).is_some();
+
+    // C-IMPLICIT-LAM
+    //
+    // Insert an implicit lambda if the expected type has an implicit parameter
+    // that is not already bound by the term
     match (&*raw_term.inner, &*expected_ty.inner) {
-        (&raw::Term::Literal(ref raw_literal), _) => {
-            let literal = check_literal(env, raw_literal, expected_ty)?;
+        (&raw::Term::Lam(_, Plicity::Implicit, _, _), _) => {},
+        (_, &Value::Pi(Plicity::Implicit, multiplicity, ref pi_ann, ref pi_closure)) => {
+            let (Binder(pi_name), pi_body) = open_closure(env, pi_closure)?;
+
+            let lam_ann = RcTerm::from(Term::from(&**pi_ann));
+            let lam_body = {
+                let mut body_env = env.clone();
+                body_env.insert_declaration(pi_name.clone(), pi_ann.clone());
+                env.track_usage(pi_name.clone());
+                check_term(&body_env, raw_term, &pi_body)?
+            };
+            check_usage(env, raw_term.span(), Binder(pi_name.clone()), multiplicity)?;
+            let lam_param = (Binder(pi_name), Embed(lam_ann));
+            let lam_scope = Scope::new(lam_param, zonk_term(env, &lam_body));
+
+            return Ok(RcTerm::from(Term::Lam(Plicity::Implicit, multiplicity, lam_scope)));
+        },
+        _ => {},
+    }
+
+    match (&*raw_term.inner, &*expected_ty.inner) {
+        (&raw::Term::Literal(ref raw_literal), _) if !is_unsolved => {
+            let literal = check_literal(env, raw_literal, given_ty)?;
             return Ok(RcTerm::from(Term::Literal(literal)));
         },
 
         // C-LAM
-        (&raw::Term::Lam(_, ref lam_scope), &Value::Pi(ref pi_scope)) => {
-            let ((lam_name, Embed(lam_ann)), lam_body, (Binder(pi_name), Embed(pi_ann)), pi_body) =
-                Scope::unbind2(lam_scope.clone(), pi_scope.clone());
-
-            // Elaborate the hole, if it exists
-            if let raw::Term::Hole(_) = *lam_ann.inner {
-                let lam_ann = RcTerm::from(Term::from(&*pi_ann));
-                let lam_body = {
-                    let mut body_env = env.clone();
-                    body_env.insert_declaration(pi_name, pi_ann);
-                    check_term(&body_env, &lam_body, &pi_body)?
-                };
-                let lam_scope = Scope::new((lam_name, Embed(lam_ann)), lam_body);
+        //
+        // Lambdas that were written without a multiplicity take it from the
+        // pi type that they are checked against
+        (
+            &raw::Term::Lam(span, lam_plicity, lam_multiplicity, ref lam_scope),
+            &Value::Pi(pi_plicity, pi_multiplicity, ref pi_ann, ref pi_closure),
+        )
+            if lam_plicity == pi_plicity
+                && lam_multiplicity.map_or(true, |lam_multiplicity| {
+                    lam_multiplicity == pi_multiplicity
+                }) =>
+        {
+            let ((lam_name, Embed(raw_lam_ann)), lam_body) = lam_scope.clone().unbind();
+            let pi_arg = RcValue::from(Value::var(Var::Free(lam_name.0.clone()), 0));
+            let pi_body = apply_closure(env, pi_closure, pi_arg)?;
+
+            let (lam_ann, lam_ann_value) = match *raw_lam_ann.inner {
+                // Elaborate the hole, if it exists
+                raw::Term::Hole(_) => (RcTerm::from(Term::from(&**pi_ann)), pi_ann.clone()),
+                _ => {
+                    let (lam_ann, _) = infer_universe(env, &raw_lam_ann)?;
+                    let lam_ann_value = eval_term(env, &lam_ann)?;
+
+                    if !is_subtype(env, pi_ann, &lam_ann_value)? {
+                        let (found, expected) = resugar_mismatch(env, &lam_ann_value, pi_ann);
+                        return Err(TypeError::Mismatch {
+                            span: raw_lam_ann.span(),
+                            found,
+                            expected,
+                        });
+                    }
 
-                return Ok(RcTerm::from(Term::Lam(lam_scope)));
-            }
+                    (lam_ann, lam_ann_value)
+                },
+            };
+            let lam_body = {
+                let mut body_env = env.clone();
+                body_env.insert_declaration(lam_name.0.clone(), lam_ann_value);
+                env.track_usage(lam_name.0.clone());
+                check_term(&body_env, &lam_body, &pi_body)?
+            };
+            check_usage(env, span, lam_name.clone(), pi_multiplicity)?;
+            let lam_scope = Scope::new((lam_name, Embed(lam_ann)), zonk_term(env, &lam_body));
 
-            // TODO: We might want to optimise for this case, rather than
-            // falling through to `infer` and unbinding again at I-LAM
+            return Ok(RcTerm::from(Term::Lam(lam_plicity, pi_multiplicity, lam_scope)));
         },
-        (&raw::Term::Lam(_, _), _) => {
+        // Let C-CONV report the mismatched plicities and multiplicities
+        (&raw::Term::Lam(_, _, _, _), &Value::Pi(_, _, _, _)) => {},
+        // The body of a lambda can still be checked against a poisoned type,
+        // with any unannotated parameter being poisoned as well
+        (&raw::Term::Lam(_, lam_plicity, lam_multiplicity, ref lam_scope), &Value::Error) => {
+            let ((Binder(free_var), Embed(raw_lam_ann)), raw_lam_body) = lam_scope.clone().unbind();
+
+            let (lam_ann, lam_ann_value) = match *raw_lam_ann.inner {
+                raw::Term::Hole(_) => (RcTerm::from(Term::Error), expected_ty.clone()),
+                _ => {
+                    let (lam_ann, _) = infer_universe(env, &raw_lam_ann)?;
+                    let lam_ann_value = eval_term(env, &lam_ann)?;
+                    (lam_ann, lam_ann_value)
+                },
+            };
+            let lam_body = {
+                let mut body_env = env.clone();
+                body_env.insert_declaration(free_var.clone(), lam_ann_value);
+                check_term(&body_env, &raw_lam_body, expected_ty)?
+            };
+            let lam_multiplicity = lam_multiplicity.unwrap_or(Multiplicity::Many);
+            let lam_scope = Scope::new((Binder(free_var), Embed(lam_ann)), lam_body);
+
+            return Ok(RcTerm::from(Term::Lam(lam_plicity, lam_multiplicity, lam_scope)));
+        },
+        (&raw::Term::Lam(_, _, _, _), _) if !is_unsolved => {
             return Err(TypeError::UnexpectedFunction {
                 span: raw_term.span(),
-                expected: Box::new(expected_ty.resugar(env.resugar_env())),
+                expected: resugar_ty(env, given_ty),
             });
         },
 
@@ -445,50 +2119,54 @@ where
             let bool_ty = RcValue::from(Value::var(Var::Free(env.globals().bool.clone()), 0));
 
             let cond = check_term(env, raw_cond, &bool_ty)?;
-            let if_true = check_term(env, raw_if_true, expected_ty)?;
-            let if_false = check_term(env, raw_if_false, expected_ty)?;
+            let cond_value = eval_term(env, &cond)?;
+            let bool_lit = |value| RcTerm::from(Term::Literal(Literal::Bool(value)));
+
+            let raw_branches = vec![(raw_if_true, true), (raw_if_false, false)];
+            let mut branches = join_usages(env, raw_branches, |(raw_branch, value)| {
+                let (branch_env, branch_ty) =
+                    specialize_motive(env, &cond_value, expected_ty, &bool_lit(value), raw_branch)?;
+                check_term(&branch_env, raw_branch, &branch_ty)
+            })?;
+            let if_false = branches.pop().unwrap();
+            let if_true = branches.pop().unwrap();
 
             return Ok(RcTerm::from(Term::If(cond, if_true, if_false)));
         },
 
         // C-RECORD
-        (&raw::Term::Record(span, ref raw_scope), &Value::RecordType(ref raw_ty_scope)) => {
-            let (raw_fields, (), raw_ty_fields, ()) = {
-                // Until Scope::unbind2 returns a Result.
-                let found_size = raw_scope.unsafe_pattern.binders().len();
-                let expected_size = raw_ty_scope.unsafe_pattern.binders().len();
-                if found_size == expected_size {
-                    Scope::unbind2(raw_scope.clone(), raw_ty_scope.clone())
-                } else {
-                    return Err(TypeError::RecordSizeMismatch {
-                        span,
-                        found_size: found_size as u64,
-                        expected_size: expected_size as u64,
-                    });
-                }
-            };
+        (&raw::Term::Record(span, ref raw_scope), &Value::RecordType(ref ty_closure)) => {
+            let ty_fields = &ty_closure.term.unsafe_pattern.unsafe_patterns;
+            let found_size = raw_scope.unsafe_pattern.binders().len();
+            if found_size != ty_fields.len() {
+                return Err(TypeError::RecordSizeMismatch {
+                    span,
+                    found_size: found_size as u64,
+                    expected_size: ty_fields.len() as u64,
+                });
+            }
 
+            let (raw_fields, ()) = raw_scope.clone().unbind();
             let raw_fields = raw_fields.unnest();
-            let raw_ty_fields = raw_ty_fields.unnest();
 
             // FIXME: Check that record is well-formed?
             let fields = {
-                let mut mappings = Vec::with_capacity(raw_fields.len());
-                let fields = <_>::zip(raw_fields.into_iter(), raw_ty_fields.into_iter())
+                let mut values = Vec::with_capacity(ty_fields.len());
+                let fields = <_>::zip(raw_fields.into_iter(), ty_fields.iter())
                     .map(|(field, ty_field)| {
                         let (label, Binder(free_var), Embed(raw_expr)) = field;
-                        let (ty_label, Binder(ty_free_var), Embed(ann)) = ty_field;
+                        let (ref ty_label, _, Embed(ref ann)) = *ty_field;
 
-                        if label == ty_label {
-                            let ann = nf_term(env, &ann.substs(&mappings))?;
+                        if label == *ty_label {
+                            let ann = eval_nest_term(env, &ty_closure.locals, &values, ann)?;
                             let expr = check_term(env, &raw_expr, &ann)?;
-                            mappings.push((ty_free_var, expr.clone()));
+                            values.push(eval_term(env, &expr)?);
                             Ok((label, Binder(free_var), Embed(expr)))
                         } else {
                             Err(TypeError::LabelMismatch {
                                 span,
                                 found: label,
-                                expected: ty_label,
+                                expected: ty_label.clone(),
                             })
                         }
                     }).collect::<Result<_, _>>()?;
@@ -499,35 +2177,43 @@ where
             return Ok(RcTerm::from(Term::Record(Scope::new(fields, ()))));
         },
 
-        (&raw::Term::Case(_, ref raw_head, ref raw_clauses), _) => {
+        (&raw::Term::Case(span, ref raw_head, ref raw_clauses), _) => {
             let (head, head_ty) = infer_term(env, raw_head)?;
+            let (head, head_ty) = insert_implicit_args(env, raw_head.span(), head, head_ty)?;
+            let head_value = eval_term(env, &head)?;
 
-            // TODO: ensure that patterns are exhaustive
-            let clauses = raw_clauses
-                .iter()
-                .map(|raw_clause| {
-                    let (raw_pattern, raw_body) = raw_clause.clone().unbind();
-                    let (pattern, declarations) = check_pattern(env, &raw_pattern, &head_ty)?;
+            let clauses = join_usages(env, raw_clauses, |raw_clause| {
+                let (raw_pattern, raw_body) = raw_clause.clone().unbind();
+                let (pattern, declarations) = check_pattern(env, &raw_pattern, &head_ty)?;
 
-                    let mut body_env = env.clone();
-                    body_env.extend_declarations(declarations);
-                    let body = check_term(&body_env, &raw_body, expected_ty)?;
+                let mut body_env = env.clone();
+                body_env.extend_declarations(declarations);
+                // Binder patterns tell us nothing new about the scrutinee
+                let (body_env, body_ty) = if is_binder_pattern(&pattern) {
+                    (body_env, expected_ty.clone())
+                } else {
+                    let pattern_term = pattern_to_term(&pattern);
+                    specialize_motive(&body_env, &head_value, expected_ty, &pattern_term, &raw_body)?
+                };
+                let body = check_term(&body_env, &raw_body, &body_ty)?;
 
-                    Ok(Scope::new(pattern, body))
-                }).collect::<Result<_, TypeError>>()?;
+                Ok(Scope::new(pattern, body))
+            })?;
+
+            check_clauses_coverage(env, span, &head_ty, raw_clauses, &clauses)?;
 
             return Ok(RcTerm::from(Term::Case(head, clauses)));
         },
 
-        (&raw::Term::Array(span, ref elems), ty) => match ty.free_var_app() {
+        (&raw::Term::Array(span, ref elems), ty) if !is_unsolved => match ty.free_var_app() {
             // Conservatively forcing the shift to be zero for now. Perhaps this
             // could be relaxed in the future if it becomes a problem?
             Some((free_var, LevelShift(0), spine))
                 if *free_var == env.globals().array && spine.len() == 2 =>
             {
-                let len = &spine[0];
-                let elem_ty = &spine[1];
-                if let Value::Literal(Literal::U64(len)) = **len {
+                let len = &spine[0].1;
+                let elem_ty = &spine[1].1;
+                if let Value::Literal(Literal::U64(len)) = **len.unfold() {
                     if len != elems.len() as u64 {
                         return Err(TypeError::ArrayLengthMismatch {
                             span,
@@ -552,9 +2238,29 @@ where
             },
         },
 
+        // C-REFL
+        (&raw::Term::Refl(span), &Value::Eq(ref ty, ref lhs, ref rhs)) => {
+            if unify_at(env, ty, lhs, rhs)? {
+                return Ok(RcTerm::from(Term::Refl));
+            }
+
+            let found = RcValue::from(Value::Eq(ty.clone(), lhs.clone(), lhs.clone()));
+            let (found, expected) = resugar_mismatch(env, &found, given_ty);
+            return Err(TypeError::Mismatch {
+                span,
+                found,
+                expected,
+            });
+        },
+
+        // Inferring the type of `refl` would leave metavariables that can
+        // never be solved from a poisoned type
+        (&raw::Term::Refl(_), &Value::Error) => return Ok(RcTerm::from(Term::Refl)),
+
+        // C-HOLE
         (&raw::Term::Hole(span), _) => {
-            let expected = Some(Box::new(expected_ty.resugar(env.resugar_env())));
-            return Err(TypeError::UnableToElaborateHole { span, expected });
+            let meta = env.fresh_meta(span, MetaSource::Hole, expected_ty.clone());
+            return Ok(RcTerm::from(Term::Meta(meta)));
         },
 
         _ => {},
@@ -562,13 +2268,18 @@ where
 
     // C-CONV
     let (term, inferred_ty) = infer_term(env, raw_term)?;
-    if is_subtype(env, &inferred_ty, expected_ty) {
+    let (term, inferred_ty) = match **expected_ty {
+        Value::Pi(Plicity::Implicit, _, _, _) => (term, inferred_ty),
+        _ => insert_implicit_args(env, raw_term.span(), term, inferred_ty)?,
+    };
+    if is_subtype(env, &inferred_ty, expected_ty)? {
         Ok(term)
     } else {
+        let (found, expected) = resugar_mismatch(env, &inferred_ty, given_ty);
         Err(TypeError::Mismatch {
             span: raw_term.span(),
-            found: Box::new(inferred_ty.resugar(env.resugar_env())),
-            expected: Box::new(expected_ty.resugar(env.resugar_env())),
+            found,
+            expected,
         })
     }
 }
@@ -577,29 +2288,40 @@ where
 /// inferred type if successful
 pub fn infer_term<Env>(env: &Env, raw_term: &raw::RcTerm) -> Result<(RcTerm, RcType), TypeError>
 where
-    Env: DeclarationEnv + DefinitionEnv,
+    Env: MetaEnv + DefinitionEnv + DataEnv + UsageEnv + WarningEnv,
 {
-    use std::cmp;
-
     match *raw_term.inner {
         //  I-ANN
         raw::Term::Ann(ref raw_expr, ref raw_ty) => {
             let (ty, _) = infer_universe(env, raw_ty)?;
-            let value_ty = nf_term(env, &ty)?;
+            let value_ty = eval_term(env, &ty)?;
             let expr = check_term(env, raw_expr, &value_ty)?;
 
             Ok((RcTerm::from(Term::Ann(expr, ty)), value_ty))
         },
 
         // I-TYPE
-        raw::Term::Universe(_, level) => Ok((
-            RcTerm::from(Term::Universe(level)),
-            RcValue::from(Value::Universe(level.succ())),
-        )),
+        raw::Term::Universe(_, ref level) => {
+            // Universes that were written without a level are given a fresh
+            // level metavariable, to be solved based on how they are used
+            let level = match *level {
+                Some(ref level) => level.clone(),
+                None => Level::meta(env.fresh_level_meta()),
+            };
+
+            Ok((
+                RcTerm::from(Term::Universe(level.clone())),
+                RcValue::from(Value::Universe(level.succ())),
+            ))
+        },
 
+        // I-HOLE
         raw::Term::Hole(span) => {
-            let expected = None;
-            Err(TypeError::UnableToElaborateHole { span, expected })
+            let ty_meta = env.fresh_meta(span, MetaSource::Hole, RcValue::from(Value::universe(0)));
+            let ty = RcValue::from(Value::from(Neutral::Head(Head::Meta(ty_meta))));
+            let meta = env.fresh_meta(span, MetaSource::Hole, ty.clone());
+
+            Ok((RcTerm::from(Term::Meta(meta)), ty))
         },
 
         raw::Term::Literal(ref raw_literal) => {
@@ -611,10 +2333,26 @@ where
         raw::Term::Var(span, ref var, shift) => match *var {
             Var::Free(ref free_var) => match env.get_declaration(free_var) {
                 Some(ty) => {
+                    env.record_usage(free_var);
+
                     let mut ty = ty.clone();
                     ty.shift_universes(shift);
-
-                    Ok((RcTerm::from(Term::Var(var.clone(), shift)), ty))
+                    let term = RcTerm::from(Term::Var(var.clone(), shift));
+
+                    match env.get_level_params(free_var) {
+                        // Instantiate level-polymorphic definitions with fresh
+                        // level metavariables, to be solved by the use site
+                        Some(params) => {
+                            let levels = params
+                                .iter()
+                                .map(|param| (param.clone(), Level::meta(env.fresh_level_meta())))
+                                .collect::<Vec<_>>();
+                            ty.instantiate_universes(&levels);
+
+                            Ok((RcTerm::from(Term::Inst(term, levels)), ty))
+                        },
+                        None => Ok((term, ty)),
+                    }
                 },
                 None => Err(TypeError::UndefinedName {
                     span,
@@ -642,55 +2380,63 @@ where
 
         raw::Term::Extern(_, _, ref name, ref raw_ty) => {
             let (ty, _) = infer_universe(env, raw_ty)?;
-            let value_ty = nf_term(env, &ty)?;
+            let value_ty = eval_term(env, &ty)?;
             Ok((RcTerm::from(Term::Extern(name.clone(), ty)), value_ty))
         },
 
         // I-PI
-        raw::Term::Pi(_, ref raw_scope) => {
+        raw::Term::Pi(_, plicity, multiplicity, ref raw_scope) => {
             let ((Binder(free_var), Embed(raw_ann)), raw_body) = raw_scope.clone().unbind();
 
             let (ann, ann_level) = infer_universe(env, &raw_ann)?;
             let (body, body_level) = {
-                let ann = nf_term(env, &ann)?;
+                let ann = eval_term(env, &ann)?;
                 let mut body_env = env.clone();
                 body_env.insert_declaration(free_var.clone(), ann);
                 infer_universe(&body_env, &raw_body)?
             };
 
+            let body = zonk_term(env, &body);
+
             Ok((
-                RcTerm::from(Term::Pi(Scope::new((Binder(free_var), Embed(ann)), body))),
-                RcValue::from(Value::Universe(cmp::max(ann_level, body_level))),
+                RcTerm::from(Term::Pi(
+                    plicity,
+                    multiplicity,
+                    Scope::new((Binder(free_var), Embed(ann)), body),
+                )),
+                RcValue::from(Value::Universe(ann_level.max(&body_level))),
             ))
         },
 
         // I-LAM
-        raw::Term::Lam(_, ref raw_scope) => {
+        raw::Term::Lam(span, plicity, multiplicity, ref raw_scope) => {
             let ((Binder(free_var), Embed(raw_ann)), raw_body) = raw_scope.clone().unbind();
-
-            // Check for holes before entering to ensure we get a nice error
-            if let raw::Term::Hole(_) = *raw_ann {
-                return Err(TypeError::FunctionParamNeedsAnnotation {
-                    param_span: ByteSpan::default(), // TODO: param.span(),
-                    var_span: None,
-                    name: free_var.clone(),
-                });
-            }
+            let multiplicity = multiplicity.unwrap_or(Multiplicity::Many);
 
             let (lam_ann, _) = infer_universe(env, &raw_ann)?;
-            let pi_ann = nf_term(env, &lam_ann)?;
+            let pi_ann = eval_term(env, &lam_ann)?;
             let (lam_body, pi_body) = {
                 let mut body_env = env.clone();
                 body_env.insert_declaration(free_var.clone(), pi_ann.clone());
+                env.track_usage(free_var.clone());
                 infer_term(&body_env, &raw_body)?
             };
+            check_usage(env, span, Binder(free_var.clone()), multiplicity)?;
+
+            let lam_body = zonk_term(env, &lam_body);
+            let pi_body = zonk_value(env, &pi_body)?;
 
             let lam_param = (Binder(free_var.clone()), Embed(lam_ann));
-            let pi_param = (Binder(free_var.clone()), Embed(pi_ann));
+            let pi_body = Scope::new(Binder(free_var.clone()), RcTerm::from(&*pi_body));
 
             Ok((
-                RcTerm::from(Term::Lam(Scope::new(lam_param, lam_body))),
-                RcValue::from(Value::Pi(Scope::new(pi_param, pi_body))),
+                RcTerm::from(Term::Lam(plicity, multiplicity, Scope::new(lam_param, lam_body))),
+                RcValue::from(Value::Pi(
+                    plicity,
+                    multiplicity,
+                    pi_ann,
+                    Closure::new(Locals::new(), pi_body),
+                )),
             ))
         },
 
@@ -699,13 +2445,19 @@ where
             let ((Binder(free_var), Embed((raw_ann, raw_bind))), raw_body) =
                 raw_scope.clone().unbind();
 
-            let (bind_term, bind_type) = if let raw::Term::Hole(_) = *raw_ann {
-                infer_term(env, &raw_bind)?
-            } else {
-                let (bind_ann, _) = infer_universe(env, &raw_ann)?;
-                let ann = nf_term(env, &bind_ann)?;
-                (check_term(env, &raw_bind, &ann)?, ann)
-            };
+            // Let bindings may be used any number of times in the body
+            let (bind_term, bind_type) = scale_usages(env, Multiplicity::Many, || {
+                if let raw::Term::Hole(_) = *raw_ann {
+                    infer_term(env, &raw_bind)
+                } else {
+                    let (bind_ann, _) = infer_universe(env, &raw_ann)?;
+                    let ann = eval_term(env, &bind_ann)?;
+                    Ok((check_term(env, &raw_bind, &ann)?, ann))
+                }
+            })?;
+            let bind_term = zonk_term(env, &bind_term);
+            let bind_type = zonk_value(env, &bind_type)?;
+
             let mut inner_env = env.clone();
             inner_env.insert_definition(free_var.clone(), bind_term.clone());
             inner_env.insert_declaration(free_var.clone(), bind_type);
@@ -720,29 +2472,156 @@ where
         raw::Term::If(_, ref raw_cond, ref raw_if_true, ref raw_if_false) => {
             let bool_ty = RcValue::from(Value::var(Var::Free(env.globals().bool.clone()), 0));
             let cond = check_term(env, raw_cond, &bool_ty)?;
-            let (if_true, ty) = infer_term(env, raw_if_true)?;
-            let if_false = check_term(env, raw_if_false, &ty)?;
 
-            Ok((RcTerm::from(Term::If(cond, if_true, if_false)), ty))
+            let mut ty = None;
+            let mut branches = join_usages(env, vec![raw_if_true, raw_if_false], |raw_branch| {
+                match ty {
+                    None => {
+                        let (branch, branch_ty) = infer_term(env, raw_branch)?;
+                        ty = Some(branch_ty);
+                        Ok(branch)
+                    },
+                    Some(ref ty) => check_term(env, raw_branch, ty),
+                }
+            })?;
+            let if_false = branches.pop().unwrap();
+            let if_true = branches.pop().unwrap();
+
+            Ok((RcTerm::from(Term::If(cond, if_true, if_false)), ty.unwrap()))
+        },
+
+        // I-EQ
+        raw::Term::Eq(ref raw_lhs, ref raw_rhs) => {
+            let (lhs, ty) = infer_term(env, raw_lhs)?;
+            let (lhs, ty) = insert_implicit_args(env, raw_lhs.span(), lhs, ty)?;
+            let rhs = check_term(env, raw_rhs, &ty)?;
+            let level = match type_level(env, &ty)? {
+                Some(level) => level,
+                None => {
+                    return Err(TypeError::Internal(InternalError::Unimplemented {
+                        span: Some(raw_lhs.span()),
+                        message: "finding the universe of the type of this term".to_owned(),
+                    }));
+                },
+            };
+
+            Ok((
+                RcTerm::from(Term::Eq(RcTerm::from(&*ty), lhs, rhs)),
+                RcValue::from(Value::Universe(level)),
+            ))
+        },
+
+        // I-REFL
+        raw::Term::Refl(span) => {
+            let ty_meta = env.fresh_meta(span, MetaSource::Hole, RcValue::from(Value::universe(0)));
+            let ty = RcValue::from(Value::from(Neutral::Head(Head::Meta(ty_meta))));
+            let meta = env.fresh_meta(span, MetaSource::Hole, ty.clone());
+            let term = RcValue::from(Value::from(Neutral::Head(Head::Meta(meta))));
+
+            Ok((
+                RcTerm::from(Term::Refl),
+                RcValue::from(Value::Eq(ty, term.clone(), term)),
+            ))
+        },
+
+        // I-J
+        raw::Term::J(_, ref raw_motive, ref raw_base, ref raw_proof) => {
+            let (proof, proof_ty) = infer_term(env, raw_proof)?;
+            let (ty, lhs, rhs) = match *force(env, &proof_ty)? {
+                Value::Eq(ref ty, ref lhs, ref rhs) => (ty.clone(), lhs.clone(), rhs.clone()),
+                // The proof was poisoned, but we can still look for errors in
+                // the motive and the base case
+                Value::Error => {
+                    let motive = check_term(env, raw_motive, &proof_ty)?;
+                    let base = check_term(env, raw_base, &proof_ty)?;
+                    return Ok((RcTerm::from(Term::J(motive, base, proof)), proof_ty.clone()));
+                },
+                _ => {
+                    return Err(TypeError::ExpectedEquality {
+                        span: raw_proof.span(),
+                        found: resugar_ty(env, &proof_ty),
+                    });
+                },
+            };
+
+            // The motive has the type `(y : A) -> lhs = y -> Type`
+            let motive_ty = {
+                let y_var = FreeVar::fresh_named("y");
+                let p_var = FreeVar::fresh_named("p");
+                let y = RcTerm::from(Term::var(Var::Free(y_var.clone()), 0));
+                let ty = RcTerm::from(&*ty);
+                let eq_ty = RcTerm::from(Term::Eq(ty.clone(), RcTerm::from(&*lhs), y));
+                let universe = RcTerm::from(Term::Universe(Level::meta(env.fresh_level_meta())));
+
+                eval_term(
+                    env,
+                    &RcTerm::from(Term::Pi(
+                        Plicity::Explicit,
+                        Multiplicity::Many,
+                        Scope::new(
+                            (Binder(y_var), Embed(ty)),
+                            RcTerm::from(Term::Pi(
+                                Plicity::Explicit,
+                                Multiplicity::Many,
+                                Scope::new((Binder(p_var), Embed(eq_ty)), universe),
+                            )),
+                        ),
+                    )),
+                )?
+            };
+            // The motive is only used to find the type of the elimination
+            let motive = scale_usages(env, Multiplicity::Zero, || {
+                check_term(env, raw_motive, &motive_ty)
+            })?;
+
+            // Apply the motive to the endpoint of the equality and its proof
+            let apply_motive = |end: &RcValue, proof: RcTerm| {
+                let motive_app = RcTerm::from(Term::App(
+                    motive.clone(),
+                    Plicity::Explicit,
+                    RcTerm::from(&**end),
+                ));
+                eval_term(env, &RcTerm::from(Term::App(motive_app, Plicity::Explicit, proof)))
+            };
+
+            let base_ty = apply_motive(&lhs, RcTerm::from(Term::Refl))?;
+            let base = check_term(env, raw_base, &base_ty)?;
+            let ty = apply_motive(&rhs, proof.clone())?;
+
+            Ok((RcTerm::from(Term::J(motive, base, proof)), ty))
         },
 
         // I-APP
-        raw::Term::App(ref raw_head, ref raw_arg) => {
+        raw::Term::App(ref raw_head, plicity, ref raw_arg) => {
             let (head, head_ty) = infer_term(env, raw_head)?;
+            let (head, head_ty) = match plicity {
+                Plicity::Explicit => insert_implicit_args(env, raw_head.span(), head, head_ty)?,
+                Plicity::Implicit => (head, head_ty),
+            };
 
-            match *head_ty {
-                Value::Pi(ref scope) => {
-                    let ((Binder(free_var), Embed(ann)), body) = scope.clone().unbind();
-
-                    let arg = check_term(env, raw_arg, &ann)?;
-                    let body = nf_term(env, &body.substs(&[(free_var, arg.clone())]))?;
+            match *force(env, &head_ty)? {
+                Value::Pi(pi_plicity, multiplicity, ref ann, ref closure) if pi_plicity == plicity => {
+                    let arg = scale_usages(env, multiplicity, || check_term(env, raw_arg, ann))?;
+                    let body = apply_closure(env, closure, eval_term(env, &arg)?)?;
 
-                    Ok((RcTerm::from(Term::App(head, arg)), body))
+                    Ok((RcTerm::from(Term::App(head, plicity, arg)), body))
+                },
+                Value::Pi(pi_plicity, _, _, _) => Err(TypeError::PlicityMismatch {
+                    fn_span: raw_head.span(),
+                    arg_span: raw_arg.span(),
+                    found: plicity,
+                    expected: pi_plicity,
+                }),
+                // The head was poisoned, but we can still look for errors in
+                // the argument
+                Value::Error => {
+                    let arg = check_term(env, raw_arg, &head_ty)?;
+                    Ok((RcTerm::from(Term::App(head, plicity, arg)), head_ty.clone()))
                 },
                 _ => Err(TypeError::ArgAppliedToNonFunction {
                     fn_span: raw_head.span(),
                     arg_span: raw_arg.span(),
-                    found: Box::new(head_ty.resugar(env.resugar_env())),
+                    found: resugar_ty(env, &head_ty),
                 }),
             }
         },
@@ -750,7 +2629,7 @@ where
         // I-RECORD-TYPE, I-EMPTY-RECORD-TYPE
         raw::Term::RecordType(_, ref raw_scope) => {
             let (raw_fields, ()) = raw_scope.clone().unbind();
-            let mut max_level = Level(0);
+            let mut max_level = Level::from(0);
 
             // FIXME: Check that record is well-formed?
             let fields = {
@@ -760,9 +2639,9 @@ where
                     .into_iter()
                     .map(|(label, Binder(free_var), Embed(raw_ann))| {
                         let (ann, ann_level) = infer_universe(&env, &raw_ann)?;
-                        let nf_ann = nf_term(&env, &ann)?;
+                        let nf_ann = eval_term(&env, &ann)?;
 
-                        max_level = cmp::max(max_level, ann_level);
+                        max_level = max_level.max(&ann_level);
                         env.insert_declaration(free_var.clone(), nf_ann);
 
                         Ok((label, Binder(free_var), Embed(ann)))
@@ -788,7 +2667,7 @@ where
                 let mut ty_mappings = Vec::with_capacity(raw_fields.len());
                 for (label, Binder(free_var), Embed(raw_term)) in raw_fields {
                     let (term, term_ty) = infer_term(env, &raw_term)?;
-                    let term_ty = nf_term(env, &term_ty.substs(&ty_mappings))?;
+                    let term_ty = term_ty.substs(&ty_mappings);
 
                     fields.push((label.clone(), Binder(free_var.clone()), Embed(term.clone())));
                     ty_fields.push((label, Binder(free_var.clone()), Embed(term_ty)));
@@ -798,29 +2677,37 @@ where
 
             Ok((
                 RcTerm::from(Term::Record(Scope::new(Nest::new(fields), ()))),
-                RcValue::from(Value::RecordType(Scope::new(Nest::new(ty_fields), ()))),
+                RcValue::from(Value::RecordType(Closure::new(
+                    Locals::new(),
+                    Scope::new(Nest::new(ty_fields), ()),
+                ))),
             ))
         },
 
         // I-PROJ
-        raw::Term::Proj(_, ref expr, label_span, ref label) => {
-            let (expr, ty) = infer_term(env, expr)?;
+        raw::Term::Proj(_, ref raw_expr, label_span, ref label) => {
+            let (expr, ty) = infer_term(env, raw_expr)?;
+            let (expr, ty) = insert_implicit_args(env, raw_expr.span(), expr, ty)?;
+            let forced_ty = force(env, &ty)?;
+
+            if let Value::Error = *forced_ty.inner {
+                return Ok((RcTerm::from(Term::Proj(expr, label.clone())), ty));
+            }
 
-            if let Value::RecordType(ref scope) = *ty.inner {
-                let (fields, ()) = scope.clone().unbind();
-                let mut mappings = vec![];
+            if let Value::RecordType(ref closure) = *forced_ty.inner {
+                let mut values = Vec::new();
 
-                for (current_label, Binder(free_var), Embed(current_ann)) in fields.unnest() {
-                    if current_label == *label {
+                for &(ref current_label, _, Embed(ref current_ann)) in
+                    &closure.term.unsafe_pattern.unsafe_patterns
+                {
+                    if current_label == label {
                         return Ok((
-                            RcTerm::from(Term::Proj(expr, current_label)),
-                            nf_term(env, &current_ann.substs(&mappings))?,
+                            RcTerm::from(Term::Proj(expr, current_label.clone())),
+                            eval_nest_term(env, &closure.locals, &values, current_ann)?,
                         ));
                     } else {
-                        mappings.push((
-                            free_var,
-                            RcTerm::from(Term::Proj(expr.clone(), current_label)),
-                        ));
+                        let proj = RcTerm::from(Term::Proj(expr.clone(), current_label.clone()));
+                        values.push(eval_term(env, &proj)?);
                     }
                 }
             }
@@ -828,16 +2715,16 @@ where
             Err(TypeError::NoFieldInType {
                 label_span,
                 expected_label: label.clone(),
-                found: Box::new(ty.resugar(env.resugar_env())),
+                found: resugar_ty(env, &ty),
             })
         },
 
         // I-CASE
         raw::Term::Case(span, ref raw_head, ref raw_clauses) => {
             let (head, head_ty) = infer_term(env, raw_head)?;
+            let (head, head_ty) = insert_implicit_args(env, raw_head.span(), head, head_ty)?;
             let mut ty = None;
 
-            // TODO: ensure that patterns are exhaustive
             let clauses = raw_clauses
                 .iter()
                 .map(|raw_clause| {
@@ -853,25 +2740,38 @@ where
                     match ty {
                         None => ty = Some(body_ty),
                         // FIXME: use common subtype?
-                        Some(ref ty) if RcValue::term_eq(&body_ty, ty) => {},
+                        Some(ref ty) if unify(env, &body_ty, ty)? => {},
                         Some(ref ty) => {
+                            let (found, expected) = resugar_mismatch(env, &body_ty, ty);
                             return Err(TypeError::Mismatch {
                                 span: raw_body.span(),
-                                found: Box::new(body_ty.resugar(env.resugar_env())),
-                                expected: Box::new(ty.resugar(env.resugar_env())),
+                                found,
+                                expected,
                             });
                         },
                     }
 
                     Ok(Scope::new(pattern, body))
-                }).collect::<Result<_, TypeError>>()?;
+                }).collect::<Result<Vec<_>, TypeError>>()?;
 
-            match ty {
-                Some(ty) => Ok((RcTerm::from(Term::Case(head, clauses)), ty)),
-                None => Err(TypeError::AmbiguousEmptyCase { span }),
-            }
+            let ty = match ty {
+                Some(ty) => ty,
+                None => match *force(env, &head_ty)?.inner {
+                    Value::Error => {
+                        return Ok((RcTerm::from(Term::Error), RcValue::from(Value::Error)));
+                    },
+                    _ => return Err(TypeError::AmbiguousEmptyCase { span }),
+                },
+            };
+            check_clauses_coverage(env, span, &head_ty, raw_clauses, &clauses)?;
+
+            Ok((RcTerm::from(Term::Case(head, clauses)), ty))
         },
 
         raw::Term::Array(span, _) => Err(TypeError::AmbiguousArrayLiteral { span }),
+
+        // Terms that could not be parsed have already been reported, so we
+        // poison them to avoid reporting any errors that follow from them
+        raw::Term::Error(_) => Ok((RcTerm::from(Term::Error), RcValue::from(Value::Error))),
     }
 }
@@ -1,261 +1,706 @@
-use moniker::{Binder, Embed, FreeVar, Nest, Scope, Var};
+use moniker::{Binder, BoundPattern, Embed, FreeVar, Nest, Scope, Var};
 
 use syntax::core::{
-    Head, Literal, Neutral, Pattern, RcNeutral, RcPattern, RcTerm, RcValue, Term, Value,
+    Closure, Head, InstantiateLocals, Literal, Locals, Neutral, Pattern, RcNeutral, RcPattern,
+    RcTerm, RcValue, Spine, Term, Thunk, Value,
 };
+use syntax::{Label, LevelShift, Plicity};
 
 use semantics::errors::InternalError;
 use semantics::DefinitionEnv;
 
 /// Reduce a term to its normal form
-pub fn nf_term<Env>(env: &Env, term: &RcTerm) -> Result<RcValue, InternalError>
+///
+/// This evaluates the term to a value, and then reads the value back into a
+/// term, evaluating the bodies of any closures along the way.
+pub fn nf_term<Env>(env: &Env, term: &RcTerm) -> Result<RcTerm, InternalError>
+where
+    Env: DefinitionEnv,
+{
+    quote_value(env, &eval_term(env, term)?)
+}
+
+/// Evaluate a term to a value
+pub fn eval_term<Env>(env: &Env, term: &RcTerm) -> Result<RcValue, InternalError>
+where
+    Env: DefinitionEnv,
+{
+    eval_term_in(env, &Locals::new(), term)
+}
+
+/// Evaluate a term to a value, looking up the values of local variables in
+/// `locals` before the definitions in the environment
+pub fn eval_term_in<Env>(env: &Env, locals: &Locals, term: &RcTerm) -> Result<RcValue, InternalError>
 where
     Env: DefinitionEnv,
 {
     match *term.inner {
         // E-ANN
-        Term::Ann(ref expr, _) => nf_term(env, expr),
+        Term::Ann(ref expr, _) => eval_term_in(env, locals, expr),
 
         // E-TYPE
-        Term::Universe(level) => Ok(RcValue::from(Value::Universe(level))),
+        Term::Universe(ref level) => Ok(RcValue::from(Value::Universe(level.clone()))),
 
         Term::Literal(ref lit) => Ok(RcValue::from(Value::Literal(lit.clone()))),
 
         // E-VAR, E-VAR-DEF
         Term::Var(ref var, shift) => match *var {
-            Var::Free(ref name) => match env.get_definition(name) {
-                Some(term) => {
-                    let mut value = nf_term(env, term)?;
-                    value.shift_universes(shift);
-                    Ok(value)
-                },
-                None => Ok(RcValue::from(Value::var(var.clone(), shift))),
+            // Recursive definitions are only unfolded once they have been
+            // applied to some arguments - see `unfold_recursive`
+            Var::Free(ref name) if env.is_recursive(name) => {
+                Ok(RcValue::from(Value::var(var.clone(), shift)))
             },
+            Var::Free(ref name) => {
+                let thunk = match (env.get_definition_value(name), env.get_definition(name)) {
+                    (Some(value), _) if shift == LevelShift(0) => Thunk::from_value(value.clone()),
+                    (Some(value), _) => {
+                        let mut value = value.clone();
+                        Thunk::new(move || {
+                            value.shift_universes(shift);
+                            Ok(value)
+                        })
+                    },
+                    // Wait until the definition is needed before evaluating it
+                    (None, Some(term)) => {
+                        let (env, term) = (env.clone(), term.clone());
+                        Thunk::new(move || {
+                            let mut value = eval_term(&env, &term)?;
+                            value.shift_universes(shift);
+                            Ok(value)
+                        })
+                    },
+                    (None, None) => return Ok(RcValue::from(Value::var(var.clone(), shift))),
+                };
+                // Remember the name of the definition, so that we can show
+                // it to the user instead of its definition
+                let head = Head::Var(var.clone(), shift);
+                Ok(RcValue::from(Value::Glued(head, Spine::new(), thunk)))
+            },
+
+            // Bound variables refer to the scopes that we entered on the way
+            // to this term, so if we can't find one in the locals then this
+            // is definitely a bug!
+            Var::Bound(ref bound_var) => match locals.lookup(bound_var) {
+                Some(value) => Ok(value.clone()),
+                None => Err(InternalError::UnexpectedBoundVar {
+                    span: None,
+                    var: var.clone(),
+                }),
+            },
+        },
 
-            // We should always be substituting bound variables with fresh
-            // variables when entering scopes using `unbind`, so if we've
-            // encountered one here this is definitely a bug!
-            Var::Bound(_) => Err(InternalError::UnexpectedBoundVar {
-                span: None,
-                var: var.clone(),
-            }),
+        // E-INST
+        Term::Inst(ref term, ref levels) => {
+            let mut value = eval_term_in(env, locals, term)?;
+            value.instantiate_universes(levels);
+            Ok(value)
         },
 
+        Term::Meta(meta) => Ok(RcValue::from(Value::from(Neutral::Head(Head::Meta(meta))))),
+
         Term::Extern(ref name, ref ty) => Ok(RcValue::from(Value::from(Neutral::Head(
-            Head::Extern(name.clone(), nf_term(env, ty)?),
+            Head::Extern(name.clone(), eval_term_in(env, locals, ty)?),
         )))),
 
+        // E-CONSTR
+        Term::Constr(ref var, ref args) => Ok(RcValue::from(Value::Constr(
+            var.clone(),
+            args.iter()
+                .map(|&(plicity, ref arg)| Ok((plicity, eval_term_in(env, locals, arg)?)))
+                .collect::<Result<_, _>>()?,
+        ))),
+
         // E-PI
-        Term::Pi(ref scope) => {
-            let ((name, Embed(ann)), body) = scope.clone().unbind();
+        Term::Pi(plicity, multiplicity, ref scope) => {
+            let (ref binder, Embed(ref ann)) = scope.unsafe_pattern;
 
-            Ok(RcValue::from(Value::Pi(Scope::new(
-                (name, Embed(nf_term(env, &ann)?)),
-                nf_term(env, &body)?,
-            ))))
+            Ok(RcValue::from(Value::Pi(
+                plicity,
+                multiplicity,
+                eval_term_in(env, locals, ann)?,
+                binder_closure(locals, binder, &scope.unsafe_body),
+            )))
         },
 
         // E-LAM
-        Term::Lam(ref scope) => {
-            let ((name, Embed(ann)), body) = scope.clone().unbind();
+        Term::Lam(plicity, multiplicity, ref scope) => {
+            let (ref binder, Embed(ref ann)) = scope.unsafe_pattern;
 
-            Ok(RcValue::from(Value::Lam(Scope::new(
-                (name, Embed(nf_term(env, &ann)?)),
-                nf_term(env, &body)?,
-            ))))
+            Ok(RcValue::from(Value::Lam(
+                plicity,
+                multiplicity,
+                eval_term_in(env, locals, ann)?,
+                binder_closure(locals, binder, &scope.unsafe_body),
+            )))
         },
 
         // E-APP
-        Term::App(ref head, ref arg) => {
-            match *nf_term(env, head)?.inner {
-                Value::Lam(ref scope) => {
-                    // FIXME: do a local unbind here
-                    let ((Binder(free_var), Embed(_)), body) = scope.clone().unbind();
-                    nf_term(env, &body.substs(&[(free_var, arg.clone())]))
-                },
-                Value::Neutral(ref neutral, ref spine) => {
-                    let arg = nf_term(env, arg)?;
-                    let mut spine = spine.clone();
-
-                    match *neutral.inner {
-                        Neutral::Head(Head::Extern(ref name, _)) => {
-                            spine.push(arg);
-
-                            // Apply the arguments to primitive definitions if the number of
-                            // arguments matches the arity of the primitive, all aof the arguments
-                            // are fully nfd
-                            if let Some(prim) = env.get_extern_definition(name) {
-                                if prim.arity == spine.len() && spine.iter().all(|arg| arg.is_nf())
-                                {
-                                    match (prim.interpretation)(spine) {
-                                        Ok(value) => return Ok(value),
-                                        Err(()) => unimplemented!("proper error"),
-                                    }
-                                }
-                            }
-                        },
-                        Neutral::Head(Head::Var(_, _))
-                        | Neutral::If(_, _, _)
-                        | Neutral::Proj(_, _)
-                        | Neutral::Case(_, _) => spine.push(arg),
-                    }
-
-                    Ok(RcValue::from(Value::Neutral(neutral.clone(), spine)))
-                },
-                _ => Err(InternalError::ArgumentAppliedToNonFunction),
-            }
-        },
+        Term::App(ref head, plicity, ref arg) => apply(
+            env,
+            eval_term_in(env, locals, head)?,
+            plicity,
+            eval_term_in(env, locals, arg)?,
+        ),
 
         // E-LET
         Term::Let(ref scope) => {
-            let ((Binder(free_var), Embed(bind)), body) = scope.clone().unbind();
-            nf_term(env, &body.substs(&[(free_var, bind.clone())]))
+            let (_, Embed(ref bind)) = scope.unsafe_pattern;
+            let bind = eval_term_in(env, locals, bind)?;
+            eval_term_in(env, &locals.push(vec![bind]), &scope.unsafe_body)
         },
 
         // E-IF, E-IF-TRUE, E-IF-FALSE
         Term::If(ref cond, ref if_true, ref if_false) => {
-            let value_cond = nf_term(env, cond)?;
+            let value_cond = eval_term_in(env, locals, cond)?;
 
-            match *value_cond {
-                Value::Literal(Literal::Bool(true)) => nf_term(env, if_true),
-                Value::Literal(Literal::Bool(false)) => nf_term(env, if_false),
+            match **value_cond.unfold() {
+                Value::Literal(Literal::Bool(true)) => eval_term_in(env, locals, if_true),
+                Value::Literal(Literal::Bool(false)) => eval_term_in(env, locals, if_false),
                 Value::Neutral(ref cond, ref spine) => Ok(RcValue::from(Value::Neutral(
                     RcNeutral::from(Neutral::If(
                         cond.clone(),
-                        nf_term(env, if_true)?,
-                        nf_term(env, if_false)?,
+                        eval_term_in(env, locals, if_true)?,
+                        eval_term_in(env, locals, if_false)?,
                     )),
                     spine.clone(),
                 ))),
+                Value::Error => Ok(RcValue::from(Value::Error)),
                 _ => Err(InternalError::ExpectedBoolExpr),
             }
         },
 
-        // E-RECORD-TYPE, E-EMPTY-RECORD-TYPE
-        Term::RecordType(ref scope) => {
-            let (fields, ()) = scope.clone().unbind();
-            let fields = Nest::new(
-                fields
-                    .unnest()
-                    .into_iter()
-                    .map(|(label, binder, Embed(ann))| {
-                        Ok((label, binder, Embed(nf_term(env, &ann)?)))
-                    }).collect::<Result<_, _>>()?,
-            );
-
-            Ok(RcValue::from(Value::RecordType(Scope::new(fields, ()))))
+        // E-EQ
+        Term::Eq(ref ty, ref lhs, ref rhs) => Ok(RcValue::from(Value::Eq(
+            eval_term_in(env, locals, ty)?,
+            eval_term_in(env, locals, lhs)?,
+            eval_term_in(env, locals, rhs)?,
+        ))),
+
+        // E-REFL
+        Term::Refl => Ok(RcValue::from(Value::Refl)),
+
+        // E-J, E-J-REFL
+        Term::J(ref motive, ref base, ref proof) => {
+            let value_proof = eval_term_in(env, locals, proof)?;
+
+            match **value_proof.unfold() {
+                Value::Refl => eval_term_in(env, locals, base),
+                Value::Neutral(ref proof, ref spine) => Ok(RcValue::from(Value::Neutral(
+                    RcNeutral::from(Neutral::J(
+                        eval_term_in(env, locals, motive)?,
+                        eval_term_in(env, locals, base)?,
+                        proof.clone(),
+                    )),
+                    spine.clone(),
+                ))),
+                Value::Error => Ok(RcValue::from(Value::Error)),
+                _ => Err(InternalError::ExpectedEqualityProof),
+            }
         },
 
+        // E-RECORD-TYPE, E-EMPTY-RECORD-TYPE
+        Term::RecordType(ref scope) => Ok(RcValue::from(Value::RecordType(Closure::new(
+            locals.clone(),
+            scope.clone(),
+        )))),
+
         // E-RECORD, E-EMPTY-RECORD
         Term::Record(ref scope) => {
-            let (fields, ()) = scope.clone().unbind();
-            let fields = Nest::new(
-                fields
-                    .unnest()
-                    .into_iter()
-                    .map(|(label, binder, Embed(term))| {
-                        Ok((label, binder, Embed(nf_term(env, &term)?)))
-                    }).collect::<Result<_, _>>()?,
-            );
-
-            Ok(RcValue::from(Value::Record(Scope::new(fields, ()))))
+            let mut values = Vec::with_capacity(scope.unsafe_pattern.unsafe_patterns.len());
+
+            // Later fields can refer to the earlier ones, so we add them to
+            // the locals as we go
+            let fields = scope
+                .unsafe_pattern
+                .unsafe_patterns
+                .iter()
+                .map(|&(ref label, _, Embed(ref term))| {
+                    let value = eval_term_in(env, &locals.push_nest(&values), term)?;
+                    values.push(value.clone());
+                    Ok((label.clone(), value))
+                }).collect::<Result<_, _>>()?;
+
+            Ok(RcValue::from(Value::Record(fields)))
         },
 
         // E-PROJ
-        Term::Proj(ref expr, ref label) => {
-            match *nf_term(env, expr)? {
-                Value::Neutral(ref neutral, ref spine) => {
-                    return Ok(RcValue::from(Value::Neutral(
-                        RcNeutral::from(Neutral::Proj(neutral.clone(), label.clone())),
-                        spine.clone(),
-                    )));
-                },
-                Value::Record(ref scope) => {
-                    let (fields, ()) = scope.clone().unbind();
+        Term::Proj(ref expr, ref label) => project(&eval_term_in(env, locals, expr)?, label),
 
-                    // FIXME: mappings?
-                    for (current_label, _, Embed(current_expr)) in fields.unnest() {
-                        if current_label == *label {
-                            return Ok(current_expr.clone());
+        // E-CASE
+        Term::Case(ref head, ref clauses) => {
+            let head = eval_term_in(env, locals, head)?.unfold().clone();
+
+            if let Value::Error = *head {
+                return Ok(head);
+            }
+
+            for clause in clauses {
+                match match_value(env, locals, &clause.unsafe_pattern, &head)? {
+                    Match::Matched(values) => {
+                        return eval_term_in(env, &locals.push(values), &clause.unsafe_body);
+                    },
+                    Match::Failed => {},
+                    Match::Stuck => {
+                        return Ok(RcValue::from(Value::from(Neutral::Case(
+                            head.clone(),
+                            Closure::new(locals.clone(), clauses.clone()),
+                        ))));
+                    },
+                }
+            }
+            Err(InternalError::NoPatternsApplicable)
+        },
+
+        // E-ARRAY
+        Term::Array(ref elems) => Ok(RcValue::from(Value::Array(
+            elems
+                .iter()
+                .map(|elem| eval_term_in(env, locals, elem))
+                .collect::<Result<_, _>>()?,
+        ))),
+
+        // Poisoned terms stay poisoned
+        Term::Error => Ok(RcValue::from(Value::Error)),
+    }
+}
+
+/// Capture the body of a pi type or lambda in a closure
+fn binder_closure(
+    locals: &Locals,
+    binder: &Binder<String>,
+    body: &RcTerm,
+) -> Closure<Scope<Binder<String>, RcTerm>> {
+    // The annotation is evaluated separately, so the body can be reused
+    // without rebinding it to the binder on its own
+    Closure::new(
+        locals.clone(),
+        Scope {
+            unsafe_pattern: binder.clone(),
+            unsafe_body: body.clone(),
+        },
+    )
+}
+
+/// Apply an argument to a value
+pub fn apply<Env>(
+    env: &Env,
+    head: RcValue,
+    plicity: Plicity,
+    arg: RcValue,
+) -> Result<RcValue, InternalError>
+where
+    Env: DefinitionEnv,
+{
+    match *head.inner {
+        Value::Lam(_, _, _, ref closure) => apply_closure(env, closure, arg),
+        Value::Glued(ref glued_head, ref spine, ref thunk) => {
+            let mut spine = spine.clone();
+            spine.push((plicity, arg.clone()));
+            let (env, thunk) = (env.clone(), thunk.clone());
+            let thunk = Thunk::new(move || apply(&env, thunk.force()?.clone(), plicity, arg));
+            Ok(RcValue::from(Value::Glued(glued_head.clone(), spine, thunk)))
+        },
+        Value::Neutral(ref neutral, ref spine) => {
+            let mut spine = spine.clone();
+
+            match *neutral.inner {
+                Neutral::Head(Head::Extern(ref name, _)) => {
+                    // Primitives are interpreted on the unfolded arguments
+                    spine.push((plicity, arg.unfold().clone()));
+
+                    // Apply the arguments to primitive definitions if the number of
+                    // arguments matches the arity of the primitive, all aof the arguments
+                    // are fully nfd
+                    if let Some(prim) = env.get_extern_definition(name) {
+                        if prim.arity == spine.len() && spine.iter().all(|&(_, ref arg)| arg.is_nf())
+                        {
+                            match (prim.interpretation)(spine) {
+                                Ok(value) => return Ok(value),
+                                Err(()) => unimplemented!("proper error"),
+                            }
                         }
                     }
                 },
-                _ => {},
+                Neutral::Head(Head::Var(Var::Free(ref name), shift)) if env.is_recursive(name) => {
+                    spine.push((plicity, arg));
+
+                    if let Some(value) = unfold_recursive(env, name, shift, &spine)? {
+                        return Ok(value);
+                    }
+                },
+                Neutral::Head(Head::Var(_, _))
+                | Neutral::Head(Head::Meta(_))
+                | Neutral::If(_, _, _)
+                | Neutral::J(_, _, _)
+                | Neutral::Proj(_, _)
+                | Neutral::Case(_, _) => spine.push((plicity, arg)),
             }
 
-            Err(InternalError::ProjectedOnNonExistentField {
-                label: label.clone(),
-            })
+            Ok(RcValue::from(Value::Neutral(neutral.clone(), spine)))
         },
+        Value::Error => Ok(RcValue::from(Value::Error)),
+        _ => Err(InternalError::ArgumentAppliedToNonFunction),
+    }
+}
 
-        // E-CASE
-        Term::Case(ref head, ref clauses) => {
-            let head = nf_term(env, head)?;
-
-            if let Value::Neutral(ref neutral, ref spine) = *head {
-                Ok(RcValue::from(Value::Neutral(
-                    RcNeutral::from(Neutral::Case(
-                        neutral.clone(),
-                        clauses
-                            .iter()
-                            .map(|clause| {
-                                let (pattern, body) = clause.clone().unbind();
-                                Ok(Scope::new(pattern, nf_term(env, &body)?))
-                            }).collect::<Result<_, _>>()?,
-                    )),
-                    spine.clone(),
-                )))
-            } else {
-                for clause in clauses {
-                    let (pattern, body) = clause.clone().unbind();
-                    if let Some(mappings) = match_value(env, &pattern, &head)? {
-                        let mappings = mappings
-                            .into_iter()
-                            .map(|(free_var, value)| (free_var, RcTerm::from(&*value.inner)))
-                            .collect::<Vec<_>>();
-                        return nf_term(env, &body.substs(&mappings));
-                    }
+/// Project a field from a value
+pub fn project(value: &RcValue, label: &Label) -> Result<RcValue, InternalError> {
+    match **value.unfold() {
+        Value::Neutral(ref neutral, ref spine) => {
+            return Ok(RcValue::from(Value::Neutral(
+                RcNeutral::from(Neutral::Proj(neutral.clone(), label.clone())),
+                spine.clone(),
+            )));
+        },
+        Value::Record(ref fields) => {
+            for &(ref current_label, ref current_expr) in fields {
+                if current_label == label {
+                    return Ok(current_expr.clone());
                 }
-                Err(InternalError::NoPatternsApplicable)
             }
         },
+        Value::Error => return Ok(RcValue::from(Value::Error)),
+        _ => {},
+    }
 
-        // E-ARRAY
-        Term::Array(ref elems) => Ok(RcValue::from(Value::Array(
+    Err(InternalError::ProjectedOnNonExistentField {
+        label: label.clone(),
+    })
+}
+
+/// Instantiate the body of a pi type or lambda with an argument
+pub fn apply_closure<Env>(
+    env: &Env,
+    closure: &Closure<Scope<Binder<String>, RcTerm>>,
+    arg: RcValue,
+) -> Result<RcValue, InternalError>
+where
+    Env: DefinitionEnv,
+{
+    eval_term_in(env, &closure.locals.push(vec![arg]), &closure.term.unsafe_body)
+}
+
+/// Instantiate the body of a pi type or lambda with a fresh variable,
+/// returning the binder for that variable along with the body
+pub fn open_closure<Env>(
+    env: &Env,
+    closure: &Closure<Scope<Binder<String>, RcTerm>>,
+) -> Result<(Binder<String>, RcValue), InternalError>
+where
+    Env: DefinitionEnv,
+{
+    let Binder(ref free_var) = closure.term.unsafe_pattern;
+    let free_var = FreeVar::fresh(free_var.pretty_name.clone());
+    let arg = RcValue::from(Value::var(Var::Free(free_var.clone()), 0));
+
+    Ok((Binder(free_var), apply_closure(env, closure, arg)?))
+}
+
+/// Evaluate a term that is embedded in a pattern of a nest, like the
+/// annotation of a field in a record type
+///
+/// `values` are the values bound by the patterns that come before the one
+/// that the term is embedded in.
+pub fn eval_nest_term<Env>(
+    env: &Env,
+    locals: &Locals,
+    values: &[RcValue],
+    term: &RcTerm,
+) -> Result<RcValue, InternalError>
+where
+    Env: DefinitionEnv,
+{
+    eval_term_in(env, &locals.push_nest(values), term)
+}
+
+/// Read a value back into a term in normal form
+pub fn quote_value<Env>(env: &Env, value: &RcValue) -> Result<RcTerm, InternalError>
+where
+    Env: DefinitionEnv,
+{
+    let term = match *value.inner {
+        Value::Glued(_, _, ref thunk) => return quote_value(env, thunk.force()?),
+        Value::Universe(ref level) => Term::Universe(level.clone()),
+        Value::Literal(ref lit) => Term::Literal(lit.clone()),
+        Value::Pi(plicity, multiplicity, ref ann, ref closure) => {
+            let (binder, body) = open_closure(env, closure)?;
+            Term::Pi(plicity, multiplicity, Scope::new(
+                (binder, Embed(quote_value(env, ann)?)),
+                quote_value(env, &body)?,
+            ))
+        },
+        Value::Lam(plicity, multiplicity, ref ann, ref closure) => {
+            let (binder, body) = open_closure(env, closure)?;
+            Term::Lam(plicity, multiplicity, Scope::new(
+                (binder, Embed(quote_value(env, ann)?)),
+                quote_value(env, &body)?,
+            ))
+        },
+        Value::RecordType(ref closure) => {
+            let mut values = Vec::new();
+            let fields = closure
+                .term
+                .unsafe_pattern
+                .unsafe_patterns
+                .iter()
+                .map(|&(ref label, Binder(ref free_var), Embed(ref ann))| {
+                    let ann = eval_nest_term(env, &closure.locals, &values, ann)?;
+                    let free_var = FreeVar::fresh(free_var.pretty_name.clone());
+                    values.push(RcValue::from(Value::var(Var::Free(free_var.clone()), 0)));
+                    Ok((label.clone(), Binder(free_var), Embed(quote_value(env, &ann)?)))
+                }).collect::<Result<_, _>>()?;
+
+            Term::RecordType(Scope::new(Nest::new(fields), ()))
+        },
+        Value::Record(ref fields) => {
+            let fields = fields
+                .iter()
+                .map(|&(ref label, ref expr)| {
+                    let binder = Binder(FreeVar::fresh_named(label.0.clone()));
+                    Ok((label.clone(), binder, Embed(quote_value(env, expr)?)))
+                }).collect::<Result<_, _>>()?;
+
+            Term::Record(Scope::new(Nest::new(fields), ()))
+        },
+        Value::Array(ref elems) => Term::Array(
             elems
                 .iter()
-                .map(|elem| nf_term(env, elem))
+                .map(|elem| quote_value(env, elem))
                 .collect::<Result<_, _>>()?,
-        ))),
+        ),
+        Value::Eq(ref ty, ref lhs, ref rhs) => Term::Eq(
+            quote_value(env, ty)?,
+            quote_value(env, lhs)?,
+            quote_value(env, rhs)?,
+        ),
+        Value::Refl => Term::Refl,
+        Value::Constr(ref var, ref args) => Term::Constr(
+            var.clone(),
+            args.iter()
+                .map(|&(plicity, ref arg)| Ok((plicity, quote_value(env, arg)?)))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Neutral(ref neutral, ref spine) => {
+            return spine
+                .iter()
+                .fold(quote_neutral(env, neutral), |acc, &(plicity, ref arg)| {
+                    Ok(RcTerm::from(Term::App(acc?, plicity, quote_value(env, arg)?)))
+                });
+        },
+        Value::Error => Term::Error,
+    };
+
+    Ok(RcTerm::from(term))
+}
+
+/// Read a neutral value back into a term in normal form
+fn quote_neutral<Env>(env: &Env, neutral: &RcNeutral) -> Result<RcTerm, InternalError>
+where
+    Env: DefinitionEnv,
+{
+    let term = match *neutral.inner {
+        Neutral::Head(Head::Var(ref var, shift)) => Term::Var(var.clone(), shift),
+        Neutral::Head(Head::Extern(ref name, ref ty)) => {
+            Term::Extern(name.clone(), quote_value(env, ty)?)
+        },
+        Neutral::Head(Head::Meta(meta)) => Term::Meta(meta),
+        Neutral::If(ref cond, ref if_true, ref if_false) => Term::If(
+            quote_neutral(env, cond)?,
+            quote_value(env, if_true)?,
+            quote_value(env, if_false)?,
+        ),
+        Neutral::J(ref motive, ref base, ref proof) => Term::J(
+            quote_value(env, motive)?,
+            quote_value(env, base)?,
+            quote_neutral(env, proof)?,
+        ),
+        Neutral::Proj(ref expr, ref label) => Term::Proj(quote_neutral(env, expr)?, label.clone()),
+        Neutral::Case(ref head, ref closure) => Term::Case(
+            quote_value(env, head)?,
+            closure
+                .term
+                .iter()
+                .map(|clause| {
+                    // The variables bound by the pattern are left neutral
+                    let pattern = clause.unsafe_pattern.instantiate_locals(0, &closure.locals);
+                    let (pattern, ()) = Scope::new(pattern, ()).unbind();
+                    let values = pattern
+                        .binders()
+                        .into_iter()
+                        .map(|Binder(free_var)| RcValue::from(Value::var(Var::Free(free_var), 0)))
+                        .collect();
+                    let body = eval_term_in(env, &closure.locals.push(values), &clause.unsafe_body)?;
+                    Ok(Scope::new(pattern, quote_value(env, &body)?))
+                }).collect::<Result<_, _>>()?,
+        ),
+    };
+
+    Ok(RcTerm::from(term))
+}
+
+/// Unfold an application of a recursive definition, but only if this would
+/// allow the `if` or `case` expression at the head of its body to reduce
+///
+/// Otherwise we leave the application stuck. This prevents the normalizer from
+/// unfolding recursive calls forever when it goes under binders, or when the
+/// arguments are not yet known.
+fn unfold_recursive<Env>(
+    env: &Env,
+    name: &FreeVar<String>,
+    shift: LevelShift,
+    spine: &Spine,
+) -> Result<Option<RcValue>, InternalError>
+where
+    Env: DefinitionEnv,
+{
+    // Partial definitions have not been shown to terminate, so unfolding
+    // them could send us into an infinite loop
+    if env.is_partial(name) {
+        return Ok(None);
+    }
+
+    let mut term = match env.get_definition(name) {
+        Some(term) => term.clone(),
+        None => return Ok(None),
+    };
+    // Shift the definition before binding the arguments, which are already
+    // at the correct levels
+    term.shift_universes(shift);
+    let mut locals = Locals::new();
+    let mut args = spine.iter();
+
+    // Bind the arguments to the parameters of the definition
+    loop {
+        let body = match *term.inner {
+            Term::Ann(ref term, _) => term.clone(),
+            Term::Lam(_, _, ref scope) => match args.next() {
+                Some(&(_, ref arg)) => {
+                    locals = locals.push(vec![arg.clone()]);
+                    scope.unsafe_body.clone()
+                },
+                // Not enough arguments have been supplied
+                None => return Ok(None),
+            },
+            _ => break,
+        };
+        term = body;
+    }
+
+    if !is_reducible(env, &locals, &term)? {
+        return Ok(None);
+    }
+
+    let mut value = eval_term_in(env, &locals, &term)?;
+
+    // Apply any remaining arguments to the result
+    for &(plicity, ref arg) in args {
+        value = apply(env, value, plicity, arg.clone())?;
     }
+
+    Ok(Some(value))
 }
 
-/// If the pattern matches the value, this function returns the substitutions
-/// needed to apply the pattern to some body expression
+/// Returns `true` if the `if`, `J`, or `case` expression at the head of the term
+/// would reduce when evaluated
+fn is_reducible<Env>(env: &Env, locals: &Locals, term: &RcTerm) -> Result<bool, InternalError>
+where
+    Env: DefinitionEnv,
+{
+    match *term.inner {
+        Term::Ann(ref term, _) => is_reducible(env, locals, term),
+        Term::Let(ref scope) => {
+            let (_, Embed(ref bind)) = scope.unsafe_pattern;
+            let bind = eval_term_in(env, locals, bind)?;
+            is_reducible(env, &locals.push(vec![bind]), &scope.unsafe_body)
+        },
+        Term::If(ref head, _, _) | Term::J(_, _, ref head) => {
+            match **eval_term_in(env, locals, head)?.unfold() {
+                Value::Neutral(_, _) => Ok(false),
+                _ => Ok(true),
+            }
+        },
+        Term::Case(ref head, ref clauses) => {
+            let head = eval_term_in(env, locals, head)?;
+            for clause in clauses {
+                match match_value(env, locals, &clause.unsafe_pattern, &head)? {
+                    Match::Matched(_) => return Ok(true),
+                    Match::Failed => {},
+                    Match::Stuck => return Ok(false),
+                }
+            }
+            Ok(false)
+        },
+        _ => Ok(false),
+    }
+}
+
+/// The result of matching a value against a pattern
+#[derive(Debug, Clone, PartialEq)]
+pub enum Match {
+    /// The pattern matched, binding the given values to the variables of the
+    /// pattern, in the order that they are bound
+    Matched(Vec<RcValue>),
+    /// The pattern did not match
+    Failed,
+    /// A neutral value prevented us from knowing if the pattern matches
+    Stuck,
+}
+
+/// Match a value against a pattern
+///
+/// Matching is stuck if the pattern needs to inspect a neutral value, even if
+/// the neutral value is nested inside a constructor. We must not fall through
+/// to the next clause in this case, because the neutral value might later turn
+/// out to match.
 pub fn match_value<Env>(
     env: &Env,
+    locals: &Locals,
     pattern: &RcPattern,
     value: &RcValue,
-) -> Result<Option<Vec<(FreeVar<String>, RcValue)>>, InternalError>
+) -> Result<Match, InternalError>
 where
     Env: DefinitionEnv,
 {
+    let value = value.unfold();
+
     match (&*pattern.inner, &*value.inner) {
-        (&Pattern::Binder(Binder(ref free_var)), _) => {
-            Ok(Some(vec![(free_var.clone(), value.clone())]))
-        },
+        (&Pattern::Ann(ref pattern, _), _) => match_value(env, locals, pattern, value),
+        (&Pattern::Binder(_), _) => Ok(Match::Matched(vec![value.clone()])),
+        (_, &Value::Neutral(_, _)) | (_, &Value::Error) => Ok(Match::Stuck),
         (&Pattern::Var(Embed(Var::Free(ref free_var)), _), _) => {
-            match env.get_definition(free_var).map(|term| nf_term(env, term)) {
-                Some(Ok(ref term)) if term == value => Ok(Some(vec![])),
-                Some(Ok(_)) | None => Ok(None),
+            match env.get_definition(free_var).map(|term| eval_term(env, term)) {
+                Some(Ok(ref term)) if term.unfold() == value => Ok(Match::Matched(vec![])),
+                Some(Ok(_)) | None => Ok(Match::Failed),
                 Some(Err(err)) => Err(err),
             }
         },
+        (&Pattern::Var(Embed(Var::Bound(ref bound_var)), _), _) => {
+            match locals.lookup(bound_var) {
+                Some(local) if local.unfold() == value => Ok(Match::Matched(vec![])),
+                Some(_) | None => Ok(Match::Failed),
+            }
+        },
         (&Pattern::Literal(ref pattern_lit), &Value::Literal(ref value_lit))
             if pattern_lit == value_lit =>
         {
-            Ok(Some(vec![]))
+            Ok(Match::Matched(vec![]))
+        },
+        (&Pattern::Constr(Embed(ref pattern_var), ref pattern_args), &Value::Constr(ref value_var, ref value_args))
+            if pattern_var == value_var && pattern_args.len() == value_args.len() =>
+        {
+            // A later argument that fails to match takes precedence over an
+            // earlier argument that is stuck
+            let mut values = Vec::new();
+            let mut stuck = false;
+            for (&(_, ref pattern_arg), &(_, ref value_arg)) in <_>::zip(pattern_args.iter(), value_args) {
+                match match_value(env, locals, pattern_arg, value_arg)? {
+                    Match::Matched(arg_values) => values.extend(arg_values),
+                    Match::Failed => return Ok(Match::Failed),
+                    Match::Stuck => stuck = true,
+                }
+            }
+            Ok(if stuck { Match::Stuck } else { Match::Matched(values) })
         },
-        (_, _) => Ok(None),
+        (_, _) => Ok(Match::Failed),
     }
 }
@@ -0,0 +1,583 @@
+//! Termination checking for recursive definitions
+//!
+//! We use a simplified version of the size-change principle, described in
+//! _The Size-Change Principle for Program Termination_ by Lee, Jones, and
+//! Ben-Amram. For each call between the definitions of a module we record how
+//! the size of each argument relates to the parameters of the caller, where
+//! the only way to make an argument smaller is to take it apart with a
+//! constructor pattern in a case expression. We then compose these calls until
+//! no new ones are found. A module terminates if every call that loops back to
+//! its caller, and that stays the same when composed with itself, makes at
+//! least one of the parameters of the caller strictly smaller.
+
+use codespan::ByteSpan;
+use moniker::{Binder, Embed, FreeVar, Var};
+use std::collections::{HashMap, HashSet};
+
+use syntax::core::{Item, Module, Pattern, RcPattern, RcTerm, Term};
+use syntax::raw;
+
+use semantics::errors::TypeError;
+use semantics::pretty_name;
+
+/// How the size of an argument is related to one of the parameters of the
+/// calling definition
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Relation {
+    /// We don't know anything about the size of the argument
+    Unknown,
+    /// The argument is the same size as the parameter
+    Equal,
+    /// The argument is strictly smaller than the parameter
+    Smaller,
+}
+
+impl Relation {
+    /// Combine the relations of two calls that are made one after another
+    fn then(self, other: Relation) -> Relation {
+        match (self, other) {
+            (Relation::Unknown, _) | (_, Relation::Unknown) => Relation::Unknown,
+            (Relation::Equal, Relation::Equal) => Relation::Equal,
+            (_, _) => Relation::Smaller,
+        }
+    }
+}
+
+/// Relates the arguments of a call (the rows) to the parameters of the calling
+/// definition (the columns)
+type Matrix = Vec<Vec<Relation>>;
+
+/// Compose the matrix of a call from `f` to `g` with the matrix of a call
+/// from `g` to `h`, producing a matrix for calls from `f` to `h`
+fn compose(first: &Matrix, second: &Matrix, arity: usize) -> Matrix {
+    second
+        .iter()
+        .map(|row| {
+            (0..arity)
+                .map(|param| {
+                    row.iter()
+                        .zip(first)
+                        .map(|(&rel, first_row)| first_row[param].then(rel))
+                        .max()
+                        .unwrap_or(Relation::Unknown)
+                }).collect()
+        }).collect()
+}
+
+/// A call from one definition to another
+#[derive(Debug, Clone)]
+struct Call {
+    caller: FreeVar<String>,
+    callee: FreeVar<String>,
+    span: ByteSpan,
+    matrix: Matrix,
+}
+
+/// A sequence of calls that starts from the definition being checked
+#[derive(Debug, Clone)]
+struct Path {
+    callee: FreeVar<String>,
+    matrix: Matrix,
+    /// The indices of the calls that make up this path
+    calls: Vec<usize>,
+}
+
+/// Check that the recursive definitions in a module terminate
+///
+/// Each definition is checked against the items that came before it, as in
+/// `check_definition_termination`, reporting an error for every definition
+/// that could not be shown to terminate.
+pub fn check_termination(raw_module: &raw::Module, module: &Module) -> Vec<TypeError> {
+    module
+        .items
+        .iter()
+        .enumerate()
+        .filter_map(|(index, item)| {
+            check_definition_termination(raw_module, &module.items[..index], item).err()
+        }).collect()
+}
+
+/// Check that a definition terminates, given the items that came before it in
+/// the module
+///
+/// Only the calls that lead back to the definition are considered, because
+/// any loops between the earlier definitions have already been checked.
+/// Definitions that were declared as `partial` are skipped. The raw module is
+/// used to find the spans of the offending call sites.
+pub fn check_definition_termination(
+    raw_module: &raw::Module,
+    items: &[Item],
+    item: &Item,
+) -> Result<(), TypeError> {
+    let free_var = match *item {
+        Item::Definition { ref binder, .. } => &binder.0,
+        Item::Declaration { .. } | Item::Data { .. } | Item::Error { .. } => return Ok(()),
+    };
+
+    let mut partial = HashSet::new();
+    let mut defs = HashMap::new();
+
+    for item in items.iter().chain(Some(item)) {
+        match *item {
+            Item::Declaration {
+                ref binder,
+                partial: true,
+                ..
+            } => {
+                partial.insert(binder.0.clone());
+            },
+            Item::Definition {
+                ref binder,
+                ref term,
+                ..
+            } => {
+                defs.insert(binder.0.clone(), term);
+            },
+            Item::Declaration { .. } | Item::Data { .. } | Item::Error { .. } => {},
+        }
+    }
+
+    if partial.contains(free_var) {
+        return Ok(());
+    }
+
+    let def_names = defs.keys().cloned().collect::<HashSet<_>>();
+    let raw_defs = raw_module
+        .items
+        .iter()
+        .filter_map(|item| match *item {
+            raw::Item::Definition {
+                label_span,
+                ref binder,
+                ref term,
+                ..
+            } => Some((binder.0.clone(), (label_span, term))),
+            _ => None,
+        }).collect::<HashMap<_, _>>();
+
+    // Collect the calls made by the definitions that can be reached from
+    // the one that we are checking
+    let mut arities = HashMap::new();
+    let mut calls = Vec::new();
+    let mut pending = vec![free_var.clone()];
+
+    while let Some(caller) = pending.pop() {
+        if partial.contains(&caller) || arities.contains_key(&caller) {
+            continue;
+        }
+        let term = match defs.get(&caller) {
+            Some(term) => term,
+            None => continue,
+        };
+
+        let mut collector = CallCollector::new(&def_names);
+        let arity = collector.collect_params(term);
+        arities.insert(caller.clone(), arity);
+
+        // Line up the calls with their call sites in the source code
+        let mut spans = Vec::new();
+        let label_span = match raw_defs.get(&caller) {
+            Some(&(label_span, raw_term)) => {
+                collect_call_spans(&def_names, raw_term, &mut spans);
+                label_span
+            },
+            None => ByteSpan::default(),
+        };
+
+        for (index, (callee, matrix)) in collector.calls.into_iter().enumerate() {
+            pending.push(callee.clone());
+            calls.push(Call {
+                caller: caller.clone(),
+                callee,
+                span: spans.get(index).cloned().unwrap_or(label_span),
+                matrix,
+            });
+        }
+    }
+
+    // Find all the paths through the call graph that start from the
+    // definition, shortest paths first
+    let arity = arities[free_var];
+    let mut seen = HashSet::new();
+    let mut paths = Vec::new();
+    for (index, call) in calls.iter().enumerate() {
+        if call.caller == *free_var && seen.insert((call.callee.clone(), call.matrix.clone())) {
+            paths.push(Path {
+                callee: call.callee.clone(),
+                matrix: call.matrix.clone(),
+                calls: vec![index],
+            });
+        }
+    }
+
+    let mut next = 0;
+    while next < paths.len() {
+        let path = paths[next].clone();
+        next += 1;
+
+        for (index, call) in calls.iter().enumerate() {
+            if call.caller != path.callee {
+                continue;
+            }
+
+            let matrix = compose(&path.matrix, &call.matrix, arity);
+            if seen.insert((call.callee.clone(), matrix.clone())) {
+                let mut path_calls = path.calls.clone();
+                path_calls.push(index);
+
+                paths.push(Path {
+                    callee: call.callee.clone(),
+                    matrix,
+                    calls: path_calls,
+                });
+            }
+        }
+    }
+
+    for path in &paths {
+        if path.callee != *free_var || compose(&path.matrix, &path.matrix, arity) != path.matrix {
+            continue;
+        }
+
+        let decreases = path
+            .matrix
+            .iter()
+            .enumerate()
+            .any(|(param, row)| row.get(param) == Some(&Relation::Smaller));
+
+        if !decreases {
+            return Err(TypeError::NonTerminating {
+                name: pretty_name(free_var),
+                call_spans: path.calls.iter().map(|&index| calls[index].span).collect(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Collects the calls to other definitions in the module from the body of a
+/// definition, recording how the size of each argument is related to the
+/// parameters of the definition
+struct CallCollector<'a> {
+    defs: &'a HashSet<FreeVar<String>>,
+    /// The number of parameters of the definition
+    arity: usize,
+    /// The parameter that each variable is derived from, and its size in
+    /// relation to that parameter
+    sizes: HashMap<FreeVar<String>, (usize, Relation)>,
+    /// The constructor patterns that have been matched against terms derived
+    /// from the parameters. Rebuilding one of these patterns results in a term
+    /// of the same size as the term that was matched.
+    patterns: Vec<(RcPattern, (usize, Relation))>,
+    calls: Vec<(FreeVar<String>, Matrix)>,
+}
+
+impl<'a> CallCollector<'a> {
+    fn new(defs: &'a HashSet<FreeVar<String>>) -> CallCollector<'a> {
+        CallCollector {
+            defs,
+            arity: 0,
+            sizes: HashMap::new(),
+            patterns: Vec::new(),
+            calls: Vec::new(),
+        }
+    }
+
+    /// Collect the calls in a definition, treating the leading lambdas as the
+    /// parameters of the definition. Returns the number of parameters.
+    fn collect_params(&mut self, term: &RcTerm) -> usize {
+        match *term.inner {
+            Term::Ann(ref term, _) => self.collect_params(term),
+            Term::Lam(_, _, ref scope) => {
+                let ((Binder(free_var), Embed(_)), body) = scope.clone().unbind();
+                self.sizes.insert(free_var, (self.arity, Relation::Equal));
+                self.arity += 1;
+                self.collect_params(&body)
+            },
+            _ => {
+                self.collect(term);
+                self.arity
+            },
+        }
+    }
+
+    /// The size of a term, if it is derived from one of the parameters
+    fn size_of(&self, term: &RcTerm) -> Option<(usize, Relation)> {
+        match *term.inner {
+            Term::Ann(ref term, _) => self.size_of(term),
+            Term::Var(Var::Free(ref free_var), _) if self.sizes.contains_key(free_var) => {
+                self.sizes.get(free_var).cloned()
+            },
+            _ => self
+                .patterns
+                .iter()
+                .find(|&&(ref pattern, _)| rebuilds(term, pattern))
+                .map(|&(_, size)| size),
+        }
+    }
+
+    /// Record the sizes of the variables bound by a pattern that was matched
+    /// against a term of the given size
+    fn bind_pattern(&mut self, pattern: &RcPattern, size: (usize, Relation)) {
+        match *pattern.inner {
+            Pattern::Ann(ref pattern, _) => self.bind_pattern(pattern, size),
+            Pattern::Binder(Binder(ref free_var)) => {
+                self.sizes.insert(free_var.clone(), size);
+            },
+            Pattern::Constr(_, ref args) => {
+                self.patterns.push((pattern.clone(), size));
+                for &(_, ref arg) in args {
+                    self.bind_pattern(arg, (size.0, Relation::Smaller));
+                }
+            },
+            Pattern::Var(_, _) | Pattern::Literal(_) | Pattern::Error => {},
+        }
+    }
+
+    fn record_call(&mut self, callee: &FreeVar<String>, args: &[&RcTerm]) {
+        let matrix = args
+            .iter()
+            .map(|arg| {
+                let size = self.size_of(arg);
+                (0..self.arity)
+                    .map(|param| match size {
+                        Some((arg_param, rel)) if arg_param == param => rel,
+                        _ => Relation::Unknown,
+                    }).collect()
+            }).collect();
+
+        self.calls.push((callee.clone(), matrix));
+    }
+
+    /// Collect the calls in a term, in the order that they appear in the
+    /// source code. Calls in type annotations are ignored.
+    fn collect(&mut self, term: &RcTerm) {
+        match *term.inner {
+            Term::Ann(ref term, _) | Term::Inst(ref term, _) => self.collect(term),
+            Term::Universe(_)
+            | Term::Literal(_)
+            | Term::Meta(_)
+            | Term::Extern(_, _)
+            | Term::Refl
+            | Term::Error => {},
+            Term::Var(Var::Free(ref free_var), _) if self.defs.contains(free_var) => {
+                self.record_call(free_var, &[]);
+            },
+            Term::Var(_, _) => {},
+            Term::Constr(_, ref args) => {
+                for &(_, ref arg) in args {
+                    self.collect(arg);
+                }
+            },
+            // Types can be computed by recursive definitions too
+            Term::Pi(_, _, ref scope) => {
+                let ((_, Embed(ann)), body) = scope.clone().unbind();
+                self.collect(&ann);
+                self.collect(&body);
+            },
+            Term::Lam(_, _, ref scope) => {
+                let (_, body) = scope.clone().unbind();
+                self.collect(&body);
+            },
+            Term::App(_, _, _) => {
+                let (head, args) = spine(term);
+                match *head.inner {
+                    Term::Var(Var::Free(ref free_var), _) if self.defs.contains(free_var) => {
+                        self.record_call(free_var, &args);
+                    },
+                    _ => self.collect(head),
+                }
+                for arg in args {
+                    self.collect(arg);
+                }
+            },
+            Term::If(ref cond, ref if_true, ref if_false) => {
+                self.collect(cond);
+                self.collect(if_true);
+                self.collect(if_false);
+            },
+            Term::J(ref motive, ref base, ref proof) => {
+                self.collect(motive);
+                self.collect(base);
+                self.collect(proof);
+            },
+            // The type of the two sides is elaborated, so we skip it in order
+            // to stay in step with `collect_call_spans`
+            Term::Eq(_, ref lhs, ref rhs) => {
+                self.collect(lhs);
+                self.collect(rhs);
+            },
+            Term::RecordType(ref scope) | Term::Record(ref scope) => {
+                let (fields, ()) = scope.clone().unbind();
+                for (_, _, Embed(term)) in fields.unnest() {
+                    self.collect(&term);
+                }
+            },
+            Term::Proj(ref expr, _) => self.collect(expr),
+            Term::Case(ref head, ref clauses) => {
+                self.collect(head);
+                let head_size = self.size_of(head);
+                for clause in clauses {
+                    let (pattern, body) = clause.clone().unbind();
+                    if let Some(size) = head_size {
+                        self.bind_pattern(&pattern, size);
+                    }
+                    self.collect(&body);
+                }
+            },
+            Term::Array(ref elems) => {
+                for elem in elems {
+                    self.collect(elem);
+                }
+            },
+            Term::Let(ref scope) => {
+                let ((Binder(free_var), Embed(bind)), body) = scope.clone().unbind();
+                self.collect(&bind);
+                if let Some(size) = self.size_of(&bind) {
+                    self.sizes.insert(free_var, size);
+                }
+                self.collect(&body);
+            },
+        }
+    }
+}
+
+/// Split an application into its head and its arguments
+fn spine(term: &RcTerm) -> (&RcTerm, Vec<&RcTerm>) {
+    let mut head = term;
+    let mut args = Vec::new();
+    while let Term::App(ref fn_term, _, ref arg) = *head.inner {
+        args.push(arg);
+        head = fn_term;
+    }
+    // Level-polymorphic definitions are called at some universe levels
+    while let Term::Inst(ref term, _) = *head.inner {
+        head = term;
+    }
+    args.reverse();
+    (head, args)
+}
+
+/// Returns `true` if the term rebuilds the pattern. For example `succ n`
+/// rebuilds the pattern `succ n`.
+fn rebuilds(term: &RcTerm, pattern: &RcPattern) -> bool {
+    match (&*term.inner, &*pattern.inner) {
+        (_, &Pattern::Ann(ref pattern, _)) => rebuilds(term, pattern),
+        (&Term::Ann(ref term, _), _) => rebuilds(term, pattern),
+        (&Term::Var(Var::Free(ref free_var), _), &Pattern::Binder(Binder(ref binder))) => {
+            free_var == binder
+        },
+        (&Term::Var(ref var, _), &Pattern::Var(Embed(ref pattern_var), _)) => var == pattern_var,
+        (
+            &Term::Constr(ref var, ref args),
+            &Pattern::Constr(Embed(ref pattern_var), ref pattern_args),
+        ) => {
+            var == pattern_var
+                && args.len() == pattern_args.len()
+                && <_>::zip(args.iter(), pattern_args)
+                    .all(|(&(_, ref arg), &(_, ref pattern_arg))| rebuilds(arg, pattern_arg))
+        },
+        (_, &Pattern::Constr(Embed(ref pattern_var), ref pattern_args)) => {
+            // The arguments to the parameters of the data type come before the
+            // arguments of the constructor, so we only compare the latter
+            let (head, args) = spine(term);
+            match *head.inner {
+                Term::Var(ref var, _) if var == pattern_var && args.len() >= pattern_args.len() => {
+                    <_>::zip(args.iter().rev(), pattern_args.iter().rev())
+                        .all(|(arg, &(_, ref pattern_arg))| rebuilds(arg, pattern_arg))
+                },
+                _ => false,
+            }
+        },
+        (_, _) => false,
+    }
+}
+
+/// Collect the spans of the names of the definitions called in a raw term, in
+/// the same order as `CallCollector::collect`
+fn collect_call_spans(
+    defs: &HashSet<FreeVar<String>>,
+    term: &raw::RcTerm,
+    spans: &mut Vec<ByteSpan>,
+) {
+    match *term.inner {
+        raw::Term::Ann(ref term, _) => collect_call_spans(defs, term, spans),
+        raw::Term::Universe(_, _)
+        | raw::Term::Literal(_)
+        | raw::Term::Hole(_)
+        | raw::Term::Extern(_, _, _, _)
+        | raw::Term::Refl(_)
+        | raw::Term::Error(_) => {},
+        raw::Term::Var(span, Var::Free(ref free_var), _) if defs.contains(free_var) => {
+            spans.push(span);
+        },
+        raw::Term::Var(_, _, _) => {},
+        raw::Term::Pi(_, _, _, ref scope) => {
+            let ((_, Embed(ann)), body) = scope.clone().unbind();
+            collect_call_spans(defs, &ann, spans);
+            collect_call_spans(defs, &body, spans);
+        },
+        raw::Term::Lam(_, _, _, ref scope) => {
+            let (_, body) = scope.clone().unbind();
+            collect_call_spans(defs, &body, spans);
+        },
+        raw::Term::App(_, _, _) => {
+            let mut head = term;
+            let mut args = Vec::new();
+            while let raw::Term::App(ref fn_term, _, ref arg) = *head.inner {
+                args.push(arg);
+                head = fn_term;
+            }
+            args.reverse();
+
+            match *head.inner {
+                raw::Term::Var(span, Var::Free(ref free_var), _) if defs.contains(free_var) => {
+                    spans.push(span);
+                },
+                _ => collect_call_spans(defs, head, spans),
+            }
+            for arg in args {
+                collect_call_spans(defs, arg, spans);
+            }
+        },
+        raw::Term::If(_, ref cond, ref if_true, ref if_false) => {
+            collect_call_spans(defs, cond, spans);
+            collect_call_spans(defs, if_true, spans);
+            collect_call_spans(defs, if_false, spans);
+        },
+        raw::Term::J(_, ref motive, ref base, ref proof) => {
+            collect_call_spans(defs, motive, spans);
+            collect_call_spans(defs, base, spans);
+            collect_call_spans(defs, proof, spans);
+        },
+        raw::Term::Eq(ref lhs, ref rhs) => {
+            collect_call_spans(defs, lhs, spans);
+            collect_call_spans(defs, rhs, spans);
+        },
+        raw::Term::RecordType(_, ref scope) | raw::Term::Record(_, ref scope) => {
+            let (fields, ()) = scope.clone().unbind();
+            for (_, _, Embed(term)) in fields.unnest() {
+                collect_call_spans(defs, &term, spans);
+            }
+        },
+        raw::Term::Proj(_, ref expr, _, _) => collect_call_spans(defs, expr, spans),
+        raw::Term::Case(_, ref head, ref clauses) => {
+            collect_call_spans(defs, head, spans);
+            for clause in clauses {
+                let (_, body) = clause.clone().unbind();
+                collect_call_spans(defs, &body, spans);
+            }
+        },
+        raw::Term::Array(_, ref elems) => {
+            for elem in elems {
+                collect_call_spans(defs, elem, spans);
+            }
+        },
+        raw::Term::Let(_, ref scope) => {
+            let ((_, Embed((_, bind))), body) = scope.clone().unbind();
+            collect_call_spans(defs, &bind, spans);
+            collect_call_spans(defs, &body, spans);
+        },
+    }
+}
@@ -18,8 +18,11 @@ fn prelude() {
 
     let tc_env = TcEnv::default();
     let desugar_env = DesugarEnv::new(tc_env.mappings());
-    if let Err(err) = check_module(&tc_env, &concrete_module.desugar(&desugar_env)) {
-        codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+    let (_, errors) = check_module(&tc_env, &concrete_module.desugar(&desugar_env));
+    if !errors.is_empty() {
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
         panic!("type error!")
     }
 }
@@ -35,9 +38,12 @@ fn infer_bare_definition() {
     ";
 
     let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
-    if let Err(err) = check_module(&tc_env, &raw_module) {
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
         let writer = StandardStream::stdout(ColorChoice::Always);
-        codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
         panic!("type error!")
     }
 }
@@ -56,9 +62,12 @@ fn forward_declarations() {
     ";
 
     let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
-    if let Err(err) = check_module(&tc_env, &raw_module) {
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
         let writer = StandardStream::stdout(ColorChoice::Always);
-        codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
         panic!("type error!")
     }
 }
@@ -77,10 +86,33 @@ fn forward_declarations_forward_ref() {
     ";
 
     let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
-    match check_module(&tc_env, &raw_module) {
-        Ok(_) => panic!("expected error"),
-        Err(TypeError::UndefinedName { .. }) => {},
-        Err(err) => panic!("unexpected error: {}", err),
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn forward_ref_undeclared() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = "
+        bar = foo;
+        foo : Bool;
+        foo = false;
+    ";
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::UndefinedName { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
     }
 }
 
@@ -96,10 +128,10 @@ fn declaration_after_definition() {
     ";
 
     let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
-    match check_module(&tc_env, &raw_module) {
-        Ok(_) => panic!("expected error"),
-        Err(TypeError::DeclarationFollowedDefinition { .. }) => {},
-        Err(err) => panic!("unexpected error: {}", err),
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::DeclarationFollowedDefinition { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
     }
 }
 
@@ -115,10 +147,10 @@ fn duplicate_declarations() {
     ";
 
     let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
-    match check_module(&tc_env, &raw_module) {
-        Ok(_) => panic!("expected error"),
-        Err(TypeError::DuplicateDeclarations { .. }) => {},
-        Err(err) => panic!("unexpected error: {}", err),
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::DuplicateDeclarations { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
     }
 }
 
@@ -134,10 +166,10 @@ fn duplicate_definitions() {
     ";
 
     let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
-    match check_module(&tc_env, &raw_module) {
-        Ok(_) => panic!("expected error"),
-        Err(TypeError::DuplicateDefinitions { .. }) => {},
-        Err(err) => panic!("unexpected error: {}", err),
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::DuplicateDefinitions { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
     }
 }
 
@@ -165,9 +197,12 @@ fn shift_universes() {
     "#;
 
     let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
-    if let Err(err) = check_module(&tc_env, &raw_module) {
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
         let writer = StandardStream::stdout(ColorChoice::Always);
-        codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
         panic!("type error!")
     }
 }
@@ -201,9 +236,12 @@ fn shift_universes_id_self_application() {
     "#;
 
     let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
-    if let Err(err) = check_module(&tc_env, &raw_module) {
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
         let writer = StandardStream::stdout(ColorChoice::Always);
-        codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
         panic!("type error!")
     }
 }
@@ -222,9 +260,12 @@ fn shift_universes_literals() {
     "#;
 
     let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
-    if let Err(err) = check_module(&tc_env, &raw_module) {
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
         let writer = StandardStream::stdout(ColorChoice::Always);
-        codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
         panic!("type error!")
     }
 }
@@ -243,10 +284,10 @@ fn shift_universes_literals_bad() {
     "#;
 
     let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
-    match check_module(&tc_env, &raw_module) {
-        Ok(_) => panic!("expected error"),
-        Err(TypeError::LiteralMismatch { .. }) => {},
-        Err(err) => panic!("unexpected error: {}", err),
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::LiteralMismatch { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
     }
 }
 
@@ -264,10 +305,10 @@ fn shift_universes_too_little() {
     "#;
 
     let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
-    match check_module(&tc_env, &raw_module) {
-        Ok(_) => panic!("expected error"),
-        Err(TypeError::Mismatch { .. }) => {},
-        Err(err) => panic!("unexpected error: {}", err),
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::Mismatch { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
     }
 }
 
@@ -285,9 +326,1870 @@ fn shift_universes_too_much() {
     "#;
 
     let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
-    if let Err(err) = check_module(&tc_env, &raw_module) {
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn universe_polymorphism() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        id : (a : Type^l) -> a -> a;
+        id a x = x;
+
+        test1 = id String "hello";
+        test2 = id Type String;
+        test3 = id Type^1 Type;
+        test4 = id ((a : Type) -> a -> a) id;
+
+        void : Type^(l + 1);
+        void = (a : Type^l) -> a;
+
+        void0 : Type^1 = void;
+        void1 : Type^2 = void;
+
+        Pair : Type^l -> Type^k -> Type^(max l k);
+        Pair a b = Record { fst : a; snd : b };
+
+        test5 : Pair String Type = record { fst = "hello"; snd = String };
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn universe_polymorphism_rigid_params() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        bad : Type^l;
+        bad = Type^l;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::Mismatch { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn universe_level_inference() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        void : Type;
+        void = (a : Type) -> a;
+
+        not : Type -> Type;
+        not a = a -> void;
+
+        and : Type -> Type -> Type;
+        and p q = (c : Type) -> (p -> q -> c) -> c;
+
+        small : Type;
+        small = String;
+
+        test1 : Type^1 = not String;
+        test2 : Type^1 = and String I32;
+        test3 : Type^0 = small;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (module, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+
+    let not_ty = module.items.iter().find_map(|item| match *item {
+        Item::Declaration {
+            ref label,
+            ref term,
+            ..
+        } if label.0 == "not" => Some(term.resugar(tc_env.resugar_env())),
+        _ => None,
+    });
+    assert_eq!(not_ty.unwrap().to_string(), "Type -> Type^1");
+}
+
+#[test]
+fn universe_level_inference_too_small() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        void : Type;
+        void = (a : Type) -> a;
+
+        test1 : Type^0 = void;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::Mismatch {
+            ref found,
+            ref expected,
+            ..
+        }] => {
+            assert_eq!(found.to_string(), "Type^1");
+            assert_eq!(expected.to_string(), "Type");
+        },
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn universe_level_inference_unsolved_in_errors() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        foo : Type;
+        foo = "hi";
+
+        Box : Type -> Type;
+        Box a = a;
+
+        bar : Box Type;
+        bar = 1;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::LiteralMismatch {
+            expected: ref expected1,
+            ..
+        }, TypeError::Mismatch {
+            found: ref found2,
+            expected: ref expected2,
+            ..
+        }] => {
+            assert_eq!(expected1.to_string(), "Type^?l");
+            assert_eq!(found2.to_string(), "Type^(?l + 1)");
+            assert_eq!(expected2.to_string(), "Type");
+        },
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn universe_level_inference_inconsistent() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        bad : Type^0;
+        bad = (\(a : Type) => a) Type;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::UniverseInconsistency { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn type_in_type() {
+    let mut codemap = CodeMap::new();
+    let mut tc_env = TcEnv::default();
+    tc_env.set_type_in_type(true);
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        type-of-types : Type^0;
+        type-of-types = Type^0;
+
+        bad : Type^0;
+        bad = (\(a : Type) => a) Type;
+
+        data Any : Type^0 where {
+            any : (a : Type) -> a -> Any;
+        };
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn implicit_args() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        id : {a : Type} -> a -> a;
+        id x = x;
+
+        const : {a b : Type} -> a -> b -> a;
+        const x y = x;
+
+        test-infer = id "hello";
+        test-explicit = id {String} "hello";
+        test-check : String -> String = id;
+        test-nested : Bool -> String -> Bool = const;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn implicit_args_unsolved() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = "
+        const-ty : {a : Type} -> Type;
+        const-ty = String;
+
+        test : Type;
+        test = const-ty;
+    ";
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::UnsolvedMetas { ref metas }] if metas.len() == 1 => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn holes() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        test-arg = (\(a : Type) (x : a) => x) ? "hello";
+        test-ann : ? -> String;
+        test-ann x = x;
+        test-param = (\x y => x) "hello" 'a';
+        test-later : ?;
+        test-later = "hello";
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn holes_pattern_unification() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = "
+        test : (x : Bool) -> ({P : Bool -> Type} -> P x -> P x) -> String -> String;
+        test x f = f;
+    ";
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn holes_unsolved() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r"
+        test1 : Type;
+        test1 = ?;
+
+        test2 = \x => x;
+    ";
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::UnsolvedMetas { ref metas }] => {
+            assert_eq!(metas.len(), 2);
+            assert!(metas.iter().all(|meta| meta.source == MetaSource::Hole));
+        },
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn holes_unsolved_unknown_type() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r"
+        test = ?;
+    ";
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::UnsolvedMetas { ref metas }] => {
+            assert_eq!(metas.len(), 1);
+            assert_eq!(metas[0].expected, None);
+        },
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn data_types() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        data Option (a : Type) : Type where {
+            none : Option a;
+            some : a -> Option a;
+        };
+
+        data List (a : Type) : Type where {
+            nil : List a;
+            cons : a -> List a -> List a;
+        };
+
+        data Nat : Type where {
+            zero : Nat;
+            succ : Nat -> Nat;
+        };
+
+        pred : Nat -> Nat;
+        pred n = case n of {
+            zero => zero;
+            succ m => m;
+        };
+
+        head : {a : Type} -> List a -> Option a;
+        head xs = case xs of {
+            nil => none;
+            cons x _ => some x;
+        };
+
+        test-pred : case pred (succ (succ zero)) of { zero => Bool; succ _ => String };
+        test-pred = "hello";
+
+        test-head : case head (cons "hello" nil) of { none => Bool; some x => String };
+        test-head = "hello";
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
         let writer = StandardStream::stdout(ColorChoice::Always);
-        codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
         panic!("type error!")
     }
 }
+
+#[test]
+fn data_type_case_nested_neutral() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        data Nat : Type where {
+            Z : Nat;
+            S : Nat -> Nat;
+        };
+
+        is-two-plus : Nat -> Bool;
+        is-two-plus n = case n of {
+            S (S k) => true;
+            _ => false;
+        };
+
+        wrong : (n : Nat) -> (is-two-plus (S n) = false);
+        wrong n = refl;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::Mismatch { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn data_type_non_strictly_positive() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = "
+        data Bad : Type where {
+            bad : (Bad -> Bool) -> Bad;
+        };
+    ";
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::NonStrictlyPositive { ref name, .. }] => assert_eq!(name, "Bad"),
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn data_type_invalid_constructor_return_type() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = "
+        data Box (a : Type) : Type where {
+            box : a -> Box String;
+        };
+    ";
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::InvalidConstructorReturnType { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn data_type_constructor_too_large() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = "
+        data Any : Type^0 where {
+            any : (a : Type) -> a -> Any;
+        };
+    ";
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::ConstructorTooLarge { ref name, .. }] => assert_eq!(name, "any"),
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn constructor_pattern_arity_mismatch() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = "
+        data Nat : Type where {
+            zero : Nat;
+            succ : Nat -> Nat;
+        };
+
+        is-zero : Nat -> Bool;
+        is-zero n = case n of {
+            zero => true;
+            succ => false;
+        };
+    ";
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::ConstructorArityMismatch { ref name, .. }] => assert_eq!(name, "succ"),
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn constructor_pattern_implicit_args() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = "
+        data Nat : Type where {
+            Z : Nat;
+            S : Nat -> Nat;
+        };
+
+        data Vec (a : Type) : Nat -> Type where {
+            vnil : Vec a Z;
+            vcons : {n : Nat} -> a -> Vec a n -> Vec a (S n);
+        };
+
+        head-or : {a : Type} {n : Nat} -> a -> Vec a n -> a;
+        head-or default xs = case xs of {
+            vnil => default;
+            vcons x _ => x;
+        };
+    ";
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn data_type_non_exhaustive() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = "
+        data List (a : Type) : Type where {
+            nil : List a;
+            cons : a -> List a -> List a;
+        };
+
+        is-singleton : List Bool -> Bool;
+        is-singleton xs = case xs of {
+            nil => false;
+            cons _ nil => true;
+        };
+    ";
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::NonExhaustivePatterns { ref missing, .. }] => {
+            let missing = missing.iter().map(|pattern| pattern.to_string()).collect::<Vec<_>>();
+            assert_eq!(missing, ["cons _ (cons _ _)"]);
+        },
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn data_type_indexed_exhaustive() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = "
+        data Nat : Type where {
+            Z : Nat;
+            S : Nat -> Nat;
+        };
+
+        data Vec (a : Type) : Nat -> Type where {
+            vnil : Vec a Z;
+            vcons : {n : Nat} -> a -> Vec a n -> Vec a (S n);
+        };
+
+        head : {a : Type} {n : Nat} -> Vec a (S n) -> a;
+        head xs = case xs of {
+            vcons x _ => x;
+        };
+
+        second : {a : Type} {n : Nat} -> Vec a (S (S n)) -> a;
+        second xs = case xs of {
+            vcons y (vcons x _) => x;
+        };
+    ";
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+    assert!(tc_env.take_warnings().is_empty());
+}
+
+#[test]
+fn data_type_indexed_non_exhaustive() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = "
+        data Nat : Type where {
+            Z : Nat;
+            S : Nat -> Nat;
+        };
+
+        data Vec (a : Type) : Nat -> Type where {
+            vnil : Vec a Z;
+            vcons : {n : Nat} -> a -> Vec a n -> Vec a (S n);
+        };
+
+        second : {a : Type} {n : Nat} -> Vec a (S n) -> a;
+        second xs = case xs of {
+            vcons y (vcons x _) => x;
+        };
+    ";
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::NonExhaustivePatterns { ref missing, .. }] => {
+            let missing = missing.iter().map(|pattern| pattern.to_string()).collect::<Vec<_>>();
+            assert_eq!(missing, ["vcons _ vnil"]);
+        },
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn data_type_dependent_case() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        data Nat : Type where {
+            zero : Nat;
+            succ : Nat -> Nat;
+        };
+
+        IsZero : Nat -> Type;
+        IsZero n = case n of {
+            zero => Bool;
+            succ _ => String;
+        };
+
+        test : (n : Nat) -> IsZero n;
+        test n = case n of {
+            zero => true;
+            succ m => "nope";
+        };
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn data_type_dependent_case_mismatch() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        data Nat : Type where {
+            zero : Nat;
+            succ : Nat -> Nat;
+        };
+
+        wrong : (P : Nat -> Type) (n : Nat) -> P zero -> P n;
+        wrong P n z = case n of {
+            zero => z;
+            succ m => z;
+        };
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        // The binder from the pattern is mentioned in the expected type
+        [TypeError::Mismatch { ref expected, .. }] => {
+            let expected = expected.to_string();
+            assert!(expected.ends_with(" (succ m)"), "{}", expected);
+        },
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn recursive_definitions() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        data Nat : Type where {
+            zero : Nat;
+            succ : Nat -> Nat;
+        };
+
+        add : Nat -> Nat -> Nat;
+        add m n = case m of {
+            zero => n;
+            succ k => succ (add k n);
+        };
+
+        IsTwo : Nat -> Type;
+        IsTwo n = case n of {
+            succ (succ zero) => Bool;
+            _ => String;
+        };
+
+        two : IsTwo (add (succ zero) (succ zero));
+        two = true;
+
+        stuck : (n : Nat) -> IsTwo (add n zero) -> IsTwo (add n zero);
+        stuck n x = x;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn recursive_definition_shifted() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    // Shifting `pick` must leave the universes in its arguments alone
+    let src = r#"
+        data Nat : Type where {
+            zero : Nat;
+            succ : Nat -> Nat;
+        };
+
+        pick : Nat -> Type^1 -> Type^1;
+        pick n A = case n of {
+            zero => A;
+            succ k => pick k A;
+        };
+
+        picked : (pick^1 (succ zero) Type^0 = Type^0);
+        picked = refl;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn forward_declared_definitions() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        Name : Type;
+
+        Nickname : Type;
+        Nickname = Name;
+
+        Name = String;
+
+        nickname : Nickname;
+        nickname = "Ali";
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn mutually_recursive_definitions() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        data Nat : Type where {
+            zero : Nat;
+            succ : Nat -> Nat;
+        };
+
+        even : Nat -> Bool;
+        odd : Nat -> Bool;
+
+        even n = case n of {
+            zero => true;
+            succ k => odd k;
+        };
+        odd n = case n of {
+            zero => false;
+            succ k => even k;
+        };
+
+        test : if even (succ (succ zero)) then Bool else String;
+        test = true;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn termination_lexicographic() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        data Nat : Type where {
+            zero : Nat;
+            succ : Nat -> Nat;
+        };
+
+        ack : Nat -> Nat -> Nat;
+        ack m n = case m of {
+            zero => succ n;
+            succ j => case n of {
+                zero => ack j (succ zero);
+                succ k => ack j (ack (succ j) k);
+            };
+        };
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn termination_non_structural() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        data Nat : Type where {
+            zero : Nat;
+            succ : Nat -> Nat;
+        };
+
+        loop : Nat -> Nat;
+        loop n = case n of {
+            zero => zero;
+            succ k => loop (succ k);
+        };
+    "#;
+
+    let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
+    let raw_module = parse::module(&filemap).0.desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::NonTerminating {
+            ref name,
+            ref call_spans,
+        }] => {
+            let call_srcs = call_spans
+                .iter()
+                .map(|&span| filemap.src_slice(span).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(name, "loop");
+            assert_eq!(call_srcs, vec!["loop"]);
+        },
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn termination_mutual() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        ping : Bool -> Bool;
+        pong : Bool -> Bool;
+
+        ping b = pong b;
+        pong b = ping b;
+    "#;
+
+    let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
+    let raw_module = parse::module(&filemap).0.desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::NonTerminating {
+            ref name,
+            ref call_spans,
+        }] => {
+            let call_srcs = call_spans
+                .iter()
+                .map(|&span| filemap.src_slice(span).unwrap())
+                .collect::<Vec<_>>();
+            // The loop is only closed once `pong` has been defined
+            assert_eq!(name, "pong");
+            assert_eq!(call_srcs, vec!["ping", "pong"]);
+        },
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn termination_partial() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        partial ping : Bool -> Bool;
+        pong : Bool -> Bool;
+
+        ping b = pong b;
+        pong b = ping b;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn termination_checked_before_unfolding() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        data Nat : Type where {
+            Z : Nat;
+            S : Nat -> Nat;
+        };
+
+        spin : Nat -> Nat;
+        spin n = case n of {
+            Z => spin Z;
+            S k => Z;
+        };
+
+        t : (spin Z = Z);
+        t = refl;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::NonTerminating { ref name, .. }, TypeError::Mismatch { .. }] => {
+            assert_eq!(name, "spin");
+        },
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn termination_partial_not_unfolded() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        data Nat : Type where {
+            Z : Nat;
+            S : Nat -> Nat;
+        };
+
+        partial spin : Nat -> Nat;
+        spin n = case n of {
+            Z => spin Z;
+            S k => Z;
+        };
+
+        t : (spin Z = Z);
+        t = refl;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::Mismatch { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn termination_calls_in_types() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        data Nat : Type where {
+            Z : Nat;
+            S : Nat -> Nat;
+        };
+
+        h : Nat -> Type;
+        h n = case n of {
+            Z => String;
+            S k => (x : h n) -> String;
+        };
+    "#;
+
+    let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
+    let raw_module = parse::module(&filemap).0.desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::NonTerminating {
+            ref name,
+            ref call_spans,
+        }] => {
+            let call_srcs = call_spans
+                .iter()
+                .map(|&span| filemap.src_slice(span).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(name, "h");
+            assert_eq!(call_srcs, vec!["h"]);
+        },
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn equality_laws() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        id : {A : Type} -> A -> A;
+        id x = x;
+
+        compose : {A B C : Type} -> (B -> C) -> (A -> B) -> A -> C;
+        compose f g x = f (g x);
+
+        id-left : {A B : Type} (f : A -> B) (x : A) -> (compose id f x = f x);
+        id-left f x = refl;
+
+        sym : {A : Type} {x y : A} -> (x = y) -> (y = x);
+        sym {A} {x} {y} p = J (\z q => z = x) refl p;
+
+        cong : {A B : Type} {x y : A} (f : A -> B) -> (x = y) -> (f x = f y);
+        cong {A} {B} {x} {y} f p = J (\z q => f x = f z) refl p;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn equality_law_false() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        not : Bool -> Bool;
+        not b = if b then false else true;
+
+        not-id : (not true = true);
+        not-id = refl;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::Mismatch { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn mismatch_keeps_definition_names() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        and : Type -> Type -> Type^1;
+        and p q = (c : Type) -> (p -> q -> c) -> c;
+
+        swap : (p q : Type) -> and p q -> and q p;
+        swap p q pq = pq;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::Mismatch {
+            ref found,
+            ref expected,
+            ..
+        }] => {
+            assert_eq!(found.to_string(), "and p q");
+            assert_eq!(expected.to_string(), "and q p");
+        },
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn eta_equality() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        fun-eta : (f : String -> String) -> (f = (\x => f x));
+        fun-eta f = refl;
+
+        record-eta : (p : Record { x : String; y : String }) -> (p = record { x = p.x; y = p.y });
+        record-eta p = refl;
+
+        record-eta-flip : (p : Record { x : String; y : String }) -> (record { x = p.x; y = p.y } = p);
+        record-eta-flip p = refl;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn eta_equality_neutrals() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        unit-eta : (a b : Record {}) -> (a = b);
+        unit-eta a b = refl;
+
+        fun-unit-eta : (f g : String -> Record {}) -> (f = g);
+        fun-unit-eta f g = refl;
+
+        record-unit-eta : (p q : Record { x : Record {} }) -> (p = q);
+        record-unit-eta p q = refl;
+
+        arg-unit-eta : (P : Record {} -> Type) (a b : Record {}) -> P a -> P b;
+        arg-unit-eta P a b pa = pa;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn eta_equality_neutrals_mismatch() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        fun-eta : (f g : String -> String) -> (f = g);
+        fun-eta f g = refl;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [TypeError::Mismatch { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn multiplicities() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        id : {0 A : Type} -> (1 x : A) -> A;
+        id x = x;
+
+        const : {0 A B : Type} -> (1 x : A) -> (0 y : B) -> A;
+        const x y = x;
+
+        dup : {0 A : Type} -> A -> Record { fst : A; snd : A };
+        dup x = record { fst = x; snd = x };
+
+        swap : {0 A : Type} -> Bool -> (1 x : A) -> (1 y : A) -> Record { fst : A; snd : A };
+        swap b x y = if b then record { fst = x; snd = y } else record { fst = id y; snd = x };
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn multiplicity_erased_used_at_runtime() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        id : {A : Type} -> (0 x : A) -> A;
+        id x = x;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::UsageMismatch {
+            ref name,
+            found: Multiplicity::One,
+            expected: Multiplicity::Zero,
+            ..
+        }] => assert_eq!(name, "x"),
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn multiplicity_linear_used_twice() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        dup : {0 A : Type} -> (1 x : A) -> Record { fst : A; snd : A };
+        dup x = record { fst = x; snd = x };
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::UsageMismatch {
+            found: Multiplicity::Many,
+            expected: Multiplicity::One,
+            ..
+        }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn multiplicity_linear_used_in_one_branch() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        drop : {0 A : Type} -> Bool -> (1 x : A) -> (y : A) -> A;
+        drop b x y = if b then x else y;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::UsageMismatch {
+            found: Multiplicity::Many,
+            expected: Multiplicity::One,
+            ..
+        }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn imports() {
+    let mut codemap = CodeMap::new();
+    let mut tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let nat_src = r#"
+        data Nat : Type where {
+            zero : Nat;
+            succ : Nat -> Nat;
+        };
+
+        one = succ zero;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, nat_src).desugar(&desugar_env);
+    let (_, errors) = check_imported_module(&mut tc_env, "lib.nat", &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+
+    let src = r#"
+        import lib.nat;
+
+        two : nat.Nat;
+        two = nat.succ nat.one;
+
+        two-is-two : (two = nat.succ (nat.succ nat.zero));
+        two-is-two = refl;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+}
+
+#[test]
+fn imports_unresolved() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        import lib.nat;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::UnresolvedImport { ref module, .. }] if module == "lib.nat" => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn imports_private_items() {
+    let mut codemap = CodeMap::new();
+    let mut tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let bool_src = r#"
+        data Bool : Type where {
+            true : Bool;
+            false : Bool;
+        };
+
+        private not : Bool -> Bool;
+        not b = case b of {
+            true => false;
+            false => true;
+        };
+
+        private helper = true;
+
+        or : Bool -> Bool -> Bool;
+        or a b = case a of {
+            true => true;
+            false => b;
+        };
+
+        and : Bool -> Bool -> Bool;
+        and a b = not (or (not a) (not b));
+    "#;
+
+    let raw_module = parse_module(&mut codemap, bool_src).desugar(&desugar_env);
+    let (_, errors) = check_imported_module(&mut tc_env, "bool", &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+
+    let src = r#"
+        import bool;
+
+        false-and-true : (bool.and bool.false bool.true = bool.false);
+        false-and-true = refl;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (_, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+
+    for src in &["import bool; x = bool.not;", "import bool; x = bool.helper;"] {
+        let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+        match *check_module(&tc_env, &raw_module).1 {
+            [] => panic!("expected error"),
+            [TypeError::NoFieldInType { .. }] => {},
+            ref errors => panic!("unexpected errors: {:?}", errors),
+        }
+    }
+}
+
+#[test]
+fn doc_comments() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        ||! Natural numbers
+        ||!
+        ||! Defined inductively
+
+        ||| The natural numbers
+        data Nat : Type where {
+            ||| Zero
+            zero : Nat;
+            ||| The successor of a natural number
+            succ : Nat -> Nat;
+        };
+
+        ||| One
+        |||
+        ||| The successor of zero
+        one : Nat;
+        one = succ zero;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (module, errors) = check_module(&tc_env, &raw_module);
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for err in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &err.to_diagnostic()).unwrap();
+        }
+        panic!("type error!")
+    }
+
+    assert_eq!(module.doc, "Natural numbers\n\nDefined inductively");
+    match module.items[0] {
+        Item::Data {
+            ref doc,
+            ref constructors,
+            ..
+        } => {
+            assert_eq!(doc, "The natural numbers");
+            assert_eq!(constructors[0].doc, "Zero");
+            assert_eq!(constructors[1].doc, "The successor of a natural number");
+        },
+        _ => panic!("expected a data type"),
+    }
+    match module.items[1] {
+        Item::Declaration { ref doc, .. } => assert_eq!(doc, "One\n\nThe successor of zero"),
+        _ => panic!("expected a declaration"),
+    }
+}
+
+#[test]
+fn multiple_errors() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        foo : Bool;
+        foo = "hello";
+
+        bar : String;
+        bar = "hello";
+
+        baz : String;
+        baz = true;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (module, errors) = check_module(&tc_env, &raw_module);
+    match *errors {
+        [TypeError::LiteralMismatch { .. }, TypeError::Mismatch { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+
+    assert_eq!(module.items.len(), 6);
+    match module.items[1] {
+        Item::Error { ref label, .. } => assert_eq!(label.0, "foo"),
+        _ => panic!("expected an error item"),
+    }
+    match module.items[3] {
+        Item::Definition { ref label, .. } => assert_eq!(label.0, "bar"),
+        _ => panic!("expected a definition"),
+    }
+    match module.items[5] {
+        Item::Error { ref label, .. } => assert_eq!(label.0, "baz"),
+        _ => panic!("expected an error item"),
+    }
+}
+
+#[test]
+fn poisoned_definition() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        foo = true "hello";
+
+        bar : Bool;
+        bar = foo;
+
+        baz : String -> String;
+        baz x = foo x 1 "hello";
+
+        qux = foo.field;
+
+        quux : Bool -> Bool;
+        quux = foo;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [TypeError::ArgAppliedToNonFunction { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn poisoned_declaration() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        id : Foo -> Foo;
+        id x = x;
+
+        test : String;
+        test = id "hello";
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [TypeError::UndefinedName { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn poisoned_data() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        data Nat : Type where {
+            zero : Nat;
+            succ : Foo -> Nat;
+        };
+
+        one : Nat;
+        one = succ zero;
+
+        is-zero : Nat -> Bool;
+        is-zero n = case n of {
+            zero => true;
+            succ _ => false;
+        };
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [TypeError::UndefinedName { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn duplicate_definitions_poisoned() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        foo : Bool;
+        foo = true;
+        foo = "hello";
+
+        bar : Bool;
+        bar = foo;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    let (module, errors) = check_module(&tc_env, &raw_module);
+    match *errors {
+        [TypeError::DuplicateDefinitions { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+    match module.items[1] {
+        Item::Definition { ref label, .. } => assert_eq!(label.0, "foo"),
+        _ => panic!("expected a definition"),
+    }
+}
+
+#[test]
+fn termination_multiple() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        l1 : Bool;
+        l1 = l1;
+
+        l2 : Bool;
+        l2 = l2;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [] => panic!("expected error"),
+        [TypeError::NonTerminating {
+            name: ref name1, ..
+        }, TypeError::NonTerminating {
+            name: ref name2, ..
+        }] => {
+            assert_eq!(name1, "l1");
+            assert_eq!(name2, "l2");
+        },
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn poisoned_equality_proof() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        proof : Foo;
+        proof = refl;
+
+        test : String;
+        test = J (\y p => String) "hello" proof;
+    "#;
+
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [TypeError::UndefinedName { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn poisoned_item_solutions_forgotten() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        foo : ?;
+
+        bar : Record { a : String; b : String };
+        bar = record { a = foo; b = 1 };
+
+        foo = true;
+    "#;
+
+    // The type of `foo` is solved by the definition, not by the item that
+    // failed to type check
+    let raw_module = parse_module(&mut codemap, src).desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [TypeError::LiteralMismatch { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn parse_errors_poisoned() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        foo : String;
+        foo = ;
+
+        bar : String;
+        bar = foo;
+
+        baz : String -> Bool;
+        baz x = case x of {
+            "hello" => true;
+            ) => false;
+        };
+
+        ) ) );
+
+        qux (7 x : String) = x;
+
+        quux : String;
+        quux = qux "hello";
+
+        corge : String;
+        corge = 1;
+    "#;
+
+    let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
+    let (concrete_module, parse_errors) = parse::module(&filemap);
+    assert_eq!(parse_errors.len(), 4, "{:?}", parse_errors);
+
+    let raw_module = concrete_module.desugar(&desugar_env);
+    match *check_module(&tc_env, &raw_module).1 {
+        [TypeError::LiteralMismatch { .. }] => {},
+        ref errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn parse_errors_poisoned_case_scrutinee() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        foo : Bool;
+        foo = case ) of {};
+    "#;
+
+    let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
+    let (concrete_module, parse_errors) = parse::module(&filemap);
+    let raw_module = concrete_module.desugar(&desugar_env);
+    let (_, type_errors) = check_module(&tc_env, &raw_module);
+
+    // Only the parse error is reported, and not the missing patterns
+    assert_eq!(parse_errors.len(), 1, "{:?}", parse_errors);
+    assert_eq!(type_errors, vec![]);
+}
+
+#[test]
+fn parse_errors_poisoned_case_scrutinee_infer() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let src = r#"
+        foo = case ) of {};
+    "#;
+
+    let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
+    let (concrete_module, parse_errors) = parse::module(&filemap);
+    let raw_module = concrete_module.desugar(&desugar_env);
+    let (_, type_errors) = check_module(&tc_env, &raw_module);
+
+    // Only the parse error is reported, and not the ambiguous empty case
+    assert_eq!(parse_errors.len(), 1, "{:?}", parse_errors);
+    assert_eq!(type_errors, vec![]);
+}
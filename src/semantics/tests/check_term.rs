@@ -12,6 +12,37 @@ fn record() {
     parse_check_term(&mut codemap, &tc_env, given_expr, &expected_ty);
 }
 
+#[test]
+fn implicit_lam_insertion() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+
+    let expected_ty = r"{a : Type} -> a -> a";
+    let given_expr = r"\x => x";
+
+    let expected_ty = parse_nf_term(&mut codemap, &tc_env, expected_ty);
+    parse_check_term(&mut codemap, &tc_env, given_expr, &expected_ty);
+}
+
+#[test]
+fn implicit_lam_mismatch() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let expected_ty = r"(a : Type) -> a -> a";
+    let given_expr = r"\{a : Type} (x : a) => x";
+
+    let expected_ty = parse_nf_term(&mut codemap, &tc_env, expected_ty);
+    let raw_term = parse_term(&mut codemap, given_expr).desugar(&desugar_env);
+
+    match check_term(&tc_env, &raw_term, &eval_term(&tc_env, &expected_ty).unwrap()) {
+        Err(TypeError::Mismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(term) => panic!("expected error but found: {}", term),
+    }
+}
+
 #[test]
 fn record_field_mismatch_lt() {
     let mut codemap = CodeMap::new();
@@ -24,7 +55,7 @@ fn record_field_mismatch_lt() {
     let expected_ty = parse_nf_term(&mut codemap, &tc_env, expected_ty);
     let raw_term = parse_term(&mut codemap, given_expr).desugar(&desugar_env);
 
-    match check_term(&tc_env, &raw_term, &expected_ty) {
+    match check_term(&tc_env, &raw_term, &eval_term(&tc_env, &expected_ty).unwrap()) {
         Err(TypeError::RecordSizeMismatch { .. }) => {},
         Err(err) => panic!("unexpected error: {:?}", err),
         Ok(term) => panic!("expected error but found: {}", term),
@@ -43,7 +74,7 @@ fn record_field_mismatch_gt() {
     let expected_ty = parse_nf_term(&mut codemap, &tc_env, expected_ty);
     let raw_term = parse_term(&mut codemap, given_expr).desugar(&desugar_env);
 
-    match check_term(&tc_env, &raw_term, &expected_ty) {
+    match check_term(&tc_env, &raw_term, &eval_term(&tc_env, &expected_ty).unwrap()) {
         Err(TypeError::RecordSizeMismatch { .. }) => {},
         Err(err) => panic!("unexpected error: {:?}", err),
         Ok(term) => panic!("expected error but found: {}", term),
@@ -74,6 +105,54 @@ fn dependent_record_propagate_types() {
     parse_check_term(&mut codemap, &tc_env, given_expr, &expected_ty);
 }
 
+#[test]
+fn if_dependent() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+
+    let expected_ty = r"(b : Bool) -> (if b then String else I32)";
+    let given_expr = r#"\b => if b then "hello" else 1"#;
+
+    let expected_ty = parse_nf_term(&mut codemap, &tc_env, expected_ty);
+    parse_check_term(&mut codemap, &tc_env, given_expr, &expected_ty);
+}
+
+#[test]
+fn if_dependent_context() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+
+    let expected_ty = r"(P : Bool -> Type) (b : Bool) -> P b -> P b";
+    let given_expr = r"\P b x => if b then x else x";
+
+    let expected_ty = parse_nf_term(&mut codemap, &tc_env, expected_ty);
+    parse_check_term(&mut codemap, &tc_env, given_expr, &expected_ty);
+}
+
+#[test]
+fn case_expr_dependent_context() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+
+    let expected_ty = r"(P : String -> Type) (s : String) -> P s -> P s";
+    let given_expr = r#"\P s x => case s of { "a" => x; _ => x }"#;
+
+    let expected_ty = parse_nf_term(&mut codemap, &tc_env, expected_ty);
+    parse_check_term(&mut codemap, &tc_env, given_expr, &expected_ty);
+}
+
+#[test]
+fn case_expr_dependent() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+
+    let expected_ty = r"(b : Bool) -> case b of { true => String; false => I32 }";
+    let given_expr = r#"\b => case b of { true => "hello"; false => 1 }"#;
+
+    let expected_ty = parse_nf_term(&mut codemap, &tc_env, expected_ty);
+    parse_check_term(&mut codemap, &tc_env, given_expr, &expected_ty);
+}
+
 #[test]
 fn case_expr() {
     let mut codemap = CodeMap::new();
@@ -105,7 +184,7 @@ fn case_expr_bad_literal() {
     let expected_ty = parse_nf_term(&mut codemap, &tc_env, expected_ty);
     let raw_term = parse_term(&mut codemap, given_expr).desugar(&desugar_env);
 
-    match check_term(&tc_env, &raw_term, &expected_ty) {
+    match check_term(&tc_env, &raw_term, &eval_term(&tc_env, &expected_ty).unwrap()) {
         Err(TypeError::LiteralMismatch { .. }) => {},
         Err(err) => panic!("unexpected error: {:?}", err),
         Ok(term) => panic!("expected error but found: {}", term),
@@ -130,12 +209,67 @@ fn case_expr_wildcard() {
 fn case_expr_empty() {
     let mut codemap = CodeMap::new();
     let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
 
     let expected_ty = r"String";
     let given_expr = r#"case "helloo" of {}"#;
 
+    let expected_ty = parse_nf_term(&mut codemap, &tc_env, expected_ty);
+    let raw_term = parse_term(&mut codemap, given_expr).desugar(&desugar_env);
+
+    match check_term(&tc_env, &raw_term, &eval_term(&tc_env, &expected_ty).unwrap()) {
+        Err(TypeError::NonExhaustivePatterns { ref missing, .. }) => {
+            assert_eq!(missing.len(), 1);
+        },
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(term) => panic!("expected error but found: {}", term),
+    }
+}
+
+#[test]
+fn case_expr_non_exhaustive() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let expected_ty = r"String";
+    let given_expr = r#"case "helloo" of {
+        "" => "empty";
+        "hi" => "haha";
+    }"#;
+
+    let expected_ty = parse_nf_term(&mut codemap, &tc_env, expected_ty);
+    let raw_term = parse_term(&mut codemap, given_expr).desugar(&desugar_env);
+
+    match check_term(&tc_env, &raw_term, &eval_term(&tc_env, &expected_ty).unwrap()) {
+        Err(TypeError::NonExhaustivePatterns { ref missing, .. }) => {
+            assert_eq!(missing.len(), 1);
+            assert_eq!(missing[0].to_string(), r#""a""#);
+        },
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(term) => panic!("expected error but found: {}", term),
+    }
+}
+
+#[test]
+fn case_expr_unreachable() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"case true of {
+        true => "true";
+        _ => "other";
+        false => "false";
+    }"#;
+
     let expected_ty = parse_nf_term(&mut codemap, &tc_env, expected_ty);
     parse_check_term(&mut codemap, &tc_env, given_expr, &expected_ty);
+
+    match tc_env.take_warnings().as_slice() {
+        [TypeError::UnreachablePattern { .. }] => {},
+        warnings => panic!("unexpected warnings: {:?}", warnings),
+    }
 }
 
 #[test]
@@ -174,7 +308,7 @@ fn array_len_mismatch() {
     let expected_ty = parse_nf_term(&mut codemap, &tc_env, expected_ty);
     let raw_term = parse_term(&mut codemap, given_expr).desugar(&desugar_env);
 
-    match check_term(&tc_env, &raw_term, &expected_ty) {
+    match check_term(&tc_env, &raw_term, &eval_term(&tc_env, &expected_ty).unwrap()) {
         Err(TypeError::ArrayLengthMismatch { .. }) => {},
         Err(err) => panic!("unexpected error: {:?}", err),
         Ok(term) => panic!("expected error but found: {}", term),
@@ -193,8 +327,39 @@ fn array_elem_ty_mismatch() {
     let expected_ty = parse_nf_term(&mut codemap, &tc_env, expected_ty);
     let raw_term = parse_term(&mut codemap, given_expr).desugar(&desugar_env);
 
-    match check_term(&tc_env, &raw_term, &expected_ty) {
+    match check_term(&tc_env, &raw_term, &eval_term(&tc_env, &expected_ty).unwrap()) {
         Err(_) => {},
         Ok(term) => panic!("expected error but found: {}", term),
     }
 }
+
+#[test]
+fn refl() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+
+    let expected_ty = r#"(\x : String => x) "hello" = "hello""#;
+    let given_expr = r"refl";
+
+    let expected_ty = parse_nf_term(&mut codemap, &tc_env, expected_ty);
+    parse_check_term(&mut codemap, &tc_env, given_expr, &expected_ty);
+}
+
+#[test]
+fn refl_mismatch() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let expected_ty = r#""hello" = "goodbye""#;
+    let given_expr = r"refl";
+
+    let expected_ty = parse_nf_term(&mut codemap, &tc_env, expected_ty);
+    let raw_term = parse_term(&mut codemap, given_expr).desugar(&desugar_env);
+
+    match check_term(&tc_env, &raw_term, &eval_term(&tc_env, &expected_ty).unwrap()) {
+        Err(TypeError::Mismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(term) => panic!("expected error but found: {}", term),
+    }
+}
@@ -65,6 +65,20 @@ fn ty_levels() {
     );
 }
 
+#[test]
+fn ty_level_exprs() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+
+    let expected_ty = r"Type^(max (l + 2) 3)";
+    let given_expr = r"Type^(max 2 (l + 1) (max l 1))";
+
+    assert_term_eq!(
+        parse_infer_term(&mut codemap, &tc_env, given_expr).1,
+        parse_nf_term(&mut codemap, &tc_env, expected_ty),
+    );
+}
+
 #[test]
 fn ann_ty_id() {
     let mut codemap = CodeMap::new();
@@ -138,7 +152,17 @@ fn app_ty() {
         Err(TypeError::ArgAppliedToNonFunction {
             fn_span: ByteSpan::new(ByteIndex(1), ByteIndex(5)),
             arg_span: ByteSpan::new(ByteIndex(6), ByteIndex(10)),
-            found: Box::new(concrete::Term::Universe(ByteSpan::default(), Some(1))),
+            found: Box::new(concrete::Term::Universe(
+                ByteSpan::default(),
+                Some(concrete::Level::Parens(
+                    ByteSpan::default(),
+                    Box::new(concrete::Level::Succ(
+                        ByteSpan::default(),
+                        Box::new(concrete::Level::Var(ByteSpan::default(), "?l".to_owned())),
+                        1,
+                    )),
+                )),
+            )),
         }),
     )
 }
@@ -258,6 +282,107 @@ fn id_app_arr_pi_ty() {
     );
 }
 
+#[test]
+fn id_implicit() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+
+    let expected_ty = r"{a : Type} -> a -> a";
+    let given_expr = r"\{a : Type} (x : a) => x";
+
+    assert_term_eq!(
+        parse_infer_term(&mut codemap, &tc_env, given_expr).1,
+        parse_nf_term(&mut codemap, &tc_env, expected_ty),
+    );
+}
+
+#[test]
+fn id_implicit_app() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"(\{a : Type} (x : a) => x) "hello""#;
+
+    assert_term_eq!(
+        parse_infer_term(&mut codemap, &tc_env, given_expr).1,
+        parse_nf_term(&mut codemap, &tc_env, expected_ty),
+    );
+}
+
+#[test]
+fn id_implicit_app_explicit_ty() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+
+    let expected_ty = r"I32 -> I32";
+    let given_expr = r"(\{a : Type} (x : a) => x) {I32}";
+
+    assert_term_eq!(
+        parse_infer_term(&mut codemap, &tc_env, given_expr).1,
+        parse_nf_term(&mut codemap, &tc_env, expected_ty),
+    );
+}
+
+#[test]
+fn id_implicit_app_higher_order() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+
+    let expected_ty = r"(String -> String) -> String -> String";
+    let given_expr = r"(\{a b : Type} (f : a -> b) (x : a) => f x) {String} {String}";
+
+    assert_term_eq!(
+        parse_infer_term(&mut codemap, &tc_env, given_expr).1,
+        parse_nf_term(&mut codemap, &tc_env, expected_ty),
+    );
+}
+
+#[test]
+fn implicit_arg_applied_to_explicit_param() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let given_expr = r"(\(a : Type) (x : a) => x) {String}";
+
+    let raw_term = parse_term(&mut codemap, given_expr).desugar(&desugar_env);
+
+    match infer_term(&tc_env, &raw_term) {
+        Err(TypeError::PlicityMismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {}", term, ty),
+    }
+}
+
+#[test]
+fn id_unannotated_param_app() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"(\x => x) "hello""#;
+
+    assert_term_eq!(
+        parse_infer_term(&mut codemap, &tc_env, given_expr).1,
+        parse_nf_term(&mut codemap, &tc_env, expected_ty),
+    );
+}
+
+#[test]
+fn id_hole_app() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"(\(a : Type) (x : a) => x) ? "hello""#;
+
+    assert_term_eq!(
+        parse_infer_term(&mut codemap, &tc_env, given_expr).1,
+        parse_nf_term(&mut codemap, &tc_env, expected_ty),
+    );
+}
+
 #[test]
 fn apply() {
     let mut codemap = CodeMap::new();
@@ -529,6 +654,54 @@ mod church_encodings {
     }
 }
 
+#[test]
+fn eq_ty() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+
+    let expected_ty = r"Type^1";
+    let given_expr = r"String = (Bool -> String)";
+
+    assert_term_eq!(
+        parse_infer_term(&mut codemap, &tc_env, given_expr).1,
+        parse_nf_term(&mut codemap, &tc_env, expected_ty),
+    );
+}
+
+#[test]
+fn eq_mismatch() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let given_expr = r#""hello" = true"#;
+
+    let raw_term = parse_term(&mut codemap, given_expr).desugar(&desugar_env);
+
+    match infer_term(&tc_env, &raw_term) {
+        Err(TypeError::Mismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {}", term, ty),
+    }
+}
+
+#[test]
+fn j_expected_equality() {
+    let mut codemap = CodeMap::new();
+    let tc_env = TcEnv::default();
+    let desugar_env = DesugarEnv::new(tc_env.mappings());
+
+    let given_expr = r#"J (\y p => String) "hello" true"#;
+
+    let raw_term = parse_term(&mut codemap, given_expr).desugar(&desugar_env);
+
+    match infer_term(&tc_env, &raw_term) {
+        Err(TypeError::ExpectedEquality { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {}", term, ty),
+    }
+}
+
 #[test]
 fn empty_record_ty() {
     let mut codemap = CodeMap::new();
@@ -622,13 +795,13 @@ fn proj_weird1() {
 
     let expected_ty = r"Type^1";
     let given_expr = r"Record {
-        data : Record {
+        inner : Record {
             t : Type;
             x : t;
         };
 
-        f : data.t -> Type;
-        test : f data.x;
+        f : inner.t -> Type;
+        test : f inner.x;
     }";
 
     assert_term_eq!(
@@ -38,10 +38,18 @@ fn parse_term(codemap: &mut CodeMap, src: &str) -> concrete::Term {
     concrete_term
 }
 
-fn parse_infer_term(codemap: &mut CodeMap, tc_env: &TcEnv, src: &str) -> (RcTerm, RcType) {
+fn parse_infer_term(codemap: &mut CodeMap, tc_env: &TcEnv, src: &str) -> (RcTerm, RcTerm) {
     let raw_term = parse_term(codemap, src).desugar(&DesugarEnv::new(tc_env.mappings()));
-    match infer_term(tc_env, &raw_term) {
-        Ok((term, ty)) => (term, ty),
+    let result = infer_term(tc_env, &raw_term).and_then(|(term, ty)| {
+        solve_level_constraints(tc_env, raw_term.span())?;
+        tc_env.default_level_metas();
+        Ok((term, ty))
+    });
+    match result {
+        Ok((term, ty)) => {
+            let ty = zonk_value(tc_env, &ty).unwrap();
+            (zonk_term(tc_env, &term), quote_value(tc_env, &ty).unwrap())
+        },
         Err(error) => {
             let writer = StandardStream::stdout(ColorChoice::Always);
             codespan_reporting::emit(&mut writer.lock(), &codemap, &error.to_diagnostic()).unwrap();
@@ -50,10 +58,10 @@ fn parse_infer_term(codemap: &mut CodeMap, tc_env: &TcEnv, src: &str) -> (RcTerm
     }
 }
 
-fn parse_nf_term(codemap: &mut CodeMap, tc_env: &TcEnv, src: &str) -> RcValue {
+fn parse_nf_term(codemap: &mut CodeMap, tc_env: &TcEnv, src: &str) -> RcTerm {
     let term = parse_infer_term(codemap, tc_env, src).0;
     match nf_term(tc_env, &term) {
-        Ok(value) => value,
+        Ok(term) => term,
         Err(error) => {
             let writer = StandardStream::stdout(ColorChoice::Always);
             codespan_reporting::emit(&mut writer.lock(), &codemap, &error.to_diagnostic()).unwrap();
@@ -62,10 +70,13 @@ fn parse_nf_term(codemap: &mut CodeMap, tc_env: &TcEnv, src: &str) -> RcValue {
     }
 }
 
-fn parse_check_term(codemap: &mut CodeMap, tc_env: &TcEnv, src: &str, expected: &RcType) {
+fn parse_check_term(codemap: &mut CodeMap, tc_env: &TcEnv, src: &str, expected: &RcTerm) {
     let raw_term = parse_term(codemap, src).desugar(&DesugarEnv::new(tc_env.mappings()));
-    match check_term(tc_env, &raw_term, expected) {
-        Ok(_) => {},
+    let expected = eval_term(tc_env, expected).unwrap();
+    match check_term(tc_env, &raw_term, &expected)
+        .and_then(|_| solve_level_constraints(tc_env, raw_term.span()))
+    {
+        Ok(()) => {},
         Err(error) => {
             let writer = StandardStream::stdout(ColorChoice::Always);
             codespan_reporting::emit(&mut writer.lock(), &codemap, &error.to_diagnostic()).unwrap();
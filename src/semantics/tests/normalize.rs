@@ -1,8 +1,6 @@
 use super::*;
 
 mod nf_term {
-    use syntax::core::{Neutral, RcNeutral};
-
     use super::*;
 
     #[test]
@@ -14,7 +12,25 @@ mod nf_term {
 
         assert_eq!(
             nf_term(&tc_env, &var).unwrap(),
-            RcValue::from(Value::var(Var::Free(x), 0)),
+            RcTerm::from(Term::var(Var::Free(x), 0)),
+        );
+    }
+
+    #[test]
+    fn var_definition_unfolded_lazily() {
+        let mut tc_env = TcEnv::default();
+
+        let x = FreeVar::fresh_named("x");
+        let hello = RcTerm::from(Term::Literal(Literal::String("hello".to_owned())));
+        let broken = RcTerm::from(Term::App(hello.clone(), Plicity::Explicit, hello));
+        tc_env.insert_definition(x.clone(), broken);
+        let var = RcTerm::from(Term::var(Var::Free(x), 0));
+
+        // The definition is only evaluated once the value is needed
+        assert!(eval_term(&tc_env, &var).is_ok());
+        assert_eq!(
+            nf_term(&tc_env, &var),
+            Err(InternalError::ArgumentAppliedToNonFunction),
         );
     }
 
@@ -25,7 +41,7 @@ mod nf_term {
 
         assert_eq!(
             parse_nf_term(&mut codemap, &tc_env, r"Type"),
-            RcValue::from(Value::universe(0)),
+            RcTerm::from(Term::universe(0)),
         );
     }
 
@@ -38,10 +54,14 @@ mod nf_term {
 
         assert_term_eq!(
             parse_nf_term(&mut codemap, &tc_env, r"\x : Type => x"),
-            RcValue::from(Value::Lam(Scope::new(
-                (Binder(x.clone()), Embed(RcValue::from(Value::universe(0)))),
-                RcValue::from(Value::var(Var::Free(x), 0)),
-            ))),
+            RcTerm::from(Term::Lam(
+                Plicity::Explicit,
+                Multiplicity::Many,
+                Scope::new(
+                    (Binder(x.clone()), Embed(RcTerm::from(Term::universe(0)))),
+                    RcTerm::from(Term::var(Var::Free(x), 0)),
+                )
+            )),
         );
     }
 
@@ -54,10 +74,14 @@ mod nf_term {
 
         assert_term_eq!(
             parse_nf_term(&mut codemap, &tc_env, r"(x : Type) -> x"),
-            RcValue::from(Value::Pi(Scope::new(
-                (Binder(x.clone()), Embed(RcValue::from(Value::universe(0)))),
-                RcValue::from(Value::var(Var::Free(x), 0)),
-            ))),
+            RcTerm::from(Term::Pi(
+                Plicity::Explicit,
+                Multiplicity::Many,
+                Scope::new(
+                    (Binder(x.clone()), Embed(RcTerm::from(Term::universe(0)))),
+                    RcTerm::from(Term::var(Var::Free(x), 0)),
+                )
+            )),
         );
     }
 
@@ -70,26 +94,39 @@ mod nf_term {
 
         let x = FreeVar::fresh_named("x");
         let y = FreeVar::fresh_named("y");
-        let ty_arr = RcValue::from(Value::Pi(Scope::new(
-            (
-                Binder(FreeVar::fresh_unnamed()),
-                Embed(RcValue::from(Value::universe(0))),
+        let ty_arr = RcTerm::from(Term::Pi(
+            Plicity::Explicit,
+            Multiplicity::Many,
+            Scope::new(
+                (
+                    Binder(FreeVar::fresh_unnamed()),
+                    Embed(RcTerm::from(Term::universe(0))),
+                ),
+                RcTerm::from(Term::universe(0)),
             ),
-            RcValue::from(Value::universe(0)),
-        )));
+        ));
 
         assert_term_eq!(
             parse_nf_term(&mut codemap, &tc_env, given_expr,),
-            RcValue::from(Value::Lam(Scope::new(
-                (Binder(x.clone()), Embed(ty_arr)),
-                RcValue::from(Value::Lam(Scope::new(
-                    (Binder(y.clone()), Embed(RcValue::from(Value::universe(0)))),
-                    RcValue::from(Value::Neutral(
-                        RcNeutral::from(Neutral::var(Var::Free(x), 0)),
-                        vec![RcValue::from(Value::var(Var::Free(y), 0))],
+            RcTerm::from(Term::Lam(
+                Plicity::Explicit,
+                Multiplicity::Many,
+                Scope::new(
+                    (Binder(x.clone()), Embed(ty_arr)),
+                    RcTerm::from(Term::Lam(
+                        Plicity::Explicit,
+                        Multiplicity::Many,
+                        Scope::new(
+                            (Binder(y.clone()), Embed(RcTerm::from(Term::universe(0)))),
+                            RcTerm::from(Term::App(
+                                RcTerm::from(Term::var(Var::Free(x), 0)),
+                                Plicity::Explicit,
+                                RcTerm::from(Term::var(Var::Free(y), 0)),
+                            )),
+                        )
                     )),
-                ))),
-            ))),
+                )
+            )),
         );
     }
 
@@ -102,26 +139,39 @@ mod nf_term {
 
         let x = FreeVar::fresh_named("x");
         let y = FreeVar::fresh_named("y");
-        let ty_arr = RcValue::from(Value::Pi(Scope::new(
-            (
-                Binder(FreeVar::fresh_unnamed()),
-                Embed(RcValue::from(Value::universe(0))),
+        let ty_arr = RcTerm::from(Term::Pi(
+            Plicity::Explicit,
+            Multiplicity::Many,
+            Scope::new(
+                (
+                    Binder(FreeVar::fresh_unnamed()),
+                    Embed(RcTerm::from(Term::universe(0))),
+                ),
+                RcTerm::from(Term::universe(0)),
             ),
-            RcValue::from(Value::universe(0)),
-        )));
+        ));
 
         assert_term_eq!(
             parse_nf_term(&mut codemap, &tc_env, given_expr),
-            RcValue::from(Value::Pi(Scope::new(
-                (Binder(x.clone()), Embed(ty_arr)),
-                RcValue::from(Value::Pi(Scope::new(
-                    (Binder(y.clone()), Embed(RcValue::from(Value::universe(0)))),
-                    RcValue::from(Value::Neutral(
-                        RcNeutral::from(Neutral::var(Var::Free(x), 0)),
-                        vec![RcValue::from(Value::var(Var::Free(y), 0))],
+            RcTerm::from(Term::Pi(
+                Plicity::Explicit,
+                Multiplicity::Many,
+                Scope::new(
+                    (Binder(x.clone()), Embed(ty_arr)),
+                    RcTerm::from(Term::Pi(
+                        Plicity::Explicit,
+                        Multiplicity::Many,
+                        Scope::new(
+                            (Binder(y.clone()), Embed(RcTerm::from(Term::universe(0)))),
+                            RcTerm::from(Term::App(
+                                RcTerm::from(Term::var(Var::Free(x), 0)),
+                                Plicity::Explicit,
+                                RcTerm::from(Term::var(Var::Free(y), 0)),
+                            )),
+                        )
                     )),
-                ))),
-            ))),
+                )
+            )),
         );
     }
 
@@ -347,6 +397,24 @@ mod nf_term {
         );
     }
 
+    #[test]
+    fn j_refl() {
+        let mut codemap = CodeMap::new();
+        let tc_env = TcEnv::default();
+
+        let given_expr = r#"
+            J (\y p => String) "hello" (refl : "a" = "a")
+        "#;
+        let expected_expr = r#"
+            "hello"
+        "#;
+
+        assert_term_eq!(
+            parse_nf_term(&mut codemap, &tc_env, given_expr),
+            parse_nf_term(&mut codemap, &tc_env, expected_expr),
+        );
+    }
+
     #[test]
     fn case_expr_bool() {
         let mut codemap = CodeMap::new();
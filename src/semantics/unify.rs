@@ -0,0 +1,899 @@
+//! Metavariable solving
+//!
+//! Metavariables are inserted during elaboration to stand in for terms that
+//! the user has left implicit. We solve them by unifying the types that we
+//! infer against the types that we expect.
+//!
+//! We restrict ourselves to the 'pattern fragment' of higher-order
+//! unification: a metavariable may only be solved when it is applied to
+//! distinct variables, in which case the solution is abstracted over those
+//! variables. This is enough for most of the holes that come up in practice,
+//! and unlike full higher-order unification it always produces most general
+//! solutions.
+
+use moniker::{Binder, BoundTerm, Embed, FreeVar, Nest, Scope, Var};
+
+use syntax::core::{
+    Closure, Head, MetaVar, Neutral, Pattern, RcNeutral, RcPattern, RcTerm, RcValue, Spine, Term,
+    Thunk, Value,
+};
+use syntax::{Label, Level, LevelMeta, LevelShift, LevelVar, Multiplicity, Plicity};
+
+use semantics::errors::InternalError;
+use semantics::{
+    apply, apply_closure, eval_nest_term, eval_term, open_closure, project, DefinitionEnv, MetaEnv,
+};
+
+/// Replace the solved metavariables in a term with their solutions
+///
+/// Unsolved metavariables are left in place.
+pub fn zonk_term<Env>(env: &Env, term: &RcTerm) -> RcTerm
+where
+    Env: MetaEnv,
+{
+    // NOTE: We deliberately avoid unbinding scopes here. Solutions may refer
+    // to the same free variables that the binders in `term` were created with,
+    // so rebuilding the scopes from their original patterns will bind them
+    // correctly.
+    let zonk_scope = |scope: &Scope<_, RcTerm>| {
+        let (ref binder, Embed(ref ann)) = scope.unsafe_pattern;
+        Scope::new(
+            (Clone::clone(binder), Embed(zonk_term(env, ann))),
+            zonk_term(env, &scope.unsafe_body),
+        )
+    };
+
+    let zonk_fields = |scope: &Scope<Nest<_>, ()>| {
+        let fields = scope
+            .unsafe_pattern
+            .unsafe_patterns
+            .iter()
+            .map(|&(ref label, ref binder, Embed(ref term))| {
+                (
+                    Clone::clone(label),
+                    Clone::clone(binder),
+                    Embed(zonk_term(env, term)),
+                )
+            })
+            .collect();
+
+        Scope::new(Nest::new(fields), ())
+    };
+
+    let term = match *term.inner {
+        Term::Literal(_) | Term::Var(_, _) | Term::Refl | Term::Error => return term.clone(),
+        Term::Universe(ref level) => Term::Universe(zonk_level(env, level)),
+        Term::Inst(ref term, ref levels) => Term::Inst(
+            zonk_term(env, term),
+            levels
+                .iter()
+                .map(|&(ref param, ref level)| (param.clone(), zonk_level(env, level)))
+                .collect(),
+        ),
+        Term::Meta(meta) => match env.get_meta(meta).solution {
+            Some(ref solution) => return zonk_term(env, &RcTerm::from(&**solution)),
+            None => return term.clone(),
+        },
+        Term::Ann(ref expr, ref ty) => Term::Ann(zonk_term(env, expr), zonk_term(env, ty)),
+        Term::Extern(ref name, ref ty) => Term::Extern(name.clone(), zonk_term(env, ty)),
+        Term::Constr(ref var, ref args) => Term::Constr(
+            var.clone(),
+            args.iter()
+                .map(|&(plicity, ref arg)| (plicity, zonk_term(env, arg)))
+                .collect(),
+        ),
+        Term::Pi(plicity, multiplicity, ref scope) => {
+            Term::Pi(plicity, multiplicity, zonk_scope(scope))
+        },
+        Term::Lam(plicity, multiplicity, ref scope) => {
+            Term::Lam(plicity, multiplicity, zonk_scope(scope))
+        },
+        Term::App(ref head, plicity, ref arg) => {
+            Term::App(zonk_term(env, head), plicity, zonk_term(env, arg))
+        },
+        Term::If(ref cond, ref if_true, ref if_false) => Term::If(
+            zonk_term(env, cond),
+            zonk_term(env, if_true),
+            zonk_term(env, if_false),
+        ),
+        Term::Eq(ref ty, ref lhs, ref rhs) => {
+            Term::Eq(zonk_term(env, ty), zonk_term(env, lhs), zonk_term(env, rhs))
+        },
+        Term::J(ref motive, ref base, ref proof) => Term::J(
+            zonk_term(env, motive),
+            zonk_term(env, base),
+            zonk_term(env, proof),
+        ),
+        Term::RecordType(ref scope) => Term::RecordType(zonk_fields(scope)),
+        Term::Record(ref scope) => Term::Record(zonk_fields(scope)),
+        Term::Proj(ref expr, ref label) => Term::Proj(zonk_term(env, expr), label.clone()),
+        Term::Case(ref head, ref clauses) => Term::Case(
+            zonk_term(env, head),
+            clauses
+                .iter()
+                .map(|clause| {
+                    Scope::new(
+                        zonk_pattern(env, &clause.unsafe_pattern),
+                        zonk_term(env, &clause.unsafe_body),
+                    )
+                })
+                .collect(),
+        ),
+        Term::Array(ref elems) => Term::Array(elems.iter().map(|e| zonk_term(env, e)).collect()),
+        Term::Let(ref scope) => Term::Let(zonk_scope(scope)),
+    };
+
+    RcTerm::from(term)
+}
+
+/// Replace the solved level metavariables in a level with their solutions
+pub fn zonk_level<Env>(env: &Env, level: &Level) -> Level
+where
+    Env: MetaEnv,
+{
+    level.substs(|var| match *var {
+        LevelVar::Meta(meta) => env.get_level_meta(meta).map(|level| zonk_level(env, &level)),
+        LevelVar::Param(_) => None,
+    })
+}
+
+/// Replace the solved metavariables in a pattern with their solutions
+fn zonk_pattern<Env>(env: &Env, pattern: &RcPattern) -> RcPattern
+where
+    Env: MetaEnv,
+{
+    match *pattern.inner {
+        Pattern::Ann(ref pattern, Embed(ref ty)) => RcPattern::from(Pattern::Ann(
+            zonk_pattern(env, pattern),
+            Embed(zonk_term(env, ty)),
+        )),
+        Pattern::Constr(ref var, ref args) => RcPattern::from(Pattern::Constr(
+            var.clone(),
+            args.iter()
+                .map(|&(plicity, ref arg)| (plicity, zonk_pattern(env, arg)))
+                .collect(),
+        )),
+        Pattern::Binder(_) | Pattern::Var(_, _) | Pattern::Literal(_) | Pattern::Error => {
+            pattern.clone()
+        },
+    }
+}
+
+/// Replace the solved metavariables in a value with their solutions,
+/// renormalizing the result
+pub fn zonk_value<Env>(env: &Env, value: &RcValue) -> Result<RcValue, InternalError>
+where
+    Env: MetaEnv + DefinitionEnv,
+{
+    match **value {
+        // Keep the application of the definition, so that it can still be
+        // shown to the user
+        Value::Glued(ref head, ref spine, ref thunk) => {
+            let spine = spine
+                .iter()
+                .map(|&(plicity, ref arg)| Ok((plicity, zonk_value(env, arg)?)))
+                .collect::<Result<_, _>>()?;
+            let (env, thunk) = (env.clone(), thunk.clone());
+            let thunk = Thunk::new(move || zonk_value(&env, thunk.force()?));
+            Ok(RcValue::from(Value::Glued(head.clone(), spine, thunk)))
+        },
+        _ => eval_term(env, &zonk_term(env, &RcTerm::from(&**value))),
+    }
+}
+
+/// Returns the metavariable at the head of a neutral term
+fn neutral_meta(neutral: &Neutral) -> Option<MetaVar> {
+    match *neutral {
+        Neutral::Head(Head::Meta(meta)) => Some(meta),
+        Neutral::Head(Head::Var(_, _)) | Neutral::Head(Head::Extern(_, _)) => None,
+        Neutral::If(ref cond, _, _) => neutral_meta(cond),
+        Neutral::J(_, _, ref proof) => neutral_meta(proof),
+        Neutral::Proj(ref expr, _) => neutral_meta(expr),
+        Neutral::Case(ref head, _) => match **head {
+            Value::Neutral(ref head, _) => neutral_meta(head),
+            _ => None,
+        },
+    }
+}
+
+/// Unfold any global definitions or solved metavariables that are blocking
+/// the evaluation of a value
+pub fn force<Env>(env: &Env, value: &RcValue) -> Result<RcValue, InternalError>
+where
+    Env: MetaEnv + DefinitionEnv,
+{
+    let mut value = value.unfold().clone();
+
+    loop {
+        let is_solved = match *value {
+            Value::Neutral(ref neutral, _) => match neutral_meta(neutral) {
+                Some(meta) => env.get_meta(meta).solution.is_some(),
+                None => false,
+            },
+            _ => false,
+        };
+
+        if is_solved {
+            value = zonk_value(env, &value)?.unfold().clone();
+        } else {
+            return Ok(value);
+        }
+    }
+}
+
+/// Returns the metavariable if the value is an unsolved metavariable with no
+/// arguments applied to it
+pub fn unsolved_meta<Env>(env: &Env, value: &Value) -> Option<MetaVar>
+where
+    Env: MetaEnv,
+{
+    match *value {
+        Value::Neutral(ref neutral, ref spine) if spine.is_empty() => match *neutral.inner {
+            Neutral::Head(Head::Meta(meta)) if env.get_meta(meta).solution.is_none() => Some(meta),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns the metavariable and its arguments if the value is an unsolved
+/// metavariable applied to a spine
+fn unsolved_meta_app<'a, Env>(env: &Env, value: &'a Value) -> Option<(MetaVar, &'a Spine)>
+where
+    Env: MetaEnv,
+{
+    match *value {
+        Value::Neutral(ref neutral, ref spine) => match *neutral.inner {
+            Neutral::Head(Head::Meta(meta)) if env.get_meta(meta).solution.is_none() => {
+                Some((meta, spine))
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns `true` if the metavariable occurs somewhere in the term
+fn occurs(meta: MetaVar, term: &RcTerm) -> bool {
+    fn occurs_pattern(meta: MetaVar, pattern: &RcPattern) -> bool {
+        match *pattern.inner {
+            Pattern::Ann(ref pattern, Embed(ref ty)) => {
+                occurs_pattern(meta, pattern) || occurs(meta, ty)
+            },
+            Pattern::Constr(_, ref args) => args.iter().any(|&(_, ref arg)| occurs_pattern(meta, arg)),
+            Pattern::Binder(_) | Pattern::Var(_, _) | Pattern::Literal(_) | Pattern::Error => false,
+        }
+    }
+
+    match *term.inner {
+        Term::Meta(other_meta) => meta == other_meta,
+        Term::Universe(_) | Term::Literal(_) | Term::Var(_, _) | Term::Refl | Term::Error => false,
+        Term::Ann(ref expr, ref ty) => occurs(meta, expr) || occurs(meta, ty),
+        Term::Inst(ref term, _) | Term::Proj(ref term, _) => occurs(meta, term),
+        Term::Extern(_, ref ty) => occurs(meta, ty),
+        Term::Constr(_, ref args) => args.iter().any(|&(_, ref arg)| occurs(meta, arg)),
+        Term::Pi(_, _, ref scope) | Term::Lam(_, _, ref scope) | Term::Let(ref scope) => {
+            let (_, Embed(ref ann)) = scope.unsafe_pattern;
+            occurs(meta, ann) || occurs(meta, &scope.unsafe_body)
+        },
+        Term::App(ref head, _, ref arg) => occurs(meta, head) || occurs(meta, arg),
+        Term::If(ref term1, ref term2, ref term3)
+        | Term::Eq(ref term1, ref term2, ref term3)
+        | Term::J(ref term1, ref term2, ref term3) => {
+            occurs(meta, term1) || occurs(meta, term2) || occurs(meta, term3)
+        },
+        Term::RecordType(ref scope) | Term::Record(ref scope) => scope
+            .unsafe_pattern
+            .unsafe_patterns
+            .iter()
+            .any(|&(_, _, Embed(ref term))| occurs(meta, term)),
+        Term::Case(ref head, ref clauses) => {
+            occurs(meta, head)
+                || clauses.iter().any(|clause| {
+                    occurs_pattern(meta, &clause.unsafe_pattern)
+                        || occurs(meta, &clause.unsafe_body)
+                })
+        },
+        Term::Array(ref elems) => elems.iter().any(|elem| occurs(meta, elem)),
+    }
+}
+
+/// Attempt to solve the equation `?meta spine = value`
+///
+/// This only succeeds if the spine is made up of distinct variables, in which
+/// case the solution is a function that abstracts over them.
+fn solve<Env>(
+    env: &Env,
+    meta: MetaVar,
+    spine: &Spine,
+    value: &RcValue,
+) -> Result<bool, InternalError>
+where
+    Env: MetaEnv + DefinitionEnv,
+{
+    // The solution is built as a term, with the locals of any closures in the
+    // value substituted into their bodies
+    let value = RcTerm::from(&*zonk_value(env, value)?);
+
+    if occurs(meta, &value) {
+        return Ok(false);
+    }
+
+    // Ensure that the spine is a pattern, recording the parameters of the
+    // solution as we go
+    let mut params = Vec::<(Plicity, FreeVar<String>, RcValue)>::with_capacity(spine.len());
+    for &(plicity, ref arg) in spine {
+        match arg.free_var_app() {
+            Some((free_var, LevelShift(0), arg_spine)) if arg_spine.is_empty() => {
+                if params.iter().any(|&(_, ref param, _)| param == free_var) {
+                    return Ok(false);
+                }
+                match env.get_declaration(free_var) {
+                    Some(ann) => params.push((plicity, free_var.clone(), ann.clone())),
+                    None => return Ok(false),
+                }
+            },
+            _ => return Ok(false),
+        }
+    }
+
+    // Ensure that the solution only refers to the parameters, or to variables
+    // that were in scope when the metavariable was inserted
+    let entry = env.get_meta(meta);
+    if value.free_vars().iter().any(|free_var| {
+        !entry.scope.contains_key(free_var)
+            && !params.iter().any(|&(_, ref param, _)| param == free_var)
+    }) {
+        return Ok(false);
+    }
+
+    let solution = params
+        .into_iter()
+        .rev()
+        .fold(value, |body, (plicity, free_var, ann)| {
+            let param = (Binder(free_var), Embed(RcTerm::from(&*ann)));
+            RcTerm::from(Term::Lam(plicity, Multiplicity::Many, Scope::new(param, body)))
+        });
+    let solution = eval_term(env, &solution)?;
+
+    env.solve_meta(meta, solution);
+
+    Ok(true)
+}
+
+/// Returns `true` if the level metavariable occurs somewhere in the level
+fn occurs_level(meta: LevelMeta, level: &Level) -> bool {
+    level
+        .vars()
+        .iter()
+        .any(|&(ref var, _)| *var == LevelVar::Meta(meta))
+}
+
+/// Attempt to solve the equation `?meta + offset = level`
+fn solve_level<Env>(env: &Env, meta: LevelMeta, offset: u32, level: &Level) -> bool
+where
+    Env: MetaEnv,
+{
+    match level.checked_sub(offset) {
+        Some(ref solution) if !occurs_level(meta, solution) => {
+            env.solve_level_meta(meta, solution.clone());
+            true
+        },
+        Some(_) | None => false,
+    }
+}
+
+/// Returns the level metavariable and its offset if the level is an unsolved
+/// level metavariable
+fn unsolved_level_meta(level: &Level) -> Option<(LevelMeta, u32)> {
+    match level.as_var() {
+        Some((&LevelVar::Meta(meta), offset)) => Some((meta, offset)),
+        Some((&LevelVar::Param(_), _)) | None => None,
+    }
+}
+
+/// Unify two universe levels, solving level metavariables along the way
+///
+/// Returns `true` if the levels could be made equal, which is always the case
+/// when we have `Type : Type`.
+pub fn unify_levels<Env>(env: &Env, level1: &Level, level2: &Level) -> bool
+where
+    Env: MetaEnv,
+{
+    if env.type_in_type() {
+        return true;
+    }
+
+    let level1 = zonk_level(env, level1);
+    let level2 = zonk_level(env, level2);
+
+    if level1 == level2 {
+        return true;
+    }
+
+    match (unsolved_level_meta(&level1), unsolved_level_meta(&level2)) {
+        (Some((meta, offset)), _) if solve_level(env, meta, offset, &level2) => true,
+        (_, Some((meta, offset))) if solve_level(env, meta, offset, &level1) => true,
+        (_, _) if level1.has_metas() || level2.has_metas() => {
+            env.add_level_constraint(level1.clone(), level2.clone());
+            env.add_level_constraint(level2, level1);
+            true
+        },
+        (_, _) => false,
+    }
+}
+
+/// Check that `level1` is less than or equal to `level2`
+///
+/// If this depends on level metavariables that have yet to be solved, the
+/// check is deferred as a constraint to be solved later on, using
+/// `solve_level_constraints`. We still fail early if the level on the left is
+/// too large for the right even when its metavariables are as small as can be.
+/// All levels are collapsed into one when we have `Type : Type`.
+pub fn level_le<Env>(env: &Env, level1: &Level, level2: &Level) -> bool
+where
+    Env: MetaEnv,
+{
+    if env.type_in_type() {
+        return true;
+    }
+
+    let level1 = zonk_level(env, level1);
+    let level2 = zonk_level(env, level2);
+
+    let lowest_level1 = level1.substs(|var| match *var {
+        LevelVar::Meta(_) => Some(Level::from(0)),
+        LevelVar::Param(_) => None,
+    });
+
+    if level1.is_le(&level2) {
+        true
+    } else if level2.has_metas() || (level1.has_metas() && lowest_level1.is_le(&level2)) {
+        env.add_level_constraint(level1, level2);
+        true
+    } else {
+        false
+    }
+}
+
+/// Solve the level constraints that have been deferred so far, choosing the
+/// smallest levels for the level metavariables that satisfy them
+///
+/// We start with every level metavariable at zero, and raise them until all
+/// of the constraints are satisfied. If a constraint can't be satisfied by
+/// raising a metavariable, or if the levels keep on growing, the offending
+/// constraint is returned as an error.
+pub fn solve_level_constraints<Env>(env: &Env) -> Result<(), (Level, Level)>
+where
+    Env: MetaEnv,
+{
+    let constraints = env
+        .take_level_constraints()
+        .into_iter()
+        .map(|(lower, upper)| (zonk_level(env, &lower), zonk_level(env, &upper)))
+        .collect::<Vec<_>>();
+
+    let mut solutions = Vec::<(LevelMeta, Level)>::new();
+    for &(ref lower, ref upper) in &constraints {
+        for &(ref var, _) in lower.vars().iter().chain(upper.vars()) {
+            if let LevelVar::Meta(meta) = *var {
+                if solutions.iter().all(|&(other, _)| other != meta) {
+                    solutions.push((meta, Level::from(0)));
+                }
+            }
+        }
+    }
+
+    let assign = |solutions: &[(LevelMeta, Level)], level: &Level| {
+        level.substs(|var| match *var {
+            LevelVar::Meta(meta) => solutions
+                .iter()
+                .find(|&&(other, _)| other == meta)
+                .map(|&(_, ref solution)| solution.clone()),
+            LevelVar::Param(_) => None,
+        })
+    };
+
+    // Without any cycles, each meta needs to be raised at most once for
+    // every other meta that it depends on
+    for _ in 0..=solutions.len() {
+        let mut changed = false;
+        for &(ref lower, ref upper) in &constraints {
+            let lower = assign(&solutions, lower);
+            if lower.is_le(&assign(&solutions, upper)) {
+                continue;
+            }
+
+            let (meta, offset) = match upper.vars().iter().find_map(|&(ref var, offset)| match *var {
+                LevelVar::Meta(meta) => Some((meta, offset)),
+                LevelVar::Param(_) => None,
+            }) {
+                Some(meta_offset) => meta_offset,
+                None => return Err((lower, assign(&solutions, upper))),
+            };
+
+            let solution = solutions
+                .iter_mut()
+                .find(|&&mut (other, _)| other == meta)
+                .map(|&mut (_, ref mut solution)| solution)
+                .unwrap();
+            *solution = solution.max(&lower.saturating_sub(offset));
+            changed = true;
+        }
+
+        if !changed {
+            for (meta, solution) in solutions {
+                env.solve_level_meta(meta, solution);
+            }
+            return Ok(());
+        }
+    }
+
+    let (lower, upper) = constraints
+        .iter()
+        .map(|&(ref lower, ref upper)| (assign(&solutions, lower), assign(&solutions, upper)))
+        .find(|&(ref lower, ref upper)| !lower.is_le(upper))
+        .unwrap();
+
+    Err((lower, upper))
+}
+
+/// Unify two values, solving metavariables along the way
+///
+/// Returns `true` if the values could be made equal.
+pub fn unify<Env>(env: &Env, value1: &RcValue, value2: &RcValue) -> Result<bool, InternalError>
+where
+    Env: MetaEnv + DefinitionEnv,
+{
+    let value1 = force(env, value1)?;
+    let value2 = force(env, value2)?;
+
+    match (
+        unsolved_meta_app(env, &value1),
+        unsolved_meta_app(env, &value2),
+    ) {
+        // The same metavariable is on both sides, so compare the spines
+        (Some((meta1, _)), Some((meta2, _))) if meta1 == meta2 => {},
+        (Some((meta1, spine1)), Some((meta2, spine2))) => {
+            return Ok(solve(env, meta1, spine1, &value2)? || solve(env, meta2, spine2, &value1)?);
+        },
+        (Some((meta, spine)), None) => return solve(env, meta, spine, &value2),
+        (None, Some((meta, spine))) => return solve(env, meta, spine, &value1),
+        (None, None) => {},
+    }
+
+    match (&*value1.inner, &*value2.inner) {
+        // Poisoned values have already been reported as errors
+        (&Value::Error, _) | (_, &Value::Error) => Ok(true),
+
+        (&Value::Universe(ref level1), &Value::Universe(ref level2)) => {
+            Ok(unify_levels(env, level1, level2))
+        },
+        (&Value::Literal(ref lit1), &Value::Literal(ref lit2)) => Ok(lit1 == lit2),
+
+        (
+            &Value::Pi(plicity1, multiplicity1, ref ann1, ref closure1),
+            &Value::Pi(plicity2, multiplicity2, ref ann2, ref closure2),
+        )
+        | (
+            &Value::Lam(plicity1, multiplicity1, ref ann1, ref closure1),
+            &Value::Lam(plicity2, multiplicity2, ref ann2, ref closure2),
+        ) => {
+            if plicity1 != plicity2 || multiplicity1 != multiplicity2 {
+                return Ok(false);
+            }
+
+            Ok(unify(env, ann1, ann2)? && {
+                let (Binder(free_var), body1) = open_closure(env, closure1)?;
+                let var = RcValue::from(Value::var(Var::Free(free_var.clone()), 0));
+                let body2 = apply_closure(env, closure2, var)?;
+
+                let mut env = env.clone();
+                env.insert_declaration(free_var, ann1.clone());
+                unify(&env, &body1, &body2)?
+            })
+        },
+
+        (&Value::RecordType(ref closure1), &Value::RecordType(ref closure2)) => {
+            if closure1.term.unsafe_pattern.unsafe_patterns.len()
+                != closure2.term.unsafe_pattern.unsafe_patterns.len()
+            {
+                return Ok(false);
+            }
+
+            let fields1 = &closure1.term.unsafe_pattern.unsafe_patterns;
+            let fields2 = &closure2.term.unsafe_pattern.unsafe_patterns;
+
+            // The fields of record types can refer to the earlier fields, so we
+            // need to declare them as we go
+            let mut env = env.clone();
+            let mut values = Vec::with_capacity(fields1.len());
+            for (field1, field2) in <_>::zip(fields1.iter(), fields2) {
+                let (ref label1, Binder(ref free_var1), Embed(ref ann1)) = *field1;
+                let (ref label2, _, Embed(ref ann2)) = *field2;
+
+                let ann1 = eval_nest_term(&env, &closure1.locals, &values, ann1)?;
+                let ann2 = eval_nest_term(&env, &closure2.locals, &values, ann2)?;
+                if label1 != label2 || !unify(&env, &ann1, &ann2)? {
+                    return Ok(false);
+                }
+
+                let free_var = FreeVar::fresh(free_var1.pretty_name.clone());
+                values.push(RcValue::from(Value::var(Var::Free(free_var.clone()), 0)));
+                env.insert_declaration(free_var, ann1);
+            }
+
+            Ok(true)
+        },
+
+        (&Value::Record(ref fields1), &Value::Record(ref fields2)) => {
+            if fields1.len() != fields2.len() {
+                return Ok(false);
+            }
+
+            for (&(ref label1, ref expr1), &(ref label2, ref expr2)) in <_>::zip(fields1.iter(), fields2) {
+                if label1 != label2 || !unify(env, expr1, expr2)? {
+                    return Ok(false);
+                }
+            }
+
+            Ok(true)
+        },
+
+        (&Value::Array(ref elems1), &Value::Array(ref elems2)) => {
+            if elems1.len() != elems2.len() {
+                return Ok(false);
+            }
+
+            for (elem1, elem2) in <_>::zip(elems1.iter(), elems2.iter()) {
+                if !unify(env, elem1, elem2)? {
+                    return Ok(false);
+                }
+            }
+
+            Ok(true)
+        },
+
+        (&Value::Eq(ref ty1, ref lhs1, ref rhs1), &Value::Eq(ref ty2, ref lhs2, ref rhs2)) => {
+            Ok(unify(env, ty1, ty2)?
+                && unify_at(env, ty1, lhs1, lhs2)?
+                && unify_at(env, ty1, rhs1, rhs2)?)
+        },
+        (&Value::Refl, &Value::Refl) => Ok(true),
+
+        (&Value::Constr(ref var1, ref args1), &Value::Constr(ref var2, ref args2)) => {
+            if var1 != var2 || args1.len() != args2.len() {
+                return Ok(false);
+            }
+
+            for (&(plicity1, ref arg1), &(plicity2, ref arg2)) in <_>::zip(args1.iter(), args2) {
+                if plicity1 != plicity2 || !unify(env, arg1, arg2)? {
+                    return Ok(false);
+                }
+            }
+
+            Ok(true)
+        },
+
+        (&Value::Neutral(ref neutral1, ref spine1), &Value::Neutral(ref neutral2, ref spine2)) => {
+            if spine1.len() != spine2.len() || !unify_neutral(env, neutral1, neutral2)? {
+                return Ok(false);
+            }
+
+            // Compare the arguments at the parameter types of the head, when
+            // we know them
+            let mut head_ty = neutral_ty(env, neutral1);
+            for (&(plicity1, ref arg1), &(plicity2, ref arg2)) in <_>::zip(spine1.iter(), spine2) {
+                if plicity1 != plicity2 {
+                    return Ok(false);
+                }
+                head_ty = match head_ty.as_ref().map(|ty| force(env, ty)).transpose()? {
+                    Some(ref ty) => match **ty {
+                        Value::Pi(_, _, ref ann, ref closure) => {
+                            if !unify_at(env, ann, arg1, arg2)? {
+                                return Ok(false);
+                            }
+                            Some(apply_closure(env, closure, arg1.clone())?)
+                        },
+                        _ => None,
+                    },
+                    None => None,
+                };
+                if head_ty.is_none() && !unify(env, arg1, arg2)? {
+                    return Ok(false);
+                }
+            }
+
+            Ok(true)
+        },
+
+        // Both values have the same type, so if one of them is a lambda or a
+        // record then a neutral value on the other side must be a function
+        // or a record too. We compare them by eta-expanding the neutral
+        // value, so that `f` is equal to `\x => f x`, and `r` is equal to
+        // `record { x = r.x }`.
+        (&Value::Lam(plicity, _, ref ann, ref closure), &Value::Neutral(_, _)) => {
+            unify_lam_eta(env, plicity, ann, closure, &value2)
+        },
+        (&Value::Neutral(_, _), &Value::Lam(plicity, _, ref ann, ref closure)) => {
+            unify_lam_eta(env, plicity, ann, closure, &value1)
+        },
+        (&Value::Record(ref fields), &Value::Neutral(_, _)) => {
+            unify_record_eta(env, fields, &value2)
+        },
+        (&Value::Neutral(_, _), &Value::Record(ref fields)) => {
+            unify_record_eta(env, fields, &value1)
+        },
+
+        (_, _) => Ok(false),
+    }
+}
+
+/// Unify two values of the given type, solving metavariables along the way
+///
+/// Knowing the type lets us use eta rules that `unify` can't: two functions
+/// are equal if they are equal when applied to a fresh variable, even if
+/// neither of them is a lambda, and two records are equal if their fields
+/// are equal, so every record of a type with no fields is equal.
+pub fn unify_at<Env>(
+    env: &Env,
+    ty: &RcValue,
+    value1: &RcValue,
+    value2: &RcValue,
+) -> Result<bool, InternalError>
+where
+    Env: MetaEnv + DefinitionEnv,
+{
+    let ty = force(env, ty)?;
+    match *ty {
+        Value::Pi(_, _, _, _) | Value::RecordType(_) => {},
+        _ => return unify(env, value1, value2),
+    }
+
+    let value1 = force(env, value1)?;
+    let value2 = force(env, value2)?;
+
+    // Metavariables are solved more directly without eta-expansion, and
+    // poisoned values have already been reported as errors
+    for value in &[&value1, &value2] {
+        match ***value {
+            Value::Error => return unify(env, &value1, &value2),
+            _ if unsolved_meta_app(env, value).is_some() => return unify(env, &value1, &value2),
+            _ => {},
+        }
+    }
+
+    match *ty {
+        Value::Pi(plicity, _, ref ann, ref closure) => {
+            let (Binder(free_var), body_ty) = open_closure(env, closure)?;
+            let var = RcValue::from(Value::var(Var::Free(free_var.clone()), 0));
+            let body1 = apply(env, value1.clone(), plicity, var.clone())?;
+            let body2 = apply(env, value2.clone(), plicity, var)?;
+
+            let mut env = env.clone();
+            env.insert_declaration(free_var, ann.clone());
+            unify_at(&env, &body_ty, &body1, &body2)
+        },
+        Value::RecordType(ref closure) => {
+            let fields = &closure.term.unsafe_pattern.unsafe_patterns;
+            let mut values = Vec::with_capacity(fields.len());
+            for &(ref label, _, Embed(ref ann)) in fields {
+                let ann = eval_nest_term(env, &closure.locals, &values, ann)?;
+                let expr1 = project(&value1, label)?;
+                let expr2 = project(&value2, label)?;
+                if !unify_at(env, &ann, &expr1, &expr2)? {
+                    return Ok(false);
+                }
+                values.push(expr1);
+            }
+
+            Ok(true)
+        },
+        _ => unify(env, &value1, &value2),
+    }
+}
+
+/// The type of a neutral term, if it can be found without inferring it
+fn neutral_ty<Env>(env: &Env, neutral: &Neutral) -> Option<RcValue>
+where
+    Env: MetaEnv,
+{
+    match *neutral {
+        Neutral::Head(Head::Var(Var::Free(ref free_var), _)) => {
+            env.get_declaration(free_var).cloned()
+        },
+        Neutral::Head(Head::Extern(_, ref ty)) => Some(ty.clone()),
+        Neutral::Head(Head::Meta(meta)) => Some(env.get_meta(meta).ty),
+        Neutral::Head(Head::Var(Var::Bound(_), _))
+        | Neutral::If(_, _, _)
+        | Neutral::J(_, _, _)
+        | Neutral::Proj(_, _)
+        | Neutral::Case(_, _) => None,
+    }
+}
+
+/// Unify a lambda with a neutral value, by applying the neutral value to the
+/// parameter of the lambda
+fn unify_lam_eta<Env>(
+    env: &Env,
+    plicity: Plicity,
+    ann: &RcValue,
+    closure: &Closure<Scope<Binder<String>, RcTerm>>,
+    value: &RcValue,
+) -> Result<bool, InternalError>
+where
+    Env: MetaEnv + DefinitionEnv,
+{
+    let (Binder(free_var), body) = open_closure(env, closure)?;
+    let var = RcValue::from(Value::var(Var::Free(free_var.clone()), 0));
+    let value_body = apply(env, value.clone(), plicity, var)?;
+
+    let mut env = env.clone();
+    env.insert_declaration(free_var, ann.clone());
+    unify(&env, &body, &value_body)
+}
+
+/// Unify a record with a neutral value, by projecting each of the fields of
+/// the record from the neutral value
+fn unify_record_eta<Env>(
+    env: &Env,
+    fields: &[(Label, RcValue)],
+    value: &RcValue,
+) -> Result<bool, InternalError>
+where
+    Env: MetaEnv + DefinitionEnv,
+{
+    for &(ref label, ref expr) in fields {
+        if !unify(env, expr, &project(value, label)?)? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Unify two neutral terms, solving metavariables along the way
+fn unify_neutral<Env>(
+    env: &Env,
+    neutral1: &RcNeutral,
+    neutral2: &RcNeutral,
+) -> Result<bool, InternalError>
+where
+    Env: MetaEnv + DefinitionEnv,
+{
+    match (&*neutral1.inner, &*neutral2.inner) {
+        (
+            &Neutral::Head(Head::Var(ref var1, shift1)),
+            &Neutral::Head(Head::Var(ref var2, shift2)),
+        ) => Ok(var1 == var2 && shift1 == shift2),
+        (
+            &Neutral::Head(Head::Extern(ref name1, _)),
+            &Neutral::Head(Head::Extern(ref name2, _)),
+        ) => Ok(name1 == name2),
+        (&Neutral::Head(Head::Meta(meta1)), &Neutral::Head(Head::Meta(meta2))) => {
+            Ok(meta1 == meta2)
+        },
+        (
+            &Neutral::If(ref cond1, ref if_true1, ref if_false1),
+            &Neutral::If(ref cond2, ref if_true2, ref if_false2),
+        ) => Ok(unify_neutral(env, cond1, cond2)?
+            && unify(env, if_true1, if_true2)?
+            && unify(env, if_false1, if_false2)?),
+        (
+            &Neutral::J(ref motive1, ref base1, ref proof1),
+            &Neutral::J(ref motive2, ref base2, ref proof2),
+        ) => Ok(unify_neutral(env, proof1, proof2)?
+            && unify(env, motive1, motive2)?
+            && unify(env, base1, base2)?),
+        (&Neutral::Proj(ref expr1, ref label1), &Neutral::Proj(ref expr2, ref label2)) => {
+            Ok(label1 == label2 && unify_neutral(env, expr1, expr2)?)
+        },
+        (&Neutral::Case(ref head1, ref closure1), &Neutral::Case(ref head2, ref closure2)) => {
+            // Compare the clauses with the locals of the closures substituted
+            // into them
+            Ok(unify(env, head1, head2)?
+                && closure1.instantiate().term_eq(&closure2.instantiate()))
+        },
+        (_, _) => Ok(false),
+    }
+}
@@ -4,6 +4,7 @@ use codespan::{ByteIndex, ByteOffset, ByteSpan};
 use std::fmt;
 
 use syntax::pretty::{self, ToDoc};
+use syntax::{Multiplicity, Plicity};
 
 /// Commands entered in the REPL
 #[derive(Debug, Clone)]
@@ -68,11 +69,12 @@ pub enum Module {
     /// A module definition:
     ///
     /// ```text
+    /// ||! some documentation
     /// module my-module;
     ///
     /// <items>
     /// ```
-    Valid { items: Vec<Item> },
+    Valid { doc: Vec<String>, items: Vec<Item> },
     /// Modules commands that could not be parsed correctly
     ///
     /// This is used for error recovery
@@ -86,19 +88,28 @@ impl fmt::Display for Module {
 }
 
 /// A group of lambda parameters that share an annotation
-pub type LamParamGroup = (Vec<(ByteIndex, String)>, Option<Box<Term>>);
+///
+/// Lambda parameters without a multiplicity take it from the type that they
+/// are checked against.
+pub type LamParamGroup = (
+    Plicity,
+    Option<Multiplicity>,
+    Vec<(ByteIndex, String)>,
+    Option<Box<Term>>,
+);
 
 /// The parameters to a lambda abstraction
 pub type LamParams = Vec<LamParamGroup>;
 
 /// A group of parameters to a dependent function that share an annotation
-pub type PiParamGroup = (Vec<(ByteIndex, String)>, Term);
+pub type PiParamGroup = (Plicity, Multiplicity, Vec<(ByteIndex, String)>, Term);
 
 /// The parameters to a dependent function type
 pub type PiParams = Vec<PiParamGroup>;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct RecordTypeField {
+    pub doc: Vec<String>,
     pub label: (ByteIndex, String),
     pub binder: Option<(ByteIndex, String)>,
     pub ann: Term,
@@ -112,15 +123,34 @@ pub struct RecordField {
     pub term: Term,
 }
 
+/// A constructor of a data type
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataConstructor {
+    pub doc: Vec<String>,
+    pub name: (ByteIndex, String),
+    pub ann: Term,
+}
+
 /// Top-level items within a module
+///
+/// Items can be preceded by doc comments, which are stored one line at a time:
+///
+/// ```text
+/// ||| some documentation
+/// ```
 #[derive(Debug, Clone, PartialEq)]
 pub enum Item {
     /// Declares the type associated with a name, prior to its definition
     ///
     /// ```text
     /// foo : some-type
+    /// partial foo : some-type
+    /// private foo : some-type
     /// ```
     Declaration {
+        doc: Vec<String>,
+        private: Option<ByteSpan>,
+        partial: Option<ByteSpan>,
         name: (ByteIndex, String),
         ann: Term,
     },
@@ -129,13 +159,47 @@ pub enum Item {
     /// ```text
     /// foo = some-body
     /// foo x (y : some-type) = some-body
+    /// private foo = some-body
     /// ```
     Definition {
+        doc: Vec<String>,
+        private: Option<ByteSpan>,
         name: (ByteIndex, String),
         params: LamParams,
         return_ann: Option<Box<Term>>,
         body: Term,
     },
+    /// Declares a data type, along with its constructors
+    ///
+    /// ```text
+    /// data List (a : Type) : Type where {
+    ///     nil : List a;
+    ///     cons : a -> List a -> List a;
+    /// }
+    /// ```
+    Data {
+        doc: Vec<String>,
+        private: Option<ByteSpan>,
+        span: ByteSpan,
+        name: (ByteIndex, String),
+        params: PiParams,
+        ann: Option<Box<Term>>,
+        constructors: Vec<DataConstructor>,
+    },
+    /// Imports a module from another file, binding it to the last name in
+    /// the module path
+    ///
+    /// ```text
+    /// import foo;
+    /// import data.nat;
+    /// private import data.nat;
+    /// ```
+    Import {
+        doc: Vec<String>,
+        private: Option<ByteSpan>,
+        span: ByteSpan,
+        path: Vec<(ByteIndex, String)>,
+    },
     /// Items that could not be correctly parsed
     ///
     /// This is used for error recovery
@@ -146,6 +210,21 @@ impl Item {
     /// Return the span of source code that this declaration originated from
     pub fn span(&self) -> ByteSpan {
         match *self {
+            Item::Declaration {
+                private: Some(private_span),
+                ann: ref term,
+                ..
+            }
+            | Item::Definition {
+                private: Some(private_span),
+                body: ref term,
+                ..
+            } => private_span.to(term.span()),
+            Item::Declaration {
+                partial: Some(partial_span),
+                ann: ref term,
+                ..
+            } => partial_span.to(term.span()),
             Item::Definition {
                 name: (start, _),
                 body: ref term,
@@ -154,8 +233,19 @@ impl Item {
             | Item::Declaration {
                 name: (start, _),
                 ann: ref term,
+                ..
             } => ByteSpan::new(start, term.span().end()),
-            Item::Error(span) => span,
+            Item::Data {
+                private: Some(private_span),
+                span,
+                ..
+            }
+            | Item::Import {
+                private: Some(private_span),
+                span,
+                ..
+            } => private_span.to(span),
+            Item::Data { span, .. } | Item::Import { span, .. } | Item::Error(span) => span,
         }
     }
 }
@@ -191,6 +281,33 @@ impl Literal {
     }
 }
 
+/// Arguments supplied to a function application
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    /// Explicit arguments
+    ///
+    /// ```text
+    /// e
+    /// ```
+    Explicit(Term),
+    /// Arguments supplied to implicit parameters
+    ///
+    /// ```text
+    /// {e}
+    /// ```
+    Implicit(ByteSpan, Term),
+}
+
+impl Arg {
+    /// Return the span of source code that this argument originated from
+    pub fn span(&self) -> ByteSpan {
+        match *self {
+            Arg::Explicit(ref term) => term.span(),
+            Arg::Implicit(span, _) => span,
+        }
+    }
+}
+
 /// Patterns
 #[derive(Debug, Clone, PartialEq)]
 pub enum Pattern {
@@ -217,6 +334,12 @@ pub enum Pattern {
     /// false
     /// ```
     Name(ByteSpan, String, Option<u32>),
+    /// Constructor patterns
+    ///
+    /// ```text
+    /// cons x xs
+    /// ```
+    App(Box<Pattern>, Vec<Pattern>),
     /// Terms that could not be correctly parsed
     ///
     /// This is used for error recovery
@@ -230,10 +353,71 @@ impl Pattern {
             Pattern::Parens(span, _) | Pattern::Name(span, _, _) | Pattern::Error(span) => span,
             Pattern::Ann(ref pattern, ref ty) => pattern.span().to(ty.span()),
             Pattern::Literal(ref literal) => literal.span(),
+            Pattern::App(ref head, ref args) => head.span().to(args.last().unwrap().span()),
         }
     }
 }
 
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_doc().group().render_fmt(pretty::FALLBACK_WIDTH, f)
+    }
+}
+
+/// Universe levels
+#[derive(Debug, Clone, PartialEq)]
+pub enum Level {
+    /// A level that is surrounded with parentheses
+    ///
+    /// ```text
+    /// (l)
+    /// ```
+    Parens(ByteSpan, Box<Level>),
+    /// Constant levels
+    ///
+    /// ```text
+    /// 0, 1, 2, ...
+    /// ```
+    Const(ByteSpan, u32),
+    /// Level parameters
+    ///
+    /// ```text
+    /// l
+    /// ```
+    Var(ByteSpan, String),
+    /// Level successors
+    ///
+    /// ```text
+    /// l + n
+    /// ```
+    Succ(ByteSpan, Box<Level>, u32),
+    /// The maximum of some levels
+    ///
+    /// ```text
+    /// max l1 l2 ... ln
+    /// ```
+    Max(ByteSpan, Vec<Level>),
+}
+
+impl Level {
+    /// Return the span of source code that this level originated from
+    pub fn span(&self) -> ByteSpan {
+        match *self {
+            Level::Parens(span, _)
+            | Level::Const(span, _)
+            | Level::Var(span, _)
+            | Level::Succ(span, _, _)
+            | Level::Max(span, _) => span,
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_doc().group().render_fmt(pretty::FALLBACK_WIDTH, f)
+    }
+}
+
 /// Terms
 #[derive(Debug, Clone, PartialEq)]
 pub enum Term {
@@ -253,8 +437,9 @@ pub enum Term {
     ///
     /// ```text
     /// Type
+    /// Type^l
     /// ```
-    Universe(ByteSpan, Option<u32>),
+    Universe(ByteSpan, Option<Level>),
     /// Literals
     Literal(Literal),
     /// Array literals
@@ -262,7 +447,7 @@ pub enum Term {
     /// Holes
     ///
     /// ```text
-    /// _
+    /// ?
     /// ```
     Hole(ByteSpan),
     /// Names
@@ -286,6 +471,8 @@ pub enum Term {
     /// \x : t1 => t2
     /// \(x : t1) y (z : t2) => t3
     /// \(x y : t1) => t3
+    /// \{x} (y : x) => t2
+    /// \(0 x : t1) (1 y : x) => t2
     /// ```
     Lam(ByteIndex, LamParams, Box<Term>),
     /// Dependent function type
@@ -293,6 +480,8 @@ pub enum Term {
     /// ```text
     /// (x : t1) -> t2
     /// (x y : t1) -> t2
+    /// {x : t1} -> t2
+    /// (0 x : t1) -> (1 y : x) -> t2
     /// ```
     Pi(ByteIndex, PiParams, Box<Term>),
     /// Non-Dependent function type
@@ -305,8 +494,9 @@ pub enum Term {
     ///
     /// ```text
     /// e1 e2
+    /// e1 {e2}
     /// ```
-    App(Box<Term>, Vec<Term>),
+    App(Box<Term>, Vec<Arg>),
     /// Let binding
     ///
     /// ```text
@@ -328,6 +518,24 @@ pub enum Term {
     /// case t1 of { pat => t2; .. }
     /// ```
     Case(ByteSpan, Box<Term>, Vec<(Pattern, Term)>),
+    /// Propositional equality type
+    ///
+    /// ```text
+    /// t1 = t2
+    /// ```
+    Eq(Box<Term>, Box<Term>),
+    /// Proof of reflexivity
+    ///
+    /// ```text
+    /// refl
+    /// ```
+    Refl(ByteSpan),
+    /// Dependent elimination of equality proofs
+    ///
+    /// ```text
+    /// J t1 t2 t3
+    /// ```
+    J(ByteIndex, Box<Term>, Box<Term>, Box<Term>),
     /// Record type
     ///
     /// ```text
@@ -366,14 +574,17 @@ impl Term {
             | Term::RecordType(span, _)
             | Term::Record(span, _)
             | Term::Array(span, _)
+            | Term::Refl(span)
             | Term::Error(span) => span,
             Term::Literal(ref literal) => literal.span(),
             Term::Pi(start, _, ref body)
             | Term::Lam(start, _, ref body)
             | Term::Let(start, _, ref body)
-            | Term::If(start, _, _, ref body) => ByteSpan::new(start, body.span().end()),
+            | Term::If(start, _, _, ref body)
+            | Term::J(start, _, _, ref body) => ByteSpan::new(start, body.span().end()),
             Term::Ann(ref term, ref ty) => term.span().to(ty.span()),
             Term::Arrow(ref ann, ref body) => ann.span().to(body.span()),
+            Term::Eq(ref lhs, ref rhs) => lhs.span().to(rhs.span()),
             Term::App(ref head, ref arg) => head.span().to(arg.last().unwrap().span()),
             Term::Proj(ref term, label_start, ref label) => term
                 .span()
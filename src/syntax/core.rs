@@ -1,15 +1,19 @@
 //! The core syntax of the language
 
-use moniker::{Binder, Embed, FreeVar, Nest, Scope, Var};
+use moniker::{Binder, BoundVar, Embed, FreeVar, Nest, Scope, ScopeOffset, Var};
+use std::cell::LazyCell;
 use std::fmt;
 use std::ops;
 use std::rc::Rc;
 
+use semantics::InternalError;
 use syntax::pretty::{self, ToDoc};
-use syntax::{Label, Level, LevelShift};
+use syntax::{Label, Level, LevelMeta, LevelShift, LevelVar, Multiplicity, Plicity};
 
 /// A module definition
 pub struct Module {
+    /// The documentation for the module, taken from its module doc comments
+    pub doc: String,
     /// The items contained in the module
     pub items: Vec<Item>,
 }
@@ -19,6 +23,8 @@ pub struct Module {
 pub enum Item {
     /// Declares the type associated with a label, prior to its definition
     Declaration {
+        /// The documentation for this declaration, taken from its doc comments
+        doc: String,
         /// The external name for this declaration, to be used when referring
         /// to this item from other modules
         label: Label,
@@ -27,9 +33,16 @@ pub enum Item {
         binder: Binder<String>,
         /// The type annotation for associated with the label
         term: RcTerm,
+        /// Whether the subsequent definition is allowed to skip termination
+        /// checking
+        partial: bool,
+        /// Whether this declaration is hidden from modules that import it
+        private: bool,
     },
     /// Defines the term that should be associated with a label
     Definition {
+        /// The documentation for this definition, taken from its doc comments
+        doc: String,
         /// The external name for this definition, to be used when referring
         /// to this item from other modules
         label: Label,
@@ -38,9 +51,60 @@ pub enum Item {
         binder: Binder<String>,
         /// The term for associated with the label
         term: RcTerm,
+        /// Whether this definition is hidden from modules that import it
+        private: bool,
+    },
+    /// Declares a data type, along with its constructors
+    Data {
+        /// The documentation for this data type, taken from its doc comments
+        doc: String,
+        /// The external name for this data type, to be used when referring
+        /// to this item from other modules
+        label: Label,
+        /// The internal name for this data type, to be used when binding
+        /// this name to variables
+        binder: Binder<String>,
+        /// The parameters of the data type. These are in scope in the type
+        /// annotation and in the constructors
+        params: Vec<(Binder<String>, RcTerm)>,
+        /// The type of the data type, after the parameters have been applied
+        term: RcTerm,
+        /// The constructors of the data type
+        constructors: Vec<Constructor>,
+        /// Whether this data type is hidden from modules that import it
+        private: bool,
+    },
+    /// An item that failed to type check
+    ///
+    /// The names that the item would have bound are given poisoned types, so
+    /// that later items can still refer to them without reporting further
+    /// errors.
+    Error {
+        /// The documentation for this item, taken from its doc comments
+        doc: String,
+        /// The external name for this item
+        label: Label,
+        /// The internal name for this item, to be used when binding this name
+        /// to variables
+        binder: Binder<String>,
     },
 }
 
+/// A constructor of a data type
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constructor {
+    /// The documentation for this constructor
+    pub doc: String,
+    /// The external name for this constructor
+    pub label: Label,
+    /// The internal name for this constructor, to be used when binding this
+    /// name to variables
+    pub binder: Binder<String>,
+    /// The type of the constructor, which may refer to the parameters of the
+    /// data type
+    pub term: RcTerm,
+}
+
 /// Literals
 ///
 /// We could church encode all the things, but that would be prohibitively expensive!
@@ -77,6 +141,10 @@ pub enum Pattern {
     Var(Embed<Var<String>>, LevelShift),
     /// Literal patterns
     Literal(Literal),
+    /// Constructor patterns
+    Constr(Embed<Var<String>>, Vec<(Plicity, RcPattern)>),
+    /// Patterns that could not be parsed, matching anything
+    Error,
 }
 
 impl fmt::Display for Pattern {
@@ -113,7 +181,22 @@ impl fmt::Display for RcPattern {
     }
 }
 
+/// A metavariable, standing in for a term that is yet to be determined by
+/// unification during elaboration
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, BoundTerm)]
+pub struct MetaVar(pub u32);
+
+impl fmt::Display for MetaVar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "?{}", self.0)
+    }
+}
+
 /// The core term syntax
+///
+/// Binders are still represented with `moniker` scopes. Evaluation looks up
+/// bound variables in `Locals` by their de Bruijn indices, but the type
+/// checker unbinds scopes with fresh names when it goes underneath them.
 #[derive(Debug, Clone, PartialEq, BoundTerm)]
 pub enum Term {
     /// A term annotated with a type
@@ -124,16 +207,32 @@ pub enum Term {
     Literal(Literal),
     /// A variable
     Var(Var<String>, LevelShift),
+    /// A level-polymorphic definition, instantiated at some universe levels
+    Inst(RcTerm, Vec<(String, Level)>),
+    /// A metavariable, inserted during elaboration
+    Meta(MetaVar),
     /// An external definition
     Extern(String, RcTerm),
+    /// A fully applied data constructor
+    ///
+    /// The arguments supplied to the parameters of the data type are not
+    /// included here.
+    Constr(Var<String>, Vec<(Plicity, RcTerm)>),
     /// Dependent function types
-    Pi(Scope<(Binder<String>, Embed<RcTerm>), RcTerm>),
+    Pi(Plicity, Multiplicity, Scope<(Binder<String>, Embed<RcTerm>), RcTerm>),
     /// Lambda abstractions
-    Lam(Scope<(Binder<String>, Embed<RcTerm>), RcTerm>),
+    Lam(Plicity, Multiplicity, Scope<(Binder<String>, Embed<RcTerm>), RcTerm>),
     /// Term application
-    App(RcTerm, RcTerm),
+    App(RcTerm, Plicity, RcTerm),
     /// If expression
     If(RcTerm, RcTerm, RcTerm),
+    /// Identity types, annotated with the type of the two sides
+    Eq(RcTerm, RcTerm, RcTerm),
+    /// The proof that a term is equal to itself
+    Refl,
+    /// The eliminator for identity types, taking a motive, the case for
+    /// `refl`, and the proof of equality that is being eliminated
+    J(RcTerm, RcTerm, RcTerm),
     /// Dependent record types
     RecordType(Scope<Nest<(Label, Binder<String>, Embed<RcTerm>)>, ()>),
     /// Dependent record
@@ -146,6 +245,8 @@ pub enum Term {
     Array(Vec<RcTerm>),
     /// Let bindings
     Let(Scope<(Binder<String>, Embed<RcTerm>), RcTerm>),
+    /// A poisoned term, standing in for something that failed to type check
+    Error,
 }
 
 impl Term {
@@ -176,24 +277,35 @@ impl RcTerm {
             Term::Ann(ref term, ref ty) => {
                 RcTerm::from(Term::Ann(term.substs(mappings), ty.substs(mappings)))
             },
-            Term::Universe(_) | Term::Literal(_) => self.clone(),
+            Term::Universe(_) | Term::Literal(_) | Term::Meta(_) | Term::Refl | Term::Error => {
+                self.clone()
+            },
             Term::Var(ref var, _) => match mappings.iter().find(|&(ref name, _)| var == name) {
                 Some(&(_, ref term)) => term.clone(),
                 None => self.clone(),
             },
+            Term::Inst(ref term, ref levels) => {
+                RcTerm::from(Term::Inst(term.substs(mappings), levels.clone()))
+            },
             Term::Extern(ref name, ref ty) => {
                 RcTerm::from(Term::Extern(name.clone(), ty.substs(mappings)))
             },
-            Term::Pi(ref scope) => {
+            Term::Constr(ref var, ref args) => RcTerm::from(Term::Constr(
+                var.clone(),
+                args.iter()
+                    .map(|&(plicity, ref arg)| (plicity, arg.substs(mappings)))
+                    .collect(),
+            )),
+            Term::Pi(plicity, multiplicity, ref scope) => {
                 let (ref name, Embed(ref ann)) = scope.unsafe_pattern;
-                RcTerm::from(Term::Pi(Scope {
+                RcTerm::from(Term::Pi(plicity, multiplicity, Scope {
                     unsafe_pattern: (name.clone(), Embed(ann.substs(mappings))),
                     unsafe_body: scope.unsafe_body.substs(mappings),
                 }))
             },
-            Term::Lam(ref scope) => {
+            Term::Lam(plicity, multiplicity, ref scope) => {
                 let (ref name, Embed(ref ann)) = scope.unsafe_pattern;
-                RcTerm::from(Term::Lam(Scope {
+                RcTerm::from(Term::Lam(plicity, multiplicity, Scope {
                     unsafe_pattern: (name.clone(), Embed(ann.substs(mappings))),
                     unsafe_body: scope.unsafe_body.substs(mappings),
                 }))
@@ -205,14 +317,26 @@ impl RcTerm {
                     unsafe_body: scope.unsafe_body.substs(mappings),
                 }))
             },
-            Term::App(ref head, ref arg) => {
-                RcTerm::from(Term::App(head.substs(mappings), arg.substs(mappings)))
-            },
+            Term::App(ref head, plicity, ref arg) => RcTerm::from(Term::App(
+                head.substs(mappings),
+                plicity,
+                arg.substs(mappings),
+            )),
             Term::If(ref cond, ref if_true, ref if_false) => RcTerm::from(Term::If(
                 cond.substs(mappings),
                 if_true.substs(mappings),
                 if_false.substs(mappings),
             )),
+            Term::Eq(ref ty, ref lhs, ref rhs) => RcTerm::from(Term::Eq(
+                ty.substs(mappings),
+                lhs.substs(mappings),
+                rhs.substs(mappings),
+            )),
+            Term::J(ref motive, ref base, ref proof) => RcTerm::from(Term::J(
+                motive.substs(mappings),
+                base.substs(mappings),
+                proof.substs(mappings),
+            )),
             Term::RecordType(ref scope) | Term::Record(ref scope)
                 if scope.unsafe_pattern.unsafe_patterns.is_empty() =>
             {
@@ -265,6 +389,200 @@ impl RcTerm {
             )),
         }
     }
+
+    /// Shift the universe levels in the term
+    pub fn shift_universes(&mut self, shift: LevelShift) {
+        // Avoid copying terms that are shared with the environment
+        if shift != LevelShift(0) {
+            self.map_universes(&|level: &mut Level| *level += shift);
+        }
+    }
+
+    /// The universe level parameters that are mentioned in the term, in the
+    /// order that they first appear
+    pub fn level_params(&self) -> Vec<String> {
+        let mut vars = Vec::new();
+        self.collect_level_vars(&mut vars);
+        vars.into_iter()
+            .filter_map(|var| match var {
+                LevelVar::Param(name) => Some(name),
+                LevelVar::Meta(_) => None,
+            }).collect()
+    }
+
+    /// The universe level metavariables that are mentioned in the term, in
+    /// the order that they first appear
+    pub fn level_metas(&self) -> Vec<LevelMeta> {
+        let mut vars = Vec::new();
+        self.collect_level_vars(&mut vars);
+        vars.into_iter()
+            .filter_map(|var| match var {
+                LevelVar::Param(_) => None,
+                LevelVar::Meta(meta) => Some(meta),
+            }).collect()
+    }
+
+    /// Replace the universe level variables in the term using the given
+    /// function, leaving them in place if it returns `None`
+    pub fn substs_levels<F>(&self, mapping: &F) -> RcTerm
+    where
+        F: Fn(&LevelVar) -> Option<Level>,
+    {
+        let mut term = self.clone();
+        term.map_universes(&|level: &mut Level| *level = level.substs(mapping));
+        term
+    }
+
+    fn collect_level_vars(&self, vars: &mut Vec<LevelVar>) {
+        fn collect_level(level: &Level, vars: &mut Vec<LevelVar>) {
+            for &(ref var, _) in level.vars() {
+                if !vars.contains(var) {
+                    vars.push(var.clone());
+                }
+            }
+        }
+
+        fn collect_pattern(pattern: &RcPattern, vars: &mut Vec<LevelVar>) {
+            match *pattern.inner {
+                Pattern::Ann(ref pattern, Embed(ref ty)) => {
+                    collect_pattern(pattern, vars);
+                    ty.collect_level_vars(vars);
+                },
+                Pattern::Constr(_, ref args) => for &(_, ref arg) in args {
+                    collect_pattern(arg, vars);
+                },
+                Pattern::Binder(_) | Pattern::Var(_, _) | Pattern::Literal(_) | Pattern::Error => {},
+            }
+        }
+
+        match *self.inner {
+            Term::Universe(ref level) => collect_level(level, vars),
+            Term::Literal(_) | Term::Var(_, _) | Term::Meta(_) | Term::Refl | Term::Error => {},
+            Term::Inst(ref term, ref levels) => {
+                term.collect_level_vars(vars);
+                for &(_, ref level) in levels {
+                    collect_level(level, vars);
+                }
+            },
+            Term::Ann(ref term, ref ty) => {
+                term.collect_level_vars(vars);
+                ty.collect_level_vars(vars);
+            },
+            Term::Extern(_, ref ty) => ty.collect_level_vars(vars),
+            Term::Constr(_, ref args) => for &(_, ref arg) in args {
+                arg.collect_level_vars(vars);
+            },
+            Term::Pi(_, _, ref scope) | Term::Lam(_, _, ref scope) | Term::Let(ref scope) => {
+                let (_, Embed(ref ann)) = scope.unsafe_pattern;
+                ann.collect_level_vars(vars);
+                scope.unsafe_body.collect_level_vars(vars);
+            },
+            Term::App(ref head, _, ref arg) => {
+                head.collect_level_vars(vars);
+                arg.collect_level_vars(vars);
+            },
+            Term::If(ref cond, ref if_true, ref if_false) => {
+                cond.collect_level_vars(vars);
+                if_true.collect_level_vars(vars);
+                if_false.collect_level_vars(vars);
+            },
+            Term::Eq(ref ty, ref lhs, ref rhs) => {
+                ty.collect_level_vars(vars);
+                lhs.collect_level_vars(vars);
+                rhs.collect_level_vars(vars);
+            },
+            Term::J(ref motive, ref base, ref proof) => {
+                motive.collect_level_vars(vars);
+                base.collect_level_vars(vars);
+                proof.collect_level_vars(vars);
+            },
+            Term::RecordType(ref scope) | Term::Record(ref scope) => {
+                for &(_, _, Embed(ref term)) in &scope.unsafe_pattern.unsafe_patterns {
+                    term.collect_level_vars(vars);
+                }
+            },
+            Term::Proj(ref term, _) => term.collect_level_vars(vars),
+            Term::Case(ref head, ref clauses) => {
+                head.collect_level_vars(vars);
+                for clause in clauses {
+                    collect_pattern(&clause.unsafe_pattern, vars);
+                    clause.unsafe_body.collect_level_vars(vars);
+                }
+            },
+            Term::Array(ref elems) => for elem in elems {
+                elem.collect_level_vars(vars);
+            },
+        }
+    }
+
+    /// Apply a function to the universe levels in the term
+    ///
+    /// This is used when shifting or instantiating the universes of values
+    /// that contain closures.
+    // FIXME: The level shifts of variables are left alone, so definitions that
+    // are referred to from within a closure won't be shifted when they are
+    // eventually unfolded
+    fn map_universes<F: Fn(&mut Level)>(&mut self, f: &F) {
+        match *Rc::make_mut(&mut self.inner) {
+            Term::Universe(ref mut level) => f(level),
+            Term::Literal(_) | Term::Var(_, _) | Term::Meta(_) | Term::Refl | Term::Error => {},
+            Term::Inst(ref mut term, ref mut levels) => {
+                term.map_universes(f);
+                for &mut (_, ref mut level) in levels {
+                    f(level);
+                }
+            },
+            Term::Ann(ref mut term, ref mut ty) => {
+                term.map_universes(f);
+                ty.map_universes(f);
+            },
+            Term::Extern(_, ref mut ty) => ty.map_universes(f),
+            Term::Constr(_, ref mut args) => for &mut (_, ref mut arg) in args {
+                arg.map_universes(f);
+            },
+            Term::Pi(_, _, ref mut scope)
+            | Term::Lam(_, _, ref mut scope)
+            | Term::Let(ref mut scope) => {
+                (scope.unsafe_pattern.1).0.map_universes(f);
+                scope.unsafe_body.map_universes(f);
+            },
+            Term::App(ref mut head, _, ref mut arg) => {
+                head.map_universes(f);
+                arg.map_universes(f);
+            },
+            Term::If(ref mut cond, ref mut if_true, ref mut if_false) => {
+                cond.map_universes(f);
+                if_true.map_universes(f);
+                if_false.map_universes(f);
+            },
+            Term::Eq(ref mut ty, ref mut lhs, ref mut rhs) => {
+                ty.map_universes(f);
+                lhs.map_universes(f);
+                rhs.map_universes(f);
+            },
+            Term::J(ref mut motive, ref mut base, ref mut proof) => {
+                motive.map_universes(f);
+                base.map_universes(f);
+                proof.map_universes(f);
+            },
+            Term::RecordType(ref mut scope) | Term::Record(ref mut scope) => {
+                for &mut (_, _, Embed(ref mut term)) in &mut scope.unsafe_pattern.unsafe_patterns {
+                    term.map_universes(f);
+                }
+            },
+            Term::Proj(ref mut term, _) => term.map_universes(f),
+            Term::Case(ref mut head, ref mut clauses) => {
+                head.map_universes(f);
+                for clause in clauses {
+                    // FIXME: implement shifting for patterns as well!
+                    clause.unsafe_body.map_universes(f);
+                }
+            },
+            Term::Array(ref mut elems) => for elem in elems {
+                elem.map_universes(f);
+            },
+        }
+    }
 }
 
 impl From<Term> for RcTerm {
@@ -289,32 +607,329 @@ impl fmt::Display for RcTerm {
     }
 }
 
+/// The values of the local variables that were in scope when a closure was
+/// created
+///
+/// Each scope that is entered during evaluation pushes a frame holding the
+/// values of the variables bound by its pattern. The bound variables of the
+/// term are then looked up using their de Bruijn indices: the scope offset
+/// selects the frame, and the binder index selects the value within it. This
+/// lets us enter scopes without freshening and substituting into their bodies.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Locals {
+    frames: Option<Rc<Frame>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Frame {
+    values: Vec<RcValue>,
+    rest: Locals,
+}
+
+impl Locals {
+    pub fn new() -> Locals {
+        Locals { frames: None }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_none()
+    }
+
+    /// Enter a scope, binding its variables to the given values
+    pub fn push(&self, values: Vec<RcValue>) -> Locals {
+        Locals {
+            frames: Some(Rc::new(Frame {
+                values,
+                rest: self.clone(),
+            })),
+        }
+    }
+
+    /// Enter the scopes of the patterns of a nest, where `values` are the
+    /// values bound by the patterns that come before the current one
+    ///
+    /// `moniker` numbers the earlier patterns of a nest from the first one
+    /// outwards, so the frame of the first pattern is pushed last.
+    pub fn push_nest(&self, values: &[RcValue]) -> Locals {
+        values
+            .iter()
+            .rev()
+            .fold(self.clone(), |locals, value| locals.push(vec![value.clone()]))
+    }
+
+    /// Look up the value of a bound variable
+    pub fn lookup(&self, var: &BoundVar<String>) -> Option<&RcValue> {
+        let mut frame = self.frames.as_ref()?;
+        for _ in 0..var.scope.0 {
+            frame = frame.rest.frames.as_ref()?;
+        }
+        frame.values.get(var.binder.to_usize())
+    }
+}
+
+/// Terms that can be captured in closures
+pub trait InstantiateLocals {
+    /// Replace the bound variables that refer to the locals with their
+    /// values, read back into terms
+    ///
+    /// `depth` is the number of scopes that have been entered since leaving
+    /// the locals.
+    fn instantiate_locals(&self, depth: u32, locals: &Locals) -> Self;
+}
+
+/// A term that has yet to be evaluated, along with the values of the local
+/// variables that it refers to
+///
+/// Closures are used to delay evaluation underneath binders until a value is
+/// supplied for the bound variable, avoiding the need to substitute into the
+/// body of the term.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Closure<T> {
+    pub locals: Locals,
+    pub term: T,
+}
+
+impl<T> Closure<T> {
+    pub fn new(locals: Locals, term: T) -> Closure<T> {
+        Closure { locals, term }
+    }
+}
+
+impl<T: InstantiateLocals> Closure<T> {
+    /// The term of the closure, with its locals substituted in
+    ///
+    /// The term is otherwise left unevaluated.
+    pub fn instantiate(&self) -> T {
+        self.term.instantiate_locals(0, &self.locals)
+    }
+
+    /// Substitute the locals into the term of the closure, leaving it with no
+    /// locals of its own
+    ///
+    /// This is used before mapping over the universes of the closure, so that
+    /// we only need to visit the locals that are actually referred to.
+    fn take_locals(&mut self) {
+        if !self.locals.is_empty() {
+            self.term = self.instantiate();
+            self.locals = Locals::new();
+        }
+    }
+}
+
+impl InstantiateLocals for RcTerm {
+    fn instantiate_locals(&self, depth: u32, locals: &Locals) -> RcTerm {
+        if locals.is_empty() {
+            return self.clone();
+        }
+
+        let term = match *self.inner {
+            Term::Ann(ref term, ref ty) => Term::Ann(
+                term.instantiate_locals(depth, locals),
+                ty.instantiate_locals(depth, locals),
+            ),
+            Term::Universe(_) | Term::Literal(_) | Term::Meta(_) | Term::Refl | Term::Error => {
+                return self.clone();
+            },
+            Term::Var(Var::Bound(ref var), _) if var.scope.0 >= depth => {
+                let var = BoundVar {
+                    scope: ScopeOffset(var.scope.0 - depth),
+                    binder: var.binder,
+                    pretty_name: None,
+                };
+                return match locals.lookup(&var) {
+                    Some(value) => RcTerm::from(&**value),
+                    None => self.clone(),
+                };
+            },
+            Term::Var(_, _) => return self.clone(),
+            Term::Inst(ref term, ref levels) => {
+                Term::Inst(term.instantiate_locals(depth, locals), levels.clone())
+            },
+            Term::Extern(ref name, ref ty) => {
+                Term::Extern(name.clone(), ty.instantiate_locals(depth, locals))
+            },
+            Term::Constr(ref var, ref args) => Term::Constr(
+                var.clone(),
+                args.iter()
+                    .map(|&(plicity, ref arg)| (plicity, arg.instantiate_locals(depth, locals)))
+                    .collect(),
+            ),
+            Term::Pi(plicity, multiplicity, ref scope) => Term::Pi(
+                plicity,
+                multiplicity,
+                scope.instantiate_locals(depth, locals),
+            ),
+            Term::Lam(plicity, multiplicity, ref scope) => Term::Lam(
+                plicity,
+                multiplicity,
+                scope.instantiate_locals(depth, locals),
+            ),
+            Term::Let(ref scope) => Term::Let(scope.instantiate_locals(depth, locals)),
+            Term::App(ref head, plicity, ref arg) => Term::App(
+                head.instantiate_locals(depth, locals),
+                plicity,
+                arg.instantiate_locals(depth, locals),
+            ),
+            Term::If(ref cond, ref if_true, ref if_false) => Term::If(
+                cond.instantiate_locals(depth, locals),
+                if_true.instantiate_locals(depth, locals),
+                if_false.instantiate_locals(depth, locals),
+            ),
+            Term::Eq(ref ty, ref lhs, ref rhs) => Term::Eq(
+                ty.instantiate_locals(depth, locals),
+                lhs.instantiate_locals(depth, locals),
+                rhs.instantiate_locals(depth, locals),
+            ),
+            Term::J(ref motive, ref base, ref proof) => Term::J(
+                motive.instantiate_locals(depth, locals),
+                base.instantiate_locals(depth, locals),
+                proof.instantiate_locals(depth, locals),
+            ),
+            Term::RecordType(ref scope) => Term::RecordType(scope.instantiate_locals(depth, locals)),
+            Term::Record(ref scope) => Term::Record(scope.instantiate_locals(depth, locals)),
+            Term::Proj(ref expr, ref label) => {
+                Term::Proj(expr.instantiate_locals(depth, locals), label.clone())
+            },
+            Term::Case(ref head, ref clauses) => Term::Case(
+                head.instantiate_locals(depth, locals),
+                clauses.instantiate_locals(depth, locals),
+            ),
+            Term::Array(ref elems) => Term::Array(
+                elems
+                    .iter()
+                    .map(|elem| elem.instantiate_locals(depth, locals))
+                    .collect(),
+            ),
+        };
+
+        RcTerm::from(term)
+    }
+}
+
+impl InstantiateLocals for RcPattern {
+    fn instantiate_locals(&self, depth: u32, locals: &Locals) -> RcPattern {
+        match *self.inner {
+            Pattern::Ann(ref pattern, Embed(ref ty)) => RcPattern::from(Pattern::Ann(
+                pattern.instantiate_locals(depth, locals),
+                Embed(ty.instantiate_locals(depth, locals)),
+            )),
+            Pattern::Constr(ref var, ref args) => RcPattern::from(Pattern::Constr(
+                var.clone(),
+                args.iter()
+                    .map(|&(plicity, ref arg)| (plicity, arg.instantiate_locals(depth, locals)))
+                    .collect(),
+            )),
+            Pattern::Binder(_) | Pattern::Var(_, _) | Pattern::Literal(_) | Pattern::Error => {
+                self.clone()
+            },
+        }
+    }
+}
+
+impl InstantiateLocals for Scope<Binder<String>, RcTerm> {
+    fn instantiate_locals(&self, depth: u32, locals: &Locals) -> Self {
+        Scope {
+            unsafe_pattern: self.unsafe_pattern.clone(),
+            unsafe_body: self.unsafe_body.instantiate_locals(depth + 1, locals),
+        }
+    }
+}
+
+impl InstantiateLocals for Scope<(Binder<String>, Embed<RcTerm>), RcTerm> {
+    fn instantiate_locals(&self, depth: u32, locals: &Locals) -> Self {
+        let (ref binder, Embed(ref ann)) = self.unsafe_pattern;
+        Scope {
+            unsafe_pattern: (binder.clone(), Embed(ann.instantiate_locals(depth, locals))),
+            unsafe_body: self.unsafe_body.instantiate_locals(depth + 1, locals),
+        }
+    }
+}
+
+impl InstantiateLocals for Scope<RcPattern, RcTerm> {
+    fn instantiate_locals(&self, depth: u32, locals: &Locals) -> Scope<RcPattern, RcTerm> {
+        Scope {
+            unsafe_pattern: self.unsafe_pattern.instantiate_locals(depth, locals),
+            unsafe_body: self.unsafe_body.instantiate_locals(depth + 1, locals),
+        }
+    }
+}
+
+impl InstantiateLocals for Scope<Nest<(Label, Binder<String>, Embed<RcTerm>)>, ()> {
+    fn instantiate_locals(&self, depth: u32, locals: &Locals) -> Self {
+        // Each pattern of a nest is one scope deeper than the last
+        let unsafe_patterns = self
+            .unsafe_pattern
+            .unsafe_patterns
+            .iter()
+            .enumerate()
+            .map(|(i, &(ref label, ref binder, Embed(ref term)))| {
+                let term = term.instantiate_locals(depth + i as u32, locals);
+                (label.clone(), binder.clone(), Embed(term))
+            }).collect();
+
+        Scope {
+            unsafe_pattern: Nest { unsafe_patterns },
+            unsafe_body: (),
+        }
+    }
+}
+
+impl<T: InstantiateLocals> InstantiateLocals for Vec<T> {
+    fn instantiate_locals(&self, depth: u32, locals: &Locals) -> Vec<T> {
+        self.iter()
+            .map(|elem| elem.instantiate_locals(depth, locals))
+            .collect()
+    }
+}
+
 /// Values
 ///
 /// These are either in _normal form_ (they cannot be reduced further) or are
 /// _neutral terms_ (there is a possibility of reducing further depending
-/// on the bindings given in the context)
-#[derive(Debug, Clone, PartialEq, BoundTerm)]
+/// on the bindings given in the context). The bodies of binders are left
+/// unevaluated in closures, and can be normalized by reading them back into
+/// terms using `semantics::quote_value`.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     /// Universes
     Universe(Level),
     /// Literals
     Literal(Literal),
     /// A pi type
-    Pi(Scope<(Binder<String>, Embed<RcValue>), RcValue>),
+    Pi(Plicity, Multiplicity, RcValue, Closure<Scope<Binder<String>, RcTerm>>),
     /// A lambda abstraction
-    Lam(Scope<(Binder<String>, Embed<RcValue>), RcValue>),
+    Lam(Plicity, Multiplicity, RcValue, Closure<Scope<Binder<String>, RcTerm>>),
     /// Dependent record types
-    RecordType(Scope<Nest<(Label, Binder<String>, Embed<RcValue>)>, ()>),
+    RecordType(Closure<Scope<Nest<(Label, Binder<String>, Embed<RcTerm>)>, ()>>),
     /// Dependent record
-    Record(Scope<Nest<(Label, Binder<String>, Embed<RcValue>)>, ()>),
+    Record(Vec<(Label, RcValue)>),
     /// Array literals
     Array(Vec<RcValue>),
+    /// Identity types, annotated with the type of the two sides
+    Eq(RcValue, RcValue, RcValue),
+    /// The proof that a term is equal to itself
+    Refl,
+    /// A fully applied data constructor
+    Constr(Var<String>, Spine),
     /// Neutral terms
     ///
     /// A term whose computation has stopped because of an attempt to compute an
     /// application `Head`.
     Neutral(RcNeutral, Spine),
+    /// A global definition applied to a spine of arguments, glued to the
+    /// value that it unfolds to
+    ///
+    /// Computation proceeds on the unfolded value, but remembering the
+    /// application lets us show the user the name that they wrote, rather
+    /// than its definition. The unfolded value is only computed once it is
+    /// needed, so definitions that are merely passed around are never
+    /// evaluated.
+    Glued(Head, Spine, Thunk),
+    /// A poisoned value, standing in for something that failed to type check
+    ///
+    /// This is compatible with every other value, preventing a single error
+    /// from causing many more to be reported.
+    Error,
 }
 
 impl Value {
@@ -336,34 +951,63 @@ impl Value {
         match *self {
             Value::Universe(_)
             | Value::Literal(_)
-            | Value::Pi(_)
-            | Value::Lam(_)
+            | Value::Pi(_, _, _, _)
+            | Value::Lam(_, _, _, _)
             | Value::RecordType(_)
             | Value::Record(_)
-            | Value::Array(_) => true,
+            | Value::Array(_)
+            | Value::Eq(_, _, _)
+            | Value::Refl
+            | Value::Constr(_, _)
+            | Value::Error => true,
             Value::Neutral(_, _) => false,
+            Value::Glued(_, _, ref thunk) => match thunk.force() {
+                Ok(value) => value.is_whnf(),
+                Err(_) => false,
+            },
         }
     }
 
     /// Returns `true` if the value is in normal form (ie. it contains no neutral terms within it)
+    ///
+    /// The bodies of closures have not been evaluated yet, so values that
+    /// contain them are conservatively assumed not to be in normal form.
     pub fn is_nf(&self) -> bool {
         match *self {
-            Value::Universe(_) | Value::Literal(_) => true,
-            Value::Pi(ref scope) | Value::Lam(ref scope) => {
-                (scope.unsafe_pattern.1).0.is_nf() && scope.unsafe_body.is_nf()
-            },
-            Value::RecordType(ref scope) | Value::Record(ref scope) => scope
-                .unsafe_pattern
-                .unsafe_patterns
-                .iter()
-                .all(|(_, _, Embed(ref term))| term.is_nf()),
+            Value::Universe(_) | Value::Literal(_) | Value::Refl | Value::Error => true,
+            Value::Pi(_, _, _, _) | Value::Lam(_, _, _, _) | Value::RecordType(_) => false,
+            Value::Record(ref fields) => fields.iter().all(|&(_, ref value)| value.is_nf()),
             Value::Array(ref elems) => elems.iter().all(|elem| elem.is_nf()),
-            Value::Neutral(_, _) => false,
+            Value::Eq(ref ty, ref lhs, ref rhs) => ty.is_nf() && lhs.is_nf() && rhs.is_nf(),
+            Value::Constr(_, ref args) => args.iter().all(|&(_, ref arg)| arg.is_nf()),
+            Value::Neutral(_, _) | Value::Glued(_, _, _) => false,
+        }
+    }
+
+    /// The value with any glued applications of global definitions unfolded
+    ///
+    /// Applications that fail to unfold are left as they are. The error will
+    /// be reported when the value is normalized.
+    pub fn unfold(&self) -> &Value {
+        match *self {
+            Value::Glued(_, _, ref thunk) => match thunk.force() {
+                Ok(value) => value.unfold(),
+                Err(_) => self,
+            },
+            _ => self,
         }
     }
 
+    /// Read the value back into a term, keeping the applications of global
+    /// definitions that are glued to it, rather than unfolding them
+    ///
+    /// This is more readable, so it is what we show to the user.
+    pub fn to_folded_term(&self) -> Term {
+        read_back(self, true)
+    }
+
     pub fn head_app(&self) -> Option<(&Head, &Spine)> {
-        if let Value::Neutral(ref neutral, ref spine) = *self {
+        if let Value::Neutral(ref neutral, ref spine) = *self.unfold() {
             if let Neutral::Head(ref head) = **neutral {
                 return Some((head, spine));
             }
@@ -374,7 +1018,7 @@ impl Value {
     pub fn free_var_app(&self) -> Option<(&FreeVar<String>, LevelShift, &Spine)> {
         self.head_app().and_then(|(head, spine)| match *head {
             Head::Var(Var::Free(ref free_var), shift) => Some((free_var, shift, spine)),
-            Head::Extern(_, _) | Head::Var(Var::Bound(_), _) => None,
+            Head::Extern(_, _) | Head::Var(Var::Bound(_), _) | Head::Meta(_) => None,
         })
     }
 }
@@ -386,33 +1030,87 @@ impl fmt::Display for Value {
 }
 
 /// Reference counted values
-#[derive(Debug, Clone, PartialEq, BoundTerm)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RcValue {
     pub inner: Rc<Value>,
 }
 
 impl RcValue {
+    /// The value with any glued applications of global definitions unfolded
+    ///
+    /// Applications that fail to unfold are left as they are. The error will
+    /// be reported when the value is normalized.
+    pub fn unfold(&self) -> &RcValue {
+        match *self.inner {
+            Value::Glued(_, _, ref thunk) => match thunk.force() {
+                Ok(value) => value.unfold(),
+                Err(_) => self,
+            },
+            _ => self,
+        }
+    }
+
     pub fn shift_universes(&mut self, shift: LevelShift) {
+        // Avoid copying values that are shared with the environment
+        if shift != LevelShift(0) {
+            self.map_universes(&move |level: &mut Level| *level += shift);
+        }
+    }
+
+    /// Replace the level parameters in the value with the given levels
+    pub fn instantiate_universes(&mut self, levels: &[(String, Level)]) {
+        let levels = levels.to_vec();
+        self.map_universes(&move |level: &mut Level| *level = level.instantiate(&levels));
+    }
+
+    fn map_universes<F: Fn(&mut Level) + Clone + 'static>(&mut self, f: &F) {
         match *Rc::make_mut(&mut self.inner) {
-            Value::Universe(ref mut level) => *level += shift,
-            Value::Literal(_) => {},
-            Value::Pi(ref mut scope) | Value::Lam(ref mut scope) => {
-                (scope.unsafe_pattern.1).0.shift_universes(shift);
-                scope.unsafe_body.shift_universes(shift);
+            Value::Universe(ref mut level) => f(level),
+            Value::Literal(_) | Value::Refl | Value::Error => {},
+            Value::Pi(_, _, ref mut ann, ref mut closure)
+            | Value::Lam(_, _, ref mut ann, ref mut closure) => {
+                ann.map_universes(f);
+                closure.take_locals();
+                closure.term.unsafe_body.map_universes(f);
             },
-            Value::RecordType(ref mut scope) | Value::Record(ref mut scope) => {
-                for &mut (_, _, Embed(ref mut term)) in &mut scope.unsafe_pattern.unsafe_patterns {
-                    term.shift_universes(shift);
+            Value::RecordType(ref mut closure) => {
+                closure.take_locals();
+                for &mut (_, _, Embed(ref mut ann)) in
+                    &mut closure.term.unsafe_pattern.unsafe_patterns
+                {
+                    ann.map_universes(f);
                 }
             },
+            Value::Record(ref mut fields) => for &mut (_, ref mut value) in fields {
+                value.map_universes(f);
+            },
             Value::Array(ref mut elems) => for elem in elems {
-                elem.shift_universes(shift);
+                elem.map_universes(f);
+            },
+            Value::Eq(ref mut ty, ref mut lhs, ref mut rhs) => {
+                ty.map_universes(f);
+                lhs.map_universes(f);
+                rhs.map_universes(f);
+            },
+            Value::Constr(_, ref mut args) => for &mut (_, ref mut arg) in args {
+                arg.map_universes(f);
             },
             Value::Neutral(ref mut neutral, ref mut spine) => {
-                neutral.shift_universes(shift);
-                for arg in spine {
-                    arg.shift_universes(shift);
+                neutral.map_universes(f);
+                for &mut (_, ref mut arg) in spine {
+                    arg.map_universes(f);
+                }
+            },
+            Value::Glued(_, ref mut spine, ref mut thunk) => {
+                for &mut (_, ref mut arg) in spine {
+                    arg.map_universes(f);
                 }
+                let (thunk_, f) = (thunk.clone(), f.clone());
+                *thunk = Thunk::new(move || {
+                    let mut value = thunk_.force()?.clone();
+                    value.map_universes(&f);
+                    Ok(value)
+                });
             },
         }
     }
@@ -440,35 +1138,104 @@ impl fmt::Display for RcValue {
     }
 }
 
+/// A computation that produces a value
+type ThunkFn = Box<dyn FnOnce() -> Result<RcValue, InternalError>>;
+
+/// A value that is computed the first time that it is needed, and then
+/// shared between all of the copies of the thunk
+#[derive(Clone)]
+pub struct Thunk {
+    inner: ThunkInner,
+}
+
+#[derive(Clone)]
+enum ThunkInner {
+    /// The value is already known, so there is no need to allocate a cell
+    Value(RcValue),
+    Lazy(Rc<LazyCell<Result<RcValue, InternalError>, ThunkFn>>),
+}
+
+impl Thunk {
+    pub fn new<F>(compute: F) -> Thunk
+    where
+        F: FnOnce() -> Result<RcValue, InternalError> + 'static,
+    {
+        Thunk {
+            inner: ThunkInner::Lazy(Rc::new(LazyCell::new(Box::new(compute)))),
+        }
+    }
+
+    /// A thunk for a value that has already been computed
+    pub fn from_value(value: RcValue) -> Thunk {
+        Thunk {
+            inner: ThunkInner::Value(value),
+        }
+    }
+
+    /// Compute the value, if this has not been done already
+    pub fn force(&self) -> Result<&RcValue, InternalError> {
+        match self.inner {
+            ThunkInner::Value(ref value) => Ok(value),
+            ThunkInner::Lazy(ref cell) => match ***cell {
+                Ok(ref value) => Ok(value),
+                Err(ref error) => Err(error.clone()),
+            },
+        }
+    }
+}
+
+impl fmt::Debug for Thunk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Thunk").field(&self.force()).finish()
+    }
+}
+
+impl PartialEq for Thunk {
+    fn eq(&self, other: &Thunk) -> bool {
+        match (&self.inner, &other.inner) {
+            (&ThunkInner::Lazy(ref cell1), &ThunkInner::Lazy(ref cell2))
+                if Rc::ptr_eq(cell1, cell2) =>
+            {
+                true
+            },
+            (_, _) => self.force() == other.force(),
+        }
+    }
+}
+
 /// The head of an application
-#[derive(Debug, Clone, PartialEq, BoundTerm)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Head {
     /// Variables that have not yet been replaced with a definition
     Var(Var<String>, LevelShift),
     /// External definitions
     Extern(String, RcType),
-    // TODO: Metavariables
+    /// Metavariables that have not yet been solved
+    Meta(MetaVar),
 }
 
 /// The spine of a neutral term
 ///
 /// These are arguments that are awaiting application
-pub type Spine = Vec<RcValue>;
+pub type Spine = Vec<(Plicity, RcValue)>;
 
 /// Neutral values
 ///
 /// These might be able to be reduced further depending on the bindings in the
 /// context
-#[derive(Debug, Clone, PartialEq, BoundTerm)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Neutral {
     /// Head of an application
     Head(Head),
     /// If expression
     If(RcNeutral, RcValue, RcValue),
+    /// Identity eliminator
+    J(RcValue, RcValue, RcNeutral),
     /// Field projection
     Proj(RcNeutral, Label),
-    /// Case expressions
-    Case(RcNeutral, Vec<Scope<RcPattern, RcValue>>),
+    /// Case expressions, where either the head is neutral, or a neutral value
+    /// nested inside the head prevents the clauses from being matched
+    Case(RcValue, Closure<Vec<Scope<RcPattern, RcTerm>>>),
 }
 
 impl Neutral {
@@ -484,31 +1251,37 @@ impl fmt::Display for Neutral {
 }
 
 /// Reference counted neutral values
-#[derive(Debug, Clone, PartialEq, BoundTerm)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RcNeutral {
     pub inner: Rc<Neutral>,
 }
 
 impl RcNeutral {
-    pub fn shift_universes(&mut self, shift: LevelShift) {
+    fn map_universes<F: Fn(&mut Level) + Clone + 'static>(&mut self, f: &F) {
         match *Rc::make_mut(&mut self.inner) {
             // Neutral::Head(Head::Var(_, ref mut head_shift)) => {
             //     *head_shift += shift; // NOTE: Not sure if this is correct!
             // },
-            Neutral::Head(Head::Var(_, _)) => {},
-            Neutral::Head(Head::Extern(_, ref mut ty)) => ty.shift_universes(shift),
+            Neutral::Head(Head::Var(_, _)) | Neutral::Head(Head::Meta(_)) => {},
+            Neutral::Head(Head::Extern(_, ref mut ty)) => ty.map_universes(f),
             Neutral::If(ref mut cond, ref mut if_true, ref mut if_false) => {
-                cond.shift_universes(shift);
-                if_true.shift_universes(shift);
-                if_false.shift_universes(shift);
+                cond.map_universes(f);
+                if_true.map_universes(f);
+                if_false.map_universes(f);
             },
-            Neutral::Proj(ref mut expr, _) => expr.shift_universes(shift),
-            Neutral::Case(ref mut expr, ref mut clauses) => {
-                expr.shift_universes(shift);
-                for clause in clauses {
+            Neutral::J(ref mut motive, ref mut base, ref mut proof) => {
+                motive.map_universes(f);
+                base.map_universes(f);
+                proof.map_universes(f);
+            },
+            Neutral::Proj(ref mut expr, _) => expr.map_universes(f),
+            Neutral::Case(ref mut expr, ref mut closure) => {
+                expr.map_universes(f);
+                closure.take_locals();
+                for clause in &mut closure.term {
                     // FIXME: implement shifting for patterns as well!
                     // clause.unsafe_pattern.shift_universes(shift);
-                    clause.unsafe_body.shift_universes(shift);
+                    clause.unsafe_body.map_universes(f);
                 }
             },
         }
@@ -543,65 +1316,91 @@ impl From<Neutral> for Value {
     }
 }
 
+/// Converting values back into terms
+///
+/// The local variables of closures are substituted into their terms, but the
+/// terms are otherwise left unevaluated. Use `semantics::quote_value` to read
+/// a value back into a term in normal form.
 impl<'a> From<&'a Value> for Term {
     fn from(src: &'a Value) -> Term {
-        // Bypassing `Scope::new` and `Scope::unbind` here should be fine
-        // because we aren't altering the structure of the scopes during this
-        // transformation. This should save on some traversals of the AST!
-        match *src {
-            Value::Universe(level) => Term::Universe(level),
-            Value::Literal(ref lit) => Term::Literal(lit.clone()),
-            Value::Pi(ref scope) => {
-                let (ref name, Embed(ref ann)) = scope.unsafe_pattern;
-                Term::Pi(Scope {
-                    unsafe_pattern: (name.clone(), Embed(RcTerm::from(&**ann))),
-                    unsafe_body: RcTerm::from(&*scope.unsafe_body),
-                })
-            },
-            Value::Lam(ref scope) => {
-                let (ref name, Embed(ref ann)) = scope.unsafe_pattern;
-                Term::Lam(Scope {
-                    unsafe_pattern: (name.clone(), Embed(RcTerm::from(&**ann))),
-                    unsafe_body: RcTerm::from(&*scope.unsafe_body),
-                })
-            },
-            Value::RecordType(ref scope) => {
-                let unsafe_patterns = scope
-                    .unsafe_pattern
-                    .unsafe_patterns
-                    .iter()
-                    .map(|&(ref label, ref binder, Embed(ref ann))| {
-                        (label.clone(), binder.clone(), Embed(RcTerm::from(&**ann)))
-                    }).collect();
+        read_back(src, false)
+    }
+}
 
-                Term::RecordType(Scope {
-                    unsafe_pattern: Nest { unsafe_patterns },
-                    unsafe_body: (),
-                })
-            },
-            Value::Record(ref scope) => {
-                let unsafe_patterns = scope
-                    .unsafe_pattern
-                    .unsafe_patterns
-                    .iter()
-                    .map(|&(ref label, ref binder, Embed(ref expr))| {
-                        (label.clone(), binder.clone(), Embed(RcTerm::from(&**expr)))
-                    }).collect();
+/// Read a value back into a term, either keeping or unfolding the glued
+/// applications of global definitions
+fn read_back(src: &Value, folded: bool) -> Term {
+    let read_back_rc = |value: &RcValue| RcTerm::from(read_back(value, folded));
 
-                Term::Record(Scope {
-                    unsafe_pattern: Nest { unsafe_patterns },
-                    unsafe_body: (),
-                })
-            },
-            Value::Array(ref elems) => {
-                Term::Array(elems.iter().map(|elem| RcTerm::from(&**elem)).collect())
-            },
-            Value::Neutral(ref neutral, ref spine) => {
-                spine.iter().fold(Term::from(&*neutral.inner), |acc, arg| {
-                    Term::App(RcTerm::from(acc), RcTerm::from(&**arg))
+    // Bypassing `Scope::new` and `Scope::unbind` here should be fine
+    // because we aren't altering the structure of the scopes during this
+    // transformation. This should save on some traversals of the AST!
+    match *src {
+        Value::Universe(ref level) => Term::Universe(level.clone()),
+        Value::Literal(ref lit) => Term::Literal(lit.clone()),
+        Value::Pi(plicity, multiplicity, ref ann, ref closure) => {
+            Term::Pi(plicity, multiplicity, binder_scope(read_back_rc(ann), closure))
+        },
+        Value::Lam(plicity, multiplicity, ref ann, ref closure) => {
+            Term::Lam(plicity, multiplicity, binder_scope(read_back_rc(ann), closure))
+        },
+        Value::RecordType(ref closure) => Term::RecordType(closure.instantiate()),
+        Value::Record(ref fields) => {
+            // The fields of record values never refer to each other, so
+            // we can use fresh binders for them
+            let unsafe_patterns = fields
+                .iter()
+                .map(|&(ref label, ref expr)| {
+                    let binder = Binder(FreeVar::fresh_named(label.0.clone()));
+                    (label.clone(), binder, Embed(read_back_rc(expr)))
+                }).collect();
+
+            Term::Record(Scope {
+                unsafe_pattern: Nest { unsafe_patterns },
+                unsafe_body: (),
+            })
+        },
+        Value::Array(ref elems) => Term::Array(elems.iter().map(read_back_rc).collect()),
+        Value::Eq(ref ty, ref lhs, ref rhs) => {
+            Term::Eq(read_back_rc(ty), read_back_rc(lhs), read_back_rc(rhs))
+        },
+        Value::Refl => Term::Refl,
+        Value::Error => Term::Error,
+        Value::Constr(ref var, ref args) => Term::Constr(
+            var.clone(),
+            args.iter()
+                .map(|&(plicity, ref arg)| (plicity, read_back_rc(arg)))
+                .collect(),
+        ),
+        Value::Neutral(ref neutral, ref spine) => {
+            spine
+                .iter()
+                .fold(Term::from(&*neutral.inner), |acc, &(plicity, ref arg)| {
+                    Term::App(RcTerm::from(acc), plicity, read_back_rc(arg))
                 })
-            },
-        }
+        },
+        Value::Glued(ref head, ref spine, ref thunk) => match thunk.force() {
+            Ok(value) if !folded => read_back(value, folded),
+            // Keep the application if it could not be unfolded
+            _ => spine
+                .iter()
+                .fold(Term::from(head), |acc, &(plicity, ref arg)| {
+                    Term::App(RcTerm::from(acc), plicity, read_back_rc(arg))
+                }),
+        },
+    }
+}
+
+/// Rebuild the scope of a pi type or lambda from its annotation and closure
+fn binder_scope(
+    ann: RcTerm,
+    closure: &Closure<Scope<Binder<String>, RcTerm>>,
+) -> Scope<(Binder<String>, Embed<RcTerm>), RcTerm> {
+    // The annotation is embedded in the pattern, so the body can be reused
+    // without rebinding it
+    Scope {
+        unsafe_pattern: (closure.term.unsafe_pattern.clone(), Embed(ann)),
+        unsafe_body: closure.instantiate().unsafe_body,
     }
 }
 
@@ -620,16 +1419,15 @@ impl<'a> From<&'a Neutral> for Term {
                 RcTerm::from(&**if_true),
                 RcTerm::from(&**if_false),
             ),
-            Neutral::Proj(ref expr, ref name) => Term::Proj(RcTerm::from(&**expr), name.clone()),
-            Neutral::Case(ref head, ref clauses) => Term::Case(
-                RcTerm::from(&**head),
-                clauses
-                    .iter()
-                    .map(|clause| Scope {
-                        unsafe_pattern: clause.unsafe_pattern.clone(),
-                        unsafe_body: RcTerm::from(&*clause.unsafe_body),
-                    }).collect(),
+            Neutral::J(ref motive, ref base, ref proof) => Term::J(
+                RcTerm::from(&**motive),
+                RcTerm::from(&**base),
+                RcTerm::from(&**proof),
             ),
+            Neutral::Proj(ref expr, ref name) => Term::Proj(RcTerm::from(&**expr), name.clone()),
+            Neutral::Case(ref head, ref closure) => {
+                Term::Case(RcTerm::from(&**head), closure.instantiate())
+            },
         }
     }
 }
@@ -645,6 +1443,7 @@ impl<'a> From<&'a Head> for Term {
         match *src {
             Head::Var(ref var, shift) => Term::Var(var.clone(), shift),
             Head::Extern(ref name, ref ty) => Term::Extern(name.clone(), RcTerm::from(&**ty)),
+            Head::Meta(meta) => Term::Meta(meta),
         }
     }
 }
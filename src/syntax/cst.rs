@@ -0,0 +1,356 @@
+//! The lossless concrete syntax tree
+//!
+//! Unlike `syntax::concrete`, this tree keeps every byte of the source code
+//! that it was parsed from, including whitespace and comments, allowing the
+//! source code to be reconstructed exactly. This makes it suitable for tools
+//! that need to refer back to the original source code, like formatters and
+//! editor integrations. The concrete syntax is in turn derived from this tree.
+//!
+//! The tree is split into two layers:
+//!
+//! - the _green_ tree, which is immutable and records only the lengths of the
+//!   nodes and tokens, allowing identical subtrees to be shared
+//! - the _red_ tree, which is built on demand on top of the green tree, and
+//!   records the absolute positions of nodes, along with their parents
+
+use codespan::{ByteIndex, ByteOffset, ByteSpan, RawOffset};
+use std::fmt;
+use std::rc::Rc;
+
+use syntax::parse::Token;
+
+/// The kind of a node in the syntax tree
+///
+/// Nodes that share the same structure between terms, patterns and levels
+/// share the same kind, for example `Parens` and `Name`. Which of these is
+/// meant is decided by where the node appears.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    /// A module, containing items: `||! doc item; item;`
+    Module,
+    /// A command entered into the REPL: `:t term`
+    ReplCommand,
+
+    /// A declaration: `name : ann;`
+    Declaration,
+    /// A definition: `name params = body;`
+    Definition,
+    /// A data type definition: `data Name params where { ... };`
+    Data,
+    /// A parameter of a data type: `(a b : Type)`
+    DataParam,
+    /// A constructor of a data type: `name : ann`
+    DataConstructor,
+    /// An import: `import path.to.module;`
+    Import,
+
+    /// A parenthesized term, pattern or level: `(x)`
+    Parens,
+    /// A term or pattern with a type annotation: `x : t`
+    Ann,
+    /// A literal term or pattern: `"hello"`
+    Literal,
+    /// A name, with an optional level shift: `x^1`
+    Name,
+    /// A function application, or a constructor pattern: `f x {y}`
+    App,
+    /// An implicit argument of a function application: `{x}`
+    ImplicitArg,
+    /// Source code that could not be parsed
+    Error,
+
+    /// A successor level: `l + 1`
+    LevelSucc,
+    /// An application of a level function: `max l1 l2`
+    LevelApp,
+
+    /// An external definition: `extern "name" : t`
+    Extern,
+    /// A universe: `Type^l`
+    Universe,
+    /// An array: `[x; y]`
+    Array,
+    /// A hole: `?`
+    Hole,
+    /// A lambda abstraction: `\x y => body`
+    Lam,
+    /// A group of lambda parameters: `(x y : t)`
+    LamParam,
+    /// A function type whose binder is parsed as an application. This is a
+    /// pi type if every argument is a parameter group, for example
+    /// `(a : Type) -> a`, and a non-dependent function type otherwise, for
+    /// example `Bool -> Bool`.
+    Arrow,
+    /// A pi type whose binder begins with implicit parameters: `{a : Type} -> a`
+    Pi,
+    /// An equality type: `x = y`
+    Eq,
+    /// A let expression: `let items in body`
+    Let,
+    /// An if expression: `if c then t else f`
+    If,
+    /// A case expression: `case x of { ... }`
+    Case,
+    /// A clause of a case expression: `pattern => body`
+    CaseArm,
+    /// A reflexivity proof: `refl`
+    Refl,
+    /// An eliminator for equality types: `J motive base proof`
+    J,
+    /// A record type: `Record { ... }`
+    RecordType,
+    /// A field of a record type: `label as binder : ann`
+    RecordTypeField,
+    /// A record: `record { ... }`
+    Record,
+    /// A field of a record: `label params = term`
+    RecordField,
+    /// A field projection: `x.label`
+    Proj,
+}
+
+/// The kind of a token in the syntax tree
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    /// A token that was produced by the lexer
+    Token(Token<String>),
+    /// Whitespace between tokens
+    Whitespace,
+    /// Source code that the lexer could not make sense of
+    Error,
+}
+
+impl TokenKind {
+    /// Returns `true` if the token has no bearing on the meaning of the
+    /// source code
+    pub fn is_trivia(&self) -> bool {
+        match *self {
+            TokenKind::Token(Token::LineComment(_)) | TokenKind::Whitespace | TokenKind::Error => {
+                true
+            },
+            TokenKind::Token(_) => false,
+        }
+    }
+}
+
+/// A token in the green tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct GreenToken {
+    pub kind: TokenKind,
+    pub text: String,
+}
+
+/// A node in the green tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct GreenNode {
+    pub kind: NodeKind,
+    /// The length of the source code covered by the node
+    pub len: ByteOffset,
+    pub children: Vec<GreenElement>,
+}
+
+impl GreenNode {
+    pub fn new(kind: NodeKind, children: Vec<GreenElement>) -> GreenNode {
+        let len = children
+            .iter()
+            .fold(ByteOffset(0), |len, child| len + child.len());
+
+        GreenNode {
+            kind,
+            len,
+            children,
+        }
+    }
+}
+
+/// A child of a node in the green tree
+#[derive(Debug, Clone, PartialEq)]
+pub enum GreenElement {
+    Node(Rc<GreenNode>),
+    Token(Rc<GreenToken>),
+}
+
+impl GreenElement {
+    /// The length of the source code covered by the element
+    pub fn len(&self) -> ByteOffset {
+        match *self {
+            GreenElement::Node(ref node) => node.len,
+            GreenElement::Token(ref token) => ByteOffset(token.text.len() as RawOffset),
+        }
+    }
+}
+
+/// A node in the red tree
+#[derive(Debug, Clone)]
+pub struct SyntaxNode(Rc<SyntaxNodeData>);
+
+#[derive(Debug)]
+struct SyntaxNodeData {
+    green: Rc<GreenNode>,
+    start: ByteIndex,
+    parent: Option<SyntaxNode>,
+}
+
+impl SyntaxNode {
+    /// Create the root of a red tree, beginning at the given position in the
+    /// codemap
+    pub fn new_root(green: Rc<GreenNode>, start: ByteIndex) -> SyntaxNode {
+        SyntaxNode(Rc::new(SyntaxNodeData {
+            green,
+            start,
+            parent: None,
+        }))
+    }
+
+    pub fn green(&self) -> &Rc<GreenNode> {
+        &self.0.green
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.0.green.kind
+    }
+
+    pub fn parent(&self) -> Option<&SyntaxNode> {
+        self.0.parent.as_ref()
+    }
+
+    /// The span of source code covered by the node, including any leading
+    /// or trailing trivia
+    pub fn span(&self) -> ByteSpan {
+        ByteSpan::from_offset(self.0.start, self.0.green.len)
+    }
+
+    /// The span of source code covered by the node, excluding any leading or
+    /// trailing trivia
+    pub fn trimmed_span(&self) -> ByteSpan {
+        let mut tokens = self
+            .descendant_tokens()
+            .into_iter()
+            .filter(|token| !token.kind().is_trivia());
+
+        match tokens.next() {
+            None => ByteSpan::new(self.0.start, self.0.start),
+            Some(first) => match tokens.last() {
+                None => first.span(),
+                Some(last) => first.span().to(last.span()),
+            },
+        }
+    }
+
+    /// The children of this node, in the order that they appear in the
+    /// source code
+    pub fn elements(&self) -> Vec<SyntaxElement> {
+        let mut start = self.0.start;
+
+        self.0
+            .green
+            .children
+            .iter()
+            .map(|child| {
+                let child_start = start;
+                start += child.len();
+
+                match *child {
+                    GreenElement::Node(ref green) => SyntaxElement::Node(SyntaxNode(Rc::new(
+                        SyntaxNodeData {
+                            green: green.clone(),
+                            start: child_start,
+                            parent: Some(self.clone()),
+                        },
+                    ))),
+                    GreenElement::Token(ref green) => SyntaxElement::Token(SyntaxToken {
+                        green: green.clone(),
+                        start: child_start,
+                        parent: self.clone(),
+                    }),
+                }
+            }).collect()
+    }
+
+    /// The child nodes of this node
+    pub fn children(&self) -> Vec<SyntaxNode> {
+        self.elements()
+            .into_iter()
+            .filter_map(|element| match element {
+                SyntaxElement::Node(node) => Some(node),
+                SyntaxElement::Token(_) => None,
+            }).collect()
+    }
+
+    /// The tokens that are direct children of this node
+    pub fn tokens(&self) -> Vec<SyntaxToken> {
+        self.elements()
+            .into_iter()
+            .filter_map(|element| match element {
+                SyntaxElement::Node(_) => None,
+                SyntaxElement::Token(token) => Some(token),
+            }).collect()
+    }
+
+    /// All of the tokens contained in this node, in the order that they
+    /// appear in the source code
+    pub fn descendant_tokens(&self) -> Vec<SyntaxToken> {
+        let mut tokens = Vec::new();
+        for element in self.elements() {
+            match element {
+                SyntaxElement::Node(node) => tokens.extend(node.descendant_tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+
+    /// Find the innermost node that covers the given position
+    pub fn covering_node(&self, index: ByteIndex) -> SyntaxNode {
+        for child in self.children() {
+            let span = child.span();
+            if span.start() <= index && index < span.end() {
+                return child.covering_node(index);
+            }
+        }
+        self.clone()
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for token in self.descendant_tokens() {
+            write!(f, "{}", token.text())?;
+        }
+        Ok(())
+    }
+}
+
+/// A token in the red tree
+#[derive(Debug, Clone)]
+pub struct SyntaxToken {
+    green: Rc<GreenToken>,
+    start: ByteIndex,
+    parent: SyntaxNode,
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> &TokenKind {
+        &self.green.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub fn parent(&self) -> &SyntaxNode {
+        &self.parent
+    }
+
+    /// The span of source code covered by the token
+    pub fn span(&self) -> ByteSpan {
+        ByteSpan::from_offset(self.start, ByteOffset(self.green.text.len() as RawOffset))
+    }
+}
+
+/// A child of a node in the red tree
+#[derive(Debug, Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
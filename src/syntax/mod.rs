@@ -1,34 +1,265 @@
 //! The syntax of the language
 
+use std::cmp;
 use std::fmt;
-use std::ops::{Add, AddAssign};
+use std::ops::{Add, AddAssign, Mul};
 
 pub mod concrete;
 pub mod core;
+pub mod cst;
 pub mod parse;
 pub mod pretty;
 pub mod raw;
 pub mod translation;
 
+/// A metavariable standing in for a universe level that is yet to be
+/// determined during elaboration
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, BoundTerm)]
+pub struct LevelMeta(pub u32);
+
+impl fmt::Display for LevelMeta {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "?u{}", self.0)
+    }
+}
+
+/// A variable that can stand for a universe level
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, BoundTerm)]
+pub enum LevelVar {
+    /// A level parameter of a level-polymorphic definition, eg. the `l` in
+    /// `Type^l`
+    Param(String),
+    /// A level metavariable, inserted when instantiating a level-polymorphic
+    /// definition
+    Meta(LevelMeta),
+}
+
+impl fmt::Display for LevelVar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LevelVar::Param(ref name) => write!(f, "{}", name),
+            LevelVar::Meta(meta) => write!(f, "{}", meta),
+        }
+    }
+}
+
 /// A universe level
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, BoundTerm)]
-pub struct Level(pub u32);
+///
+/// Levels are kept in a normal form: the maximum of a constant level and of
+/// some level variables, each with a number of successors added to it. For
+/// example, `max 2 (l + 1) k` is stored as a constant of `2`, along with
+/// the variables `k + 0` and `l + 1`. This means that two levels are equal
+/// for every choice of their variables exactly when they are equal here.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, BoundTerm)]
+pub struct Level {
+    /// The constant part of the level
+    ///
+    /// This is kept at zero if it would be subsumed by one of the variables.
+    base: u32,
+    /// The variables in the level, along with their offsets, sorted by
+    /// variable with no duplicates
+    vars: Vec<(LevelVar, u32)>,
+}
 
 impl Level {
-    pub fn succ(self) -> Level {
-        Level(self.0 + 1)
+    /// A level consisting of a single variable
+    pub fn var(var: LevelVar) -> Level {
+        Level {
+            base: 0,
+            vars: vec![(var, 0)],
+        }
+    }
+
+    /// A level consisting of a single level parameter
+    pub fn param(name: impl Into<String>) -> Level {
+        Level::var(LevelVar::Param(name.into()))
+    }
+
+    /// A level consisting of a single level metavariable
+    pub fn meta(meta: LevelMeta) -> Level {
+        Level::var(LevelVar::Meta(meta))
+    }
+
+    fn new(base: u32, mut vars: Vec<(LevelVar, u32)>) -> Level {
+        vars.sort();
+        // Only the largest offset of each variable matters
+        let mut deduped: Vec<(LevelVar, u32)> = Vec::with_capacity(vars.len());
+        for (var, offset) in vars {
+            match deduped.last_mut() {
+                Some(&mut (ref last_var, ref mut last_offset)) if *last_var == var => {
+                    *last_offset = offset;
+                },
+                _ => deduped.push((var, offset)),
+            }
+        }
+
+        let base = match deduped.iter().map(|&(_, offset)| offset).max() {
+            Some(max_offset) if base <= max_offset => 0,
+            _ => base,
+        };
+
+        Level {
+            base,
+            vars: deduped,
+        }
+    }
+
+    /// The constant part of the level
+    pub fn base(&self) -> u32 {
+        self.base
+    }
+
+    /// The variables in the level, along with the number of successors added
+    /// to them
+    pub fn vars(&self) -> &[(LevelVar, u32)] {
+        &self.vars
+    }
+
+    /// Returns `true` if the level contains no variables
+    pub fn is_const(&self) -> bool {
+        self.vars.is_empty()
+    }
+
+    /// The level above this one
+    pub fn succ(&self) -> Level {
+        self.clone() + LevelShift(1)
+    }
+
+    /// The maximum of two levels
+    pub fn max(&self, other: &Level) -> Level {
+        Level::new(
+            cmp::max(self.base, other.base),
+            self.vars.iter().chain(&other.vars).cloned().collect(),
+        )
+    }
+
+    /// Returns the variable and its offset if the level is made up of a
+    /// single variable
+    pub fn as_var(&self) -> Option<(&LevelVar, u32)> {
+        match self.vars[..] {
+            [(ref var, offset)] if self.base == 0 => Some((var, offset)),
+            _ => None,
+        }
+    }
+
+    /// Take `shift` successors away from the level, if it is large enough
+    pub fn checked_sub(&self, shift: u32) -> Option<Level> {
+        if self.is_const() && self.base < shift {
+            return None;
+        }
+
+        let vars = self
+            .vars
+            .iter()
+            .map(|&(ref var, offset)| Some((var.clone(), offset.checked_sub(shift)?)))
+            .collect::<Option<_>>()?;
+
+        Some(Level::new(self.base.saturating_sub(shift), vars))
+    }
+
+    /// Take `shift` successors away from each part of the level, stopping at
+    /// zero. The result is the smallest level that is at least as large as
+    /// this one once `shift` successors are added back to it.
+    pub fn saturating_sub(&self, shift: u32) -> Level {
+        let vars = self
+            .vars
+            .iter()
+            .map(|&(ref var, offset)| (var.clone(), offset.saturating_sub(shift)))
+            .collect();
+
+        Level::new(self.base.saturating_sub(shift), vars)
+    }
+
+    /// Returns `true` if the level contains any level metavariables
+    pub fn has_metas(&self) -> bool {
+        self.vars.iter().any(|&(ref var, _)| match *var {
+            LevelVar::Meta(_) => true,
+            LevelVar::Param(_) => false,
+        })
+    }
+
+    /// Returns `true` if this level is less than or equal to `other`, for
+    /// every choice of the variables that occur in them
+    pub fn is_le(&self, other: &Level) -> bool {
+        // Every variable is at least zero, so `other` is at least as large as
+        // its largest offset
+        let other_min = other
+            .vars
+            .iter()
+            .map(|&(_, offset)| offset)
+            .fold(other.base, cmp::max);
+
+        self.base <= other_min && self.vars.iter().all(|&(ref var, offset)| {
+            other
+                .vars
+                .iter()
+                .any(|&(ref other_var, other_offset)| var == other_var && offset <= other_offset)
+        })
+    }
+
+    /// Replace the variables in the level using the given function,
+    /// leaving them in place if it returns `None`
+    pub fn substs<F>(&self, mut mapping: F) -> Level
+    where
+        F: FnMut(&LevelVar) -> Option<Level>,
+    {
+        self.vars
+            .iter()
+            .fold(Level::from(self.base), |acc, &(ref var, offset)| {
+                let level = match mapping(var) {
+                    Some(level) => level + LevelShift(offset),
+                    None => Level::new(0, vec![(var.clone(), offset)]),
+                };
+                acc.max(&level)
+            })
+    }
+
+    /// Replace the level parameters with the given levels
+    pub fn instantiate(&self, levels: &[(String, Level)]) -> Level {
+        self.substs(|var| match *var {
+            LevelVar::Param(ref name) => levels
+                .iter()
+                .find(|&&(ref param, _)| param == name)
+                .map(|&(_, ref level)| level.clone()),
+            LevelVar::Meta(_) => None,
+        })
     }
 }
 
 impl From<u32> for Level {
     fn from(src: u32) -> Level {
-        Level(src)
+        Level {
+            base: src,
+            vars: Vec::new(),
+        }
     }
 }
 
 impl fmt::Display for Level {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.0)
+        fn fmt_atom(f: &mut fmt::Formatter, var: &LevelVar, offset: u32) -> fmt::Result {
+            match offset {
+                0 => write!(f, "{}", var),
+                _ => write!(f, "({} + {})", var, offset),
+            }
+        }
+
+        match (self.base, &self.vars[..]) {
+            (base, []) => write!(f, "{}", base),
+            (0, [(ref var, 0)]) => write!(f, "{}", var),
+            (0, [(ref var, offset)]) => write!(f, "({} + {})", var, offset),
+            (base, vars) => {
+                write!(f, "(max")?;
+                if base != 0 {
+                    write!(f, " {}", base)?;
+                }
+                for &(ref var, offset) in vars {
+                    write!(f, " ")?;
+                    fmt_atom(f, var, offset)?;
+                }
+                write!(f, ")")
+            },
+        }
     }
 }
 
@@ -66,13 +297,110 @@ impl Add<LevelShift> for Level {
     type Output = Level;
 
     fn add(self, other: LevelShift) -> Level {
-        Level(self.0 + other.0)
+        Level::new(
+            self.base + other.0,
+            self.vars
+                .into_iter()
+                .map(|(var, offset)| (var, offset + other.0))
+                .collect(),
+        )
     }
 }
 
 impl AddAssign<LevelShift> for Level {
     fn add_assign(&mut self, other: LevelShift) {
-        self.0 += other.0;
+        *self = self.clone() + other;
+    }
+}
+
+/// Whether a parameter, or the argument that is supplied for it, is explicit
+/// or implicit
+///
+/// Implicit arguments are inserted during elaboration, with their values being
+/// found using unification
+#[derive(Debug, Copy, Clone, PartialEq, Eq, BoundTerm, BoundPattern)]
+pub enum Plicity {
+    /// Parameters that must be supplied explicitly, written `(x : t)`
+    Explicit,
+    /// Parameters that are normally inferred, written `{x : t}`
+    Implicit,
+}
+
+impl fmt::Display for Plicity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Plicity::Explicit => write!(f, "explicit"),
+            Plicity::Implicit => write!(f, "implicit"),
+        }
+    }
+}
+
+/// The number of times that the variable bound by a parameter may be used at
+/// runtime, as described by quantitative type theory
+///
+/// Multiplicities form a semiring, with addition combining the uses in
+/// different parts of a term, and multiplication scaling the uses in an
+/// argument by the multiplicity of the parameter that it is supplied for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, BoundTerm, BoundPattern)]
+pub enum Multiplicity {
+    /// Erased parameters that may only be used in types, written `(0 x : t)`
+    Zero,
+    /// Linear parameters that must be used exactly once, written `(1 x : t)`
+    One,
+    /// Parameters that may be used any number of times, written `(x : t)`
+    Many,
+}
+
+impl Multiplicity {
+    /// Combine the uses of a variable in two alternative branches
+    ///
+    /// The branches must agree on how many times the variable is used, and if
+    /// not then we can only say that it might be used any number of times.
+    pub fn join(self, other: Multiplicity) -> Multiplicity {
+        if self == other {
+            self
+        } else {
+            Multiplicity::Many
+        }
+    }
+}
+
+impl Add for Multiplicity {
+    type Output = Multiplicity;
+
+    fn add(self, other: Multiplicity) -> Multiplicity {
+        match (self, other) {
+            (Multiplicity::Zero, multiplicity) | (multiplicity, Multiplicity::Zero) => multiplicity,
+            (_, _) => Multiplicity::Many,
+        }
+    }
+}
+
+impl AddAssign for Multiplicity {
+    fn add_assign(&mut self, other: Multiplicity) {
+        *self = *self + other;
+    }
+}
+
+impl Mul for Multiplicity {
+    type Output = Multiplicity;
+
+    fn mul(self, other: Multiplicity) -> Multiplicity {
+        match (self, other) {
+            (Multiplicity::Zero, _) | (_, Multiplicity::Zero) => Multiplicity::Zero,
+            (Multiplicity::One, multiplicity) | (multiplicity, Multiplicity::One) => multiplicity,
+            (Multiplicity::Many, Multiplicity::Many) => Multiplicity::Many,
+        }
+    }
+}
+
+impl fmt::Display for Multiplicity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Multiplicity::Zero => write!(f, "0"),
+            Multiplicity::One => write!(f, "1"),
+            Multiplicity::Many => write!(f, "ω"),
+        }
     }
 }
 
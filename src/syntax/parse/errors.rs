@@ -12,6 +12,14 @@ pub enum ParseError {
     Lexer(#[cause] LexerError),
     #[fail(display = "An identifier was expected when parsing a pi type.")]
     IdentifierExpectedInPiType { span: ByteSpan },
+    #[fail(display = "A parameter group was expected when parsing a pi type.")]
+    ParamGroupExpectedInPiType { span: ByteSpan },
+    #[fail(display = "A constructor was expected at the head of a pattern.")]
+    ConstructorExpectedInPattern { span: ByteSpan },
+    #[fail(display = "Unknown level function `{}` found.", name)]
+    UnknownLevelFunction { span: ByteSpan, name: String },
+    #[fail(display = "Unknown multiplicity `{}` found.", found)]
+    UnknownMultiplicity { span: ByteSpan, found: u64 },
     #[fail(display = "Unknown repl command `:{}` found.", command)]
     UnknownReplCommand { span: ByteSpan, command: String },
     #[fail(display = "Unexpected EOF, expected one of: {}.", expected)]
@@ -74,6 +82,10 @@ impl ParseError {
         match *self {
             ParseError::Lexer(ref err) => err.span(),
             ParseError::IdentifierExpectedInPiType { span }
+            | ParseError::ParamGroupExpectedInPiType { span }
+            | ParseError::ConstructorExpectedInPattern { span }
+            | ParseError::UnknownLevelFunction { span, .. }
+            | ParseError::UnknownMultiplicity { span, .. }
             | ParseError::UnknownReplCommand { span, .. }
             | ParseError::UnexpectedToken { span, .. }
             | ParseError::ExtraToken { span, .. } => span,
@@ -91,6 +103,24 @@ impl ParseError {
                         Label::new_primary(span).with_message("ill-formed dependent function type"),
                     )
             },
+            ParseError::ParamGroupExpectedInPiType { span } => Diagnostic::new_error(
+                "parameter group expected when parsing dependent function type",
+            ).with_label(
+                Label::new_primary(span).with_message("expected `(x : t)` or `{x : t}` here"),
+            ),
+            ParseError::ConstructorExpectedInPattern { span } => {
+                Diagnostic::new_error("constructor expected at the head of a pattern").with_label(
+                    Label::new_primary(span).with_message("expected a constructor name here"),
+                )
+            },
+            ParseError::UnknownLevelFunction { span, ref name } => {
+                Diagnostic::new_error(format!("unknown level function `{}`", name))
+                    .with_label(Label::new_primary(span).with_message("expected `max` here"))
+            },
+            ParseError::UnknownMultiplicity { span, found } => {
+                Diagnostic::new_error(format!("unknown multiplicity `{}`", found))
+                    .with_label(Label::new_primary(span).with_message("expected `0` or `1` here"))
+            },
             ParseError::UnknownReplCommand { span, ref command } => {
                 Diagnostic::new_error(format!("unknown repl command `:{}`", command))
                     .with_label(Label::new_primary(span).with_message("unexpected command"))
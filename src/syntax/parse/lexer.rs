@@ -8,8 +8,9 @@ use unicode_xid::UnicodeXID;
 
 fn is_symbol(ch: char) -> bool {
     match ch {
-        '&' | '!' | ':' | ',' | '.' | '=' | '/' | '>' | '<' | '-' | '|' | '+' | ';' | '*' | '^'
-        | '?' => true,
+        '&' | '!' | ':' | ',' | '.' | '=' | '/' | '>' | '<' | '-' | '|' | '+' | ';' | '*' | '^' => {
+            true
+        },
         _ => false,
     }
 }
@@ -103,7 +104,9 @@ impl LexerError {
 pub enum Token<S> {
     // Data
     Ident(S),
+    LineComment(S),
     DocComment(S),
+    ModuleDocComment(S),
     ReplCommand(S),
     StringLiteral(String),
     CharLiteral(char),
@@ -113,16 +116,23 @@ pub enum Token<S> {
     // Keywords
     As,         // as
     Case,       // case
+    Data,       // data
     Else,       // else
     Extern,     // extern
     If,         // if
+    Import,     // import
     In,         // in
+    J,          // J
     Let,        // let
     Of,         // of
+    Partial,    // partial
+    Private,    // private
     Record,     // record
     RecordType, // Record
+    Refl,       // refl
     Then,       // then
     Type,       // Type
+    Where,      // where
 
     // Symbols
     BSlash,    // \
@@ -134,6 +144,7 @@ pub enum Token<S> {
     Equal,     // =
     LArrow,    // ->
     LFatArrow, // =>
+    Plus,      // +
     Question,  // ?
     Semi,      // ;
 
@@ -150,7 +161,9 @@ impl<S: fmt::Display> fmt::Display for Token<S> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Token::Ident(ref name) => write!(f, "{}", name),
+            Token::LineComment(ref comment) => write!(f, "--{}", comment),
             Token::DocComment(ref comment) => write!(f, "||| {}", comment),
+            Token::ModuleDocComment(ref comment) => write!(f, "||! {}", comment),
             Token::ReplCommand(ref command) => write!(f, ":{}", command),
             Token::StringLiteral(ref value) => write!(f, "\"{}\"", value),
             Token::CharLiteral(ref value) => write!(f, "'{}'", value),
@@ -158,16 +171,23 @@ impl<S: fmt::Display> fmt::Display for Token<S> {
             Token::FloatLiteral(ref value) => write!(f, "{}", value),
             Token::As => write!(f, "as"),
             Token::Case => write!(f, "case"),
+            Token::Data => write!(f, "data"),
             Token::Else => write!(f, "else"),
             Token::Extern => write!(f, "extern"),
             Token::If => write!(f, "if"),
+            Token::Import => write!(f, "import"),
             Token::In => write!(f, "in"),
+            Token::J => write!(f, "J"),
             Token::Let => write!(f, "let"),
             Token::Of => write!(f, "of"),
+            Token::Partial => write!(f, "partial"),
+            Token::Private => write!(f, "private"),
             Token::Record => write!(f, "record"),
             Token::RecordType => write!(f, "Record"),
+            Token::Refl => write!(f, "refl"),
             Token::Then => write!(f, "then"),
             Token::Type => write!(f, "Type"),
+            Token::Where => write!(f, "where"),
             Token::BSlash => write!(f, "\\"),
             Token::Caret => write!(f, "^"),
             Token::Colon => write!(f, ":"),
@@ -177,6 +197,7 @@ impl<S: fmt::Display> fmt::Display for Token<S> {
             Token::Equal => write!(f, "="),
             Token::LFatArrow => write!(f, "=>"),
             Token::LArrow => write!(f, "->"),
+            Token::Plus => write!(f, "+"),
             Token::Question => write!(f, "?"),
             Token::Semi => write!(f, ";"),
             Token::LParen => write!(f, "("),
@@ -193,7 +214,9 @@ impl<'input> From<Token<&'input str>> for Token<String> {
     fn from(src: Token<&'input str>) -> Token<String> {
         match src {
             Token::Ident(name) => Token::Ident(name.to_owned()),
+            Token::LineComment(comment) => Token::LineComment(comment.to_owned()),
             Token::DocComment(comment) => Token::DocComment(comment.to_owned()),
+            Token::ModuleDocComment(comment) => Token::ModuleDocComment(comment.to_owned()),
             Token::ReplCommand(command) => Token::ReplCommand(command.to_owned()),
             Token::StringLiteral(value) => Token::StringLiteral(value),
             Token::CharLiteral(value) => Token::CharLiteral(value),
@@ -201,16 +224,23 @@ impl<'input> From<Token<&'input str>> for Token<String> {
             Token::FloatLiteral(value) => Token::FloatLiteral(value),
             Token::As => Token::As,
             Token::Case => Token::Case,
+            Token::Data => Token::Data,
             Token::Else => Token::Else,
             Token::Extern => Token::Extern,
             Token::If => Token::If,
+            Token::Import => Token::Import,
             Token::In => Token::In,
+            Token::J => Token::J,
             Token::Let => Token::Let,
             Token::Of => Token::Of,
+            Token::Partial => Token::Partial,
+            Token::Private => Token::Private,
             Token::Record => Token::Record,
             Token::RecordType => Token::RecordType,
+            Token::Refl => Token::Refl,
             Token::Then => Token::Then,
             Token::Type => Token::Type,
+            Token::Where => Token::Where,
             Token::BSlash => Token::BSlash,
             Token::Caret => Token::Caret,
             Token::Colon => Token::Colon,
@@ -220,6 +250,7 @@ impl<'input> From<Token<&'input str>> for Token<String> {
             Token::Equal => Token::Equal,
             Token::LFatArrow => Token::LFatArrow,
             Token::LArrow => Token::LArrow,
+            Token::Plus => Token::Plus,
             Token::Question => Token::Question,
             Token::Semi => Token::Semi,
             Token::LParen => Token::LParen,
@@ -329,17 +360,22 @@ impl<'input> Lexer<'input> {
         }
     }
 
-    /// Consume a doc comment
-    fn doc_comment(&mut self, start: ByteIndex) -> SpannedToken<'input> {
+    /// Consume a doc comment, beginning with the given prefix
+    fn doc_comment(
+        &mut self,
+        start: ByteIndex,
+        prefix: &str,
+        token: fn(&'input str) -> Token<&'input str>,
+    ) -> SpannedToken<'input> {
         let (end, mut comment) =
-            self.take_until(start + ByteOffset::from_str("|||"), |ch| ch == '\n');
+            self.take_until(start + ByteOffset::from_str(prefix), |ch| ch == '\n');
 
         // Skip preceding space
         if comment.starts_with(' ') {
             comment = &comment[1..];
         }
 
-        (start, Token::DocComment(comment), end)
+        (start, token(comment), end)
     }
 
     /// Consume an identifier
@@ -349,16 +385,23 @@ impl<'input> Lexer<'input> {
         let token = match ident {
             "as" => Token::As,
             "case" => Token::Case,
+            "data" => Token::Data,
             "else" => Token::Else,
             "extern" => Token::Extern,
             "if" => Token::If,
+            "import" => Token::Import,
             "in" => Token::In,
+            "J" => Token::J,
             "let" => Token::Let,
             "of" => Token::Of,
+            "partial" => Token::Partial,
+            "private" => Token::Private,
             "record" => Token::Record,
             "Record" => Token::RecordType,
+            "refl" => Token::Refl,
             "then" => Token::Then,
             "Type" => Token::Type,
+            "where" => Token::Where,
             ident => Token::Ident(ident),
         };
 
@@ -473,17 +516,26 @@ impl<'input> Iterator for Lexer<'input> {
                         "=" => Ok((start, Token::Equal, end)),
                         "->" => Ok((start, Token::LArrow, end)),
                         "=>" => Ok((start, Token::LFatArrow, end)),
-                        "?" => Ok((start, Token::Question, end)),
+                        "+" => Ok((start, Token::Plus, end)),
                         ";" => Ok((start, Token::Semi, end)),
-                        symbol if symbol.starts_with("|||") => Ok(self.doc_comment(start)),
+                        symbol if symbol.starts_with("|||") => {
+                            Ok(self.doc_comment(start, "|||", Token::DocComment))
+                        },
+                        symbol if symbol.starts_with("||!") => {
+                            Ok(self.doc_comment(start, "||!", Token::ModuleDocComment))
+                        },
                         symbol if symbol.starts_with("--") => {
-                            self.take_until(start, |ch| ch == '\n');
-                            continue;
+                            let (end, comment) = self
+                                .take_until(start + ByteOffset::from_str("--"), |ch| ch == '\n');
+                            Ok((start, Token::LineComment(comment), end))
                         },
                         _ => Err(LexerError::UnexpectedCharacter { start, found: ch }),
                     }
                 },
                 '\\' => Ok((start, Token::BSlash, end)),
+                // Holes are lexed on their own so that they can be directly
+                // followed by other symbols, eg. `?;`
+                '?' => Ok((start, Token::Question, end)),
                 '(' => Ok((start, Token::LParen, end)),
                 ')' => Ok((start, Token::RParen, end)),
                 '{' => Ok((start, Token::LBrace, end)),
@@ -541,6 +593,7 @@ mod tests {
     fn comment() {
         test! {
             "       -- hello this is dog\n  ",
+            "       ~~~~~~~~~~~~~~~~~~~~     " => Token::LineComment(" hello this is dog"),
         };
     }
 
@@ -552,6 +605,14 @@ mod tests {
         };
     }
 
+    #[test]
+    fn module_doc_comment() {
+        test! {
+            "       ||! hello this is module",
+            "       ~~~~~~~~~~~~~~~~~~~~~~~~" => Token::ModuleDocComment("hello this is module"),
+        };
+    }
+
     #[test]
     fn string_literal() {
         test! {
@@ -589,36 +650,55 @@ mod tests {
     #[test]
     fn keywords() {
         test! {
-            "  as case else extern if in let of record Record then Type  ",
-            "  ~~                                                        " => Token::As,
-            "     ~~~~                                                   " => Token::Case,
-            "          ~~~~                                              " => Token::Else,
-            "               ~~~~~~                                       " => Token::Extern,
-            "                      ~~                                    " => Token::If,
-            "                         ~~                                 " => Token::In,
-            "                            ~~~                             " => Token::Let,
-            "                                ~~                          " => Token::Of,
-            "                                   ~~~~~~                   " => Token::Record,
-            "                                          ~~~~~~            " => Token::RecordType,
-            "                                                 ~~~~       " => Token::Then,
-            "                                                      ~~~~  " => Token::Type,
+            "  as case data else extern if import in J let of partial private record Record refl then Type where  ",
+            "  ~~                                                                                                 " => Token::As,
+            "     ~~~~                                                                                            " => Token::Case,
+            "          ~~~~                                                                                       " => Token::Data,
+            "               ~~~~                                                                                  " => Token::Else,
+            "                    ~~~~~~                                                                           " => Token::Extern,
+            "                           ~~                                                                        " => Token::If,
+            "                              ~~~~~~                                                                 " => Token::Import,
+            "                                     ~~                                                              " => Token::In,
+            "                                        ~                                                            " => Token::J,
+            "                                          ~~~                                                        " => Token::Let,
+            "                                              ~~                                                     " => Token::Of,
+            "                                                 ~~~~~~~                                             " => Token::Partial,
+            "                                                         ~~~~~~~                                     " => Token::Private,
+            "                                                                 ~~~~~~                              " => Token::Record,
+            "                                                                        ~~~~~~                       " => Token::RecordType,
+            "                                                                               ~~~~                  " => Token::Refl,
+            "                                                                                    ~~~~             " => Token::Then,
+            "                                                                                         ~~~~        " => Token::Type,
+            "                                                                                              ~~~~~  " => Token::Where,
         };
     }
 
     #[test]
     fn symbols() {
         test! {
-            r" \ ^ : , .. = -> => ? ; ",
-            r" ~                      " => Token::BSlash,
-            r"   ~                    " => Token::Caret,
-            r"     ~                  " => Token::Colon,
-            r"       ~                " => Token::Comma,
-            r"         ~~             " => Token::DotDot,
-            r"            ~           " => Token::Equal,
-            r"              ~~        " => Token::LArrow,
-            r"                 ~~     " => Token::LFatArrow,
-            r"                    ~   " => Token::Question,
-            r"                      ~ " => Token::Semi,
+            r" \ ^ : , .. = -> => + ? ; ",
+            r" ~                        " => Token::BSlash,
+            r"   ~                      " => Token::Caret,
+            r"     ~                    " => Token::Colon,
+            r"       ~                  " => Token::Comma,
+            r"         ~~               " => Token::DotDot,
+            r"            ~             " => Token::Equal,
+            r"              ~~          " => Token::LArrow,
+            r"                 ~~       " => Token::LFatArrow,
+            r"                    ~     " => Token::Plus,
+            r"                      ~   " => Token::Question,
+            r"                        ~ " => Token::Semi,
+        }
+    }
+
+    #[test]
+    fn hole_followed_by_symbol() {
+        test! {
+            r" ?; ?-> ",
+            r" ~      " => Token::Question,
+            r"  ~     " => Token::Semi,
+            r"    ~   " => Token::Question,
+            r"     ~~ " => Token::LArrow,
         }
     }
 
@@ -0,0 +1,642 @@
+//! Lowering of the lossless syntax tree into the concrete syntax
+//!
+//! The grammar is deliberately loose in places, for example in the binders of
+//! pi types, so some errors can only be found here. These are reported, and
+//! the offending part of the tree is replaced with an error in the concrete
+//! syntax.
+
+use codespan::{ByteIndex, ByteSpan};
+
+use syntax::concrete::{self, Arg, DataConstructor, Item, Level, Literal, Module, Pattern, Term};
+use syntax::concrete::{RecordField, RecordTypeField, ReplCommand};
+use syntax::cst::{NodeKind, SyntaxNode, TokenKind};
+use syntax::parse::{ParseError, Token};
+use syntax::{Multiplicity, Plicity};
+
+pub fn repl_command(node: &SyntaxNode, errors: &mut Vec<ParseError>) -> ReplCommand {
+    if node.kind() == NodeKind::Error {
+        return ReplCommand::Error(span(node));
+    }
+
+    let command = tokens(node, |token| match *token {
+        Token::ReplCommand(ref command) => Some(command.clone()),
+        _ => None,
+    }).into_iter()
+    .next();
+    let name = idents(node).into_iter().next();
+    let term = node.children().first().map(|term| Box::new(self::term(term, errors)));
+
+    let (command_span, command) = match command {
+        None => return term.map_or(ReplCommand::NoOp, ReplCommand::Eval),
+        Some(command) => command,
+    };
+
+    match (command.as_str(), name, term) {
+        ("?", None, None) | ("h", None, None) | ("help", None, None) => ReplCommand::Help,
+        ("q", None, None) | ("quit", None, None) => ReplCommand::Quit,
+        ("raw", None, Some(term)) => ReplCommand::Raw(term),
+        ("core", None, Some(term)) => ReplCommand::Core(term),
+        ("t", None, Some(term)) | ("type", None, Some(term)) => ReplCommand::TypeOf(term),
+        ("let", Some((_, name)), Some(term)) => ReplCommand::Let(name, term),
+        _ => {
+            errors.push(ParseError::UnknownReplCommand {
+                span: command_span,
+                command,
+            });
+            ReplCommand::Error(span(node))
+        },
+    }
+}
+
+pub fn module(node: &SyntaxNode, errors: &mut Vec<ParseError>) -> Module {
+    match node.kind() {
+        NodeKind::Module => {
+            let doc = tokens(node, |token| match *token {
+                Token::ModuleDocComment(ref doc) => Some(doc.clone()),
+                _ => None,
+            }).into_iter()
+            .map(|(_, doc)| doc)
+            .collect();
+            let items = node
+                .children()
+                .iter()
+                .map(|item| self::item(item, errors))
+                .collect();
+
+            Module::Valid { doc, items }
+        },
+        _ => Module::Error(span(node)),
+    }
+}
+
+fn item(node: &SyntaxNode, errors: &mut Vec<ParseError>) -> Item {
+    let children = node.children();
+
+    match node.kind() {
+        NodeKind::Declaration => Item::Declaration {
+            doc: doc_comments(node),
+            private: keyword(node, &Token::Private),
+            partial: keyword(node, &Token::Partial),
+            name: idents(node).remove(0),
+            ann: term(&children[0], errors),
+        },
+        NodeKind::Definition => {
+            // Malformed definitions still bind their name, so that it isn't
+            // reported as undefined wherever it is used
+            let (params, return_ann, body) = match definition(&children, errors) {
+                Ok(definition) => definition,
+                Err(error) => {
+                    errors.push(error);
+                    (Vec::new(), None, Term::Error(span(node)))
+                },
+            };
+
+            Item::Definition {
+                doc: doc_comments(node),
+                private: keyword(node, &Token::Private),
+                name: idents(node).remove(0),
+                params,
+                return_ann,
+                body,
+            }
+        },
+        NodeKind::Data => {
+            let start = keyword(node, &Token::Data).unwrap().start();
+            let end = keyword(node, &Token::RBrace).unwrap().end();
+
+            let params = children
+                .iter()
+                .filter(|child| child.kind() == NodeKind::DataParam)
+                .map(|param| data_param(param, errors))
+                .collect();
+            let ann = children
+                .iter()
+                .find(|child| {
+                    child.kind() != NodeKind::DataParam && child.kind() != NodeKind::DataConstructor
+                }).map(|ann| Box::new(term(ann, errors)));
+            let constructors = children
+                .iter()
+                .filter(|child| child.kind() == NodeKind::DataConstructor)
+                .map(|constructor| DataConstructor {
+                    doc: doc_comments(constructor),
+                    name: idents(constructor).remove(0),
+                    ann: term(&constructor.children()[0], errors),
+                }).collect();
+
+            Item::Data {
+                doc: doc_comments(node),
+                private: keyword(node, &Token::Private),
+                span: ByteSpan::new(start, end),
+                name: idents(node).remove(0),
+                params,
+                ann,
+                constructors,
+            }
+        },
+        NodeKind::Import => {
+            let start = keyword(node, &Token::Import).unwrap().start();
+            let path = tokens(node, |token| match *token {
+                Token::Ident(ref name) => Some(name.clone()),
+                _ => None,
+            });
+            let end = path.last().unwrap().0.end();
+
+            Item::Import {
+                doc: doc_comments(node),
+                private: keyword(node, &Token::Private),
+                span: ByteSpan::new(start, end),
+                path: path
+                    .into_iter()
+                    .map(|(span, name)| (span.start(), name))
+                    .collect(),
+            }
+        },
+        NodeKind::Error => Item::Error(span(node)),
+        kind => unreachable!("unexpected {:?} node in item", kind),
+    }
+}
+
+/// Lower the parameters, the return annotation and the body of a definition
+/// or a record field
+fn definition(
+    children: &[SyntaxNode],
+    errors: &mut Vec<ParseError>,
+) -> Result<(concrete::LamParams, Option<Box<Term>>, Term), ParseError> {
+    let (body, rest) = children.split_last().unwrap();
+    let (return_ann, params) = match rest.split_last() {
+        Some((ann, params)) if ann.kind() != NodeKind::LamParam => (Some(ann), params),
+        _ => (None, rest),
+    };
+
+    let params = params
+        .iter()
+        .map(|param| lam_param(param, errors))
+        .collect::<Result<_, _>>()?;
+    let return_ann = return_ann.map(|ann| Box::new(term(ann, errors)));
+
+    Ok((params, return_ann, term(body, errors)))
+}
+
+fn lam_param(
+    node: &SyntaxNode,
+    errors: &mut Vec<ParseError>,
+) -> Result<concrete::LamParamGroup, ParseError> {
+    let plicity = match keyword(node, &Token::LBrace) {
+        Some(_) => Plicity::Implicit,
+        None => Plicity::Explicit,
+    };
+    let multiplicity = match int_literals(node).first() {
+        Some(&(span, value)) => Some(multiplicity(span, value)?),
+        None => None,
+    };
+    let ann = node
+        .children()
+        .first()
+        .map(|ann| Box::new(term(ann, errors)));
+
+    Ok((plicity, multiplicity, idents(node), ann))
+}
+
+fn data_param(node: &SyntaxNode, errors: &mut Vec<ParseError>) -> concrete::PiParamGroup {
+    let ann = term(&node.children()[0], errors);
+
+    (Plicity::Explicit, Multiplicity::Many, idents(node), ann)
+}
+
+pub fn pattern(node: &SyntaxNode, errors: &mut Vec<ParseError>) -> Pattern {
+    let children = node.children();
+
+    match node.kind() {
+        NodeKind::Parens => Pattern::Parens(span(node), Box::new(pattern(&children[0], errors))),
+        NodeKind::Ann => Pattern::Ann(
+            Box::new(pattern(&children[0], errors)),
+            Box::new(term(&children[1], errors)),
+        ),
+        NodeKind::Literal => Pattern::Literal(literal(node)),
+        NodeKind::Name => {
+            let (name, shift) = name(node);
+            Pattern::Name(span(node), name, shift)
+        },
+        NodeKind::App => {
+            let head = pattern(&children[0], errors);
+            let args = children[1..]
+                .iter()
+                .map(|arg| pattern(arg, errors))
+                .collect();
+
+            if is_constr_head(&head) {
+                Pattern::App(Box::new(head), args)
+            } else {
+                errors.push(ParseError::ConstructorExpectedInPattern { span: head.span() });
+                Pattern::Error(span(node))
+            }
+        },
+        NodeKind::Error => Pattern::Error(span(node)),
+        kind => unreachable!("unexpected {:?} node in pattern", kind),
+    }
+}
+
+/// Constructor patterns must be headed by a plain name, possibly wrapped in
+/// parentheses
+fn is_constr_head(pattern: &Pattern) -> bool {
+    match *pattern {
+        Pattern::Parens(_, ref pattern) => is_constr_head(pattern),
+        Pattern::Name(_, _, None) => true,
+        _ => false,
+    }
+}
+
+fn level(node: &SyntaxNode) -> Result<Level, ParseError> {
+    let children = node.children();
+
+    match node.kind() {
+        NodeKind::Parens => Ok(Level::Parens(span(node), Box::new(level(&children[0])?))),
+        NodeKind::Literal => {
+            let value = int_literals(node)[0].1;
+            Ok(Level::Const(span(node), value as u32)) // FIXME: underflow?
+        },
+        NodeKind::Name => Ok(Level::Var(span(node), name(node).0)),
+        NodeKind::LevelSucc => {
+            let shift = int_literals(node)[0].1;
+            let level = level(&children[0])?;
+            Ok(Level::Succ(span(node), Box::new(level), shift as u32)) // FIXME: underflow?
+        },
+        NodeKind::LevelApp => {
+            let (span, name) = tokens(node, |token| match *token {
+                Token::Ident(ref name) => Some(name.clone()),
+                _ => None,
+            }).remove(0);
+
+            match name.as_str() {
+                "max" => {
+                    let levels = children.iter().map(level).collect::<Result<_, _>>()?;
+                    Ok(Level::Max(self::span(node), levels))
+                },
+                _ => Err(ParseError::UnknownLevelFunction { span, name }),
+            }
+        },
+        kind => unreachable!("unexpected {:?} node in level", kind),
+    }
+}
+
+/// Lower a term, replacing it with an error if it turns out to be malformed
+pub fn term(node: &SyntaxNode, errors: &mut Vec<ParseError>) -> Term {
+    match lower_term(node, errors) {
+        Ok(term) => term,
+        Err(error) => {
+            errors.push(error);
+            Term::Error(span(node))
+        },
+    }
+}
+
+fn lower_term(node: &SyntaxNode, errors: &mut Vec<ParseError>) -> Result<Term, ParseError> {
+    let children = node.children();
+
+    Ok(match node.kind() {
+        NodeKind::Parens => Term::Parens(span(node), Box::new(term(&children[0], errors))),
+        NodeKind::Ann => Term::Ann(
+            Box::new(term(&children[0], errors)),
+            Box::new(term(&children[1], errors)),
+        ),
+        NodeKind::Extern => {
+            let (name_span, name) = tokens(node, |token| match *token {
+                Token::StringLiteral(ref name) => Some(name.clone()),
+                _ => None,
+            }).remove(0);
+            let ty = term(&children[0], errors);
+
+            Term::Extern(span(node), name_span, name, Box::new(ty))
+        },
+        NodeKind::Universe => match children.first() {
+            None => Term::Universe(span(node), None),
+            Some(level) => Term::Universe(span(node), Some(self::level(level)?)),
+        },
+        NodeKind::Literal => Term::Literal(literal(node)),
+        NodeKind::Array => Term::Array(
+            span(node),
+            children.iter().map(|elem| term(elem, errors)).collect(),
+        ),
+        NodeKind::Hole => Term::Hole(span(node)),
+        NodeKind::Name => {
+            let (name, shift) = name(node);
+            Term::Name(span(node), name, shift)
+        },
+        NodeKind::Lam => {
+            let (body, params) = children.split_last().unwrap();
+            let params = match idents(node).into_iter().next() {
+                // A lambda with a single annotated parameter: `\x : t => body`
+                Some(name) => {
+                    let ann = term(&params[0], errors);
+                    vec![(Plicity::Explicit, None, vec![name], Some(Box::new(ann)))]
+                },
+                None => params
+                    .iter()
+                    .map(|param| lam_param(param, errors))
+                    .collect::<Result<_, _>>()?,
+            };
+
+            Term::Lam(span(node).start(), params, Box::new(term(body, errors)))
+        },
+        NodeKind::Arrow => {
+            let binder = term(&children[0], errors);
+            let body = term(&children[1], errors);
+
+            reparse_pi_type_hack(span(node), binder, body)?
+        },
+        NodeKind::Pi => {
+            let (body, binders) = children.split_last().unwrap();
+            let binders = binders.iter().map(|binder| arg(binder, errors)).collect();
+            let body = term(body, errors);
+
+            reparse_implicit_pi_type_hack(span(node), binders, body)?
+        },
+        NodeKind::App => Term::App(
+            Box::new(term(&children[0], errors)),
+            children[1..].iter().map(|arg| self::arg(arg, errors)).collect(),
+        ),
+        NodeKind::Let => {
+            let (body, items) = children.split_last().unwrap();
+            let items = items.iter().map(|item| self::item(item, errors)).collect();
+
+            Term::Let(span(node).start(), items, Box::new(term(body, errors)))
+        },
+        NodeKind::If => Term::If(
+            span(node).start(),
+            Box::new(term(&children[0], errors)),
+            Box::new(term(&children[1], errors)),
+            Box::new(term(&children[2], errors)),
+        ),
+        NodeKind::Case => {
+            let head = term(&children[0], errors);
+            let arms = children[1..]
+                .iter()
+                .map(|arm| {
+                    let arm = arm.children();
+                    (pattern(&arm[0], errors), term(&arm[1], errors))
+                }).collect();
+
+            Term::Case(span(node), Box::new(head), arms)
+        },
+        NodeKind::Eq => Term::Eq(
+            Box::new(term(&children[0], errors)),
+            Box::new(term(&children[1], errors)),
+        ),
+        NodeKind::Refl => Term::Refl(span(node)),
+        NodeKind::J => Term::J(
+            span(node).start(),
+            Box::new(term(&children[0], errors)),
+            Box::new(term(&children[1], errors)),
+            Box::new(term(&children[2], errors)),
+        ),
+        NodeKind::RecordType => {
+            let fields = children
+                .iter()
+                .map(|field| {
+                    let mut names = idents(field).into_iter();
+                    RecordTypeField {
+                        doc: doc_comments(field),
+                        label: names.next().unwrap(),
+                        binder: names.next(),
+                        ann: term(&field.children()[0], errors),
+                    }
+                }).collect();
+
+            Term::RecordType(span(node), fields)
+        },
+        NodeKind::Record => {
+            let mut fields = Vec::with_capacity(children.len());
+            for field in &children {
+                let (params, return_ann, term) = definition(&field.children(), errors)?;
+                fields.push(RecordField {
+                    label: idents(field).remove(0),
+                    params,
+                    return_ann,
+                    term,
+                });
+            }
+
+            Term::Record(span(node), fields)
+        },
+        NodeKind::Proj => {
+            let (label_start, label) = idents(node).remove(0);
+            Term::Proj(Box::new(term(&children[0], errors)), label_start, label)
+        },
+        NodeKind::Error => Term::Error(span(node)),
+        kind => unreachable!("unexpected {:?} node in term", kind),
+    })
+}
+
+fn arg(node: &SyntaxNode, errors: &mut Vec<ParseError>) -> Arg {
+    match node.kind() {
+        NodeKind::ImplicitArg => Arg::Implicit(span(node), term(&node.children()[0], errors)),
+        _ => Arg::Explicit(term(node, errors)),
+    }
+}
+
+fn literal(node: &SyntaxNode) -> Literal {
+    let span = span(node);
+    let token = node
+        .tokens()
+        .into_iter()
+        .find(|token| !token.kind().is_trivia())
+        .unwrap();
+
+    match *token.kind() {
+        TokenKind::Token(Token::StringLiteral(ref value)) => Literal::String(span, value.clone()),
+        TokenKind::Token(Token::CharLiteral(value)) => Literal::Char(span, value),
+        TokenKind::Token(Token::DecLiteral(value)) => Literal::Int(span, value),
+        TokenKind::Token(Token::FloatLiteral(value)) => Literal::Float(span, value),
+        ref kind => unreachable!("unexpected {:?} token in literal", kind),
+    }
+}
+
+/// The name and the level shift of a name node: `x^1`
+fn name(node: &SyntaxNode) -> (String, Option<u32>) {
+    let (_, name) = idents(node).remove(0);
+    let shift = int_literals(node).first().map(|&(_, shift)| shift as u32); // FIXME: underflow?
+
+    (name, shift)
+}
+
+/// The span of source code that a node was lowered from
+///
+/// Only the root of the tree can begin or end with trivia, so the spans of
+/// the other nodes can be used as they are. If the file could not be parsed
+/// at all, the error covers the whole file.
+fn span(node: &SyntaxNode) -> ByteSpan {
+    match (node.parent(), node.kind()) {
+        (None, NodeKind::Error) => node.span(),
+        (None, _) => node.trimmed_span(),
+        (Some(_), _) => node.span(),
+    }
+}
+
+/// Extract the values of the tokens that are direct children of a node
+fn tokens<T, F>(node: &SyntaxNode, value: F) -> Vec<(ByteSpan, T)>
+where
+    F: Fn(&Token<String>) -> Option<T>,
+{
+    node.tokens()
+        .iter()
+        .filter_map(|token| match *token.kind() {
+            TokenKind::Token(ref kind) => value(kind).map(|value| (token.span(), value)),
+            TokenKind::Whitespace | TokenKind::Error => None,
+        }).collect()
+}
+
+/// The span of the first occurrence of a token in the direct children of a
+/// node, if there is one
+fn keyword(node: &SyntaxNode, keyword: &Token<String>) -> Option<ByteSpan> {
+    tokens(node, |token| if token == keyword { Some(()) } else { None })
+        .first()
+        .map(|&(span, ())| span)
+}
+
+fn idents(node: &SyntaxNode) -> Vec<(ByteIndex, String)> {
+    tokens(node, |token| match *token {
+        Token::Ident(ref name) => Some(name.clone()),
+        _ => None,
+    }).into_iter()
+    .map(|(span, name)| (span.start(), name))
+    .collect()
+}
+
+fn int_literals(node: &SyntaxNode) -> Vec<(ByteSpan, u64)> {
+    tokens(node, |token| match *token {
+        Token::DecLiteral(value) => Some(value),
+        _ => None,
+    })
+}
+
+fn doc_comments(node: &SyntaxNode) -> Vec<String> {
+    tokens(node, |token| match *token {
+        Token::DocComment(ref doc) => Some(doc.clone()),
+        _ => None,
+    }).into_iter()
+    .map(|(_, doc)| doc)
+    .collect()
+}
+
+/// This is an ugly hack that cobbles together a pi type from a binder term and
+/// a body. See the comments on the `PiTerm` rule in the `grammar.lalrpop` for
+/// more information.
+fn reparse_pi_type_hack(
+    span: ByteSpan,
+    binder: Term,
+    body: Term,
+) -> Result<Term, ParseError> {
+    match binder {
+        Term::App(ref head, ref args) => {
+            use std::iter;
+
+            let mut binders = Vec::with_capacity(args.len() + 1);
+            let head = Arg::Explicit((**head).clone());
+
+            for next in iter::once(&head).chain(args).map(pi_binder) {
+                match next? {
+                    Some(param_group) => binders.push(param_group),
+                    None => return Ok(Term::Arrow(Box::new(binder.clone()), Box::new(body))),
+                }
+            }
+
+            Ok(Term::Pi(span.start(), binders, Box::new(body)))
+        },
+        binder => match pi_binder(&Arg::Explicit(binder.clone()))? {
+            Some(binder) => Ok(Term::Pi(span.start(), vec![binder], Box::new(body))),
+            None => Ok(Term::Arrow(binder.into(), Box::new(body))),
+        },
+    }
+}
+
+/// Cobbles together a pi type whose first parameter group is implicit. Unlike
+/// `reparse_pi_type_hack`, we can't fall back to a non-dependent function
+/// type, so every binder must be a valid parameter group.
+fn reparse_implicit_pi_type_hack(
+    span: ByteSpan,
+    binders: Vec<Arg>,
+    body: Term,
+) -> Result<Term, ParseError> {
+    let mut param_groups = Vec::with_capacity(binders.len());
+
+    for binder in &binders {
+        match pi_binder(binder)? {
+            Some(param_group) => param_groups.push(param_group),
+            None => {
+                return Err(ParseError::ParamGroupExpectedInPiType {
+                    span: binder.span(),
+                });
+            },
+        }
+    }
+
+    Ok(Term::Pi(span.start(), param_groups, Box::new(body)))
+}
+
+fn pi_binder(binder: &Arg) -> Result<Option<concrete::PiParamGroup>, ParseError> {
+    let (plicity, term) = match *binder {
+        Arg::Explicit(Term::Parens(_, ref term)) => (Plicity::Explicit, &**term),
+        Arg::Explicit(_) => return Ok(None),
+        Arg::Implicit(_, ref term) => (Plicity::Implicit, term),
+    };
+
+    match *term {
+        Term::Ann(ref params, ref ann) => {
+            let mut names = Vec::new();
+            let multiplicity = match **params {
+                // The multiplicity will have been parsed as the head of an
+                // application, for example `(1 x y : t)`
+                Term::App(ref head, ref args) => match **head {
+                    Term::Literal(Literal::Int(span, value)) => {
+                        pi_arg_names(args, &mut names)?;
+                        multiplicity(span, value)?
+                    },
+                    _ => {
+                        pi_param_names(&**params, &mut names)?;
+                        Multiplicity::Many
+                    },
+                },
+                _ => {
+                    pi_param_names(&**params, &mut names)?;
+                    Multiplicity::Many
+                },
+            };
+            Ok(Some((plicity, multiplicity, names, (**ann).clone())))
+        },
+        _ => Ok(None),
+    }
+}
+
+fn multiplicity(span: ByteSpan, value: u64) -> Result<Multiplicity, ParseError> {
+    match value {
+        0 => Ok(Multiplicity::Zero),
+        1 => Ok(Multiplicity::One),
+        found => Err(ParseError::UnknownMultiplicity { span, found }),
+    }
+}
+
+fn pi_param_names(term: &Term, names: &mut Vec<(ByteIndex, String)>) -> Result<(), ParseError> {
+    match *term {
+        Term::Name(span, ref name, None) => names.push((span.start(), name.clone())),
+        Term::App(ref head, ref args) => {
+            pi_param_names(head, names)?;
+            pi_arg_names(args, names)?;
+        },
+        _ => {
+            return Err(ParseError::IdentifierExpectedInPiType { span: term.span() });
+        },
+    }
+    Ok(())
+}
+
+fn pi_arg_names(args: &[Arg], names: &mut Vec<(ByteIndex, String)>) -> Result<(), ParseError> {
+    for arg in args {
+        match *arg {
+            Arg::Explicit(ref arg) => pi_param_names(arg, names)?,
+            Arg::Implicit(span, _) => {
+                return Err(ParseError::IdentifierExpectedInPiType { span });
+            },
+        }
+    }
+    Ok(())
+}
@@ -1,38 +1,57 @@
 //! Parser utilities
+//!
+//! Source files are first parsed into a lossless syntax tree, which is then
+//! lowered into the concrete syntax.
 
-use codespan::{ByteIndex, ByteSpan, FileMap};
-use lalrpop_util::ParseError as LalrpopError;
+use codespan::{ByteIndex, ByteOffset, ByteSpan, FileMap};
+use std::iter::Peekable;
+use std::rc::Rc;
 
 use syntax::concrete;
-use syntax::parse::lexer::Lexer;
+use syntax::cst::{GreenElement, GreenNode, GreenToken, NodeKind, SyntaxNode, TokenKind};
 
 mod errors;
 mod lexer;
+mod lower;
 
 pub use self::errors::{ExpectedTokens, ParseError};
-pub use self::lexer::{LexerError, Token};
+pub use self::lexer::{Lexer, LexerError, Token};
 
 macro_rules! parser {
-    ($name:ident, $output:ident, $parser_name:ident) => {
-        pub fn $name<'input>(filemap: &'input FileMap) -> (concrete::$output, Vec<ParseError>) {
+    ($name:ident, $tree_name:ident, $output:ident, $parser_name:ident) => {
+        pub fn $tree_name(filemap: &FileMap) -> (SyntaxNode, Vec<ParseError>) {
             let mut errors = Vec::new();
-            let lexer = Lexer::new(filemap).map(|x| x.map_err(ParseError::from));
-            let value = grammar::$parser_name::new()
+            let tokens = Lexer::new(filemap).collect::<Vec<_>>();
+            let lexer = tokens
+                .iter()
+                .cloned()
+                .filter(|x| match *x {
+                    Ok((_, Token::LineComment(_), _)) => false,
+                    _ => true,
+                }).map(|x| x.map_err(ParseError::from));
+            let node = grammar::$parser_name::new()
                 .parse(&mut errors, filemap, lexer)
                 .unwrap_or_else(|err| {
                     errors.push(errors::from_lalrpop(filemap, err));
-                    concrete::$output::Error(filemap.span())
+                    Node::new(NodeKind::Error, filemap.span().start(), filemap.span().end(), vec![])
                 });
 
+            (build_tree(filemap, node, &tokens), errors)
+        }
+
+        pub fn $name(filemap: &FileMap) -> (concrete::$output, Vec<ParseError>) {
+            let (tree, mut errors) = $tree_name(filemap);
+            let value = lower::$name(&tree, &mut errors);
+
             (value, errors)
         }
     };
 }
 
-parser!(repl_command, ReplCommand, ReplCommandParser);
-parser!(module, Module, ModuleParser);
-parser!(pattern, Pattern, PatternParser);
-parser!(term, Term, TermParser);
+parser!(repl_command, repl_command_tree, ReplCommand, ReplCommandParser);
+parser!(module, module_tree, Module, ModuleParser);
+parser!(pattern, pattern_tree, Pattern, PatternParser);
+parser!(term, term_tree, Term, TermParser);
 
 mod grammar {
     #![cfg_attr(feature = "cargo-clippy", allow(clippy))]
@@ -40,73 +59,185 @@ mod grammar {
     include!(concat!(env!("OUT_DIR"), "/syntax/parse/grammar.rs"));
 }
 
-/// This is an ugly hack that cobbles together a pi type from a binder term and
-/// a body. See the comments on the `PiTerm` rule in the `grammar.lalrpop` for
-/// more information.
-fn reparse_pi_type_hack<L, T>(
+/// A node of the syntax tree, as recognised by the grammar
+///
+/// Only the kinds and the spans of the nodes are recorded while parsing. The
+/// tokens of the file, including whitespace and comments, are woven into the
+/// nodes afterwards by `build_tree`.
+struct Node {
+    kind: NodeKind,
     span: ByteSpan,
-    binder: concrete::Term,
-    body: concrete::Term,
-) -> Result<concrete::Term, LalrpopError<L, T, ParseError>> {
-    use syntax::concrete::Term;
-
-    fn pi_binder<L, T>(
-        binder: &Term,
-    ) -> Result<Option<concrete::PiParamGroup>, LalrpopError<L, T, ParseError>> {
-        match *binder {
-            Term::Parens(_, ref term) => match **term {
-                Term::Ann(ref params, ref ann) => {
-                    let mut names = Vec::new();
-                    param_names(&**params, &mut names)?;
-                    Ok(Some((names, (**ann).clone())))
-                },
-                _ => Ok(None),
-            },
-            _ => Ok(None),
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn new(kind: NodeKind, start: ByteIndex, end: ByteIndex, children: Vec<Node>) -> Node {
+        Node {
+            kind,
+            span: ByteSpan::new(start, end),
+            children,
         }
     }
+}
 
-    fn param_names<L, T>(
-        term: &Term,
-        names: &mut Vec<(ByteIndex, String)>,
-    ) -> Result<(), LalrpopError<L, T, ParseError>> {
-        match *term {
-            Term::Name(span, ref name, None) => names.push((span.start(), name.clone())),
-            Term::App(ref head, ref args) => {
-                param_names(head, names)?;
-                for arg in args {
-                    param_names(arg, names)?;
-                }
-            },
-            _ => {
-                return Err(LalrpopError::User {
-                    error: ParseError::IdentifierExpectedInPiType { span: term.span() },
-                });
+/// Build a lossless syntax tree by weaving the tokens of a file into the
+/// nodes that were recognised by the grammar
+///
+/// Any source code that was skipped by the lexer, either because it was
+/// whitespace or because it could not be lexed, becomes a trivia token.
+fn build_tree(
+    filemap: &FileMap,
+    root: Node,
+    tokens: &[Result<lexer::SpannedToken, LexerError>],
+) -> SyntaxNode {
+    let span = filemap.span();
+    let slice = |start, end| filemap.src_slice(ByteSpan::new(start, end)).unwrap();
+
+    let mut green_tokens = Vec::new();
+    let mut prev_end = span.start();
+    for &(start, ref token, end) in tokens.iter().filter_map(|token| token.as_ref().ok()) {
+        push_trivia(&mut green_tokens, prev_end, slice(prev_end, start));
+        green_tokens.push((
+            start,
+            GreenToken {
+                kind: TokenKind::Token(token.clone().into()),
+                text: slice(start, end).to_owned(),
             },
+        ));
+        prev_end = end;
+    }
+    push_trivia(&mut green_tokens, prev_end, slice(prev_end, span.end()));
+
+    let significant = tokens
+        .iter()
+        .filter_map(|token| match *token {
+            Ok((_, Token::LineComment(_), _)) | Err(_) => None,
+            Ok((start, _, end)) => Some(ByteSpan::new(start, end)),
+        }).collect::<Vec<_>>();
+    let root = Node {
+        span,
+        ..trim_spans(root, &significant)
+    };
+    let green = weave(root, &mut green_tokens.into_iter().peekable());
+
+    SyntaxNode::new_root(Rc::new(green), span.start())
+}
+
+/// Shrink the spans of a node and its children to fit the significant tokens
+/// that they contain, so that any trivia at their edges is left to their
+/// parents
+///
+/// This is needed because the start of a rule that begins with an empty
+/// match, like a missing doc comment, is placed at the end of the previous
+/// token by the grammar.
+fn trim_spans(node: Node, tokens: &[ByteSpan]) -> Node {
+    let start = node.span.start();
+    let end = node.span.end();
+
+    let first = tokens
+        .binary_search_by(|span| span.start().cmp(&start))
+        .unwrap_or_else(|index| index);
+    let last = tokens
+        .binary_search_by(|span| span.end().cmp(&end))
+        .map(|index| index + 1)
+        .unwrap_or_else(|index| index);
+
+    let span = if first < last {
+        tokens[first].to(tokens[last - 1])
+    } else {
+        ByteSpan::new(start, start)
+    };
+    let children = node
+        .children
+        .into_iter()
+        .map(|child| trim_spans(child, tokens))
+        .collect();
+
+    Node {
+        kind: node.kind,
+        span,
+        children,
+    }
+}
+
+/// Split the source code between two tokens into runs of whitespace and runs
+/// of source code that could not be lexed
+fn push_trivia(tokens: &mut Vec<(ByteIndex, GreenToken)>, start: ByteIndex, src: &str) {
+    let mut run_start = 0;
+    for (index, ch) in src.char_indices() {
+        let prev_is_whitespace = src[run_start..].starts_with(char::is_whitespace);
+        if index != run_start && ch.is_whitespace() != prev_is_whitespace {
+            push_trivia_run(tokens, start, src, run_start, index);
+            run_start = index;
         }
-        Ok(())
+    }
+    push_trivia_run(tokens, start, src, run_start, src.len());
+}
+
+fn push_trivia_run(
+    tokens: &mut Vec<(ByteIndex, GreenToken)>,
+    start: ByteIndex,
+    src: &str,
+    run_start: usize,
+    run_end: usize,
+) {
+    let run = &src[run_start..run_end];
+    if run.is_empty() {
+        return;
     }
 
-    match binder {
-        Term::App(ref head, ref args) => {
-            use std::iter;
+    let kind = if run.starts_with(char::is_whitespace) {
+        TokenKind::Whitespace
+    } else {
+        TokenKind::Error
+    };
+    tokens.push((
+        start + ByteOffset::from_str(&src[..run_start]),
+        GreenToken {
+            kind,
+            text: run.to_owned(),
+        },
+    ));
+}
 
-            let mut binders = Vec::with_capacity(args.len() + 1);
+/// Weave the tokens that fall within the span of a node into it, along with
+/// its children
+fn weave<I>(node: Node, tokens: &mut Peekable<I>) -> GreenNode
+where
+    I: Iterator<Item = (ByteIndex, GreenToken)>,
+{
+    let mut elements = Vec::new();
+    let mut children = node.children.into_iter().peekable();
 
-            for next in iter::once(&**head).chain(args).map(pi_binder) {
-                match next? {
-                    Some((names, ann)) => binders.push((names, ann)),
-                    None => return Ok(Term::Arrow(Box::new(binder.clone()), Box::new(body))),
-                }
+    loop {
+        let next_start = tokens.peek().map(|&(start, _)| start);
+        let child_is_next = match (children.peek(), next_start) {
+            (Some(child), Some(start)) => child.span.start() <= start,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+
+        if child_is_next {
+            let child = children.next().unwrap();
+            elements.push(GreenElement::Node(Rc::new(weave(child, tokens))));
+        } else {
+            match next_start {
+                Some(start) if start < node.span.end() => {
+                    let (_, token) = tokens.next().unwrap();
+                    elements.push(GreenElement::Token(Rc::new(token)));
+                },
+                _ => break,
             }
+        }
+    }
 
-            Ok(Term::Pi(span.start(), binders, Box::new(body)))
-        },
-        binder => match pi_binder(&binder)? {
-            Some(binder) => Ok(Term::Pi(span.start(), vec![binder], Box::new(body))),
-            None => Ok(Term::Arrow(binder.into(), Box::new(body))),
-        },
+    // Children that end up outside of their parent should not be possible,
+    // but are kept anyway to avoid losing any of the tree
+    for child in children {
+        elements.push(GreenElement::Node(Rc::new(weave(child, tokens))));
     }
+
+    GreenNode::new(node.kind, elements)
 }
 
 #[cfg(test)]
@@ -114,6 +245,8 @@ mod tests {
     use codespan::{ByteIndex, ByteSpan};
     use codespan::{CodeMap, FileName};
 
+    use library;
+
     use super::*;
 
     #[test]
@@ -154,6 +287,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn implicit_pi_bad_param_group() {
+        let src = "{x : Type} Type -> Type";
+        let mut codemap = CodeMap::new();
+        let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
+
+        let parse_result = term(&filemap);
+
+        assert_eq!(
+            parse_result,
+            (
+                concrete::Term::Error(ByteSpan::new(ByteIndex(1), ByteIndex(24))),
+                vec![ParseError::ParamGroupExpectedInPiType {
+                    span: ByteSpan::new(ByteIndex(12), ByteIndex(16)),
+                }],
+            )
+        );
+    }
+
+    #[test]
+    fn pattern_bad_constr_head() {
+        let src = "case n of { S^1 k => k; }";
+        let mut codemap = CodeMap::new();
+        let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
+
+        let (_, errors) = term(&filemap);
+
+        assert_eq!(
+            errors,
+            vec![ParseError::ConstructorExpectedInPattern {
+                span: ByteSpan::new(ByteIndex(13), ByteIndex(16)),
+            }],
+        );
+    }
+
+    #[test]
+    fn pattern_parens_constr_head() {
+        let src = "case n of { (S) k => k; }";
+        let mut codemap = CodeMap::new();
+        let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
+
+        let (_, errors) = term(&filemap);
+
+        assert_eq!(errors, vec![]);
+    }
+
     #[test]
     fn integer_overflow() {
         let src = "Type^111111111111111111111111111111";
@@ -173,4 +352,82 @@ mod tests {
             )
         );
     }
+
+    fn assert_round_trip(src: &str) -> SyntaxNode {
+        let mut codemap = CodeMap::new();
+        let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
+
+        let (tree, _) = module_tree(&filemap);
+
+        assert_eq!(tree.to_string(), src);
+        assert_eq!(tree.span(), filemap.span());
+        tree
+    }
+
+    /// Check that trivia only ever appears at the edges of the root node,
+    /// which the lowering to the concrete syntax relies on
+    fn assert_trimmed(node: &SyntaxNode) {
+        for child in node.children() {
+            assert_eq!(child.span(), child.trimmed_span(), "{:?}", child.kind());
+            assert_trimmed(&child);
+        }
+    }
+
+    #[test]
+    fn round_trip_prelude() {
+        let tree = assert_round_trip(library::PRELUDE);
+
+        assert_trimmed(&tree);
+    }
+
+    #[test]
+    fn round_trip_comments() {
+        let tree = assert_round_trip(
+            "\n\n||! A module\n\n-- hello\n||| doc\nid : {a : Type} -> a -> a; -- world\r\n\tid x = x;\n\n-- end",
+        );
+
+        assert_trimmed(&tree);
+    }
+
+    #[test]
+    fn round_trip_parse_errors() {
+        let tree = assert_round_trip("x : Type;\n  y = ) ;\nz = record { a = 1 };\n");
+
+        assert_trimmed(&tree);
+    }
+
+    #[test]
+    fn round_trip_lexer_errors() {
+        assert_round_trip("x : Type;\n  y = $ 1111111111111111111111111111111 ;\nz = \"oops");
+    }
+
+    #[test]
+    fn tree_shape() {
+        let src = "  f x {y : Type} -- comment\n";
+        let mut codemap = CodeMap::new();
+        let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
+
+        let (tree, errors) = term_tree(&filemap);
+        let kinds = |node: &SyntaxNode| {
+            node.children()
+                .iter()
+                .map(|child| child.kind())
+                .collect::<Vec<_>>()
+        };
+
+        assert!(errors.is_empty());
+        assert_eq!(tree.kind(), NodeKind::App);
+        assert_eq!(
+            kinds(&tree),
+            vec![NodeKind::Name, NodeKind::Name, NodeKind::ImplicitArg],
+        );
+        assert_eq!(kinds(&tree.children()[2]), vec![NodeKind::Ann]);
+        assert_eq!(tree.span(), filemap.span());
+        assert_eq!(
+            tree.trimmed_span(),
+            ByteSpan::new(ByteIndex(3), ByteIndex(17)),
+        );
+        assert_eq!(tree.covering_node(ByteIndex(8)).kind(), NodeKind::Name);
+        assert_eq!(tree.covering_node(ByteIndex(12)).kind(), NodeKind::Universe);
+    }
 }
@@ -1,8 +1,12 @@
 //! Pretty printing for the concrete syntax
 
+use codespan::ByteSpan;
 use pretty::Doc;
 
-use syntax::concrete::{Item, LamParamGroup, Literal, Module, Pattern, PiParamGroup, Term};
+use syntax::concrete::{
+    Arg, Item, LamParamGroup, Level, Literal, Module, Pattern, PiParamGroup, Term,
+};
+use syntax::{Multiplicity, Plicity};
 
 use super::{StaticDoc, ToDoc};
 
@@ -11,10 +15,14 @@ const INDENT_WIDTH: usize = 4;
 impl ToDoc for Module {
     fn to_doc(&self) -> StaticDoc {
         match *self {
-            Module::Valid { ref items } => Doc::intersperse(
-                items.iter().map(|item| item.to_doc()),
-                Doc::newline().append(Doc::newline()),
-            ),
+            Module::Valid { ref doc, ref items } => pretty_doc_comment("||!", doc)
+                .append(match doc[..] {
+                    [] => Doc::nil(),
+                    _ => Doc::newline(),
+                }).append(Doc::intersperse(
+                    items.iter().map(|item| item.to_doc()),
+                    Doc::newline().append(Doc::newline()),
+                )),
             Module::Error(_) => Doc::text("<error>"),
         }
     }
@@ -24,33 +32,86 @@ impl ToDoc for Item {
     fn to_doc(&self) -> StaticDoc {
         match *self {
             Item::Declaration {
+                ref doc,
+                private,
+                ref partial,
                 name: (_, ref name),
                 ref ann,
-                ..
-            } => Doc::as_string(name)
-                .append(Doc::space())
-                .append(":")
-                .append(Doc::space())
-                .append(ann.to_doc()),
+            } => pretty_doc_comment("|||", doc).append(
+                pretty_private(private)
+                    .append(partial.map_or(Doc::nil(), |_| Doc::text("partial ")))
+                    .append(Doc::as_string(name))
+                    .append(" : ")
+                    .append(pretty_ann(ann))
+                    .append(";")
+                    .group(),
+            ),
             Item::Definition {
+                ref doc,
+                private,
                 name: (_, ref name),
                 ref params,
                 ref return_ann,
                 ref body,
-            } => Doc::as_string(name)
-                .append(Doc::space())
+            } => pretty_doc_comment("|||", doc).append(
+                pretty_private(private)
+                    .append(Doc::as_string(name))
+                    .append(match params[..] {
+                        [] => Doc::nil(),
+                        _ => Doc::text(" ").append(pretty_lam_params(params)),
+                    }).append(return_ann.as_ref().map_or(Doc::nil(), |return_ann| {
+                        Doc::text(" : ").append(return_ann.to_doc())
+                    })).append(" =")
+                    .append(pretty_body(body))
+                    .append(";")
+                    .group(),
+            ),
+            Item::Data {
+                ref doc,
+                private,
+                name: (_, ref name),
+                ref params,
+                ref ann,
+                ref constructors,
+                ..
+            } => pretty_doc_comment("|||", doc)
+                .append(pretty_private(private))
+                .append("data ")
+                .append(Doc::as_string(name))
                 .append(match params[..] {
                     [] => Doc::nil(),
-                    _ => pretty_lam_params(params).append(Doc::space()),
-                }).append(return_ann.as_ref().map_or(Doc::nil(), |return_ann| {
-                    Doc::text(":")
-                        .append(return_ann.to_doc())
-                        .append(Doc::space())
-                })).append("=")
-                .append(Doc::space())
-                .append(body.to_doc().nest(INDENT_WIDTH)),
-            Item::Error(_) => Doc::text("<error>"),
-        }.append(";")
+                    _ => Doc::text(" ").append(pretty_pi_params(params)),
+                }).append(ann.as_ref().map_or(Doc::nil(), |ann| {
+                    Doc::text(" : ").append(ann.to_doc())
+                })).append(" where {")
+                .append(
+                    Doc::concat(constructors.iter().map(|constructor| {
+                        Doc::newline()
+                            .append(pretty_doc_comment("|||", &constructor.doc))
+                            .append(
+                                Doc::as_string(&constructor.name.1)
+                                    .append(" : ")
+                                    .append(pretty_ann(&constructor.ann))
+                                    .append(";")
+                                    .group(),
+                            )
+                    })).nest(INDENT_WIDTH),
+                ).append(Doc::newline())
+                .append("};"),
+            Item::Import {
+                ref doc,
+                private,
+                ref path,
+                ..
+            } => pretty_doc_comment("|||", doc)
+                .append(pretty_private(private))
+                .append("import ")
+                .append(Doc::intersperse(
+                    path.iter().map(|&(_, ref name)| Doc::as_string(name)),
+                    Doc::text("."),
+                )).append(";"),
+            Item::Error(_) => Doc::text("<error>;"),
+        }
     }
 }
 
@@ -60,7 +121,16 @@ impl ToDoc for Literal {
             Literal::String(_, ref value) => Doc::text(format!("{:?}", value)),
             Literal::Char(_, value) => Doc::text(format!("{:?}", value)),
             Literal::Int(_, value) => Doc::as_string(&value),
-            Literal::Float(_, value) => Doc::as_string(&value),
+            Literal::Float(_, value) => Doc::text(format!("{:?}", value)),
+        }
+    }
+}
+
+impl ToDoc for Arg {
+    fn to_doc(&self) -> StaticDoc {
+        match *self {
+            Arg::Explicit(ref term) => term.to_doc(),
+            Arg::Implicit(_, ref term) => Doc::text("{").append(term.to_doc()).append("}"),
         }
     }
 }
@@ -78,188 +148,315 @@ impl ToDoc for Pattern {
             Pattern::Name(_, ref name, None) => Doc::text(format!("{}", name)),
             Pattern::Name(_, ref name, Some(shift)) => Doc::text(format!("{}^{}", name, shift)),
             Pattern::Literal(ref literal) => literal.to_doc(),
+            Pattern::App(ref head, ref args) => head.to_doc().append(Doc::space()).append(
+                Doc::intersperse(args.iter().map(|arg| arg.to_doc()), Doc::space()),
+            ),
             Pattern::Error(_) => Doc::text("<error>"),
         }
     }
 }
 
-impl ToDoc for Term {
+impl ToDoc for Level {
     fn to_doc(&self) -> StaticDoc {
         match *self {
-            Term::Parens(_, ref term) => Doc::text("(").append(term.to_doc()).append(")"),
-            Term::Ann(ref term, ref ty) => term
+            Level::Parens(_, ref level) => Doc::text("(").append(level.to_doc()).append(")"),
+            Level::Const(_, level) => Doc::as_string(&level),
+            Level::Var(_, ref name) => Doc::as_string(name),
+            Level::Succ(_, ref level, shift) => level
                 .to_doc()
-                .append(Doc::space())
-                .append(":")
-                .append(Doc::space())
-                .append(ty.to_doc()),
-            Term::Universe(_, None) => Doc::text("Type"),
-            Term::Universe(_, Some(level)) => Doc::text(format!("Type^{}", level)),
-            Term::Literal(ref literal) => literal.to_doc(),
-            Term::Array(_, ref elems) => Doc::text("[")
-                .append(Doc::intersperse(
+                .append(" + ")
+                .append(Doc::as_string(&shift)),
+            Level::Max(_, ref levels) => Doc::text("max ").append(Doc::intersperse(
+                levels.iter().map(Level::to_doc),
+                Doc::text(" "),
+            )),
+        }
+    }
+}
+
+impl ToDoc for Term {
+    fn to_doc(&self) -> StaticDoc {
+        pretty_term(self).group()
+    }
+}
+
+/// Lay out a term without grouping it, allowing it to be broken across lines
+/// along with the term that it appears in
+fn pretty_term(term: &Term) -> StaticDoc {
+    match *term {
+        Term::Parens(_, ref term) => Doc::text("(").append(term.to_doc()).append(")"),
+        Term::Ann(ref term, ref ty) => term
+            .to_doc()
+            .append(" :")
+            .append(Doc::space().append(ty.to_doc()).nest(INDENT_WIDTH)),
+        Term::Universe(_, None) => Doc::text("Type"),
+        Term::Universe(_, Some(ref level)) => Doc::text("Type^").append(level.to_doc()),
+        Term::Literal(ref literal) => literal.to_doc(),
+        Term::Array(_, ref elems) => Doc::text("[")
+            .append(
+                Doc::intersperse(
                     elems.iter().map(Term::to_doc),
                     Doc::text(";").append(Doc::space()),
-                )).append("]"),
-            Term::Hole(_) => Doc::text("_"),
-            Term::Name(_, ref name, None) => Doc::text(format!("{}", name)),
-            Term::Name(_, ref name, Some(shift)) => Doc::text(format!("{}^{}", name, shift)),
-            Term::Extern(_, _, ref name, ref ty) => Doc::text("extern")
-                .append(Doc::space())
-                .append(format!("{:?}", name))
-                .append(Doc::space())
-                .append(":")
-                .append(Doc::space())
-                .append(ty.to_doc()),
-            Term::Lam(_, ref params, ref body) => Doc::text("\\")
-                .append(pretty_lam_params(params))
-                .append(Doc::space())
-                .append("=>")
-                .append(Doc::space())
-                .append(body.to_doc()),
-            Term::Pi(_, ref params, ref body) => pretty_pi_params(params)
-                .append(Doc::space())
-                .append("->")
-                .append(Doc::space())
-                .append(body.to_doc()),
-            Term::Arrow(ref ann, ref body) => ann
-                .to_doc()
-                .append(Doc::space())
-                .append("->")
-                .append(Doc::space())
-                .append(body.to_doc()),
-            Term::App(ref head, ref args) => head.to_doc().append(Doc::space()).append(
-                Doc::intersperse(args.iter().map(|arg| arg.to_doc()), Doc::space()),
-            ),
-            Term::Let(_, ref decls, ref body) => {
-                Doc::text("let")
-                    .append(Doc::space())
+                ).nest(1),
+            ).append("]"),
+        Term::Hole(_) => Doc::text("?"),
+        Term::Name(_, ref name, None) => Doc::text(format!("{}", name)),
+        Term::Name(_, ref name, Some(shift)) => Doc::text(format!("{}^{}", name, shift)),
+        Term::Extern(_, _, ref name, ref ty) => Doc::text("extern ")
+            .append(format!("{:?}", name))
+            .append(" :")
+            .append(Doc::space().append(ty.to_doc()).nest(INDENT_WIDTH)),
+        Term::Lam(_, ref params, ref body) => Doc::text("\\")
+            .append(pretty_lam_params(params))
+            .append(" =>")
+            .append(pretty_body(body)),
+        Term::Pi(_, ref params, ref body) => pretty_pi_params(params)
+            .append(" ->")
+            .append(Doc::space())
+            .append(pretty_codomain(body)),
+        Term::Arrow(ref ann, ref body) => ann
+            .to_doc()
+            .append(" ->")
+            .append(Doc::space())
+            .append(pretty_codomain(body)),
+        Term::App(ref head, ref args) => head.to_doc().append(
+            Doc::space()
+                .append(Doc::intersperse(
+                    args.iter().map(|arg| arg.to_doc()),
+                    Doc::space(),
+                )).nest(INDENT_WIDTH),
+        ),
+        Term::Let(_, ref items, ref body) => Doc::text("let")
+            .append(
+                Doc::space()
                     .append(Doc::intersperse(
-                        // FIXME: Indentation
-                        decls.iter().map(|decl| decl.to_doc()),
-                        Doc::newline(),
-                    )).append("in")
-                    .append(body.to_doc())
-            },
-            Term::If(_, ref cond, ref if_true, ref if_false) => Doc::text("if")
-                .append(Doc::space())
-                .append(cond.to_doc())
-                .append(Doc::space())
-                .append("then")
-                .append(Doc::space())
-                .append(if_true.to_doc())
-                .append(Doc::space())
-                .append("else")
-                .append(Doc::space())
-                .append(if_false.to_doc()),
-            Term::Case(_, ref head, ref clauses) => Doc::text("case")
-                .append(Doc::space())
-                .append(head.to_doc())
-                .append(Doc::space())
-                .append("of")
-                .append(Doc::space())
-                .append("{")
-                .append(Doc::newline())
-                .append(
-                    Doc::concat(clauses.iter().map(|&(ref pattern, ref body)| {
+                        items.iter().map(|item| item.to_doc()),
+                        Doc::space(),
+                    )).nest(INDENT_WIDTH),
+            ).append(Doc::space())
+            .append("in")
+            .append(Doc::space().append(body.to_doc()).nest(INDENT_WIDTH)),
+        Term::If(_, ref cond, ref if_true, ref if_false) => Doc::text("if ")
+            .append(cond.to_doc())
+            .append(Doc::space())
+            .append("then ")
+            .append(if_true.to_doc())
+            .append(Doc::space())
+            .append("else ")
+            .append(if_false.to_doc()),
+        Term::Case(_, ref head, ref clauses) => Doc::text("case ")
+            .append(head.to_doc())
+            .append(" of {")
+            .append(
+                Doc::concat(clauses.iter().map(|&(ref pattern, ref body)| {
+                    Doc::newline().append(
                         pattern
                             .to_doc()
-                            .append(Doc::space())
-                            .append("=>")
-                            .append(Doc::space())
-                            .append(body.to_doc())
+                            .group()
+                            .append(" =>")
+                            .append(pretty_body(body))
                             .append(";")
-                            .append(Doc::newline())
-                    })).nest(INDENT_WIDTH),
-                ).append("}"),
-            Term::RecordType(_, ref fields) if fields.is_empty() => Doc::text("Record {}"),
-            Term::Record(_, ref fields) if fields.is_empty() => Doc::text("record {}"),
-            Term::RecordType(_, ref fields) => Doc::text("Record {")
+                            .group(),
+                    )
+                })).nest(INDENT_WIDTH),
+            ).append(Doc::newline())
+            .append("}"),
+        Term::Eq(ref lhs, ref rhs) => lhs
+            .to_doc()
+            .append(" =")
+            .append(Doc::space().append(rhs.to_doc()).nest(INDENT_WIDTH)),
+        Term::Refl(_) => Doc::text("refl"),
+        Term::J(_, ref motive, ref base, ref proof) => Doc::text("J").append(
+            Doc::space()
+                .append(motive.to_doc())
                 .append(Doc::space())
-                .append(
-                    Doc::intersperse(
-                        fields.iter().map(|field| {
-                            Doc::as_string(&field.label.1)
-                                .append(match field.binder {
-                                    Some((_, ref binder)) => Doc::space()
-                                        .append("as")
-                                        .append(Doc::space())
-                                        .append(Doc::as_string(binder)),
-                                    None => Doc::nil(),
-                                }).append(Doc::space())
-                                .append(":")
-                                .append(Doc::space())
-                                .append(field.ann.to_doc())
-                        }),
-                        Doc::text(";").append(Doc::space()),
-                    ).nest(INDENT_WIDTH),
-                ).append(Doc::space())
-                .append("}"),
-            Term::Record(_, ref fields) => Doc::text("record {")
+                .append(base.to_doc())
                 .append(Doc::space())
-                .append(
-                    Doc::intersperse(
-                        fields.iter().map(|field| {
-                            Doc::as_string(&field.label.1)
-                                .append(Doc::space())
-                                .append(match field.params[..] {
-                                    [] => Doc::nil(),
-                                    _ => pretty_lam_params(&field.params).append(Doc::space()),
-                                }).append(field.return_ann.as_ref().map_or(
-                                    Doc::nil(),
-                                    |return_ann| {
-                                        Doc::text(":")
-                                            .append(return_ann.to_doc())
-                                            .append(Doc::space())
-                                    },
-                                )).append("=")
-                                .append(Doc::space())
-                                .append(field.term.to_doc())
-                        }),
-                        Doc::text(";").append(Doc::space()),
-                    ).nest(INDENT_WIDTH),
-                ).append(Doc::space())
-                .append("}"),
-            Term::Proj(ref expr, _, ref label) => {
-                expr.to_doc().append(".").append(Doc::as_string(label))
-            },
-            Term::Error(_) => Doc::text("<error>"),
-        }
+                .append(proof.to_doc())
+                .nest(INDENT_WIDTH),
+        ),
+        Term::RecordType(_, ref fields) if fields.is_empty() => Doc::text("Record {}"),
+        Term::Record(_, ref fields) if fields.is_empty() => Doc::text("record {}"),
+        Term::RecordType(_, ref fields) => {
+            let is_documented = fields.iter().any(|field| !field.doc.is_empty());
+            let fields = fields.iter().map(|field| {
+                pretty_doc_comment("|||", &field.doc).append(
+                    Doc::as_string(&field.label.1)
+                        .append(match field.binder {
+                            Some((_, ref binder)) => Doc::text(" as ").append(Doc::as_string(binder)),
+                            None => Doc::nil(),
+                        }).append(" : ")
+                        .append(pretty_ann(&field.ann))
+                        .group(),
+                )
+            });
+
+            // Doc comments must be on their own lines, so we always put each
+            // field on a separate line if any of them are documented
+            if is_documented {
+                Doc::text("Record {")
+                    .append(
+                        Doc::concat(
+                            fields.map(|field| Doc::newline().append(field).append(";")),
+                        ).nest(INDENT_WIDTH),
+                    ).append(Doc::newline())
+                    .append("}")
+            } else {
+                pretty_fields("Record {", fields)
+            }
+        },
+        Term::Record(_, ref fields) => pretty_fields(
+            "record {",
+            fields.iter().map(|field| {
+                Doc::as_string(&field.label.1)
+                    .append(match field.params[..] {
+                        [] => Doc::nil(),
+                        _ => Doc::text(" ").append(pretty_lam_params(&field.params)),
+                    }).append(field.return_ann.as_ref().map_or(Doc::nil(), |return_ann| {
+                        Doc::text(" : ").append(return_ann.to_doc())
+                    })).append(" =")
+                    .append(pretty_body(&field.term))
+                    .group()
+            }),
+        ),
+        Term::Proj(ref expr, _, ref label) => {
+            expr.to_doc().append(".").append(Doc::as_string(label))
+        },
+        Term::Error(_) => Doc::text("<error>"),
+    }
+}
+
+/// Lay out the body of a definition, following on from an `=` or `=>`
+///
+/// Bodies that are delimited by braces begin on the same line, with their
+/// contents indented. Other bodies are moved onto the next line if they are
+/// too long.
+fn pretty_body(body: &Term) -> StaticDoc {
+    if is_braced(body) {
+        Doc::text(" ").append(body.to_doc())
+    } else {
+        Doc::space().append(body.to_doc()).nest(INDENT_WIDTH)
+    }
+}
+
+/// Lay out a type annotation, following on from a `:`
+///
+/// Like the bodies of definitions, annotations that are delimited by braces
+/// are not indented any further.
+fn pretty_ann(ann: &Term) -> StaticDoc {
+    if is_braced(ann) {
+        ann.to_doc()
+    } else {
+        ann.to_doc().nest(INDENT_WIDTH)
+    }
+}
+
+/// Returns `true` if the term ends with a block delimited by braces
+fn is_braced(term: &Term) -> bool {
+    match *term {
+        Term::Case(..) | Term::Record(..) | Term::RecordType(..) => true,
+        Term::Lam(_, _, ref body) => is_braced(body),
+        _ => false,
+    }
+}
+
+/// Lay out the codomain of a function type, so that chains of arrows are
+/// broken at every arrow, rather than only at the outermost one
+fn pretty_codomain(body: &Term) -> StaticDoc {
+    match *body {
+        Term::Pi(..) | Term::Arrow(..) => pretty_term(body),
+        _ => body.to_doc(),
+    }
+}
+
+/// Lay out the fields of a record, which are placed on separate lines if
+/// they don't fit on one
+fn pretty_fields<I>(open: &'static str, fields: I) -> StaticDoc
+where
+    I: Iterator<Item = StaticDoc>,
+{
+    Doc::text(open)
+        .append(
+            Doc::space()
+                .append(Doc::intersperse(fields, Doc::text(";").append(Doc::space())))
+                .nest(INDENT_WIDTH),
+        ).append(Doc::space())
+        .append("}")
+}
+
+fn delimiters(plicity: Plicity) -> (&'static str, &'static str) {
+    match plicity {
+        Plicity::Explicit => ("(", ")"),
+        Plicity::Implicit => ("{", "}"),
+    }
+}
+
+fn pretty_multiplicity(multiplicity: Option<Multiplicity>) -> StaticDoc {
+    match multiplicity {
+        None | Some(Multiplicity::Many) => Doc::nil(),
+        Some(multiplicity) => Doc::as_string(multiplicity).append(" "),
     }
 }
 
+fn pretty_doc_comment(prefix: &'static str, doc: &[String]) -> StaticDoc {
+    Doc::concat(doc.iter().map(|line| {
+        match line.as_str() {
+            "" => Doc::text(prefix),
+            line => Doc::text(format!("{} {}", prefix, line)),
+        }.append(Doc::newline())
+    }))
+}
+
+fn pretty_private(private: Option<ByteSpan>) -> StaticDoc {
+    private.map_or(Doc::nil(), |_| Doc::text("private "))
+}
+
 fn pretty_lam_params(params: &[LamParamGroup]) -> StaticDoc {
     Doc::intersperse(
-        params.iter().map(|&(ref names, ref ann)| match *ann {
-            None if names.len() == 1 => Doc::as_string(&names[0].1),
-            None => unreachable!(), // FIXME - shouldn't be possible in AST
-            Some(ref ann) => Doc::text("(")
-                .append(Doc::intersperse(
-                    names.iter().map(|name| Doc::as_string(&name.1)),
-                    Doc::space(),
-                )).append(Doc::space())
-                .append(":")
-                .append(Doc::space())
-                .append(ann.to_doc())
-                .append(")"),
+        params.iter().map(|&(plicity, multiplicity, ref names, ref ann)| {
+            match (plicity, multiplicity, ann) {
+                (Plicity::Explicit, None, &None) if names.len() == 1 => {
+                    Doc::as_string(&names[0].1)
+                },
+                (plicity, multiplicity, &None) => {
+                    let (open, close) = delimiters(plicity);
+                    Doc::text(open)
+                        .append(pretty_multiplicity(multiplicity))
+                        .append(Doc::intersperse(
+                            names.iter().map(|name| Doc::as_string(&name.1)),
+                            Doc::text(" "),
+                        )).append(close)
+                },
+                (plicity, multiplicity, &Some(ref ann)) => {
+                    let (open, close) = delimiters(plicity);
+                    Doc::text(open)
+                        .append(pretty_multiplicity(multiplicity))
+                        .append(Doc::intersperse(
+                            names.iter().map(|name| Doc::as_string(&name.1)),
+                            Doc::text(" "),
+                        )).append(" : ")
+                        .append(ann.to_doc())
+                        .append(close)
+                },
+            }
         }),
         Doc::space(),
-    )
+    ).group()
 }
 
 fn pretty_pi_params(params: &[PiParamGroup]) -> StaticDoc {
     Doc::intersperse(
-        params.iter().map(|&(ref names, ref ann)| {
-            Doc::text("(")
+        params.iter().map(|&(plicity, multiplicity, ref names, ref ann)| {
+            let (open, close) = delimiters(plicity);
+            Doc::text(open)
+                .append(pretty_multiplicity(Some(multiplicity)))
                 .append(Doc::intersperse(
                     names.iter().map(|name| Doc::as_string(&name.1)),
-                    Doc::space(),
-                )).append(Doc::space())
-                .append(":")
-                .append(Doc::space())
+                    Doc::text(" "),
+                )).append(" : ")
                 .append(ann.to_doc())
-                .append(")")
+                .append(close)
         }),
         Doc::space(),
-    )
+    ).group()
 }
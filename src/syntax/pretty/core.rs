@@ -4,11 +4,25 @@ use moniker::{Binder, Embed, Var};
 use pretty::Doc;
 use std::iter;
 
-use syntax::core::{Head, Literal, Neutral, Pattern, Term, Value};
+use syntax::core::{Head, Literal, MetaVar, Neutral, Pattern, Term, Value};
 use syntax::raw;
-use syntax::{Label, Level, LevelShift};
+use syntax::{Label, Level, LevelShift, Multiplicity, Plicity};
 
-use super::{parens, sexpr, StaticDoc, ToDoc};
+use super::{braces, parens, sexpr, StaticDoc, ToDoc};
+
+fn plicit(plicity: Plicity, doc: StaticDoc) -> StaticDoc {
+    match plicity {
+        Plicity::Explicit => parens(doc),
+        Plicity::Implicit => braces(doc),
+    }
+}
+
+fn pretty_multiplicity(multiplicity: Option<Multiplicity>) -> StaticDoc {
+    match multiplicity {
+        None | Some(Multiplicity::Many) => Doc::nil(),
+        Some(multiplicity) => Doc::space().append(Doc::as_string(multiplicity)),
+    }
+}
 
 fn pretty_ann(expr: &impl ToDoc, ty: &impl ToDoc) -> StaticDoc {
     sexpr(
@@ -17,7 +31,7 @@ fn pretty_ann(expr: &impl ToDoc, ty: &impl ToDoc) -> StaticDoc {
     )
 }
 
-fn pretty_universe(level: Level) -> StaticDoc {
+fn pretty_universe(level: &Level) -> StaticDoc {
     sexpr("Type", Doc::as_string(&level))
 }
 
@@ -29,6 +43,19 @@ fn pretty_var(var: &Var<String>, shift: LevelShift) -> StaticDoc {
     sexpr("var", Doc::text(format!("{:#}^{}", var, shift)))
 }
 
+fn pretty_inst(term: &impl ToDoc, levels: &[(String, Level)]) -> StaticDoc {
+    sexpr(
+        "inst",
+        term.to_doc().append(Doc::concat(levels.iter().map(|&(ref param, ref level)| {
+            Doc::space().append(Doc::text(format!("{}={}", param, level)))
+        }))),
+    )
+}
+
+fn pretty_meta(meta: MetaVar) -> StaticDoc {
+    sexpr("meta", Doc::as_string(&meta))
+}
+
 fn pretty_extern(name: &str, ty: &impl ToDoc) -> StaticDoc {
     sexpr(
         "extern",
@@ -38,11 +65,19 @@ fn pretty_extern(name: &str, ty: &impl ToDoc) -> StaticDoc {
     )
 }
 
-fn pretty_lam(binder: &Binder<String>, ann: &impl ToDoc, body: &impl ToDoc) -> StaticDoc {
+fn pretty_lam(
+    plicity: Plicity,
+    multiplicity: Option<Multiplicity>,
+    binder: &Binder<String>,
+    ann: &impl ToDoc,
+    body: &impl ToDoc,
+) -> StaticDoc {
     sexpr(
         "λ",
-        Doc::group(parens(
+        Doc::group(plicit(
+            plicity,
             pretty_binder(binder)
+                .append(pretty_multiplicity(multiplicity))
                 .append(Doc::space())
                 .append(ann.to_doc().group()),
         )).append(Doc::space())
@@ -81,11 +116,19 @@ fn pretty_let(binder: &Binder<String>, bind: &impl ToDoc, body: &impl ToDoc) ->
     )
 }
 
-fn pretty_pi(binder: &Binder<String>, ann: &impl ToDoc, body: &impl ToDoc) -> StaticDoc {
+fn pretty_pi(
+    plicity: Plicity,
+    multiplicity: Option<Multiplicity>,
+    binder: &Binder<String>,
+    ann: &impl ToDoc,
+    body: &impl ToDoc,
+) -> StaticDoc {
     sexpr(
         "Π",
-        Doc::group(parens(
+        Doc::group(plicit(
+            plicity,
             pretty_binder(binder)
+                .append(pretty_multiplicity(multiplicity))
                 .append(Doc::space())
                 .append(ann.to_doc().group()),
         )).append(Doc::space())
@@ -95,18 +138,39 @@ fn pretty_pi(binder: &Binder<String>, ann: &impl ToDoc, body: &impl ToDoc) -> St
 
 fn pretty_app<'a, As, A>(expr: StaticDoc, args: As) -> StaticDoc
 where
-    As: 'a + IntoIterator<Item = &'a A>,
+    As: 'a + IntoIterator<Item = (Plicity, &'a A)>,
     A: 'a + ToDoc,
 {
     sexpr(
         "app",
         expr.append(Doc::space()).append(Doc::intersperse(
-            args.into_iter().map(A::to_doc),
+            args.into_iter().map(|(plicity, arg)| match plicity {
+                Plicity::Explicit => arg.to_doc(),
+                Plicity::Implicit => braces(arg.to_doc()),
+            }),
             Doc::space(),
         )),
     )
 }
 
+fn pretty_constr<'a, As, A>(var: &Var<String>, args: As) -> StaticDoc
+where
+    As: 'a + IntoIterator<Item = (Plicity, &'a A)>,
+    A: 'a + ToDoc,
+{
+    sexpr(
+        "constr",
+        Doc::text(format!("{:#}", var)).append(Doc::concat(args.into_iter().map(
+            |(plicity, arg)| {
+                Doc::space().append(match plicity {
+                    Plicity::Explicit => arg.to_doc(),
+                    Plicity::Implicit => braces(arg.to_doc()),
+                })
+            },
+        ))),
+    )
+}
+
 fn pretty_if(cond: &impl ToDoc, if_true: &impl ToDoc, if_false: &impl ToDoc) -> StaticDoc {
     sexpr(
         "if",
@@ -118,6 +182,44 @@ fn pretty_if(cond: &impl ToDoc, if_true: &impl ToDoc, if_false: &impl ToDoc) ->
     )
 }
 
+fn pretty_eq(lhs: &impl ToDoc, rhs: &impl ToDoc) -> StaticDoc {
+    sexpr(
+        "Eq",
+        lhs.to_doc().append(Doc::space()).append(rhs.to_doc()),
+    )
+}
+
+fn pretty_eq_ann(ty: &impl ToDoc, lhs: &impl ToDoc, rhs: &impl ToDoc) -> StaticDoc {
+    sexpr(
+        "Eq",
+        ty.to_doc()
+            .append(Doc::space())
+            .append(lhs.to_doc())
+            .append(Doc::space())
+            .append(rhs.to_doc()),
+    )
+}
+
+fn pretty_refl() -> StaticDoc {
+    parens(Doc::text("refl"))
+}
+
+fn pretty_error() -> StaticDoc {
+    parens(Doc::text("error"))
+}
+
+fn pretty_j(motive: &impl ToDoc, base: &impl ToDoc, proof: &impl ToDoc) -> StaticDoc {
+    sexpr(
+        "J",
+        motive
+            .to_doc()
+            .append(Doc::space())
+            .append(base.to_doc())
+            .append(Doc::space())
+            .append(proof.to_doc()),
+    )
+}
+
 fn pretty_record_ty(inner: StaticDoc) -> StaticDoc {
     sexpr("Record", inner)
 }
@@ -170,6 +272,11 @@ impl ToDoc for raw::Pattern {
             raw::Pattern::Binder(_, ref binder) => pretty_binder(binder),
             raw::Pattern::Var(_, Embed(ref var), shift) => pretty_var(var, shift),
             raw::Pattern::Literal(ref literal) => literal.to_doc(),
+            raw::Pattern::Constr(_, Embed(ref var), ref args) => pretty_constr(
+                var,
+                args.iter().map(|arg| (Plicity::Explicit, &*arg.inner)),
+            ),
+            raw::Pattern::Error(_) => pretty_error(),
         }
     }
 }
@@ -178,25 +285,38 @@ impl ToDoc for raw::Term {
     fn to_doc(&self) -> StaticDoc {
         match *self {
             raw::Term::Ann(ref expr, ref ty) => pretty_ann(&expr.inner, &ty.inner),
-            raw::Term::Universe(_, level) => pretty_universe(level),
+            raw::Term::Universe(_, Some(ref level)) => pretty_universe(level),
+            raw::Term::Universe(_, None) => parens(Doc::text("Type")),
             raw::Term::Hole(_) => parens(Doc::text("hole")),
             raw::Term::Literal(ref literal) => literal.to_doc(),
             raw::Term::Var(_, ref var, shift) => pretty_var(var, shift),
             raw::Term::Extern(_, _, ref name, ref ty) => pretty_extern(name, &ty.inner),
-            raw::Term::Lam(_, ref scope) => pretty_lam(
+            raw::Term::Lam(_, plicity, multiplicity, ref scope) => pretty_lam(
+                plicity,
+                multiplicity,
                 &scope.unsafe_pattern.0,
                 &(scope.unsafe_pattern.1).0.inner,
                 &scope.unsafe_body.inner,
             ),
-            raw::Term::Pi(_, ref scope) => pretty_pi(
+            raw::Term::Pi(_, plicity, multiplicity, ref scope) => pretty_pi(
+                plicity,
+                Some(multiplicity),
                 &scope.unsafe_pattern.0,
                 &(scope.unsafe_pattern.1).0.inner,
                 &scope.unsafe_body.inner,
             ),
-            raw::Term::App(ref head, ref arg) => pretty_app(head.to_doc(), iter::once(&arg.inner)),
+            raw::Term::App(ref head, plicity, ref arg) => {
+                pretty_app(head.to_doc(), iter::once((plicity, &arg.inner)))
+            },
             raw::Term::If(_, ref cond, ref if_true, ref if_false) => {
                 pretty_if(&cond.inner, &if_true.inner, &if_false.inner)
             },
+            raw::Term::Eq(ref lhs, ref rhs) => pretty_eq(&lhs.inner, &rhs.inner),
+            raw::Term::Refl(_) => pretty_refl(),
+            raw::Term::Error(_) => pretty_error(),
+            raw::Term::J(_, ref motive, ref base, ref proof) => {
+                pretty_j(&motive.inner, &base.inner, &proof.inner)
+            },
             raw::Term::RecordType(_, ref scope) => pretty_record_ty(Doc::concat(
                 scope.unsafe_pattern.unsafe_patterns.iter().map(
                     |&(ref label, _, Embed(ref ann))| {
@@ -271,6 +391,12 @@ impl ToDoc for Pattern {
             Pattern::Binder(ref binder) => pretty_binder(binder),
             Pattern::Var(Embed(ref var), shift) => pretty_var(var, shift),
             Pattern::Literal(ref literal) => literal.to_doc(),
+            Pattern::Constr(Embed(ref var), ref args) => pretty_constr(
+                var,
+                args.iter()
+                    .map(|&(plicity, ref arg)| (plicity, &*arg.inner)),
+            ),
+            Pattern::Error => pretty_error(),
         }
     }
 }
@@ -279,16 +405,27 @@ impl ToDoc for Term {
     fn to_doc(&self) -> StaticDoc {
         match *self {
             Term::Ann(ref expr, ref ty) => pretty_ann(&expr.inner, &ty.inner),
-            Term::Universe(level) => pretty_universe(level),
+            Term::Universe(ref level) => pretty_universe(level),
             Term::Literal(ref literal) => literal.to_doc(),
             Term::Var(ref var, shift) => pretty_var(var, shift),
+            Term::Inst(ref term, ref levels) => pretty_inst(&term.inner, levels),
+            Term::Meta(meta) => pretty_meta(meta),
             Term::Extern(ref name, ref ty) => pretty_extern(name, &ty.inner),
-            Term::Lam(ref scope) => pretty_lam(
+            Term::Constr(ref var, ref args) => pretty_constr(
+                var,
+                args.iter()
+                    .map(|&(plicity, ref arg)| (plicity, &*arg.inner)),
+            ),
+            Term::Lam(plicity, multiplicity, ref scope) => pretty_lam(
+                plicity,
+                Some(multiplicity),
                 &scope.unsafe_pattern.0,
                 &(scope.unsafe_pattern.1).0.inner,
                 &scope.unsafe_body.inner,
             ),
-            Term::Pi(ref scope) => pretty_pi(
+            Term::Pi(plicity, multiplicity, ref scope) => pretty_pi(
+                plicity,
+                Some(multiplicity),
                 &scope.unsafe_pattern.0,
                 &(scope.unsafe_pattern.1).0.inner,
                 &scope.unsafe_body.inner,
@@ -298,10 +435,20 @@ impl ToDoc for Term {
                 &(scope.unsafe_pattern.1).0.inner,
                 &scope.unsafe_body.inner,
             ),
-            Term::App(ref head, ref arg) => pretty_app(head.to_doc(), iter::once(&arg.inner)),
+            Term::App(ref head, plicity, ref arg) => {
+                pretty_app(head.to_doc(), iter::once((plicity, &arg.inner)))
+            },
             Term::If(ref cond, ref if_true, ref if_false) => {
                 pretty_if(&cond.inner, &if_true.inner, &if_false.inner)
             },
+            Term::Eq(ref ty, ref lhs, ref rhs) => {
+                pretty_eq_ann(&ty.inner, &lhs.inner, &rhs.inner)
+            },
+            Term::Refl => pretty_refl(),
+            Term::Error => pretty_error(),
+            Term::J(ref motive, ref base, ref proof) => {
+                pretty_j(&motive.inner, &base.inner, &proof.inner)
+            },
             Term::RecordType(ref scope) => pretty_record_ty(Doc::concat(
                 scope.unsafe_pattern.unsafe_patterns.iter().map(
                     |&(ref label, _, Embed(ref ann))| {
@@ -345,50 +492,50 @@ impl ToDoc for Term {
 impl ToDoc for Value {
     fn to_doc(&self) -> StaticDoc {
         match *self {
-            Value::Universe(level) => pretty_universe(level),
+            Value::Universe(ref level) => pretty_universe(level),
             Value::Literal(ref literal) => literal.to_doc(),
-            Value::Lam(ref scope) => pretty_lam(
-                &scope.unsafe_pattern.0,
-                &(scope.unsafe_pattern.1).0.inner,
-                &scope.unsafe_body.inner,
-            ),
-            Value::Pi(ref scope) => pretty_pi(
-                &scope.unsafe_pattern.0,
-                &(scope.unsafe_pattern.1).0.inner,
-                &scope.unsafe_body.inner,
-            ),
-            Value::RecordType(ref scope) => pretty_record_ty(Doc::concat(
-                scope.unsafe_pattern.unsafe_patterns.iter().map(
-                    |&(ref label, _, Embed(ref ann))| {
-                        parens(
-                            Doc::as_string(label)
-                                .append(Doc::space())
-                                .append(ann.to_doc())
-                                .append(Doc::newline()),
-                        )
-                    },
-                ),
-            )),
-            Value::Record(ref scope) => pretty_record(Doc::concat(
-                scope.unsafe_pattern.unsafe_patterns.iter().map(
-                    |&(ref label, _, Embed(ref term))| {
-                        parens(
-                            Doc::as_string(label)
-                                .append(Doc::space())
-                                .append(term.to_doc())
-                                .append(Doc::newline()),
-                        )
-                    },
-                ),
-            )),
+            // The bodies of closures are printed with their locals substituted in
+            Value::Lam(_, _, _, _) | Value::Pi(_, _, _, _) | Value::RecordType(_) => {
+                Term::from(self).to_doc()
+            },
+            Value::Eq(ref ty, ref lhs, ref rhs) => {
+                pretty_eq_ann(&ty.inner, &lhs.inner, &rhs.inner)
+            },
+            Value::Refl => pretty_refl(),
+            Value::Error => pretty_error(),
+            Value::Record(ref fields) => pretty_record(Doc::concat(fields.iter().map(
+                |&(ref label, ref term)| {
+                    parens(
+                        Doc::as_string(label)
+                            .append(Doc::space())
+                            .append(term.to_doc())
+                            .append(Doc::newline()),
+                    )
+                },
+            ))),
             Value::Array(ref elems) => Doc::text("[")
                 .append(Doc::intersperse(
                     elems.iter().map(|elem| elem.to_doc()),
                     Doc::text(";").append(Doc::space()),
                 )).append("]"),
+            Value::Constr(ref var, ref args) => pretty_constr(
+                var,
+                args.iter()
+                    .map(|&(plicity, ref arg)| (plicity, &*arg.inner)),
+            ),
             Value::Neutral(ref neutral, ref spine) if spine.is_empty() => neutral.to_doc(),
             Value::Neutral(ref neutral, ref spine) => {
-                pretty_app(neutral.to_doc(), spine.iter().map(|arg| &arg.inner))
+                pretty_app(
+                    neutral.to_doc(),
+                    spine.iter().map(|&(plicity, ref arg)| (plicity, &arg.inner)),
+                )
+            },
+            Value::Glued(ref head, ref spine, _) if spine.is_empty() => head.to_doc(),
+            Value::Glued(ref head, ref spine, _) => {
+                pretty_app(
+                    head.to_doc(),
+                    spine.iter().map(|&(plicity, ref arg)| (plicity, &arg.inner)),
+                )
             },
         }
     }
@@ -401,13 +548,11 @@ impl ToDoc for Neutral {
             Neutral::If(ref cond, ref if_true, ref if_false) => {
                 pretty_if(&cond.inner, &if_true.inner, &if_false.inner)
             },
+            Neutral::J(ref motive, ref base, ref proof) => {
+                pretty_j(&motive.inner, &base.inner, &proof.inner)
+            },
             Neutral::Proj(ref expr, ref label) => pretty_proj(&expr.inner, label),
-            Neutral::Case(ref head, ref clauses) => pretty_case(
-                &head.inner,
-                clauses
-                    .iter()
-                    .map(|clause| (&clause.unsafe_pattern.inner, &clause.unsafe_body.inner)),
-            ),
+            Neutral::Case(_, _) => Term::from(self).to_doc(),
         }
     }
 }
@@ -417,6 +562,7 @@ impl ToDoc for Head {
         match *self {
             Head::Var(ref var, shift) => pretty_var(var, shift),
             Head::Extern(ref name, ref ty) => pretty_extern(name, &ty.inner),
+            Head::Meta(meta) => pretty_meta(meta),
         }
     }
 }
@@ -37,6 +37,10 @@ fn parens(doc: StaticDoc) -> StaticDoc {
     Doc::text("(").append(doc.append(")").nest(1))
 }
 
+fn braces(doc: StaticDoc) -> StaticDoc {
+    Doc::text("{").append(doc.append("}").nest(1))
+}
+
 fn sexpr(name: &'static str, doc: StaticDoc) -> StaticDoc {
     parens(
         Doc::text(name)
@@ -8,10 +8,12 @@ use std::ops;
 use std::rc::Rc;
 
 use syntax::pretty::{self, ToDoc};
-use syntax::{Label, Level, LevelShift};
+use syntax::{Label, Level, LevelShift, Multiplicity, Plicity};
 
 /// A module definition
 pub struct Module {
+    /// The documentation for the module, taken from its module doc comments
+    pub doc: String,
     /// The items contained in the module
     pub items: Vec<Item>,
 }
@@ -21,6 +23,8 @@ pub struct Module {
 pub enum Item {
     /// Declares the type associated with a label, prior to its definition
     Declaration {
+        /// The documentation for this declaration, taken from its doc comments
+        doc: String,
         /// The span of source code where the label was introduced
         label_span: ByteSpan,
         /// The external name for this declaration, to be used when referring
@@ -31,9 +35,16 @@ pub enum Item {
         binder: Binder<String>,
         /// The type annotation for associated with the label
         term: RcTerm,
+        /// Whether the subsequent definition is allowed to skip termination
+        /// checking
+        partial: bool,
+        /// Whether this declaration is hidden from modules that import it
+        private: bool,
     },
     /// Defines the term that should be associated with a label
     Definition {
+        /// The documentation for this definition, taken from its doc comments
+        doc: String,
         /// The span of source code where the label was introduced
         label_span: ByteSpan,
         /// The external name for this definition, to be used when referring
@@ -44,7 +55,55 @@ pub enum Item {
         binder: Binder<String>,
         /// The term for associated with the label
         term: RcTerm,
+        /// Whether this definition is hidden from modules that import it
+        private: bool,
     },
+    /// Declares a data type, along with its constructors
+    Data {
+        /// The documentation for this data type, taken from its doc comments
+        doc: String,
+        /// The span of source code where the data type was declared
+        span: ByteSpan,
+        /// The span of source code where the label was introduced
+        label_span: ByteSpan,
+        /// The external name for this data type, to be used when referring
+        /// to this item from other modules
+        label: Label,
+        /// The internal name for this data type, to be used when binding
+        /// this name to variables
+        binder: Binder<String>,
+        /// The parameters of the data type. These are in scope in the type
+        /// annotation and in the constructors
+        params: Vec<(Binder<String>, RcTerm)>,
+        /// The type of the data type, after the parameters have been applied
+        term: RcTerm,
+        /// The constructors of the data type
+        constructors: Vec<Constructor>,
+        /// Whether this data type is hidden from modules that import it
+        private: bool,
+    },
+    /// Imports a module, binding it to a record containing its items
+    Import {
+        /// The documentation for this import, taken from its doc comments
+        doc: String,
+        /// The span of source code where the module was imported
+        span: ByteSpan,
+        /// The span of source code where the label was introduced
+        label_span: ByteSpan,
+        /// The external name for this import, to be used when referring to
+        /// this item from other modules
+        label: Label,
+        /// The internal name for this import, to be used when binding this
+        /// name to variables
+        binder: Binder<String>,
+        /// The name of the module being imported, with the components of its
+        /// path separated by dots
+        module: String,
+        /// Whether this import is hidden from modules that import it
+        private: bool,
+    },
+    /// An item that could not be parsed
+    Error(ByteSpan),
 }
 
 impl Item {
@@ -60,10 +119,28 @@ impl Item {
                 ref term,
                 ..
             } => label_span.to(term.span()),
+            Item::Data { span, .. } | Item::Import { span, .. } | Item::Error(span) => span,
         }
     }
 }
 
+/// A constructor of a data type
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constructor {
+    /// The documentation for this constructor
+    pub doc: String,
+    /// The span of source code where the label was introduced
+    pub label_span: ByteSpan,
+    /// The external name for this constructor
+    pub label: Label,
+    /// The internal name for this constructor, to be used when binding this
+    /// name to variables
+    pub binder: Binder<String>,
+    /// The type of the constructor, which may refer to the parameters of the
+    /// data type
+    pub term: RcTerm,
+}
+
 /// Literals
 #[derive(Debug, Clone, PartialEq, PartialOrd, BoundTerm, BoundPattern)]
 pub enum Literal {
@@ -101,6 +178,10 @@ pub enum Pattern {
     Var(ByteSpan, Embed<Var<String>>, LevelShift),
     /// Literal patterns
     Literal(Literal),
+    /// Constructor patterns
+    Constr(ByteSpan, Embed<Var<String>>, Vec<RcPattern>),
+    /// Patterns that could not be parsed
+    Error(ByteSpan),
 }
 
 impl Pattern {
@@ -108,7 +189,10 @@ impl Pattern {
     pub fn span(&self) -> ByteSpan {
         match *self {
             Pattern::Ann(ref pattern, Embed(ref ty)) => pattern.span().to(ty.span()),
-            Pattern::Var(span, _, _) | Pattern::Binder(span, _) => span,
+            Pattern::Var(span, _, _)
+            | Pattern::Binder(span, _)
+            | Pattern::Constr(span, _, _)
+            | Pattern::Error(span) => span,
             Pattern::Literal(ref literal) => literal.span(),
         }
     }
@@ -150,14 +234,15 @@ impl fmt::Display for RcPattern {
 
 /// Terms, unchecked and with implicit syntax that needs to be elaborated
 ///
-/// For now the only implicit syntax we have is holes and lambdas that lack a
-/// type annotation.
+/// For now the only implicit syntax we have is holes, lambdas that lack a
+/// type annotation, and the arguments that are to be supplied to implicit
+/// parameters.
 #[derive(Debug, Clone, PartialEq, BoundTerm)]
 pub enum Term {
     /// A term annotated with a type
     Ann(RcTerm, RcTerm),
-    /// Universes
-    Universe(ByteSpan, Level),
+    /// Universes, with a level that is inferred if it was not given
+    Universe(ByteSpan, Option<Level>),
     /// Literals
     Literal(Literal),
     /// A hole
@@ -167,11 +252,24 @@ pub enum Term {
     /// An external definition
     Extern(ByteSpan, ByteSpan, String, RcTerm),
     /// Dependent function types
-    Pi(ByteSpan, Scope<(Binder<String>, Embed<RcTerm>), RcTerm>),
+    Pi(
+        ByteSpan,
+        Plicity,
+        Multiplicity,
+        Scope<(Binder<String>, Embed<RcTerm>), RcTerm>,
+    ),
     /// Lambda abstractions
-    Lam(ByteSpan, Scope<(Binder<String>, Embed<RcTerm>), RcTerm>),
+    ///
+    /// Lambdas that were written without a multiplicity will take it from
+    /// the type that they are checked against.
+    Lam(
+        ByteSpan,
+        Plicity,
+        Option<Multiplicity>,
+        Scope<(Binder<String>, Embed<RcTerm>), RcTerm>,
+    ),
     /// Term application
-    App(RcTerm, RcTerm),
+    App(RcTerm, Plicity, RcTerm),
     /// If expression
     If(ByteIndex, RcTerm, RcTerm, RcTerm),
     /// Dependent record types
@@ -188,6 +286,12 @@ pub enum Term {
     Proj(ByteSpan, RcTerm, ByteSpan, Label),
     /// Case expressions
     Case(ByteSpan, RcTerm, Vec<Scope<RcPattern, RcTerm>>),
+    /// Propositional equality types
+    Eq(RcTerm, RcTerm),
+    /// Proofs of reflexivity
+    Refl(ByteSpan),
+    /// Dependent elimination of equality proofs
+    J(ByteIndex, RcTerm, RcTerm, RcTerm),
     /// Array literals
     Array(ByteSpan, Vec<RcTerm>),
     /// Let bindings
@@ -195,6 +299,8 @@ pub enum Term {
         ByteSpan,
         Scope<(Binder<String>, Embed<(RcTerm, RcTerm)>), RcTerm>,
     ),
+    /// Terms that could not be parsed
+    Error(ByteSpan),
 }
 
 impl Term {
@@ -204,18 +310,22 @@ impl Term {
             | Term::Hole(span)
             | Term::Var(span, _, _)
             | Term::Extern(span, _, _, _)
-            | Term::Pi(span, _)
-            | Term::Lam(span, _)
+            | Term::Pi(span, _, _, _)
+            | Term::Lam(span, _, _, _)
             | Term::RecordType(span, _)
             | Term::Record(span, _)
             | Term::Proj(span, _, _, _)
             | Term::Case(span, _, _)
+            | Term::Refl(span)
             | Term::Array(span, _)
-            | Term::Let(span, _) => span,
+            | Term::Let(span, _)
+            | Term::Error(span) => span,
             Term::Literal(ref literal) => literal.span(),
             Term::Ann(ref expr, ref ty) => expr.span().to(ty.span()),
-            Term::App(ref head, ref arg) => head.span().to(arg.span()),
+            Term::App(ref head, _, ref arg) => head.span().to(arg.span()),
+            Term::Eq(ref lhs, ref rhs) => lhs.span().to(rhs.span()),
             Term::If(start, _, _, ref if_false) => ByteSpan::new(start, if_false.span().end()),
+            Term::J(start, _, _, ref proof) => ByteSpan::new(start, proof.span().end()),
         }
     }
 }
@@ -4,7 +4,7 @@ use moniker::{Binder, Embed, FreeVar, Nest, Scope, Var};
 
 use syntax::concrete;
 use syntax::raw;
-use syntax::{Label, Level, LevelShift};
+use syntax::{Label, Level, LevelShift, Multiplicity, Plicity};
 
 #[cfg(test)]
 mod tests;
@@ -20,11 +20,28 @@ pub struct DesugarEnv {
     /// If we arrive at a variable that has not already been assigned a free name,
     /// we assume that it is a global name.
     locals: HashMap<String, FreeVar<String>>,
+    /// The constructors of imported data types, which can be matched on in
+    /// patterns without being brought into scope in terms
+    constructors: HashMap<String, FreeVar<String>>,
 }
 
 impl DesugarEnv {
     pub fn new(mappings: HashMap<String, FreeVar<String>>) -> DesugarEnv {
-        DesugarEnv { locals: mappings }
+        DesugarEnv {
+            locals: mappings,
+            constructors: HashMap::new(),
+        }
+    }
+
+    /// Allow a constructor from an imported module to be used in patterns
+    pub fn import_constructor(&mut self, name: &str, free_var: FreeVar<String>) {
+        self.constructors.insert(name.to_owned(), free_var);
+    }
+
+    /// Look up a name used in a pattern, which may refer to an imported
+    /// constructor if it is not bound locally
+    fn pattern_name(&self, name: &str) -> Option<&FreeVar<String>> {
+        self.locals.get(name).or_else(|| self.constructors.get(name))
     }
 
     pub fn on_item(&mut self, name: &str) -> Binder<String> {
@@ -59,13 +76,13 @@ pub trait Desugar<T> {
 /// Convert a sugary pi type from something like:
 ///
 /// ```text
-/// (a b : t1) (c : t2) -> t3
+/// {a b : t1} (c : t2) -> t3
 /// ```
 ///
 /// To a bunch of nested pi types like:
 ///
 /// ```text
-/// (a : t1) -> (b : t1) -> (c : t2) -> t3
+/// {a : t1} -> {b : t1} -> (c : t2) -> t3
 /// ```
 fn desugar_pi(
     env: &DesugarEnv,
@@ -75,35 +92,37 @@ fn desugar_pi(
     let mut env = env.clone();
 
     let mut params = Vec::new();
-    for &(ref names, ref ann) in param_groups {
+    for &(plicity, multiplicity, ref names, ref ann) in param_groups {
         let ann = raw::RcTerm::from(ann.desugar(&env));
         params.extend(names.iter().map(|&(start, ref name)| {
             let free_var = env.on_binding(name);
-            (start, Binder(free_var), ann.clone())
+            (start, plicity, multiplicity, Binder(free_var), ann.clone())
         }));
     }
 
-    params
-        .into_iter()
-        .rev()
-        .fold(body.desugar(&env), |acc, (start, binder, ann)| {
+    params.into_iter().rev().fold(
+        body.desugar(&env),
+        |acc, (start, plicity, multiplicity, binder, ann)| {
             raw::RcTerm::from(raw::Term::Pi(
                 ByteSpan::new(start, acc.span().end()),
+                plicity,
+                multiplicity,
                 Scope::new((binder, Embed(ann.clone())), acc),
             ))
-        })
+        },
+    )
 }
 
 /// Convert a sugary lambda from something like:
 ///
 /// ```text
-/// \(a b : t1) c (d : t2) => t3
+/// \{a b : t1} c (d : t2) => t3
 /// ```
 ///
 /// To a bunch of nested lambdas like:
 ///
 /// ```text
-/// \(a : t1) => \(b : t1) => \c => \(d : t2) => t3
+/// \{a : t1} => \{b : t1} => \c => \(d : t2) => t3
 /// ```
 fn desugar_lam(
     env: &DesugarEnv,
@@ -114,15 +133,17 @@ fn desugar_lam(
     let mut env = env.clone();
 
     let mut params = Vec::new();
-    for &(ref names, ref ann) in param_groups {
-        let ann = match *ann {
-            None => raw::RcTerm::from(raw::Term::Hole(ByteSpan::default())),
-            Some(ref ann) => ann.desugar(&env),
-        };
+    for &(plicity, multiplicity, ref names, ref ann) in param_groups {
+        let ann = ann.as_ref().map(|ann| ann.desugar(&env));
 
         params.extend(names.iter().map(|&(start, ref name)| {
+            // Omitted annotations become holes that point at the parameter
+            let ann = ann.clone().unwrap_or_else(|| {
+                let span = ByteSpan::from_offset(start, ByteOffset::from_str(name));
+                raw::RcTerm::from(raw::Term::Hole(span))
+            });
             let free_var = env.on_binding(name);
-            (start, Binder(free_var), ann.clone())
+            (start, plicity, multiplicity, Binder(free_var), ann)
         }));
     }
 
@@ -134,9 +155,11 @@ fn desugar_lam(
     params
         .into_iter()
         .rev()
-        .fold(body, |acc, (start, binder, ann)| {
+        .fold(body, |acc, (start, plicity, multiplicity, binder, ann)| {
             raw::RcTerm::from(raw::Term::Lam(
                 ByteSpan::new(start, acc.span().end()),
+                plicity,
+                multiplicity,
                 Scope::new((binder, Embed(ann.clone())), acc),
             ))
         })
@@ -147,19 +170,27 @@ fn desugar_bindings(env: &mut DesugarEnv, items: &[concrete::Item]) -> Vec<raw::
         .iter()
         .map(|concrete_item| match *concrete_item {
             concrete::Item::Declaration {
+                ref doc,
+                private,
+                partial,
                 name: (start, ref name),
                 ref ann,
             } => {
                 let term = ann.desugar(&env);
 
                 raw::Item::Declaration {
+                    doc: doc.join("\n"),
                     label_span: ByteSpan::from_offset(start, ByteOffset::from_str(name)),
                     label: Label(name.clone()),
                     binder: env.on_item(name),
                     term,
+                    partial: partial.is_some(),
+                    private: private.is_some(),
                 }
             },
             concrete::Item::Definition {
+                ref doc,
+                private,
                 name: (start, ref name),
                 ref params,
                 ref return_ann,
@@ -169,13 +200,92 @@ fn desugar_bindings(env: &mut DesugarEnv, items: &[concrete::Item]) -> Vec<raw::
                 let term = desugar_lam(&env, params, return_ann, body);
 
                 raw::Item::Definition {
+                    doc: doc.join("\n"),
                     label_span: ByteSpan::from_offset(start, ByteOffset::from_str(name)),
                     label: Label(name.clone()),
                     binder: env.on_item(name),
                     term,
+                    private: private.is_some(),
                 }
             },
-            concrete::Item::Error(_) => unimplemented!("error recovery"),
+            concrete::Item::Data {
+                ref doc,
+                private,
+                span,
+                name: (start, ref name),
+                ref params,
+                ref ann,
+                ref constructors,
+            } => {
+                let label_span = ByteSpan::from_offset(start, ByteOffset::from_str(name));
+                let binder = env.on_item(name);
+
+                // The parameters are only in scope within the data type
+                let mut param_env = env.clone();
+                let mut raw_params = Vec::new();
+                for &(_, _, ref names, ref ann) in params {
+                    let ann = ann.desugar(&param_env);
+                    for &(_, ref name) in names {
+                        let free_var = param_env.on_binding(name);
+                        raw_params.push((Binder(free_var), ann.clone()));
+                    }
+                }
+
+                let term = match *ann {
+                    Some(ref ann) => ann.desugar(&param_env),
+                    None => raw::RcTerm::from(raw::Term::Universe(label_span, None)),
+                };
+
+                let constructors = constructors
+                    .iter()
+                    .map(|constructor| {
+                        let (start, ref name) = constructor.name;
+
+                        raw::Constructor {
+                            doc: constructor.doc.join("\n"),
+                            label_span: ByteSpan::from_offset(start, ByteOffset::from_str(name)),
+                            label: Label(name.clone()),
+                            binder: env.on_item(name),
+                            term: constructor.ann.desugar(&param_env),
+                        }
+                    }).collect();
+
+                raw::Item::Data {
+                    doc: doc.join("\n"),
+                    span,
+                    label_span,
+                    label: Label(name.clone()),
+                    binder,
+                    params: raw_params,
+                    term,
+                    constructors,
+                    private: private.is_some(),
+                }
+            },
+            concrete::Item::Import {
+                ref doc,
+                private,
+                span,
+                ref path,
+            } => {
+                let module = path
+                    .iter()
+                    .map(|&(_, ref name)| name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(".");
+                let (start, ref name) = *path.last().expect("empty module path");
+
+                raw::Item::Import {
+                    doc: doc.join("\n"),
+                    span,
+                    label_span: ByteSpan::from_offset(start, ByteOffset::from_str(name)),
+                    label: Label(name.clone()),
+                    binder: env.on_item(name),
+                    module,
+                    private: private.is_some(),
+                }
+            },
+            concrete::Item::Error(span) => raw::Item::Error(span),
         }).collect()
 }
 
@@ -188,11 +298,16 @@ fn desugar_let(env: &DesugarEnv, items: &[concrete::Item], body: &concrete::Term
 
     items.into_iter().rev().fold(body, |acc, item| match item {
         raw::Item::Declaration { .. } => acc, // TODO: Let declarations (maybe not necessary?)
+        raw::Item::Data { .. } => acc,        // TODO: Local data types
+        raw::Item::Import { .. } => acc,      // TODO: Local imports
+        raw::Item::Error(_) => acc,
         raw::Item::Definition {
+            doc: _,
             label_span,
             label: _,
             binder,
             term,
+            private: _,
         } => raw::RcTerm::from(raw::Term::Let(
             label_span.with_end(term.span().end()),
             Scope::new((binder, Embed((hole.clone(), term))), acc),
@@ -249,15 +364,31 @@ fn desugar_record(
     raw::RcTerm::from(raw::Term::Record(span, Scope::new(Nest::new(fields), ())))
 }
 
+/// Returns `true` if the pattern contains any parts that could not be parsed
+fn has_errors(pattern: &concrete::Pattern) -> bool {
+    match *pattern {
+        concrete::Pattern::Parens(_, ref pattern) | concrete::Pattern::Ann(ref pattern, _) => {
+            has_errors(pattern)
+        },
+        concrete::Pattern::Name(_, _, _) | concrete::Pattern::Literal(_) => false,
+        concrete::Pattern::App(ref head, ref args) => {
+            has_errors(head) || args.iter().any(has_errors)
+        },
+        concrete::Pattern::Error(_) => true,
+    }
+}
+
 impl Desugar<raw::Module> for concrete::Module {
     fn desugar(&self, env: &DesugarEnv) -> raw::Module {
-        let concrete_items = match *self {
-            concrete::Module::Valid { ref items } => items,
-            concrete::Module::Error(_) => unimplemented!("error recovery"),
-        };
-
-        raw::Module {
-            items: desugar_bindings(&mut env.clone(), concrete_items),
+        match *self {
+            concrete::Module::Valid { ref doc, ref items } => raw::Module {
+                doc: doc.join("\n"),
+                items: desugar_bindings(&mut env.clone(), items),
+            },
+            concrete::Module::Error(span) => raw::Module {
+                doc: String::new(),
+                items: vec![raw::Item::Error(span)],
+            },
         }
     }
 }
@@ -285,7 +416,7 @@ impl Desugar<(raw::RcPattern, DesugarEnv)> for concrete::Pattern {
 
                 (ann_pattern, env)
             },
-            concrete::Pattern::Name(_, ref name, shift) => match (env.locals.get(name), shift) {
+            concrete::Pattern::Name(_, ref name, shift) => match (env.pattern_name(name), shift) {
                 (Some(free_var), shift) => {
                     let var = Var::Free(free_var.clone());
                     let shift = LevelShift(shift.unwrap_or(0));
@@ -313,7 +444,49 @@ impl Desugar<(raw::RcPattern, DesugarEnv)> for concrete::Pattern {
                 raw::RcPattern::from(raw::Pattern::Literal(literal.desugar(env))),
                 env.clone(),
             ),
-            concrete::Pattern::Error(_) => unimplemented!("error recovery"),
+            concrete::Pattern::App(ref head, ref args) => {
+                let mut head = &**head;
+                while let concrete::Pattern::Parens(_, ref pattern) = *head {
+                    head = pattern;
+                }
+                let var = match *head {
+                    concrete::Pattern::Name(_, ref name, None) => match env.pattern_name(name) {
+                        Some(free_var) => Var::Free(free_var.clone()),
+                        None => Var::Free(FreeVar::fresh_named(name.clone())),
+                    },
+                    // Reported as an error when parsing
+                    _ => return (raw::RcPattern::from(raw::Pattern::Error(span)), env.clone()),
+                };
+
+                let mut env = env.clone();
+                let args = args
+                    .iter()
+                    .map(|arg| {
+                        let (arg, arg_env) = arg.desugar(&env);
+                        env = arg_env;
+                        arg
+                    }).collect();
+                let pattern = raw::RcPattern::from(raw::Pattern::Constr(span, Embed(var), args));
+
+                (pattern, env)
+            },
+            concrete::Pattern::Error(span) => {
+                (raw::RcPattern::from(raw::Pattern::Error(span)), env.clone())
+            },
+        }
+    }
+}
+
+impl Desugar<Level> for concrete::Level {
+    fn desugar(&self, env: &DesugarEnv) -> Level {
+        match *self {
+            concrete::Level::Parens(_, ref level) => level.desugar(env),
+            concrete::Level::Const(_, level) => Level::from(level),
+            concrete::Level::Var(_, ref name) => Level::param(name.clone()),
+            concrete::Level::Succ(_, ref level, shift) => level.desugar(env) + LevelShift(shift),
+            concrete::Level::Max(_, ref levels) => levels
+                .iter()
+                .fold(Level::from(0), |acc, level| acc.max(&level.desugar(env))),
         }
     }
 }
@@ -326,9 +499,10 @@ impl Desugar<raw::RcTerm> for concrete::Term {
             concrete::Term::Ann(ref expr, ref ty) => {
                 raw::RcTerm::from(raw::Term::Ann(expr.desugar(env), ty.desugar(env)))
             },
-            concrete::Term::Universe(_, level) => {
-                raw::RcTerm::from(raw::Term::Universe(span, Level(level.unwrap_or(0))))
-            },
+            concrete::Term::Universe(_, ref level) => raw::RcTerm::from(raw::Term::Universe(
+                span,
+                level.as_ref().map(|level| level.desugar(env)),
+            )),
             concrete::Term::Literal(ref literal) => {
                 raw::RcTerm::from(raw::Term::Literal(literal.desugar(env)))
             },
@@ -345,14 +519,21 @@ impl Desugar<raw::RcTerm> for concrete::Term {
             concrete::Term::Lam(_, ref params, ref body) => desugar_lam(env, params, None, body),
             concrete::Term::Arrow(ref ann, ref body) => raw::RcTerm::from(raw::Term::Pi(
                 span,
+                Plicity::Explicit,
+                Multiplicity::Many,
                 Scope::new(
                     (Binder(FreeVar::fresh_unnamed()), Embed(ann.desugar(env))),
                     body.desugar(env),
                 ),
             )),
             concrete::Term::App(ref head, ref args) => {
-                args.iter().fold(head.desugar(env), |acc, arg| {
-                    raw::RcTerm::from(raw::Term::App(acc, arg.desugar(env)))
+                args.iter().fold(head.desugar(env), |acc, arg| match *arg {
+                    concrete::Arg::Explicit(ref arg) => {
+                        raw::RcTerm::from(raw::Term::App(acc, Plicity::Explicit, arg.desugar(env)))
+                    },
+                    concrete::Arg::Implicit(_, ref arg) => {
+                        raw::RcTerm::from(raw::Term::App(acc, Plicity::Implicit, arg.desugar(env)))
+                    },
                 })
             },
             concrete::Term::Let(_, ref items, ref body) => desugar_let(env, items, body),
@@ -364,6 +545,18 @@ impl Desugar<raw::RcTerm> for concrete::Term {
                     if_false.desugar(env),
                 ))
             },
+            concrete::Term::Eq(ref lhs, ref rhs) => {
+                raw::RcTerm::from(raw::Term::Eq(lhs.desugar(env), rhs.desugar(env)))
+            },
+            concrete::Term::Refl(_) => raw::RcTerm::from(raw::Term::Refl(span)),
+            concrete::Term::J(start, ref motive, ref base, ref proof) => {
+                raw::RcTerm::from(raw::Term::J(
+                    start,
+                    motive.desugar(env),
+                    base.desugar(env),
+                    proof.desugar(env),
+                ))
+            },
             concrete::Term::Case(span, ref head, ref clauses) => {
                 raw::RcTerm::from(raw::Term::Case(
                     span,
@@ -371,8 +564,15 @@ impl Desugar<raw::RcTerm> for concrete::Term {
                     clauses
                         .iter()
                         .map(|(pattern, term)| {
-                            let (pattern, env) = pattern.desugar(env);
-                            Scope::new(pattern, term.desugar(&env))
+                            let (raw_pattern, env) = pattern.desugar(env);
+                            // We don't know what a poisoned pattern binds, so
+                            // the body is poisoned as well
+                            let body = if has_errors(pattern) {
+                                raw::RcTerm::from(raw::Term::Error(term.span()))
+                            } else {
+                                term.desugar(&env)
+                            };
+                            Scope::new(raw_pattern, body)
                         }).collect(),
                 ))
             },
@@ -386,7 +586,7 @@ impl Desugar<raw::RcTerm> for concrete::Term {
                     Label(label.clone()),
                 ))
             },
-            concrete::Term::Error(_) => unimplemented!("error recovery"),
+            concrete::Term::Error(span) => raw::RcTerm::from(raw::Term::Error(span)),
         }
     }
 }
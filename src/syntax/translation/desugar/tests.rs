@@ -29,7 +29,6 @@ fn parse(src: &str) -> raw::RcTerm {
 
 mod term {
     use syntax::raw::{RcTerm, Term};
-    use syntax::Level;
 
     use super::*;
 
@@ -42,7 +41,7 @@ mod term {
     }
 
     fn u0() -> RcTerm {
-        RcTerm::from(Term::Universe(ByteSpan::default(), Level(0)))
+        RcTerm::from(Term::Universe(ByteSpan::default(), None))
     }
 
     #[test]
@@ -93,11 +92,15 @@ mod term {
             parse(r"\x : Type -> Type => x"),
             RcTerm::from(Term::Lam(
                 ByteSpan::default(),
+                Plicity::Explicit,
+                None,
                 Scope::new(
                     (
                         Binder(x.clone()),
                         Embed(RcTerm::from(Term::Pi(
                             ByteSpan::default(),
+                            Plicity::Explicit,
+                            Multiplicity::Many,
                             Scope::new((Binder(FreeVar::fresh_unnamed()), Embed(u0())), u0()),
                         ))),
                     ),
@@ -117,11 +120,15 @@ mod term {
             parse(r"\x : (\y => y) => x"),
             RcTerm::from(Term::Lam(
                 ByteSpan::default(),
+                Plicity::Explicit,
+                None,
                 Scope::new(
                     (
                         Binder(x.clone()),
                         Embed(RcTerm::from(Term::Lam(
                             ByteSpan::default(),
+                            Plicity::Explicit,
+                            None,
                             Scope::new((Binder(y.clone()), Embed(hole())), var(&y)),
                         )))
                     ),
@@ -140,10 +147,14 @@ mod term {
             parse(r"\(x y : Type) => x"),
             RcTerm::from(Term::Lam(
                 ByteSpan::default(),
+                Plicity::Explicit,
+                None,
                 Scope::new(
                     (Binder(x.clone()), Embed(u0())),
                     RcTerm::from(Term::Lam(
                         ByteSpan::default(),
+                        Plicity::Explicit,
+                        None,
                         Scope::new((Binder(y.clone()), Embed(u0())), var(&x)),
                     )),
                 ),
@@ -157,6 +168,8 @@ mod term {
             parse(r"Type -> Type"),
             RcTerm::from(Term::Pi(
                 ByteSpan::default(),
+                Plicity::Explicit,
+                Multiplicity::Many,
                 Scope::new((Binder(FreeVar::fresh_unnamed()), Embed(u0())), u0()),
             )),
         );
@@ -170,11 +183,15 @@ mod term {
             parse(r"(x : Type -> Type) -> x"),
             RcTerm::from(Term::Pi(
                 ByteSpan::default(),
+                Plicity::Explicit,
+                Multiplicity::Many,
                 Scope::new(
                     (
                         Binder(x.clone()),
                         Embed(RcTerm::from(Term::Pi(
                             ByteSpan::default(),
+                            Plicity::Explicit,
+                            Multiplicity::Many,
                             Scope::new((Binder(FreeVar::fresh_unnamed()), Embed(u0())), u0()),
                         ))),
                     ),
@@ -193,10 +210,14 @@ mod term {
             parse(r"(x y : Type) -> x"),
             RcTerm::from(Term::Pi(
                 ByteSpan::default(),
+                Plicity::Explicit,
+                Multiplicity::Many,
                 Scope::new(
                     (Binder(x.clone()), Embed(u0())),
                     RcTerm::from(Term::Pi(
                         ByteSpan::default(),
+                        Plicity::Explicit,
+                        Multiplicity::Many,
                         Scope::new((Binder(y.clone()), Embed(u0())), var(&x)),
                     )),
                 ),
@@ -212,10 +233,14 @@ mod term {
             parse(r"(x : Type) -> x -> x"),
             RcTerm::from(Term::Pi(
                 ByteSpan::default(),
+                Plicity::Explicit,
+                Multiplicity::Many,
                 Scope::new(
                     (Binder(x.clone()), Embed(u0())),
                     RcTerm::from(Term::Pi(
                         ByteSpan::default(),
+                        Plicity::Explicit,
+                        Multiplicity::Many,
                         Scope::new((Binder(FreeVar::fresh_unnamed()), Embed(var(&x))), var(&x)),
                     )),
                 ),
@@ -223,6 +248,113 @@ mod term {
         );
     }
 
+    #[test]
+    fn pi_implicit() {
+        let a = FreeVar::fresh_named("a");
+
+        assert_term_eq!(
+            parse(r"{a : Type} -> a -> a"),
+            RcTerm::from(Term::Pi(
+                ByteSpan::default(),
+                Plicity::Implicit,
+                Multiplicity::Many,
+                Scope::new(
+                    (Binder(a.clone()), Embed(u0())),
+                    RcTerm::from(Term::Pi(
+                        ByteSpan::default(),
+                        Plicity::Explicit,
+                        Multiplicity::Many,
+                        Scope::new((Binder(FreeVar::fresh_unnamed()), Embed(var(&a))), var(&a)),
+                    )),
+                ),
+            )),
+        );
+    }
+
+    #[test]
+    fn pi_multiplicities() {
+        let a = FreeVar::fresh_named("a");
+        let x = FreeVar::fresh_named("x");
+
+        assert_term_eq!(
+            parse(r"{0 a : Type} -> (1 x : a) -> a"),
+            RcTerm::from(Term::Pi(
+                ByteSpan::default(),
+                Plicity::Implicit,
+                Multiplicity::Zero,
+                Scope::new(
+                    (Binder(a.clone()), Embed(u0())),
+                    RcTerm::from(Term::Pi(
+                        ByteSpan::default(),
+                        Plicity::Explicit,
+                        Multiplicity::One,
+                        Scope::new((Binder(x.clone()), Embed(var(&a))), var(&a)),
+                    )),
+                ),
+            )),
+        );
+    }
+
+    #[test]
+    fn lam_multiplicities() {
+        let a = FreeVar::fresh_named("a");
+        let x = FreeVar::fresh_named("x");
+
+        assert_term_eq!(
+            parse(r"\{0 a : Type} (1 x : a) => x"),
+            RcTerm::from(Term::Lam(
+                ByteSpan::default(),
+                Plicity::Implicit,
+                Some(Multiplicity::Zero),
+                Scope::new(
+                    (Binder(a.clone()), Embed(u0())),
+                    RcTerm::from(Term::Lam(
+                        ByteSpan::default(),
+                        Plicity::Explicit,
+                        Some(Multiplicity::One),
+                        Scope::new((Binder(x.clone()), Embed(var(&a))), var(&x)),
+                    )),
+                ),
+            )),
+        );
+    }
+
+    #[test]
+    fn lam_implicit_app() {
+        let a = FreeVar::fresh_named("a");
+        let b = FreeVar::fresh_named("b");
+        let f = FreeVar::fresh_named("f");
+
+        assert_term_eq!(
+            parse(r"\{a : Type} (f : {b : Type} -> b) => f {a}"),
+            RcTerm::from(Term::Lam(
+                ByteSpan::default(),
+                Plicity::Implicit,
+                None,
+                Scope::new(
+                    (Binder(a.clone()), Embed(u0())),
+                    RcTerm::from(Term::Lam(
+                        ByteSpan::default(),
+                        Plicity::Explicit,
+                        None,
+                        Scope::new(
+                            (
+                                Binder(f.clone()),
+                                Embed(RcTerm::from(Term::Pi(
+                                    ByteSpan::default(),
+                                    Plicity::Implicit,
+                                    Multiplicity::Many,
+                                    Scope::new((Binder(b.clone()), Embed(u0())), var(&b)),
+                                ))),
+                            ),
+                            RcTerm::from(Term::App(var(&f), Plicity::Implicit, var(&a))),
+                        ),
+                    )),
+                ),
+            )),
+        );
+    }
+
     #[test]
     fn lam_app() {
         let x = FreeVar::fresh_named("x");
@@ -232,19 +364,25 @@ mod term {
             parse(r"\(x : Type -> Type) (y : Type) => x y"),
             RcTerm::from(Term::Lam(
                 ByteSpan::default(),
+                Plicity::Explicit,
+                None,
                 Scope::new(
                     (
                         Binder(x.clone()),
                         Embed(RcTerm::from(Term::Pi(
                             ByteSpan::default(),
+                            Plicity::Explicit,
+                            Multiplicity::Many,
                             Scope::new((Binder(FreeVar::fresh_unnamed()), Embed(u0())), u0()),
                         ))),
                     ),
                     RcTerm::from(Term::Lam(
                         ByteSpan::default(),
+                        Plicity::Explicit,
+                        None,
                         Scope::new(
                             (Binder(y.clone()), Embed(u0())),
-                            RcTerm::from(Term::App(var(&x), var(&y))),
+                            RcTerm::from(Term::App(var(&x), Plicity::Explicit, var(&y))),
                         ),
                     )),
                 ),
@@ -261,10 +399,14 @@ mod term {
             parse(r"\(a : Type) (x : a) => x"),
             RcTerm::from(Term::Lam(
                 ByteSpan::default(),
+                Plicity::Explicit,
+                None,
                 Scope::new(
                     (Binder(a.clone()), Embed(u0())),
                     RcTerm::from(Term::Lam(
                         ByteSpan::default(),
+                        Plicity::Explicit,
+                        None,
                         Scope::new((Binder(x.clone()), Embed(var(&a))), var(&x)),
                     )),
                 ),
@@ -280,10 +422,14 @@ mod term {
             parse(r"(a : Type) -> a -> a"),
             RcTerm::from(Term::Pi(
                 ByteSpan::default(),
+                Plicity::Explicit,
+                Multiplicity::Many,
                 Scope::new(
                     (Binder(a.clone()), Embed(u0())),
                     RcTerm::from(Term::Pi(
                         ByteSpan::default(),
+                        Plicity::Explicit,
+                        Multiplicity::Many,
                         Scope::new((Binder(FreeVar::fresh_unnamed()), Embed(var(&a))), var(&a)),
                     )),
                 ),
@@ -326,6 +472,14 @@ mod term {
             );
         }
 
+        #[test]
+        fn pi_args_multiplicity() {
+            assert_term_eq!(
+                parse(r"(0 a : Type) (1 x y : a) -> a"),
+                parse(r"(0 a : Type) -> (1 x : a) -> (1 y : a) -> a"),
+            );
+        }
+
         #[test]
         fn arrow() {
             assert_term_eq!(
@@ -4,4 +4,4 @@ mod desugar;
 mod resugar;
 
 pub use self::desugar::{Desugar, DesugarEnv};
-pub use self::resugar::{Resugar, ResugarEnv};
+pub use self::resugar::{resugar_ann, Resugar, ResugarEnv};
@@ -4,7 +4,7 @@ use moniker::{Binder, BoundTerm, Embed, FreeVar, Scope, Var};
 
 use syntax::concrete;
 use syntax::core;
-use syntax::{Label, Level, LevelShift};
+use syntax::{Label, Level, LevelShift, Multiplicity, Plicity};
 
 #[cfg(test)]
 mod tests;
@@ -17,7 +17,8 @@ pub struct ResugarEnv {
 }
 
 const KEYWORDS: &[&str] = &[
-    "as", "case", "else", "extern", "if", "in", "let", "of", "record", "Record", "then", "Type",
+    "as", "case", "data", "else", "extern", "if", "import", "in", "J", "let", "of", "partial",
+    "private", "record", "Record", "refl", "then", "Type", "where",
 ];
 
 impl ResugarEnv {
@@ -85,6 +86,12 @@ impl ResugarEnv {
         })
     }
 
+    /// Resugar a free variable to the given name, replacing the name that it
+    /// was previously given
+    pub fn rename(&mut self, free_var: &FreeVar<String>, name: String) {
+        self.renames.insert(free_var.clone(), name);
+    }
+
     pub fn on_free_var(&self, free_var: &FreeVar<String>) -> String {
         self.renames.get(free_var).cloned().unwrap_or_else(|| {
             panic!(
@@ -109,22 +116,30 @@ impl Resugar<concrete::Module> for core::Module {
         for item in &self.items {
             match item {
                 core::Item::Declaration {
+                    ref doc,
                     ref label,
                     ref binder,
                     ref term,
+                    partial,
+                    private,
                 } => {
                     let name = env.on_item(label, binder);
                     local_decls.insert(binder, name.clone());
 
                     items.push(concrete::Item::Declaration {
+                        doc: resugar_doc(doc),
+                        private: if *private { Some(ByteSpan::default()) } else { None },
+                        partial: if *partial { Some(ByteSpan::default()) } else { None },
                         name: (ByteIndex::default(), name),
-                        ann: resugar_term(&env, term, Prec::ANN),
+                        ann: resugar_term(&env, term, Prec::ITEM_ANN),
                     });
                 },
                 core::Item::Definition {
+                    ref doc,
                     ref label,
                     ref binder,
                     ref term,
+                    private,
                 } => {
                     let name = local_decls.get(binder).cloned().unwrap_or_else(|| {
                         let name = env.on_item(label, binder);
@@ -139,16 +154,79 @@ impl Resugar<concrete::Module> for core::Module {
                     };
 
                     items.push(concrete::Item::Definition {
+                        doc: resugar_doc(doc),
+                        private: if *private { Some(ByteSpan::default()) } else { None },
                         name: (ByteIndex::default(), name),
                         return_ann: None,
                         params,
                         body,
                     });
                 },
+                core::Item::Data {
+                    ref doc,
+                    ref label,
+                    ref binder,
+                    ref params,
+                    ref term,
+                    ref constructors,
+                    private,
+                } => {
+                    let name = env.on_item(label, binder);
+                    let constructor_names = constructors
+                        .iter()
+                        .map(|constructor| env.on_item(&constructor.label, &constructor.binder))
+                        .collect::<Vec<_>>();
+
+                    // The parameters are only in scope within the data type
+                    let mut param_env = env.clone();
+                    let params = params
+                        .iter()
+                        .map(|&(ref binder, ref ann)| {
+                            let ann = resugar_term(&param_env, ann, Prec::APP);
+                            let name = param_env.on_binder(binder);
+                            let param_name = vec![(ByteIndex::default(), name)];
+                            (Plicity::Explicit, Multiplicity::Many, param_name, ann)
+                        }).collect();
+
+                    items.push(concrete::Item::Data {
+                        doc: resugar_doc(doc),
+                        private: if *private { Some(ByteSpan::default()) } else { None },
+                        span: ByteSpan::default(),
+                        name: (ByteIndex::default(), name),
+                        params,
+                        ann: Some(Box::new(resugar_term(&param_env, term, Prec::ANN))),
+                        constructors: <_>::zip(constructors.iter(), constructor_names)
+                            .map(|(constructor, name)| concrete::DataConstructor {
+                                doc: resugar_doc(&constructor.doc),
+                                name: (ByteIndex::default(), name),
+                                ann: resugar_term(&param_env, &constructor.term, Prec::ANN),
+                            }).collect(),
+                    });
+                },
+                core::Item::Error {
+                    ref label,
+                    ref binder,
+                    ..
+                } => {
+                    // Later items may still refer to the name
+                    env.on_item(label, binder);
+                    items.push(concrete::Item::Error(ByteSpan::default()));
+                },
             };
         }
 
-        concrete::Module::Valid { items }
+        concrete::Module::Valid {
+            doc: resugar_doc(&self.doc),
+            items,
+        }
+    }
+}
+
+/// Split documentation back into the lines of its doc comments
+fn resugar_doc(doc: &str) -> Vec<String> {
+    match doc {
+        "" => Vec::new(),
+        doc => doc.split('\n').map(String::from).collect(),
     }
 }
 
@@ -164,6 +242,8 @@ impl Prec {
     pub const NO_WRAP: Prec = Prec(-1);
     /// Precedence corresponding to `Term` in the parser
     pub const ANN: Prec = Prec(0);
+    /// Precedence corresponding to `AnnTerm<"no-eq">` in the parser
+    pub const ITEM_ANN: Prec = Prec(1);
     /// Precedence corresponding to `LamTerm` in the parser
     pub const LAM: Prec = Prec(1);
     /// Precedence corresponding to `PiTerm` in the parser
@@ -185,7 +265,7 @@ fn parens_if(should_wrap: bool, inner: concrete::Term) -> concrete::Term {
 fn resugar_pattern(
     env: &mut ResugarEnv,
     pattern: &core::Pattern,
-    _prec: Prec,
+    prec: Prec,
 ) -> concrete::Pattern {
     match *pattern {
         core::Pattern::Ann(ref pattern, Embed(ref ty)) => concrete::Pattern::Ann(
@@ -205,10 +285,32 @@ fn resugar_pattern(
             let name = env.on_free_var(free_var);
             concrete::Pattern::Name(ByteSpan::default(), name, shift)
         },
-        core::Pattern::Var(Embed(Var::Bound(_)), _) => {
+        core::Pattern::Error => concrete::Pattern::Error(ByteSpan::default()),
+        core::Pattern::Var(Embed(Var::Bound(_)), _)
+        | core::Pattern::Constr(Embed(Var::Bound(_)), _) => {
             // TODO: Better message
             panic!("Tried to convert a term that was not locally closed");
         },
+        core::Pattern::Constr(Embed(Var::Free(ref free_var)), ref args) => {
+            let name = env.on_free_var(free_var);
+            let head = concrete::Pattern::Name(ByteSpan::default(), name, None);
+            // Implicit arguments are inserted during elaboration, so we only
+            // need to show the explicit ones
+            let args = args
+                .iter()
+                .filter(|&&(plicity, _)| plicity == Plicity::Explicit)
+                .map(|&(_, ref arg)| resugar_pattern(env, arg, Prec::ATOMIC))
+                .collect::<Vec<_>>();
+
+            if args.is_empty() {
+                head
+            } else if Prec::APP < prec {
+                let pattern = concrete::Pattern::App(Box::new(head), args);
+                concrete::Pattern::Parens(ByteSpan::default(), Box::new(pattern))
+            } else {
+                concrete::Pattern::App(Box::new(head), args)
+            }
+        },
         core::Pattern::Literal(ref literal) => {
             use syntax::concrete::{Literal, Pattern};
 
@@ -246,22 +348,33 @@ fn resugar_pattern(
 
 fn resugar_pi(
     env: &ResugarEnv,
+    plicity: Plicity,
+    multiplicity: Multiplicity,
     scope: &Scope<(Binder<String>, Embed<core::RcTerm>), core::RcTerm>,
     prec: Prec,
 ) -> concrete::Term {
     let mut env = env.clone();
 
+    let mut plicity = plicity;
+    let mut multiplicity = multiplicity;
     let ((binder, Embed(mut ann)), mut body) = scope.clone().unbind();
     let body_fvs = body.free_vars();
 
-    // Only use explicit parameter names if the body is dependent on
-    // the parameter or there is a human-readable name given.
+    // Only use explicit parameter names if the parameter is implicit, has a
+    // restricted multiplicity, the body is dependent on the parameter, or
+    // there is a human-readable name given.
     //
     // We'll be checking for readable names as we go, because if they've
     // survived until now they're probably desirable to retain!
-    if body_fvs.contains(&binder.0) || binder.0.pretty_name.is_some() {
+    if plicity == Plicity::Implicit
+        || multiplicity != Multiplicity::Many
+        || body_fvs.contains(&binder.0)
+        || binder.0.pretty_name.is_some()
+    {
         let name = env.on_binder(&binder);
         let mut params = vec![(
+            plicity,
+            multiplicity,
             vec![(ByteIndex::default(), name)],
             resugar_term(&env, &ann, Prec::APP),
         )];
@@ -276,14 +389,22 @@ fn resugar_pi(
             // (a : Type) -> (b : Type -> Type) -> ...
             // (a : Type) (b : Type -> Type) -> ...
             // ```
-            let ((next_binder, Embed(next_ann)), next_body) = match *body {
-                core::Term::Pi(ref scope) => scope.clone().unbind(),
-                _ => break,
-            };
-
-            if core::Term::term_eq(&ann, &next_ann) && next_binder.0.pretty_name.is_some() {
-                // Combine the parameters if the type annotations are
-                // alpha-equivalent. For example:
+            let (next_plicity, next_multiplicity, ((next_binder, Embed(next_ann)), next_body)) =
+                match *body {
+                    core::Term::Pi(next_plicity, next_multiplicity, ref scope) => {
+                        (next_plicity, next_multiplicity, scope.clone().unbind())
+                    },
+                    _ => break,
+                };
+
+            if plicity == next_plicity
+                && multiplicity == next_multiplicity
+                && core::Term::term_eq(&ann, &next_ann)
+                && next_binder.0.pretty_name.is_some()
+            {
+                // Combine the parameters if they have the same plicity and
+                // multiplicity, and the type annotations are alpha-equivalent.
+                // For example:
                 //
                 // ```
                 // (a : Type) (b : Type) -> ...
@@ -291,14 +412,19 @@ fn resugar_pi(
                 // ```
                 let next_name = env.on_binder(&next_binder);
                 let next_param = (ByteIndex::default(), next_name);
-                params.last_mut().unwrap().0.push(next_param);
-            } else if next_body.free_vars().contains(&next_binder.0)
+                params.last_mut().unwrap().2.push(next_param);
+            } else if next_plicity == Plicity::Implicit
+                || next_multiplicity != Multiplicity::Many
+                || next_body.free_vars().contains(&next_binder.0)
                 || next_binder.0.pretty_name.is_some()
             {
-                // Add a new parameter if the body is dependent on the parameter
-                // or there is a human-readable name given
+                // Add a new parameter if it is implicit, has a restricted
+                // multiplicity, the body is dependent on the parameter, or
+                // there is a human-readable name given
                 let next_name = env.on_binder(&next_binder);
                 params.push((
+                    next_plicity,
+                    next_multiplicity,
                     vec![(ByteIndex::default(), next_name)],
                     resugar_term(&env, &next_ann, Prec::APP),
                 ));
@@ -317,6 +443,8 @@ fn resugar_pi(
                 );
             }
 
+            plicity = next_plicity;
+            multiplicity = next_multiplicity;
             ann = next_ann;
             body = next_body;
         }
@@ -349,15 +477,21 @@ fn resugar_pi(
 
 fn resugar_lam(
     env: &ResugarEnv,
+    plicity: Plicity,
+    multiplicity: Multiplicity,
     scope: &Scope<(Binder<String>, Embed<core::RcTerm>), core::RcTerm>,
     prec: Prec,
 ) -> concrete::Term {
     let mut env = env.clone();
 
+    let mut plicity = plicity;
+    let mut multiplicity = multiplicity;
     let ((binder, Embed(mut ann)), mut body) = scope.clone().unbind();
 
     let name = env.on_binder(&binder);
     let mut params = vec![(
+        plicity,
+        lam_multiplicity(multiplicity),
         vec![(ByteIndex::default(), name)],
         Some(Box::new(resugar_term(&env, &ann, Prec::LAM))),
     )];
@@ -372,29 +506,40 @@ fn resugar_lam(
         // \(a : Type) => \(b : Type -> Type) => ...
         // \(a : Type) (b : Type -> Type) => ...
         // ```
-        let ((next_binder, Embed(next_ann)), next_body) = match *body {
-            core::Term::Lam(ref scope) => scope.clone().unbind(),
-            _ => break,
-        };
+        let (next_plicity, next_multiplicity, ((next_binder, Embed(next_ann)), next_body)) =
+            match *body {
+                core::Term::Lam(next_plicity, next_multiplicity, ref scope) => {
+                    (next_plicity, next_multiplicity, scope.clone().unbind())
+                },
+                _ => break,
+            };
 
-        // Combine the parameters if the type annotations are alpha-equivalent.
-        // For example:
+        // Combine the parameters if they have the same plicity and
+        // multiplicity, and the type annotations are alpha-equivalent. For
+        // example:
         //
         // ```
         // \(a : Type) (b : Type) => ...
         // \(a b : Type) => ...
         // ```
         let next_name = env.on_binder(&next_binder);
-        if core::Term::term_eq(&ann, &next_ann) {
+        if plicity == next_plicity
+            && multiplicity == next_multiplicity
+            && core::Term::term_eq(&ann, &next_ann)
+        {
             let next_param = (ByteIndex::default(), next_name);
-            params.last_mut().unwrap().0.push(next_param);
+            params.last_mut().unwrap().2.push(next_param);
         } else {
             params.push((
+                next_plicity,
+                lam_multiplicity(next_multiplicity),
                 vec![(ByteIndex::default(), next_name)],
                 Some(Box::new(resugar_term(&env, &next_ann, Prec::LAM))),
             ));
         }
 
+        plicity = next_plicity;
+        multiplicity = next_multiplicity;
         ann = next_ann;
         body = next_body;
     }
@@ -409,6 +554,15 @@ fn resugar_lam(
     )
 }
 
+/// Unrestricted lambda parameters are left without a multiplicity, as that is
+/// what they default to when they are not checked against a pi type
+fn lam_multiplicity(multiplicity: Multiplicity) -> Option<Multiplicity> {
+    match multiplicity {
+        Multiplicity::Many => None,
+        multiplicity => Some(multiplicity),
+    }
+}
+
 fn resugar_let(
     env: &ResugarEnv,
     scope: &Scope<(Binder<String>, Embed<core::RcTerm>), core::RcTerm>,
@@ -427,10 +581,15 @@ fn resugar_let(
 
     let mut items = vec![
         concrete::Item::Declaration {
+            doc: Vec::new(),
+            private: None,
+            partial: None,
             name: (ByteIndex::default(), name.clone()),
-            ann: resugar_term(&env, &ann, Prec::ANN),
+            ann: resugar_term(&env, &ann, Prec::ITEM_ANN),
         },
         concrete::Item::Definition {
+            doc: Vec::new(),
+            private: None,
             name: (ByteIndex::default(), name),
             params: body_params,
             return_ann: None,
@@ -453,10 +612,15 @@ fn resugar_let(
         };
 
         items.push(concrete::Item::Declaration {
+            doc: Vec::new(),
+            private: None,
+            partial: None,
             name: (ByteIndex::default(), next_name.clone()),
-            ann: resugar_term(&env, &next_ann, Prec::ANN),
+            ann: resugar_term(&env, &next_ann, Prec::ITEM_ANN),
         });
         items.push(concrete::Item::Definition {
+            doc: Vec::new(),
+            private: None,
             name: (ByteIndex::default(), next_name),
             params: body_params,
             return_ann: None,
@@ -476,6 +640,31 @@ fn resugar_let(
     )
 }
 
+fn resugar_level(level: &Level) -> concrete::Level {
+    let span = ByteSpan::default();
+    let atoms = level.vars().iter().map(|&(ref var, offset)| {
+        let var = concrete::Level::Var(span, var.to_string());
+        match offset {
+            0 => var,
+            offset => concrete::Level::Parens(
+                span,
+                Box::new(concrete::Level::Succ(span, Box::new(var), offset)),
+            ),
+        }
+    });
+
+    let mut levels = match level.base() {
+        0 if !level.is_const() => Vec::new(),
+        base => vec![concrete::Level::Const(span, base)],
+    };
+    levels.extend(atoms);
+
+    match levels.len() {
+        1 => levels.pop().unwrap(),
+        _ => concrete::Level::Parens(span, Box::new(concrete::Level::Max(span, levels))),
+    }
+}
+
 fn resugar_term(env: &ResugarEnv, term: &core::Term, prec: Prec) -> concrete::Term {
     match *term {
         core::Term::Ann(ref term, ref ty) => parens_if(
@@ -485,10 +674,11 @@ fn resugar_term(env: &ResugarEnv, term: &core::Term, prec: Prec) -> concrete::Te
                 Box::new(resugar_term(env, ty, Prec::ANN)),
             ),
         ),
-        core::Term::Universe(level) => {
-            let level = match level {
-                Level(0) => None,
-                Level(level) => Some(level),
+        core::Term::Universe(ref level) => {
+            let level = if *level == Level::from(0) {
+                None
+            } else {
+                Some(resugar_level(level))
             };
 
             parens_if(
@@ -539,19 +729,64 @@ fn resugar_term(env: &ResugarEnv, term: &core::Term, prec: Prec) -> concrete::Te
             // TODO: Better message
             panic!("Tried to convert a term that was not locally closed");
         },
+        // Use-site instantiations of level-polymorphic definitions are
+        // always found by elaboration, so they don't need to be written
+        core::Term::Inst(ref term, _) => resugar_term(env, term, prec),
+        core::Term::Meta(_) => concrete::Term::Hole(ByteSpan::default()),
+        core::Term::Constr(Var::Free(ref free_var), ref args) => {
+            let name = env.on_free_var(free_var);
+            let head = concrete::Term::Name(ByteSpan::default(), name, None);
+
+            if args.is_empty() {
+                head
+            } else {
+                parens_if(
+                    Prec::APP < prec,
+                    concrete::Term::App(
+                        Box::new(head),
+                        args.iter()
+                            .map(|&(plicity, ref arg)| match plicity {
+                                Plicity::Explicit => {
+                                    concrete::Arg::Explicit(resugar_term(env, arg, Prec::ATOMIC))
+                                },
+                                Plicity::Implicit => concrete::Arg::Implicit(
+                                    ByteSpan::default(),
+                                    resugar_term(env, arg, Prec::NO_WRAP),
+                                ),
+                            }).collect(),
+                    ),
+                )
+            }
+        },
+        core::Term::Constr(Var::Bound(_), _) => {
+            // TODO: Better message
+            panic!("Tried to convert a term that was not locally closed");
+        },
         core::Term::Extern(ref name, ref ty) => concrete::Term::Extern(
             ByteSpan::default(),
             ByteSpan::default(),
             name.clone(),
             Box::new(resugar_term(env, ty, Prec::NO_WRAP)),
         ),
-        core::Term::Pi(ref scope) => resugar_pi(env, scope, prec),
-        core::Term::Lam(ref scope) => resugar_lam(env, scope, prec),
-        core::Term::App(ref head, ref arg) => parens_if(
+        core::Term::Pi(plicity, multiplicity, ref scope) => {
+            resugar_pi(env, plicity, multiplicity, scope, prec)
+        },
+        core::Term::Lam(plicity, multiplicity, ref scope) => {
+            resugar_lam(env, plicity, multiplicity, scope, prec)
+        },
+        core::Term::App(ref head, plicity, ref arg) => parens_if(
             Prec::APP < prec,
             concrete::Term::App(
                 Box::new(resugar_term(env, head, Prec::NO_WRAP)),
-                vec![resugar_term(env, arg, Prec::NO_WRAP)], // TODO
+                vec![match plicity {
+                    Plicity::Explicit => {
+                        concrete::Arg::Explicit(resugar_term(env, arg, Prec::ATOMIC))
+                    },
+                    Plicity::Implicit => concrete::Arg::Implicit(
+                        ByteSpan::default(),
+                        resugar_term(env, arg, Prec::NO_WRAP),
+                    ),
+                }],
             ),
         ),
         core::Term::Let(ref scope) => resugar_let(env, scope, prec),
@@ -564,21 +799,53 @@ fn resugar_term(env: &ResugarEnv, term: &core::Term, prec: Prec) -> concrete::Te
                 Box::new(resugar_term(env, if_false, Prec::APP)),
             ),
         ),
+        // Equality types are only allowed at the top of a `Term`, so they are
+        // wrapped in parentheses everywhere else
+        core::Term::Eq(_, ref lhs, ref rhs) => parens_if(
+            Prec::ANN < prec,
+            concrete::Term::Eq(
+                Box::new(resugar_term(env, lhs, Prec::APP)),
+                Box::new(resugar_term(env, rhs, Prec::APP)),
+            ),
+        ),
+        core::Term::Refl => concrete::Term::Refl(ByteSpan::default()),
+        core::Term::Error => concrete::Term::Error(ByteSpan::default()),
+        core::Term::J(ref motive, ref base, ref proof) => parens_if(
+            Prec::APP < prec,
+            concrete::Term::J(
+                ByteIndex::default(),
+                Box::new(resugar_term(env, motive, Prec::ATOMIC)),
+                Box::new(resugar_term(env, base, Prec::ATOMIC)),
+                Box::new(resugar_term(env, proof, Prec::ATOMIC)),
+            ),
+        ),
         core::Term::RecordType(ref scope) => {
             let mut env = env.clone();
             let (scope, ()) = scope.clone().unbind();
 
-            let fields = scope
-                .unnest()
-                .into_iter()
-                .map(|(label, binder, Embed(ann))| {
-                    let ann = resugar_term(&env, &ann, Prec::NO_WRAP);
-                    let name = env.on_item(&label, &binder);
+            let fields = scope.unnest();
+            let fields = fields
+                .iter()
+                .enumerate()
+                .map(|(i, &(ref label, ref binder, Embed(ref ann)))| {
+                    let ann = resugar_term(&env, ann, Prec::ITEM_ANN);
+                    let name = env.on_item(label, binder);
+                    // Binders that were renamed to avoid a clash can still be
+                    // left out if none of the later fields refer to them
+                    let is_referenced = || {
+                        fields[i + 1..]
+                            .iter()
+                            .any(|&(_, _, Embed(ref ann))| ann.free_vars().contains(&binder.0))
+                    };
 
                     concrete::RecordTypeField {
+                        doc: Vec::new(),
                         label: (ByteIndex::default(), label.0.clone()),
                         binder: match binder.0.pretty_name {
                             Some(ref pretty_name) if *pretty_name == name => None,
+                            Some(ref pretty_name) if *pretty_name == label.0 && !is_referenced() => {
+                                None
+                            },
                             None | Some(_) => Some((ByteIndex::default(), name)),
                         },
                         ann,
@@ -642,6 +909,20 @@ fn resugar_term(env: &ResugarEnv, term: &core::Term, prec: Prec) -> concrete::Te
     }
 }
 
+/// Resugar the type annotation of a declaration
+///
+/// Equality types are not allowed at the top of these annotations, so they
+/// are wrapped in parentheses.
+pub fn resugar_ann(env: &ResugarEnv, term: &core::Term) -> concrete::Term {
+    resugar_term(env, term, Prec::ITEM_ANN)
+}
+
+impl Resugar<concrete::Pattern> for core::Pattern {
+    fn resugar(&self, env: &ResugarEnv) -> concrete::Pattern {
+        resugar_pattern(&mut env.clone(), self, Prec::NO_WRAP)
+    }
+}
+
 impl Resugar<concrete::Term> for core::Term {
     fn resugar(&self, env: &ResugarEnv) -> concrete::Term {
         resugar_term(env, self, Prec::NO_WRAP)
@@ -651,7 +932,7 @@ impl Resugar<concrete::Term> for core::Term {
 impl Resugar<concrete::Term> for core::Value {
     fn resugar(&self, env: &ResugarEnv) -> concrete::Term {
         // FIXME: Make this more efficient?
-        resugar_term(env, &core::Term::from(self), Prec::NO_WRAP)
+        resugar_term(env, &self.to_folded_term(), Prec::NO_WRAP)
     }
 }
 
@@ -19,48 +19,70 @@ mod module {
         let var_else2 = FreeVar::fresh_named("else");
 
         let core_module = core::Module {
+            doc: String::new(),
             items: vec![
                 core::Item::Declaration {
+                    doc: String::new(),
                     label: Label("else".to_owned()),
                     binder: Binder(var_else1.clone()),
                     term: core::RcTerm::from(core::Term::universe(1)),
+                    partial: false,
+                    private: false,
                 },
                 core::Item::Definition {
+                    doc: String::new(),
                     label: Label("else".to_owned()),
                     binder: Binder(var_else1.clone()),
                     term: core::RcTerm::from(core::Term::universe(0)),
+                    private: false,
                 },
                 // This shouldn't happen, but let's test what happens anyway!
                 core::Item::Declaration {
+                    doc: String::new(),
                     label: Label("else".to_owned()),
                     binder: Binder(var_else2.clone()),
                     term: core::RcTerm::from(core::Term::universe(1)),
+                    partial: false,
+                    private: false,
                 },
                 core::Item::Definition {
+                    doc: String::new(),
                     label: Label("else".to_owned()),
                     binder: Binder(var_else2.clone()),
                     term: core::RcTerm::from(core::Term::universe(0)),
+                    private: false,
                 },
             ],
         };
 
         let concrete_module = concrete::Module::Valid {
+            doc: Vec::new(),
             items: vec![
                 concrete::Item::Declaration {
+                    doc: Vec::new(),
+                    private: None,
+                    partial: None,
                     name: (index(), "else1".to_owned()),
-                    ann: concrete::Term::Universe(span(), Some(1)),
+                    ann: concrete::Term::Universe(span(), Some(concrete::Level::Const(span(), 1))),
                 },
                 concrete::Item::Definition {
+                    doc: Vec::new(),
+                    private: None,
                     name: (index(), "else1".to_owned()),
                     params: vec![],
                     return_ann: None,
                     body: concrete::Term::Universe(span(), None),
                 },
                 concrete::Item::Declaration {
+                    doc: Vec::new(),
+                    private: None,
+                    partial: None,
                     name: (index(), "else2".to_owned()),
-                    ann: concrete::Term::Universe(span(), Some(1)),
+                    ann: concrete::Term::Universe(span(), Some(concrete::Level::Const(span(), 1))),
                 },
                 concrete::Item::Definition {
+                    doc: Vec::new(),
+                    private: None,
                     name: (index(), "else2".to_owned()),
                     params: vec![],
                     return_ann: None,
@@ -72,6 +94,89 @@ mod module {
         assert_eq!(core_module.resugar(&ResugarEnv::new()), concrete_module);
     }
 
+    #[test]
+    fn private_items() {
+        let var_helper = FreeVar::fresh_named("helper");
+
+        let core_module = core::Module {
+            doc: String::new(),
+            items: vec![
+                core::Item::Declaration {
+                    doc: String::new(),
+                    label: Label("helper".to_owned()),
+                    binder: Binder(var_helper.clone()),
+                    term: core::RcTerm::from(core::Term::universe(1)),
+                    partial: false,
+                    private: true,
+                },
+                core::Item::Definition {
+                    doc: String::new(),
+                    label: Label("helper".to_owned()),
+                    binder: Binder(var_helper.clone()),
+                    term: core::RcTerm::from(core::Term::universe(0)),
+                    private: false,
+                },
+            ],
+        };
+
+        let concrete_module = concrete::Module::Valid {
+            doc: Vec::new(),
+            items: vec![
+                concrete::Item::Declaration {
+                    doc: Vec::new(),
+                    private: Some(span()),
+                    partial: None,
+                    name: (index(), "helper".to_owned()),
+                    ann: concrete::Term::Universe(span(), Some(concrete::Level::Const(span(), 1))),
+                },
+                concrete::Item::Definition {
+                    doc: Vec::new(),
+                    private: None,
+                    name: (index(), "helper".to_owned()),
+                    params: vec![],
+                    return_ann: None,
+                    body: concrete::Term::Universe(span(), None),
+                },
+            ],
+        };
+
+        assert_eq!(core_module.resugar(&ResugarEnv::new()), concrete_module);
+    }
+
+    #[test]
+    fn doc_comments() {
+        let var_unit = FreeVar::fresh_named("unit");
+
+        let core_module = core::Module {
+            doc: "Some units\n\nThese are useful".to_owned(),
+            items: vec![core::Item::Definition {
+                doc: "The unit type".to_owned(),
+                label: Label("unit".to_owned()),
+                binder: Binder(var_unit.clone()),
+                term: core::RcTerm::from(core::Term::universe(0)),
+                private: false,
+            }],
+        };
+
+        let concrete_module = concrete::Module::Valid {
+            doc: vec![
+                "Some units".to_owned(),
+                "".to_owned(),
+                "These are useful".to_owned(),
+            ],
+            items: vec![concrete::Item::Definition {
+                doc: vec!["The unit type".to_owned()],
+                private: None,
+                name: (index(), "unit".to_owned()),
+                params: vec![],
+                return_ann: None,
+                body: concrete::Term::Universe(span(), None),
+            }],
+        };
+
+        assert_eq!(core_module.resugar(&ResugarEnv::new()), concrete_module);
+    }
+
     // TODO: moare tests
 }
 
@@ -104,7 +209,7 @@ mod term {
     #[test]
     fn universe1() {
         let core_term = core::Term::universe(1);
-        let concrete_term = concrete::Term::Universe(span(), Some(1));
+        let concrete_term = concrete::Term::Universe(span(), Some(concrete::Level::Const(span(), 1)));
 
         assert_eq!(core_term.resugar(&ResugarEnv::new()), concrete_term);
     }
@@ -181,15 +286,19 @@ mod term {
 
     #[test]
     fn arrow() {
-        let core_term = core::RcTerm::from(core::Term::Pi(Scope::new(
-            (
-                Binder(FreeVar::fresh_unnamed()),
-                Embed(core::RcTerm::from(core::RcTerm::from(
-                    core::Term::universe(0),
-                ))),
+        let core_term = core::RcTerm::from(core::Term::Pi(
+            Plicity::Explicit,
+            Multiplicity::Many,
+            Scope::new(
+                (
+                    Binder(FreeVar::fresh_unnamed()),
+                    Embed(core::RcTerm::from(core::RcTerm::from(
+                        core::Term::universe(0),
+                    ))),
+                ),
+                core::RcTerm::from(core::RcTerm::from(core::Term::universe(0))),
             ),
-            core::RcTerm::from(core::RcTerm::from(core::Term::universe(0))),
-        )));
+        ));
 
         let concrete_term = concrete::Term::Arrow(
             Box::new(concrete::Term::Universe(span(), None)),
@@ -201,21 +310,29 @@ mod term {
 
     #[test]
     fn arrow_parens() {
-        let core_term = core::Term::Pi(Scope::new(
-            (
-                Binder(FreeVar::fresh_unnamed()),
-                Embed(core::RcTerm::from(core::Term::Pi(Scope::new(
-                    (
-                        Binder(FreeVar::fresh_unnamed()),
-                        Embed(core::RcTerm::from(core::RcTerm::from(
-                            core::Term::universe(0),
-                        ))),
-                    ),
-                    core::RcTerm::from(core::RcTerm::from(core::Term::universe(0))),
-                )))),
+        let core_term = core::Term::Pi(
+            Plicity::Explicit,
+            Multiplicity::Many,
+            Scope::new(
+                (
+                    Binder(FreeVar::fresh_unnamed()),
+                    Embed(core::RcTerm::from(core::Term::Pi(
+                        Plicity::Explicit,
+                        Multiplicity::Many,
+                        Scope::new(
+                            (
+                                Binder(FreeVar::fresh_unnamed()),
+                                Embed(core::RcTerm::from(core::RcTerm::from(
+                                    core::Term::universe(0),
+                                ))),
+                            ),
+                            core::RcTerm::from(core::RcTerm::from(core::Term::universe(0))),
+                        ),
+                    ))),
+                ),
+                core::RcTerm::from(core::RcTerm::from(core::Term::universe(1))),
             ),
-            core::RcTerm::from(core::RcTerm::from(core::Term::universe(1))),
-        ));
+        );
 
         let concrete_term = concrete::Term::Arrow(
             Box::new(concrete::Term::Parens(
@@ -225,7 +342,7 @@ mod term {
                     Box::new(concrete::Term::Universe(span(), None)),
                 )),
             )),
-            Box::new(concrete::Term::Universe(span(), Some(1))),
+            Box::new(concrete::Term::Universe(span(), Some(concrete::Level::Const(span(), 1)))),
         );
 
         assert_eq!(core_term.resugar(&ResugarEnv::new()), concrete_term);
@@ -234,6 +351,39 @@ mod term {
     // TODO: core::Term::Lam
     // TODO: core::Term::App
 
+    #[test]
+    fn eq_parens() {
+        let string = || {
+            core::RcTerm::from(core::Term::Literal(core::Literal::String("a".to_owned())))
+        };
+        let core_term = core::RcTerm::from(core::Term::Pi(
+            Plicity::Explicit,
+            Multiplicity::Many,
+            Scope::new(
+                (
+                    Binder(FreeVar::fresh_unnamed()),
+                    Embed(core::RcTerm::from(core::Term::universe(0))),
+                ),
+                core::RcTerm::from(core::Term::Eq(
+                    core::RcTerm::from(core::Term::universe(0)),
+                    string(),
+                    string(),
+                )),
+            ),
+        ));
+
+        let string = || concrete::Term::Literal(concrete::Literal::String(span(), "a".to_owned()));
+        let concrete_term = concrete::Term::Arrow(
+            Box::new(concrete::Term::Universe(span(), None)),
+            Box::new(concrete::Term::Parens(
+                span(),
+                Box::new(concrete::Term::Eq(Box::new(string()), Box::new(string()))),
+            )),
+        );
+
+        assert_eq!(core_term.resugar(&ResugarEnv::new()), concrete_term);
+    }
+
     #[test]
     fn if_else() {
         let core_term = core::Term::If(
@@ -302,11 +452,13 @@ mod term {
             span(),
             vec![
                 concrete::RecordTypeField {
+                    doc: Vec::new(),
                     label: (index(), "String".to_owned()),
                     binder: Some((index(), "String1".to_owned())),
                     ann: concrete::Term::Universe(span(), None),
                 },
                 concrete::RecordTypeField {
+                    doc: Vec::new(),
                     label: (index(), "x".to_owned()),
                     binder: None,
                     ann: concrete::Term::Name(span(), "String1".to_owned(), None),
@@ -353,7 +505,7 @@ mod term {
         let concrete_term = concrete::Term::Proj(
             Box::new(concrete::Term::Parens(
                 span(),
-                Box::new(concrete::Term::Universe(span(), Some(1))),
+                Box::new(concrete::Term::Universe(span(), Some(concrete::Level::Const(span(), 1)))),
             )),
             index(),
             "hello".to_owned(),
@@ -374,8 +526,8 @@ mod term {
         let concrete_term = concrete::Term::Array(
             span(),
             vec![
-                concrete::Term::Universe(span(), Some(1)),
-                concrete::Term::Universe(span(), Some(1)),
+                concrete::Term::Universe(span(), Some(concrete::Level::Const(span(), 1))),
+                concrete::Term::Universe(span(), Some(concrete::Level::Const(span(), 1))),
             ],
         );
 